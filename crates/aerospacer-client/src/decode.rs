@@ -0,0 +1,157 @@
+//! Decoding of the programs' account data
+//!
+//! `decode` reads one known account type. `ProtocolAccount`, `OracleAccount` and `FeesAccount`
+//! identify an account of unknown type by its 8-byte Anchor discriminator, e.g. for the
+//! results of getProgramAccounts. `ProtocolEvent`, `OracleEvent` and `FeesEvent` do the same for
+//! the events the programs emit (the base64-decoded payload of a "Program data: " log line).
+
+use anchor_lang::prelude::*;
+use anchor_lang::error::ErrorCode;
+use anchor_lang::{Discriminator, Event};
+use aerospacer_protocol::state::*;
+use aerospacer_protocol::events::*;
+use aerospacer_oracle::state::OracleStateAccount;
+use aerospacer_oracle::events::*;
+use aerospacer_fees::state::FeeStateAccount;
+use aerospacer_fees::events::*;
+
+/// Decode account data of a known type, checking its discriminator
+pub fn decode<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    T::try_deserialize(&mut &data[..])
+}
+
+fn has_discriminator<T: Discriminator>(data: &[u8]) -> bool {
+    data.starts_with(T::DISCRIMINATOR)
+}
+
+/// Decode an event of a known type, checking its discriminator
+pub fn decode_event<T: Event>(data: &[u8]) -> Result<T> {
+    if !has_discriminator::<T>(data) {
+        return Err(ErrorCode::InstructionDidNotDeserialize.into());
+    }
+    T::try_from_slice(&data[T::DISCRIMINATOR.len()..]).map_err(|_| ErrorCode::InstructionDidNotDeserialize.into())
+}
+
+macro_rules! account_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident),+ $(,)? }) => {
+        $(#[$meta])*
+        pub enum $name {
+            $($variant($variant),)+
+        }
+
+        impl $name {
+            /// Decode account data as whichever account type its discriminator names
+            pub fn decode(data: &[u8]) -> Result<Self> {
+                $(
+                    if has_discriminator::<$variant>(data) {
+                        return decode::<$variant>(data).map(Self::$variant);
+                    }
+                )+
+                Err(ErrorCode::AccountDiscriminatorMismatch.into())
+            }
+        }
+    };
+}
+
+account_enum! {
+    /// Any account owned by aerospacer-protocol
+    ProtocolAccount {
+        StateAccount,
+        UserDebtAmount,
+        UserCollateralAmount,
+        UserStakeAmount,
+        LiquidityThreshold,
+        TotalCollateralAmount,
+        UserLiquidationCollateralGain,
+        TotalLiquidationCollateralGain,
+        StabilityPoolSnapshot,
+        EpochScaleSum,
+        UserCollateralSnapshot,
+        SharePool,
+        RedistributionSnapshot,
+        TroveRegistry,
+        TroveRegistryPage,
+        CollateralConfig,
+        PendingParameterChange,
+    }
+}
+
+account_enum! {
+    /// Any account owned by aerospacer-oracle
+    OracleAccount {
+        OracleStateAccount,
+    }
+}
+
+account_enum! {
+    /// Any account owned by aerospacer-fees
+    FeesAccount {
+        FeeStateAccount,
+    }
+}
+
+macro_rules! event_enum {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident),+ $(,)? }) => {
+        $(#[$meta])*
+        pub enum $name {
+            $($variant($variant),)+
+        }
+
+        impl $name {
+            /// Decode event data as whichever event its discriminator names
+            pub fn decode(data: &[u8]) -> Result<Self> {
+                $(
+                    if has_discriminator::<$variant>(data) {
+                        return decode_event::<$variant>(data).map(Self::$variant);
+                    }
+                )+
+                Err(ErrorCode::InstructionDidNotDeserialize.into())
+            }
+        }
+    };
+}
+
+event_enum! {
+    /// Any event emitted by aerospacer-protocol
+    ProtocolEvent {
+        TroveOpened,
+        TroveAdjusted,
+        TroveClosed,
+        TroveLiquidated,
+        TroveRedeemed,
+        Redemption,
+        Staked,
+        Unstaked,
+        SharesDeposited,
+        SharesReleased,
+        SharesRedeemed,
+        LiquidationGainsWithdrawn,
+        TroveIcrRefreshed,
+        SharePoolInitialized,
+        CollateralConfigSet,
+        ParameterChangeQueued,
+        ParameterChangeExecuted,
+        ParameterChangeCancelled,
+    }
+}
+
+event_enum! {
+    /// Any event emitted by aerospacer-oracle
+    OracleEvent {
+        CollateralDataSet,
+        CollateralDataRemoved,
+    }
+}
+
+event_enum! {
+    /// Any event emitted by aerospacer-fees
+    FeesEvent {
+        FeeDistributed,
+    }
+}
+
+/// Decode the return data set by a query instruction (query_trove_registry, query_system_mode,
+/// the oracle getters, ...)
+pub fn decode_return_data<T: AnchorDeserialize>(data: &[u8]) -> Result<T> {
+    T::try_from_slice(data).map_err(|_| ErrorCode::AccountDidNotDeserialize.into())
+}
@@ -0,0 +1,75 @@
+//! Instruction builders for aerospacer-fees
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::{system_program, InstructionData};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token;
+use aerospacer_fees::{accounts, instruction};
+use aerospacer_fees::instructions::*;
+use crate::pda;
+use crate::remaining::FeeRecipients;
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: aerospacer_fees::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+pub fn initialize(admin: &Pubkey) -> Instruction {
+    build(
+        accounts::Initialize {
+            state: pda::fee_state(),
+            admin: *admin,
+            system_program: system_program::ID,
+        },
+        instruction::Initialize {},
+    )
+}
+
+pub fn toggle_stake_contract(admin: &Pubkey) -> Instruction {
+    build(
+        accounts::ToggleStakeContract { admin: *admin, state: pda::fee_state() },
+        instruction::ToggleStakeContract {},
+    )
+}
+
+pub fn set_stake_contract_address(admin: &Pubkey, params: SetStakeContractAddressParams) -> Instruction {
+    build(
+        accounts::SetStakeContractAddress { admin: *admin, state: pda::fee_state() },
+        instruction::SetStakeContractAddress { params },
+    )
+}
+
+pub fn set_fee_addresses(admin: &Pubkey, params: SetFeeAddressesParams) -> Instruction {
+    build(
+        accounts::SetFeeAddresses { admin: *admin, state: pda::fee_state() },
+        instruction::SetFeeAddresses { params },
+    )
+}
+
+/// Pay `params.fee_amount` of `mint` from the payer's associated token account to the recipients
+pub fn distribute_fee(payer: &Pubkey, mint: &Pubkey, recipients: &FeeRecipients, params: DistributeFeeParams) -> Instruction {
+    let recipient_accounts = recipients.token_accounts(mint);
+    build(
+        accounts::DistributeFee {
+            payer: *payer,
+            state: pda::fee_state(),
+            payer_token_account: get_associated_token_address(payer, mint),
+            stability_pool_token_account: recipient_accounts.stability_pool_token_account,
+            fee_address_1_token_account: recipient_accounts.fee_address_1_token_account,
+            fee_address_2_token_account: recipient_accounts.fee_address_2_token_account,
+            token_program: token::ID,
+        },
+        instruction::DistributeFee { params },
+    )
+}
+
+pub fn get_config() -> Instruction {
+    build(
+        accounts::GetConfig { state: pda::fee_state() },
+        instruction::GetConfig {},
+    )
+}
@@ -0,0 +1,19 @@
+//! Client SDK for the Aerospacer programs
+//!
+//! Typed instruction builders for aerospacer-protocol, aerospacer-oracle and aerospacer-fees,
+//! PDA derivation from the programs' own seeds, the protocol's remaining_accounts layouts and
+//! decoding of every account type. Off-chain services (keepers, backends) should build their
+//! transactions through this crate rather than re-deriving accounts by hand.
+//!
+//! User, liquidator and fee recipient token accounts are taken to be associated token accounts.
+
+pub mod pda;
+pub mod decode;
+pub mod remaining;
+pub mod protocol;
+pub mod oracle;
+pub mod fees;
+
+pub use aerospacer_protocol;
+pub use aerospacer_oracle;
+pub use aerospacer_fees;
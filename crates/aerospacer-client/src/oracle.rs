@@ -0,0 +1,130 @@
+//! Instruction builders for aerospacer-oracle
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, InstructionData};
+use aerospacer_oracle::{accounts, instruction};
+use aerospacer_oracle::instructions::*;
+use crate::pda;
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData, remaining_accounts: Vec<AccountMeta>) -> Instruction {
+    let mut metas = accounts.to_account_metas(None);
+    metas.extend(remaining_accounts);
+    Instruction {
+        program_id: aerospacer_oracle::ID,
+        accounts: metas,
+        data: data.data(),
+    }
+}
+
+pub fn initialize(admin: &Pubkey, params: InitializeParams) -> Instruction {
+    build(
+        accounts::Initialize {
+            state: pda::oracle_state(),
+            admin: *admin,
+            system_program: system_program::ID,
+            clock: sysvar::clock::ID,
+        },
+        instruction::Initialize { params },
+        vec![],
+    )
+}
+
+pub fn update_oracle_address(admin: &Pubkey, params: UpdateOracleAddressParams) -> Instruction {
+    build(
+        accounts::UpdateOracleAddress { admin: *admin, state: pda::oracle_state(), clock: sysvar::clock::ID },
+        instruction::UpdateOracleAddress { params },
+        vec![],
+    )
+}
+
+pub fn set_data(admin: &Pubkey, params: SetDataParams) -> Instruction {
+    build(
+        accounts::SetData { admin: *admin, state: pda::oracle_state(), clock: sysvar::clock::ID },
+        instruction::SetData { params },
+        vec![],
+    )
+}
+
+pub fn set_data_batch(admin: &Pubkey, params: SetDataBatchParams) -> Instruction {
+    build(
+        accounts::SetDataBatch { admin: *admin, state: pda::oracle_state(), clock: sysvar::clock::ID },
+        instruction::SetDataBatch { params },
+        vec![],
+    )
+}
+
+pub fn remove_data(admin: &Pubkey, params: RemoveDataParams) -> Instruction {
+    build(
+        accounts::RemoveData { admin: *admin, state: pda::oracle_state(), clock: sysvar::clock::ID },
+        instruction::RemoveData { params },
+        vec![],
+    )
+}
+
+pub fn update_pyth_price(admin: &Pubkey, pyth_price_account: &Pubkey, params: UpdatePythPriceParams) -> Instruction {
+    build(
+        accounts::UpdatePythPrice {
+            admin: *admin,
+            state: pda::oracle_state(),
+            pyth_price_account: *pyth_price_account,
+            clock: sysvar::clock::ID,
+        },
+        instruction::UpdatePythPrice { params },
+        vec![],
+    )
+}
+
+pub fn get_price(pyth_price_account: &Pubkey, params: GetPriceParams) -> Instruction {
+    build(
+        accounts::GetPrice {
+            state: pda::oracle_state(),
+            pyth_price_account: *pyth_price_account,
+            clock: sysvar::clock::ID,
+        },
+        instruction::GetPrice { params },
+        vec![],
+    )
+}
+
+/// `pyth_price_accounts`: one per configured denom, in the oracle's collateral_data order
+pub fn get_all_prices(pyth_price_accounts: &[Pubkey], params: GetAllPricesParams) -> Instruction {
+    build(
+        accounts::GetAllPrices { state: pda::oracle_state(), clock: sysvar::clock::ID },
+        instruction::GetAllPrices { params },
+        pyth_price_accounts.iter().map(|a| AccountMeta::new_readonly(*a, false)).collect(),
+    )
+}
+
+pub fn get_config(params: GetConfigParams) -> Instruction {
+    build(
+        accounts::GetConfig { state: pda::oracle_state() },
+        instruction::GetConfig { params },
+        vec![],
+    )
+}
+
+pub fn get_all_denoms(params: GetAllDenomsParams) -> Instruction {
+    build(
+        accounts::GetAllDenoms { state: pda::oracle_state() },
+        instruction::GetAllDenoms { params },
+        vec![],
+    )
+}
+
+pub fn get_price_id(params: GetPriceIdParams) -> Instruction {
+    build(
+        accounts::GetPriceId { state: pda::oracle_state() },
+        instruction::GetPriceId { params },
+        vec![],
+    )
+}
+
+pub fn check_denom(params: CheckDenomParams) -> Instruction {
+    build(
+        accounts::CheckDenom { state: pda::oracle_state() },
+        instruction::CheckDenom { params },
+        vec![],
+    )
+}
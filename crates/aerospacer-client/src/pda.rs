@@ -0,0 +1,115 @@
+//! PDA derivation for every account the programs create
+//!
+//! The seeds come from the programs' own `seeds()` helpers and seed constants, so a seed
+//! changed in a program is picked up here on the next build.
+
+use anchor_lang::prelude::Pubkey;
+use aerospacer_protocol::state::*;
+use aerospacer_oracle::state::OracleStateAccount;
+use aerospacer_fees::state::FeeStateAccount;
+
+fn protocol_pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &aerospacer_protocol::ID).0
+}
+
+/// Protocol StateAccount
+pub fn state() -> Pubkey {
+    protocol_pda(&StateAccount::seeds())
+}
+
+pub fn user_debt_amount(owner: &Pubkey) -> Pubkey {
+    protocol_pda(&UserDebtAmount::seeds(owner))
+}
+
+pub fn user_collateral_amount(owner: &Pubkey, denom: &str) -> Pubkey {
+    protocol_pda(&UserCollateralAmount::seeds(owner, denom))
+}
+
+pub fn liquidity_threshold(owner: &Pubkey) -> Pubkey {
+    protocol_pda(&LiquidityThreshold::seeds(owner))
+}
+
+pub fn user_stake_amount(owner: &Pubkey) -> Pubkey {
+    protocol_pda(&UserStakeAmount::seeds(owner))
+}
+
+pub fn total_collateral_amount(denom: &str) -> Pubkey {
+    protocol_pda(&TotalCollateralAmount::seeds(denom))
+}
+
+pub fn stability_pool_snapshot(denom: &str) -> Pubkey {
+    protocol_pda(&StabilityPoolSnapshot::seeds(denom))
+}
+
+pub fn epoch_scale_sum(denom: &str, epoch: u64, scale: u64) -> Pubkey {
+    protocol_pda(&EpochScaleSum::seeds(denom, &epoch.to_le_bytes(), &scale.to_le_bytes()))
+}
+
+pub fn user_collateral_snapshot(owner: &Pubkey, denom: &str) -> Pubkey {
+    protocol_pda(&UserCollateralSnapshot::seeds(owner, denom))
+}
+
+pub fn redistribution_snapshot(denom: &str) -> Pubkey {
+    protocol_pda(&RedistributionSnapshot::seeds(denom))
+}
+
+pub fn collateral_config(denom: &str) -> Pubkey {
+    protocol_pda(&CollateralConfig::seeds(denom))
+}
+
+pub fn trove_registry(denom: &str) -> Pubkey {
+    protocol_pda(&TroveRegistry::seeds(denom))
+}
+
+pub fn trove_registry_page(denom: &str, page_index: u32) -> Pubkey {
+    protocol_pda(&TroveRegistryPage::seeds(denom, &page_index.to_le_bytes()))
+}
+
+pub fn pending_parameter_change(parameter: &ProtocolParameter) -> Pubkey {
+    protocol_pda(&[b"pending_parameter_change", parameter.seed()])
+}
+
+/// aUSD vault holding staked and repaid stablecoin
+pub fn protocol_stablecoin_vault() -> Pubkey {
+    protocol_pda(&[PROTOCOL_STABLECOIN_VAULT_SEED])
+}
+
+/// Vault holding all trove collateral of one denom
+pub fn protocol_collateral_vault(denom: &str) -> Pubkey {
+    protocol_pda(&[PROTOCOL_COLLATERAL_VAULT_SEED, denom.as_bytes()])
+}
+
+/// aUSD vault holding the gas compensation reserved by every open trove
+pub fn gas_compensation_vault() -> Pubkey {
+    protocol_pda(&[GAS_COMPENSATION_VAULT_SEED])
+}
+
+/// SharePool of the spAUSD wrapper - its stability pool deposit is `user_stake_amount(&share_pool())`
+pub fn share_pool() -> Pubkey {
+    protocol_pda(&SharePool::seeds())
+}
+
+/// spAUSD share mint
+pub fn share_mint() -> Pubkey {
+    protocol_pda(&[SHARE_MINT_SEED])
+}
+
+/// spAUSD token account holding every ShareLock's shares
+pub fn share_escrow() -> Pubkey {
+    protocol_pda(&[SHARE_ESCROW_SEED])
+}
+
+/// A depositor's shares still waiting out the unstake cooldown
+pub fn share_lock(owner: &Pubkey) -> Pubkey {
+    protocol_pda(&ShareLock::seeds(owner))
+}
+
+/// Oracle OracleStateAccount
+pub fn oracle_state() -> Pubkey {
+    OracleStateAccount::get_pda(&aerospacer_oracle::ID).0
+}
+
+/// Fees FeeStateAccount
+pub fn fee_state() -> Pubkey {
+    FeeStateAccount::get_pda(&aerospacer_fees::ID).0
+}
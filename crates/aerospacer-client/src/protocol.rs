@@ -0,0 +1,1123 @@
+//! Instruction builders for aerospacer-protocol
+//!
+//! Each builder fills the program's generated `accounts` struct, so the account order and the
+//! signer/writable flags always match the program. Builders of instructions that read
+//! remaining_accounts take them already assembled (see the `remaining` module and the
+//! `*_remaining_accounts` helpers below).
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::{system_program, InstructionData};
+use anchor_spl::associated_token::get_associated_token_address;
+use anchor_spl::token;
+use aerospacer_protocol::{accounts, instruction};
+use aerospacer_protocol::instructions::*;
+use aerospacer_protocol::stability_pool::gain_epoch_scale_sums;
+use aerospacer_protocol::state::{ProtocolParameter, StateAccount, TroveRegistryPage, UserCollateralSnapshot, UserDebtAmount, UserStakeAmount};
+use aerospacer_fees::state::FeeStateAccount;
+use crate::pda;
+use crate::remaining::*;
+
+/// Addresses shared by the trove instructions, read from the protocol and fees state accounts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolConfig {
+    pub stable_coin_mint: Pubkey,
+    pub oracle_program: Pubkey,
+    pub oracle_state: Pubkey,
+    pub fees_program: Pubkey,
+    pub fees_state: Pubkey,
+    pub fee_recipients: FeeRecipients,
+    pub pool_epoch: u64, // The stability pool's epoch and scale, which liquidations credit S in
+    pub pool_scale: u64,
+}
+
+impl ProtocolConfig {
+    pub fn new(state: &StateAccount, fee_state: &FeeStateAccount) -> Self {
+        Self {
+            stable_coin_mint: state.stable_coin_addr,
+            oracle_program: state.oracle_helper_addr,
+            oracle_state: state.oracle_state_addr,
+            fees_program: state.fee_distributor_addr,
+            fees_state: state.fee_state_addr,
+            fee_recipients: FeeRecipients::from_fee_state(fee_state),
+            pool_epoch: state.epoch,
+            pool_scale: state.current_scale,
+        }
+    }
+
+    /// The EpochScaleSum PDAs a liquidation credits `denom`'s S in: the pool's current scale,
+    /// and the next one in case an earlier trove of the batch moves P on to it
+    fn liquidation_sums(&self, denom: &str) -> [AccountMeta; 2] {
+        [
+            AccountMeta::new(pda::epoch_scale_sum(denom, self.pool_epoch, self.pool_scale), false),
+            AccountMeta::new(pda::epoch_scale_sum(denom, self.pool_epoch, self.pool_scale + 1), false),
+        ]
+    }
+
+    fn stablecoin_account(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address(owner, &self.stable_coin_mint)
+    }
+}
+
+/// The TroveRegistryPage a trove is listed on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistryEntry {
+    pub denom: String, // The denom the trove was opened with
+    pub page_index: u32,
+}
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData, remaining_accounts: Vec<AccountMeta>) -> Instruction {
+    let mut metas = accounts.to_account_metas(None);
+    metas.extend(remaining_accounts);
+    Instruction {
+        program_id: aerospacer_protocol::ID,
+        accounts: metas,
+        data: data.data(),
+    }
+}
+
+pub fn initialize(admin: &Pubkey, stable_coin_mint: &Pubkey, params: InitializeParams) -> Instruction {
+    build(
+        accounts::Initialize {
+            state: pda::state(),
+            admin: *admin,
+            stable_coin_mint: *stable_coin_mint,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::Initialize { params },
+        vec![],
+    )
+}
+
+pub fn update_protocol_addresses(admin: &Pubkey, params: UpdateProtocolAddressesParams) -> Instruction {
+    build(
+        accounts::UpdateProtocolAddresses { admin: *admin, state: pda::state() },
+        instruction::UpdateProtocolAddresses { params },
+        vec![],
+    )
+}
+
+pub fn set_minimum_collateral_ratio(admin: &Pubkey, params: SetMinimumCollateralRatioParams) -> Instruction {
+    build(
+        accounts::SetMinimumCollateralRatio {
+            admin: *admin,
+            state: pda::state(),
+            pending_parameter_change: pda::pending_parameter_change(&ProtocolParameter::MinimumCollateralRatio),
+            system_program: system_program::ID,
+        },
+        instruction::SetMinimumCollateralRatio { params },
+        vec![],
+    )
+}
+
+pub fn set_protocol_fee(admin: &Pubkey, params: SetProtocolFeeParams) -> Instruction {
+    build(
+        accounts::SetProtocolFee {
+            admin: *admin,
+            state: pda::state(),
+            pending_parameter_change: pda::pending_parameter_change(&ProtocolParameter::ProtocolFee),
+            system_program: system_program::ID,
+        },
+        instruction::SetProtocolFee { params },
+        vec![],
+    )
+}
+
+pub fn set_parameter_timelock_delay(admin: &Pubkey, params: SetParameterTimelockDelayParams) -> Instruction {
+    build(
+        accounts::SetParameterTimelockDelay {
+            admin: *admin,
+            state: pda::state(),
+            pending_parameter_change: pda::pending_parameter_change(&ProtocolParameter::ParameterTimelockDelay),
+            system_program: system_program::ID,
+        },
+        instruction::SetParameterTimelockDelay { params },
+        vec![],
+    )
+}
+
+pub fn set_unstake_cooldown(admin: &Pubkey, params: SetUnstakeCooldownParams) -> Instruction {
+    build(
+        accounts::SetUnstakeCooldown {
+            admin: *admin,
+            state: pda::state(),
+            pending_parameter_change: pda::pending_parameter_change(&ProtocolParameter::UnstakeCooldown),
+            system_program: system_program::ID,
+        },
+        instruction::SetUnstakeCooldown { params },
+        vec![],
+    )
+}
+
+pub fn execute_parameter_change(admin: &Pubkey, params: ExecuteParameterChangeParams) -> Instruction {
+    build(
+        accounts::ExecuteParameterChange {
+            admin: *admin,
+            state: pda::state(),
+            pending_parameter_change: pda::pending_parameter_change(&params.parameter),
+        },
+        instruction::ExecuteParameterChange { params },
+        vec![],
+    )
+}
+
+pub fn cancel_parameter_change(admin: &Pubkey, params: CancelParameterChangeParams) -> Instruction {
+    build(
+        accounts::CancelParameterChange {
+            admin: *admin,
+            state: pda::state(),
+            pending_parameter_change: pda::pending_parameter_change(&params.parameter),
+        },
+        instruction::CancelParameterChange { params },
+        vec![],
+    )
+}
+
+pub fn create_collateral_config(admin: &Pubkey, collateral_mint: &Pubkey, params: CreateCollateralConfigParams) -> Instruction {
+    build(
+        accounts::CreateCollateralConfig {
+            admin: *admin,
+            state: pda::state(),
+            collateral_config: pda::collateral_config(&params.collateral_denom),
+            stability_pool_snapshot: pda::stability_pool_snapshot(&params.collateral_denom),
+            collateral_mint: *collateral_mint,
+            system_program: system_program::ID,
+        },
+        instruction::CreateCollateralConfig { params },
+        vec![],
+    )
+}
+
+pub fn update_collateral_config(admin: &Pubkey, params: UpdateCollateralConfigParams) -> Instruction {
+    build(
+        accounts::UpdateCollateralConfig {
+            admin: *admin,
+            state: pda::state(),
+            collateral_config: pda::collateral_config(&params.collateral_denom),
+        },
+        instruction::UpdateCollateralConfig { params },
+        vec![],
+    )
+}
+
+/// Send aUSD from `from`'s associated token account to `to_account`
+pub fn transfer_stablecoin(config: &ProtocolConfig, from: &Pubkey, to_account: &Pubkey, params: TransferStablecoinParams) -> Instruction {
+    build(
+        accounts::TransferStablecoin {
+            from: *from,
+            state: pda::state(),
+            from_account: config.stablecoin_account(from),
+            to_account: *to_account,
+            token_program: token::ID,
+        },
+        instruction::TransferStablecoin { params },
+        vec![],
+    )
+}
+
+/// remaining_accounts: `open_trove_remaining_accounts`
+pub fn open_trove(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    collateral: &Collateral,
+    params: OpenTroveParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    let fees = config.fee_recipients.token_accounts(&config.stable_coin_mint);
+    build(
+        accounts::OpenTrove {
+            user: *user,
+            user_debt_amount: pda::user_debt_amount(user),
+            liquidity_threshold: pda::liquidity_threshold(user),
+            user_collateral_amount: pda::user_collateral_amount(user, denom),
+            user_collateral_account: get_associated_token_address(user, &collateral.mint),
+            collateral_mint: collateral.mint,
+            protocol_collateral_account: pda::protocol_collateral_vault(denom),
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            redistribution_snapshot: pda::redistribution_snapshot(denom),
+            collateral_config: pda::collateral_config(denom),
+            trove_registry: pda::trove_registry(denom),
+            trove_registry_page: pda::trove_registry_page(denom, params.registry_page),
+            state: pda::state(),
+            user_stablecoin_account: config.stablecoin_account(user),
+            protocol_stablecoin_account: pda::protocol_stablecoin_vault(),
+            gas_compensation_vault: pda::gas_compensation_vault(),
+            stable_coin_mint: config.stable_coin_mint,
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            pyth_price_account: collateral.pyth_price_account,
+            clock: sysvar::clock::ID,
+            fees_program: config.fees_program,
+            fees_state: config.fees_state,
+            stability_pool_token_account: fees.stability_pool_token_account,
+            fee_address_1_token_account: fees.fee_address_1_token_account,
+            fee_address_2_token_account: fees.fee_address_2_token_account,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::OpenTrove { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `adjust_remaining_accounts` without the system collateral block
+pub fn add_collateral(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    collateral: &Collateral,
+    params: AddCollateralParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    build(
+        accounts::AddCollateral {
+            user: *user,
+            user_debt_amount: pda::user_debt_amount(user),
+            user_collateral_amount: pda::user_collateral_amount(user, denom),
+            liquidity_threshold: pda::liquidity_threshold(user),
+            state: pda::state(),
+            user_collateral_account: get_associated_token_address(user, &collateral.mint),
+            collateral_mint: collateral.mint,
+            protocol_collateral_account: pda::protocol_collateral_vault(denom),
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            redistribution_snapshot: pda::redistribution_snapshot(denom),
+            collateral_config: pda::collateral_config(denom),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            pyth_price_account: collateral.pyth_price_account,
+            clock: sysvar::clock::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::AddCollateral { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `adjust_remaining_accounts`
+pub fn remove_collateral(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    collateral: &Collateral,
+    params: RemoveCollateralParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    build(
+        accounts::RemoveCollateral {
+            user: *user,
+            user_debt_amount: pda::user_debt_amount(user),
+            user_collateral_amount: pda::user_collateral_amount(user, denom),
+            liquidity_threshold: pda::liquidity_threshold(user),
+            state: pda::state(),
+            user_collateral_account: get_associated_token_address(user, &collateral.mint),
+            collateral_mint: collateral.mint,
+            protocol_collateral_account: pda::protocol_collateral_vault(denom),
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            redistribution_snapshot: pda::redistribution_snapshot(denom),
+            collateral_config: pda::collateral_config(denom),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            pyth_price_account: collateral.pyth_price_account,
+            clock: sysvar::clock::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::RemoveCollateral { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `adjust_remaining_accounts`
+pub fn borrow_loan(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    collateral: &Collateral,
+    params: BorrowLoanParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    let fees = config.fee_recipients.token_accounts(&config.stable_coin_mint);
+    build(
+        accounts::BorrowLoan {
+            user: *user,
+            user_debt_amount: pda::user_debt_amount(user),
+            liquidity_threshold: pda::liquidity_threshold(user),
+            state: pda::state(),
+            user_stablecoin_account: config.stablecoin_account(user),
+            stable_coin_mint: config.stable_coin_mint,
+            protocol_stablecoin_account: pda::protocol_stablecoin_vault(),
+            user_collateral_amount: pda::user_collateral_amount(user, denom),
+            user_collateral_account: get_associated_token_address(user, &collateral.mint),
+            collateral_mint: collateral.mint,
+            protocol_collateral_account: pda::protocol_collateral_vault(denom),
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            redistribution_snapshot: pda::redistribution_snapshot(denom),
+            collateral_config: pda::collateral_config(denom),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            pyth_price_account: collateral.pyth_price_account,
+            clock: sysvar::clock::ID,
+            fees_program: config.fees_program,
+            fees_state: config.fees_state,
+            stability_pool_token_account: fees.stability_pool_token_account,
+            fee_address_1_token_account: fees.fee_address_1_token_account,
+            fee_address_2_token_account: fees.fee_address_2_token_account,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::BorrowLoan { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `adjust_remaining_accounts` without the system collateral block or hints
+pub fn repay_loan(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    collateral: &Collateral,
+    params: RepayLoanParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    build(
+        accounts::RepayLoan {
+            user: *user,
+            user_debt_amount: pda::user_debt_amount(user),
+            user_collateral_amount: pda::user_collateral_amount(user, denom),
+            liquidity_threshold: pda::liquidity_threshold(user),
+            state: pda::state(),
+            user_stablecoin_account: config.stablecoin_account(user),
+            user_collateral_account: get_associated_token_address(user, &collateral.mint),
+            collateral_mint: collateral.mint,
+            protocol_collateral_account: pda::protocol_collateral_vault(denom),
+            stable_coin_mint: config.stable_coin_mint,
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            redistribution_snapshot: pda::redistribution_snapshot(denom),
+            gas_compensation_vault: pda::gas_compensation_vault(),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            pyth_price_account: collateral.pyth_price_account,
+            clock: sysvar::clock::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::RepayLoan { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `adjust_remaining_accounts`
+pub fn adjust_trove(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    collateral: &Collateral,
+    params: AdjustTroveParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    let fees = config.fee_recipients.token_accounts(&config.stable_coin_mint);
+    build(
+        accounts::AdjustTrove {
+            user: *user,
+            user_debt_amount: pda::user_debt_amount(user),
+            liquidity_threshold: pda::liquidity_threshold(user),
+            state: pda::state(),
+            user_stablecoin_account: config.stablecoin_account(user),
+            stable_coin_mint: config.stable_coin_mint,
+            protocol_stablecoin_account: pda::protocol_stablecoin_vault(),
+            user_collateral_amount: pda::user_collateral_amount(user, denom),
+            user_collateral_account: get_associated_token_address(user, &collateral.mint),
+            collateral_mint: collateral.mint,
+            protocol_collateral_account: pda::protocol_collateral_vault(denom),
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            redistribution_snapshot: pda::redistribution_snapshot(denom),
+            collateral_config: pda::collateral_config(denom),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            pyth_price_account: collateral.pyth_price_account,
+            clock: sysvar::clock::ID,
+            fees_program: config.fees_program,
+            fees_state: config.fees_state,
+            stability_pool_token_account: fees.stability_pool_token_account,
+            fee_address_1_token_account: fees.fee_address_1_token_account,
+            fee_address_2_token_account: fees.fee_address_2_token_account,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::AdjustTrove { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `remaining::close_trove_groups`
+pub fn close_trove(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    collateral: &Collateral,
+    registry: &RegistryEntry,
+    params: CloseTroveParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    build(
+        accounts::CloseTrove {
+            user: *user,
+            user_debt_amount: pda::user_debt_amount(user),
+            user_collateral_amount: pda::user_collateral_amount(user, denom),
+            liquidity_threshold: pda::liquidity_threshold(user),
+            trove_registry_page: pda::trove_registry_page(&registry.denom, registry.page_index),
+            trove_registry: pda::trove_registry(&registry.denom),
+            state: pda::state(),
+            user_stablecoin_account: config.stablecoin_account(user),
+            user_collateral_account: get_associated_token_address(user, &collateral.mint),
+            protocol_collateral_vault: pda::protocol_collateral_vault(denom),
+            stable_coin_mint: config.stable_coin_mint,
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            redistribution_snapshot: pda::redistribution_snapshot(denom),
+            gas_compensation_vault: pda::gas_compensation_vault(),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::CloseTrove { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `liquidate_troves_remaining_accounts`
+pub fn liquidate_troves(
+    config: &ProtocolConfig,
+    liquidator: &Pubkey,
+    collateral: &Collateral,
+    params: LiquidateTrovesParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    build(
+        accounts::LiquidateTroves {
+            liquidator: *liquidator,
+            state: pda::state(),
+            stable_coin_mint: config.stable_coin_mint,
+            protocol_stablecoin_vault: pda::protocol_stablecoin_vault(),
+            protocol_collateral_vault: pda::protocol_collateral_vault(denom),
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            gas_compensation_vault: pda::gas_compensation_vault(),
+            liquidator_stablecoin_account: config.stablecoin_account(liquidator),
+            fees_program: config.fees_program,
+            fees_state: config.fees_state,
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            pyth_price_account: collateral.pyth_price_account,
+            clock: sysvar::clock::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::LiquidateTroves { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `liquidate_trove_remaining_accounts`
+pub fn liquidate_trove(
+    config: &ProtocolConfig,
+    liquidator: &Pubkey,
+    collateral: &Collateral,
+    registry: &RegistryEntry,
+    params: LiquidateTroveParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    let target = &params.target_user;
+    build(
+        accounts::LiquidateTrove {
+            liquidator: *liquidator,
+            state: pda::state(),
+            stable_coin_mint: config.stable_coin_mint,
+            protocol_stablecoin_vault: pda::protocol_stablecoin_vault(),
+            protocol_collateral_vault: pda::protocol_collateral_vault(denom),
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            redistribution_snapshot: pda::redistribution_snapshot(denom),
+            stability_pool_snapshot: pda::stability_pool_snapshot(denom),
+            collateral_config: pda::collateral_config(denom),
+            gas_compensation_vault: pda::gas_compensation_vault(),
+            liquidator_stablecoin_account: config.stablecoin_account(liquidator),
+            user_debt_amount: pda::user_debt_amount(target),
+            user_collateral_amount: pda::user_collateral_amount(target, denom),
+            liquidity_threshold: pda::liquidity_threshold(target),
+            trove_registry_page: pda::trove_registry_page(&registry.denom, registry.page_index),
+            trove_registry: pda::trove_registry(&registry.denom),
+            user_collateral_token_account: get_associated_token_address(target, &collateral.mint),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            pyth_price_account: collateral.pyth_price_account,
+            fees_program: config.fees_program,
+            fees_state: config.fees_state,
+            clock: sysvar::clock::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::LiquidateTrove { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: one [Node, LiquidityThreshold] pair per trove to check
+pub fn query_liquidatable_troves(params: QueryLiquidatableTrovesParams, remaining_accounts: Vec<AccountMeta>) -> Instruction {
+    build(
+        accounts::QueryLiquidatableTroves {},
+        instruction::QueryLiquidatableTroves { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `remaining::system_collateral_block`
+pub fn query_system_mode(config: &ProtocolConfig, remaining_accounts: Vec<AccountMeta>) -> Instruction {
+    build(
+        accounts::QuerySystemMode {
+            state: pda::state(),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            clock: sysvar::clock::ID,
+        },
+        instruction::QuerySystemMode {},
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `refresh_icrs_remaining_accounts`
+pub fn refresh_icrs(config: &ProtocolConfig, caller: &Pubkey, params: RefreshIcrsParams, remaining_accounts: Vec<AccountMeta>) -> Instruction {
+    build(
+        accounts::RefreshIcrs {
+            caller: *caller,
+            state: pda::state(),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            clock: sysvar::clock::ID,
+        },
+        instruction::RefreshIcrs { params },
+        remaining_accounts,
+    )
+}
+
+pub fn query_trove_registry(params: QueryTroveRegistryParams) -> Instruction {
+    build(
+        accounts::QueryTroveRegistry {
+            trove_registry: pda::trove_registry(&params.denom),
+            trove_registry_page: pda::trove_registry_page(&params.denom, params.page_index),
+        },
+        instruction::QueryTroveRegistry { params },
+        vec![],
+    )
+}
+
+/// remaining_accounts: `deposit_remaining_accounts`
+pub fn stake(config: &ProtocolConfig, user: &Pubkey, params: StakeParams, remaining_accounts: Vec<AccountMeta>) -> Instruction {
+    build(
+        accounts::Stake {
+            user: *user,
+            user_stake_amount: pda::user_stake_amount(user),
+            state: pda::state(),
+            user_stablecoin_account: config.stablecoin_account(user),
+            protocol_stablecoin_vault: pda::protocol_stablecoin_vault(),
+            stable_coin_mint: config.stable_coin_mint,
+            oracle_state: config.oracle_state,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::Stake { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `trove_proof_accounts`, then `deposit_remaining_accounts`
+pub fn unstake(config: &ProtocolConfig, user: &Pubkey, params: UnstakeParams, remaining_accounts: Vec<AccountMeta>) -> Instruction {
+    build(
+        accounts::Unstake {
+            user: *user,
+            user_stake_amount: pda::user_stake_amount(user),
+            state: pda::state(),
+            user_stablecoin_account: config.stablecoin_account(user),
+            protocol_stablecoin_vault: pda::protocol_stablecoin_vault(),
+            stable_coin_mint: config.stable_coin_mint,
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            clock: sysvar::clock::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::Unstake { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `gain_sum_accounts`
+pub fn withdraw_liquidation_gains(
+    user: &Pubkey,
+    collateral: &Collateral,
+    params: WithdrawLiquidationGainsParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    build(
+        accounts::WithdrawLiquidationGains {
+            user: *user,
+            user_stake_amount: pda::user_stake_amount(user),
+            user_collateral_snapshot: pda::user_collateral_snapshot(user, denom),
+            stability_pool_snapshot: pda::stability_pool_snapshot(denom),
+            state: pda::state(),
+            user_collateral_account: get_associated_token_address(user, &collateral.mint),
+            protocol_collateral_vault: pda::protocol_collateral_vault(denom),
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::WithdrawLiquidationGains { params },
+        remaining_accounts,
+    )
+}
+
+/// The EpochScaleSum PDAs a staker's `denom` gain is read from - `user_snapshot` is their
+/// UserCollateralSnapshot for the denom, None before their first withdrawal of it
+pub fn gain_sum_accounts(denom: &str, stake: &UserStakeAmount, user_snapshot: Option<&UserCollateralSnapshot>) -> Vec<AccountMeta> {
+    let sums = match user_snapshot {
+        Some(user_snapshot) => gain_epoch_scale_sums(stake, user_snapshot),
+        None => [(stake.epoch_snapshot, stake.scale_snapshot), (stake.epoch_snapshot, stake.scale_snapshot + 1)],
+    };
+    sums.iter()
+        .map(|(epoch, scale)| AccountMeta::new_readonly(pda::epoch_scale_sum(denom, *epoch, *scale), false))
+        .collect()
+}
+
+/// remaining_accounts: `withdraw_all_liquidation_gains_remaining_accounts`
+pub fn withdraw_all_liquidation_gains(
+    user: &Pubkey,
+    params: WithdrawAllLiquidationGainsParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    build(
+        accounts::WithdrawAllLiquidationGains {
+            user: *user,
+            user_stake_amount: pda::user_stake_amount(user),
+            state: pda::state(),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::WithdrawAllLiquidationGains { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `gain_to_trove_remaining_accounts`
+pub fn withdraw_liquidation_gains_to_trove(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    collateral: &Collateral,
+    params: WithdrawLiquidationGainsToTroveParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    build(
+        accounts::WithdrawLiquidationGainsToTrove {
+            user: *user,
+            user_stake_amount: pda::user_stake_amount(user),
+            user_collateral_snapshot: pda::user_collateral_snapshot(user, denom),
+            state: pda::state(),
+            user_debt_amount: pda::user_debt_amount(user),
+            user_collateral_amount: pda::user_collateral_amount(user, denom),
+            liquidity_threshold: pda::liquidity_threshold(user),
+            redistribution_snapshot: pda::redistribution_snapshot(denom),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            pyth_price_account: collateral.pyth_price_account,
+            clock: sysvar::clock::ID,
+            system_program: system_program::ID,
+        },
+        instruction::WithdrawLiquidationGainsToTrove { params },
+        remaining_accounts,
+    )
+}
+
+/// A stability pool denom withdraw_all_liquidation_gains claims, with the user's
+/// UserCollateralSnapshot for it (None before their first withdrawal of the denom)
+pub struct GainClaim<'a> {
+    pub collateral: &'a Collateral,
+    pub user_snapshot: Option<&'a UserCollateralSnapshot>,
+}
+
+/// withdraw_all_liquidation_gains: a 4-account group per claim, in the order the denoms are
+/// passed in params, then by address each denom's TotalCollateralAmount and gain sums
+pub fn withdraw_all_liquidation_gains_remaining_accounts(
+    user: &Pubkey,
+    stake: &UserStakeAmount,
+    claims: &[GainClaim],
+) -> Vec<AccountMeta> {
+    let mut accounts = RemainingAccounts::new();
+    for claim in claims {
+        let denom = &claim.collateral.denom;
+        accounts = accounts.positional([
+            AccountMeta::new_readonly(pda::stability_pool_snapshot(denom), false),
+            AccountMeta::new(pda::user_collateral_snapshot(user, denom), false),
+            AccountMeta::new(pda::protocol_collateral_vault(denom), false),
+            AccountMeta::new(get_associated_token_address(user, &claim.collateral.mint), false),
+        ]);
+    }
+    for claim in claims {
+        accounts = accounts
+            .located(payout_accounts(user, claim.collateral))
+            .located(gain_sum_accounts(&claim.collateral.denom, stake, claim.user_snapshot));
+    }
+    accounts.into_vec()
+}
+
+pub fn initialize_share_pool(admin: &Pubkey) -> Instruction {
+    build(
+        accounts::InitializeSharePool {
+            admin: *admin,
+            state: pda::state(),
+            share_pool: pda::share_pool(),
+            share_mint: pda::share_mint(),
+            share_escrow: pda::share_escrow(),
+            pool_stake_amount: pda::user_stake_amount(&pda::share_pool()),
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeSharePool {},
+        vec![],
+    )
+}
+
+/// remaining_accounts: `share_pool_deposit_remaining_accounts`
+pub fn deposit_to_share_pool(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    params: DepositToSharePoolParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let share_pool = pda::share_pool();
+    build(
+        accounts::DepositToSharePool {
+            user: *user,
+            share_pool,
+            pool_stake_amount: pda::user_stake_amount(&share_pool),
+            share_mint: pda::share_mint(),
+            user_share_account: get_associated_token_address(user, &pda::share_mint()),
+            share_lock: pda::share_lock(user),
+            share_escrow: pda::share_escrow(),
+            state: pda::state(),
+            user_stablecoin_account: config.stablecoin_account(user),
+            protocol_stablecoin_vault: pda::protocol_stablecoin_vault(),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            clock: sysvar::clock::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::DepositToSharePool { params },
+        remaining_accounts,
+    )
+}
+
+pub fn release_shares(user: &Pubkey) -> Instruction {
+    build(
+        accounts::ReleaseShares {
+            user: *user,
+            share_pool: pda::share_pool(),
+            share_lock: pda::share_lock(user),
+            share_escrow: pda::share_escrow(),
+            user_share_account: get_associated_token_address(user, &pda::share_mint()),
+            token_program: token::ID,
+        },
+        instruction::ReleaseShares {},
+        vec![],
+    )
+}
+
+/// remaining_accounts: `trove_proof_accounts`, then `share_pool_redeem_remaining_accounts`
+pub fn redeem_from_share_pool(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    params: RedeemFromSharePoolParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let share_pool = pda::share_pool();
+    build(
+        accounts::RedeemFromSharePool {
+            user: *user,
+            share_pool,
+            pool_stake_amount: pda::user_stake_amount(&share_pool),
+            share_mint: pda::share_mint(),
+            user_share_account: get_associated_token_address(user, &pda::share_mint()),
+            state: pda::state(),
+            user_stablecoin_account: config.stablecoin_account(user),
+            protocol_stablecoin_vault: pda::protocol_stablecoin_vault(),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            clock: sysvar::clock::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::RedeemFromSharePool { params },
+        remaining_accounts,
+    )
+}
+
+/// remaining_accounts: `redeem_remaining_accounts`
+pub fn redeem(
+    config: &ProtocolConfig,
+    user: &Pubkey,
+    collateral: &Collateral,
+    params: RedeemParams,
+    remaining_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let denom = &params.collateral_denom;
+    let fees = config.fee_recipients.token_accounts(&config.stable_coin_mint);
+    build(
+        accounts::Redeem {
+            user: *user,
+            state: pda::state(),
+            user_debt_amount: pda::user_debt_amount(user),
+            liquidity_threshold: pda::liquidity_threshold(user),
+            user_stablecoin_account: config.stablecoin_account(user),
+            user_collateral_amount: pda::user_collateral_amount(user, denom),
+            user_collateral_account: get_associated_token_address(user, &collateral.mint),
+            protocol_stablecoin_vault: pda::protocol_stablecoin_vault(),
+            protocol_collateral_vault: pda::protocol_collateral_vault(denom),
+            stable_coin_mint: config.stable_coin_mint,
+            total_collateral_amount: pda::total_collateral_amount(denom),
+            oracle_program: config.oracle_program,
+            oracle_state: config.oracle_state,
+            pyth_price_account: collateral.pyth_price_account,
+            fees_program: config.fees_program,
+            fees_state: config.fees_state,
+            stability_pool_token_account: fees.stability_pool_token_account,
+            fee_address_1_token_account: fees.fee_address_1_token_account,
+            fee_address_2_token_account: fees.fee_address_2_token_account,
+            clock: sysvar::clock::ID,
+            token_program: token::ID,
+        },
+        instruction::Redeem { params },
+        remaining_accounts,
+    )
+}
+
+/// open_trove: the system collateral block for the debt ceiling check, then the neighbor hints
+pub fn open_trove_remaining_accounts(supported: &[Collateral], hints: Vec<AccountMeta>) -> Vec<AccountMeta> {
+    RemainingAccounts::new()
+        .positional(system_collateral_block(supported))
+        .positional(hints)
+        .into_vec()
+}
+
+/// add_collateral, remove_collateral, borrow_loan, repay_loan and adjust_trove: the trove's other
+/// denoms, then the system collateral block (pass no `supported` denoms for add_collateral and
+/// repay_loan, which skip the Recovery Mode checks), then the neighbor hints
+pub fn adjust_remaining_accounts(
+    trove: &UserDebtAmount,
+    denom: &str,
+    collaterals: &[Collateral],
+    supported: &[Collateral],
+    hints: Vec<AccountMeta>,
+) -> Result<Vec<AccountMeta>> {
+    Ok(RemainingAccounts::new()
+        .positional(other_collateral_groups(trove, denom, collaterals)?)
+        .positional(system_collateral_block(supported))
+        .positional(hints)
+        .into_vec())
+}
+
+/// withdraw_liquidation_gains_to_trove: the trove's other denoms, then the gain sums of `denom`
+/// (see `gain_sum_accounts`), then the neighbor hints
+pub fn gain_to_trove_remaining_accounts(
+    trove: &UserDebtAmount,
+    denom: &str,
+    collaterals: &[Collateral],
+    stake: &UserStakeAmount,
+    user_snapshot: Option<&UserCollateralSnapshot>,
+    hints: Vec<AccountMeta>,
+) -> Result<Vec<AccountMeta>> {
+    Ok(RemainingAccounts::new()
+        .positional(other_collateral_groups(trove, denom, collaterals)?)
+        .positional(gain_sum_accounts(denom, stake, user_snapshot))
+        .positional(hints)
+        .into_vec())
+}
+
+/// stake and unstake: by address, the pool and user snapshots of every denom the oracle supports
+/// (one claim each), and the gain sums of the user's current deposit - `stake` is None before
+/// their first stake
+pub fn deposit_remaining_accounts(user: &Pubkey, stake: Option<&UserStakeAmount>, claims: &[GainClaim]) -> Vec<AccountMeta> {
+    let mut accounts = RemainingAccounts::new();
+    for claim in claims {
+        let denom = &claim.collateral.denom;
+        accounts = accounts.located([
+            AccountMeta::new_readonly(pda::stability_pool_snapshot(denom), false),
+            AccountMeta::new(pda::user_collateral_snapshot(user, denom), false),
+        ]);
+        if let Some(stake) = stake {
+            accounts = accounts.located(gain_sum_accounts(denom, stake, claim.user_snapshot));
+        }
+    }
+    accounts.into_vec()
+}
+
+/// deposit_to_share_pool: `deposit_remaining_accounts` of the share pool's deposit (one claim per
+/// denom the oracle supports, with the pool's snapshot), then by address every denom's Pyth price account
+pub fn share_pool_deposit_remaining_accounts(pool_stake: &UserStakeAmount, claims: &[GainClaim]) -> Vec<AccountMeta> {
+    RemainingAccounts::new()
+        .located(deposit_remaining_accounts(&pda::share_pool(), Some(pool_stake), claims))
+        .located(claims.iter().map(|claim| AccountMeta::new_readonly(claim.collateral.pyth_price_account, false)))
+        .into_vec()
+}
+
+/// redeem_from_share_pool: `deposit_remaining_accounts` of the share pool's deposit, then by
+/// address the user's payout accounts for every denom
+pub fn share_pool_redeem_remaining_accounts(user: &Pubkey, pool_stake: &UserStakeAmount, claims: &[GainClaim]) -> Vec<AccountMeta> {
+    let mut accounts = RemainingAccounts::new()
+        .located(deposit_remaining_accounts(&pda::share_pool(), Some(pool_stake), claims));
+    for claim in claims {
+        accounts = accounts.located(payout_accounts(user, claim.collateral));
+    }
+    accounts.into_vec()
+}
+
+/// unstake and redeem_from_share_pool's trove proof: the TroveRegistry of every `supported` denom,
+/// in the oracle's order, each followed by its `pages` in order, then by address the accounts that
+/// price every trove they list (`troves`) with its pending redistribution rewards
+pub fn trove_proof_accounts(
+    supported: &[Collateral],
+    pages: &[TroveRegistryPage],
+    troves: &[UserDebtAmount],
+) -> Result<Vec<AccountMeta>> {
+    let mut accounts = RemainingAccounts::new();
+    for collateral in supported {
+        let denom = &collateral.denom;
+        let mut denom_pages: Vec<_> = pages.iter().filter(|p| &p.denom == denom).collect();
+        denom_pages.sort_by_key(|p| p.page_index);
+        accounts = accounts
+            .positional([AccountMeta::new_readonly(pda::trove_registry(denom), false)])
+            .positional(denom_pages.iter().map(|p| AccountMeta::new_readonly(pda::trove_registry_page(denom, p.page_index), false)));
+    }
+    for trove in troves {
+        accounts = accounts.located([AccountMeta::new_readonly(pda::user_debt_amount(&trove.owner), false)]);
+        for denom in &trove.collateral_denoms {
+            let collateral = find_collateral(supported, denom)?;
+            accounts = accounts.located([
+                AccountMeta::new_readonly(pda::user_collateral_amount(&trove.owner, denom), false),
+                AccountMeta::new_readonly(pda::redistribution_snapshot(denom), false),
+                AccountMeta::new_readonly(collateral.pyth_price_account, false),
+            ]);
+        }
+    }
+    Ok(accounts.into_vec())
+}
+
+/// A trove targeted by liquidate_troves, liquidate_trove or redeem
+#[derive(Clone)]
+pub struct TroveTarget {
+    pub trove: UserDebtAmount,
+    pub registry: RegistryEntry,
+}
+
+/// liquidate_troves: a 4-account group per trove, then by address every account the
+/// liquidation touches for each denom the troves hold
+pub fn liquidate_troves_remaining_accounts(
+    config: &ProtocolConfig,
+    liquidator: &Pubkey,
+    denom: &str,
+    targets: &[TroveTarget],
+    collaterals: &[Collateral],
+    supported: &[Collateral],
+) -> Result<Vec<AccountMeta>> {
+    let collateral = find_collateral(collaterals, denom)?;
+    let mut accounts = RemainingAccounts::new();
+    for target in targets {
+        let token_account = get_associated_token_address(&target.trove.owner, &collateral.mint);
+        accounts = accounts.positional(trove_group(&target.trove.owner, denom, &token_account));
+    }
+
+    accounts = accounts.located(system_collateral_block(supported));
+    for target in targets {
+        accounts = accounts
+            .located(trove_collateral_accounts(&target.trove, collaterals)?)
+            .located(registry_accounts(&target.registry.denom, target.registry.page_index));
+        for trove_denom in &target.trove.collateral_denoms {
+            let held = find_collateral(collaterals, trove_denom)?;
+            accounts = accounts
+                .located([
+                    AccountMeta::new(pda::stability_pool_snapshot(trove_denom), false),
+                    AccountMeta::new_readonly(pda::collateral_config(trove_denom), false),
+                ])
+                .located(config.liquidation_sums(trove_denom))
+                .located(payout_accounts(liquidator, held))
+                .located(config.fee_recipients.account_metas(&held.mint));
+        }
+    }
+    Ok(accounts.into_vec())
+}
+
+/// liquidate_trove: the trove's other denoms, the system collateral block, then by address the
+/// liquidator's and fee recipients' token accounts, the vaults, the StabilityPoolSnapshot and the
+/// EpochScaleSums of every denom seized
+pub fn liquidate_trove_remaining_accounts(
+    config: &ProtocolConfig,
+    liquidator: &Pubkey,
+    denom: &str,
+    trove: &UserDebtAmount,
+    collaterals: &[Collateral],
+    supported: &[Collateral],
+) -> Result<Vec<AccountMeta>> {
+    let mut accounts = RemainingAccounts::new()
+        .positional(other_collateral_groups(trove, denom, collaterals)?)
+        .positional(system_collateral_block(supported));
+    for trove_denom in &trove.collateral_denoms {
+        let held = find_collateral(collaterals, trove_denom)?;
+        accounts = accounts
+            .located([AccountMeta::new(pda::stability_pool_snapshot(trove_denom), false)])
+            .located(config.liquidation_sums(trove_denom))
+            .located(payout_accounts(liquidator, held))
+            .located(config.fee_recipients.account_metas(&held.mint));
+    }
+    Ok(accounts.into_vec())
+}
+
+/// redeem: a 4-account group per trove, riskiest first, then by address the accounts that
+/// reprice the troves and pay the redeemer every denom they hold
+pub fn redeem_remaining_accounts(
+    redeemer: &Pubkey,
+    denom: &str,
+    targets: &[UserDebtAmount],
+    collaterals: &[Collateral],
+) -> Result<Vec<AccountMeta>> {
+    let collateral = find_collateral(collaterals, denom)?;
+    let mut accounts = RemainingAccounts::new();
+    for trove in targets {
+        let token_account = get_associated_token_address(&trove.owner, &collateral.mint);
+        accounts = accounts.positional(trove_group(&trove.owner, denom, &token_account));
+    }
+    for trove in targets {
+        accounts = accounts.located(trove_collateral_accounts(trove, collaterals)?);
+        for trove_denom in &trove.collateral_denoms {
+            accounts = accounts.located(payout_accounts(redeemer, find_collateral(collaterals, trove_denom)?));
+        }
+    }
+    Ok(accounts.into_vec())
+}
+
+/// refresh_icrs: a [UserDebtAmount, LiquidityThreshold] pair per trove, then by address the
+/// accounts that reprice them
+pub fn refresh_icrs_remaining_accounts(troves: &[UserDebtAmount], collaterals: &[Collateral]) -> Result<Vec<AccountMeta>> {
+    let mut accounts = RemainingAccounts::new();
+    for trove in troves {
+        accounts = accounts.positional([
+            AccountMeta::new(pda::user_debt_amount(&trove.owner), false),
+            AccountMeta::new(pda::liquidity_threshold(&trove.owner), false),
+        ]);
+    }
+    for trove in troves {
+        accounts = accounts.located(trove_collateral_accounts(trove, collaterals)?);
+    }
+    Ok(accounts.into_vec())
+}
@@ -0,0 +1,279 @@
+//! remaining_accounts layouts of the protocol instructions
+//!
+//! Positional blocks (the trove groups, the system collateral block, neighbor hints) must be
+//! passed in the order the handler reads them. Everything else is located by address, so it
+//! only has to be present once - `RemainingAccounts` keeps those unique.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::AccountMeta;
+use anchor_spl::associated_token::get_associated_token_address;
+use aerospacer_protocol::error::AerospacerProtocolError;
+use aerospacer_protocol::state::UserDebtAmount;
+use crate::pda;
+
+/// A collateral denom as the instructions see it: its mint and the Pyth price account the
+/// oracle has configured for it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Collateral {
+    pub denom: String,
+    pub mint: Pubkey,
+    pub pyth_price_account: Pubkey,
+}
+
+/// Find the Collateral of `denom` among the known ones
+pub fn find_collateral<'a>(collaterals: &'a [Collateral], denom: &str) -> Result<&'a Collateral> {
+    collaterals
+        .iter()
+        .find(|c| c.denom == denom)
+        .ok_or_else(|| AerospacerProtocolError::MissingCollateralConfig.into())
+}
+
+/// The token accounts of the aerospacer-fees recipients
+///
+/// Mirrors the program's FeeRecipientAccounts: while staking is enabled only the stake contract
+/// is paid and fills all three slots, otherwise the stability pool slot repeats fee address 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeRecipients {
+    pub is_stake_enabled: bool,
+    pub stake_contract_address: Pubkey,
+    pub fee_address_1: Pubkey,
+    pub fee_address_2: Pubkey,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeRecipientTokenAccounts {
+    pub stability_pool_token_account: Pubkey,
+    pub fee_address_1_token_account: Pubkey,
+    pub fee_address_2_token_account: Pubkey,
+}
+
+impl FeeRecipients {
+    pub fn from_fee_state(fee_state: &aerospacer_fees::state::FeeStateAccount) -> Self {
+        Self {
+            is_stake_enabled: fee_state.is_stake_enabled,
+            stake_contract_address: fee_state.stake_contract_address,
+            fee_address_1: fee_state.fee_address_1,
+            fee_address_2: fee_state.fee_address_2,
+        }
+    }
+
+    /// The recipients' associated token accounts for `mint`
+    pub fn token_accounts(&self, mint: &Pubkey) -> FeeRecipientTokenAccounts {
+        if self.is_stake_enabled {
+            let stability_pool = get_associated_token_address(&self.stake_contract_address, mint);
+            FeeRecipientTokenAccounts {
+                stability_pool_token_account: stability_pool,
+                fee_address_1_token_account: stability_pool,
+                fee_address_2_token_account: stability_pool,
+            }
+        } else {
+            let fee_address_1 = get_associated_token_address(&self.fee_address_1, mint);
+            FeeRecipientTokenAccounts {
+                stability_pool_token_account: fee_address_1,
+                fee_address_1_token_account: fee_address_1,
+                fee_address_2_token_account: get_associated_token_address(&self.fee_address_2, mint),
+            }
+        }
+    }
+
+    /// The token accounts for `mint` as remaining accounts located by address
+    pub fn account_metas(&self, mint: &Pubkey) -> Vec<AccountMeta> {
+        let accounts = self.token_accounts(mint);
+        vec![
+            AccountMeta::new(accounts.stability_pool_token_account, false),
+            AccountMeta::new(accounts.fee_address_1_token_account, false),
+            AccountMeta::new(accounts.fee_address_2_token_account, false),
+        ]
+    }
+}
+
+/// Builder for an instruction's remaining_accounts
+#[derive(Clone, Debug, Default)]
+pub struct RemainingAccounts {
+    metas: Vec<AccountMeta>,
+}
+
+impl RemainingAccounts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a positional block as is
+    pub fn positional(mut self, metas: impl IntoIterator<Item = AccountMeta>) -> Self {
+        self.metas.extend(metas);
+        self
+    }
+
+    /// Append accounts the handler locates by address, skipping those already present
+    /// (an account already present is made writable if any copy needs to be)
+    pub fn located(mut self, metas: impl IntoIterator<Item = AccountMeta>) -> Self {
+        for meta in metas {
+            match self.metas.iter_mut().find(|m| m.pubkey == meta.pubkey) {
+                Some(existing) => existing.is_writable |= meta.is_writable,
+                None => self.metas.push(meta),
+            }
+        }
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.metas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.metas.is_empty()
+    }
+
+    pub fn into_vec(self) -> Vec<AccountMeta> {
+        self.metas
+    }
+}
+
+/// The 4-account group liquidate_troves and redeem read per trove:
+/// [UserDebtAmount, UserCollateralAmount(denom), LiquidityThreshold, token account]
+pub fn trove_group(owner: &Pubkey, denom: &str, token_account: &Pubkey) -> [AccountMeta; 4] {
+    [
+        AccountMeta::new(pda::user_debt_amount(owner), false),
+        AccountMeta::new(pda::user_collateral_amount(owner, denom), false),
+        AccountMeta::new(pda::liquidity_threshold(owner), false),
+        AccountMeta::new(*token_account, false),
+    ]
+}
+
+/// [UserCollateralAmount, RedistributionSnapshot, pyth_price_account, CollateralConfig] for
+/// every denom the trove holds other than `denom`, in UserDebtAmount.collateral_denoms order
+/// (see trove_management::load_other_collateral)
+pub fn other_collateral_groups(
+    trove: &UserDebtAmount,
+    denom: &str,
+    collaterals: &[Collateral],
+) -> Result<Vec<AccountMeta>> {
+    let mut metas = Vec::new();
+    for other in trove.collateral_denoms.iter().filter(|d| *d != denom) {
+        let collateral = find_collateral(collaterals, other)?;
+        metas.push(AccountMeta::new(pda::user_collateral_amount(&trove.owner, other), false));
+        metas.push(AccountMeta::new(pda::redistribution_snapshot(other), false));
+        metas.push(AccountMeta::new_readonly(collateral.pyth_price_account, false));
+        metas.push(AccountMeta::new_readonly(pda::collateral_config(other), false));
+    }
+    Ok(metas)
+}
+
+/// [TotalCollateralAmount, pyth_price_account] for every denom the oracle supports
+/// (see trove_management::load_system_collateral_block)
+pub fn system_collateral_block(supported: &[Collateral]) -> Vec<AccountMeta> {
+    supported
+        .iter()
+        .flat_map(|c| {
+            [
+                AccountMeta::new(pda::total_collateral_amount(&c.denom), false),
+                AccountMeta::new_readonly(c.pyth_price_account, false),
+            ]
+        })
+        .collect()
+}
+
+/// The LiquidityThreshold PDAs of a trove's neighbors in the sorted list
+/// (a next hint is only read after a prev hint)
+pub fn neighbor_hints(prev_owner: Option<&Pubkey>, next_owner: Option<&Pubkey>) -> Vec<AccountMeta> {
+    let Some(prev) = prev_owner else {
+        return Vec::new();
+    };
+    let mut metas = vec![AccountMeta::new_readonly(pda::liquidity_threshold(prev), false)];
+    if let Some(next) = next_owner {
+        metas.push(AccountMeta::new_readonly(pda::liquidity_threshold(next), false));
+    }
+    metas
+}
+
+/// [UserCollateralAmount, RedistributionSnapshot, protocol_collateral_vault, the owner's token
+/// account, TotalCollateralAmount] for every denom close_trove withdraws besides `denom`
+pub fn close_trove_groups(
+    trove: &UserDebtAmount,
+    denom: &str,
+    collaterals: &[Collateral],
+) -> Result<Vec<AccountMeta>> {
+    let mut metas = Vec::new();
+    for other in trove.collateral_denoms.iter().filter(|d| *d != denom) {
+        let collateral = find_collateral(collaterals, other)?;
+        metas.push(AccountMeta::new(pda::user_collateral_amount(&trove.owner, other), false));
+        metas.push(AccountMeta::new(pda::redistribution_snapshot(other), false));
+        metas.push(AccountMeta::new(pda::protocol_collateral_vault(other), false));
+        metas.push(AccountMeta::new(get_associated_token_address(&trove.owner, &collateral.mint), false));
+        metas.push(AccountMeta::new(pda::total_collateral_amount(other), false));
+    }
+    Ok(metas)
+}
+
+/// Accounts a handler needs to reprice a trove and apply its redistribution rewards:
+/// the UserCollateralAmount, RedistributionSnapshot and Pyth price account of every denom it holds
+pub fn trove_collateral_accounts(trove: &UserDebtAmount, collaterals: &[Collateral]) -> Result<Vec<AccountMeta>> {
+    let mut metas = Vec::new();
+    for denom in &trove.collateral_denoms {
+        let collateral = find_collateral(collaterals, denom)?;
+        metas.push(AccountMeta::new(pda::user_collateral_amount(&trove.owner, denom), false));
+        metas.push(AccountMeta::new(pda::redistribution_snapshot(denom), false));
+        metas.push(AccountMeta::new_readonly(collateral.pyth_price_account, false));
+    }
+    Ok(metas)
+}
+
+/// Accounts DenomPayout locates to pay `recipient` collateral of `collateral`'s denom:
+/// the protocol_collateral_vault, TotalCollateralAmount and the recipient's token account
+pub fn payout_accounts(recipient: &Pubkey, collateral: &Collateral) -> [AccountMeta; 3] {
+    [
+        AccountMeta::new(pda::protocol_collateral_vault(&collateral.denom), false),
+        AccountMeta::new(pda::total_collateral_amount(&collateral.denom), false),
+        AccountMeta::new(get_associated_token_address(recipient, &collateral.mint), false),
+    ]
+}
+
+/// A TroveRegistryPage and its TroveRegistry
+pub fn registry_accounts(denom: &str, page_index: u32) -> [AccountMeta; 2] {
+    [
+        AccountMeta::new(pda::trove_registry_page(denom, page_index), false),
+        AccountMeta::new(pda::trove_registry(denom), false),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_located_accounts_are_unique() {
+        let owner = Pubkey::new_unique();
+        let token_account = Pubkey::new_unique();
+        let accounts = RemainingAccounts::new()
+            .positional(trove_group(&owner, "SOL", &token_account))
+            .located([
+                AccountMeta::new_readonly(pda::user_collateral_amount(&owner, "SOL"), false),
+                AccountMeta::new(pda::redistribution_snapshot("SOL"), false),
+                AccountMeta::new_readonly(pda::redistribution_snapshot("SOL"), false),
+            ])
+            .into_vec();
+
+        assert_eq!(accounts.len(), 5);
+        assert_eq!(accounts[0].pubkey, pda::user_debt_amount(&owner));
+        assert_eq!(accounts[3].pubkey, token_account);
+        assert!(accounts[4].is_writable);
+    }
+
+    #[test]
+    fn test_other_collateral_groups_skip_denom() {
+        let sol = Collateral { denom: "SOL".to_string(), mint: Pubkey::new_unique(), pyth_price_account: Pubkey::new_unique() };
+        let eth = Collateral { denom: "ETH".to_string(), mint: Pubkey::new_unique(), pyth_price_account: Pubkey::new_unique() };
+        let trove = UserDebtAmount {
+            owner: Pubkey::new_unique(),
+            amount: 0,
+            collateral_denoms: vec!["SOL".to_string(), "ETH".to_string()],
+        };
+
+        let metas = other_collateral_groups(&trove, "SOL", &[sol.clone(), eth.clone()]).unwrap();
+        assert_eq!(metas.len(), 4);
+        assert_eq!(metas[0].pubkey, pda::user_collateral_amount(&trove.owner, "ETH"));
+        assert_eq!(metas[2].pubkey, eth.pyth_price_account);
+
+        assert!(other_collateral_groups(&trove, "SOL", &[sol]).is_err());
+    }
+}
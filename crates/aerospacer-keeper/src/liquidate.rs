@@ -0,0 +1,156 @@
+//! Building and sending liquidate_troves transactions
+//!
+//! Liquidatable troves are grouped by the denom they are liquidated through and batched riskiest
+//! first. A batch whose transaction would not fit in a packet is split in half until it does.
+
+use anyhow::{Context, Result};
+use aerospacer_client::aerospacer_protocol::instructions::LiquidateTrovesParams;
+use aerospacer_client::protocol::{liquidate_troves, liquidate_troves_remaining_accounts};
+use aerospacer_client::remaining::find_collateral;
+use anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction;
+use anchor_client::solana_sdk::instruction::Instruction;
+use anchor_client::solana_sdk::packet::PACKET_DATA_SIZE;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anchor_client::solana_sdk::signature::Signer;
+use anchor_client::solana_sdk::transaction::Transaction;
+use anchor_client::Program;
+use anchor_spl::associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use anchor_spl::token;
+use std::ops::Deref;
+use crate::scan::{Market, TroveRisk};
+
+const LIQUIDATION_COMPUTE_UNITS: u32 = 1_400_000;
+
+pub struct Liquidator<'a, C> {
+    pub program: &'a Program<C>,
+    pub market: &'a Market,
+    pub batch_size: usize,
+    pub dry_run: bool,
+}
+
+impl<C: Deref<Target = impl Signer> + Clone> Liquidator<'_, C> {
+    /// Liquidate every liquidatable trove in `troves` (sorted riskiest first)
+    pub fn run(&self, troves: &[TroveRisk]) -> Result<()> {
+        let mut denoms: Vec<&str> = Vec::new();
+        for trove in troves.iter().filter(|t| t.is_liquidatable()) {
+            if !denoms.contains(&trove.denom.as_str()) {
+                denoms.push(&trove.denom);
+            }
+        }
+
+        for denom in denoms {
+            let batch: Vec<TroveRisk> = troves
+                .iter()
+                .filter(|t| t.is_liquidatable() && t.denom == denom)
+                .cloned()
+                .collect();
+            for chunk in batch.chunks(self.batch_size.max(1)) {
+                if let Err(e) = self.liquidate(denom, chunk) {
+                    println!("liquidation of {} {} trove(s) failed: {:#}", chunk.len(), denom, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn liquidate(&self, denom: &str, troves: &[TroveRisk]) -> Result<()> {
+        let instructions = self.instructions(denom, troves)?;
+        let transaction = self.transaction(&instructions)?;
+
+        let size = bincode::serialize(&transaction)?.len();
+        if size > PACKET_DATA_SIZE && troves.len() > 1 {
+            let (first, second) = troves.split_at(troves.len() / 2);
+            self.liquidate(denom, first)?;
+            return self.liquidate(denom, second);
+        }
+
+        let owners: Vec<String> = troves.iter().map(|t| t.target.trove.owner.to_string()).collect();
+        if self.dry_run {
+            let simulation = self.program.rpc().simulate_transaction(&transaction)?;
+            match simulation.value.err {
+                None => println!("[dry-run] liquidate_troves {} [{}] would succeed ({} bytes)", denom, owners.join(", "), size),
+                Some(err) => {
+                    println!("[dry-run] liquidate_troves {} [{}] would fail: {}", denom, owners.join(", "), err);
+                    for log in simulation.value.logs.unwrap_or_default() {
+                        println!("    {}", log);
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        let signature = self
+            .program
+            .rpc()
+            .send_and_confirm_transaction(&transaction)
+            .with_context(|| format!("liquidate_troves {} [{}]", denom, owners.join(", ")))?;
+        println!("liquidated {} [{}]: {}", denom, owners.join(", "), signature);
+        Ok(())
+    }
+
+    /// The liquidator's token accounts (created if missing), then liquidate_troves
+    fn instructions(&self, denom: &str, troves: &[TroveRisk]) -> Result<Vec<Instruction>> {
+        let market = self.market;
+        let liquidator = self.program.payer();
+        let targets: Vec<_> = troves.iter().map(|t| t.target.clone()).collect();
+
+        let mut mints = vec![market.config.stable_coin_mint];
+        for target in &targets {
+            for held in &target.trove.collateral_denoms {
+                let mint = find_collateral(&market.collaterals, held)?.mint;
+                if !mints.contains(&mint) {
+                    mints.push(mint);
+                }
+            }
+        }
+
+        let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(LIQUIDATION_COMPUTE_UNITS)];
+        instructions.extend(mints.iter().map(|mint| create_associated_token_account_idempotent(&liquidator, &liquidator, mint, &token::ID)));
+
+        let remaining_accounts = liquidate_troves_remaining_accounts(
+            &market.config,
+            &liquidator,
+            denom,
+            &targets,
+            &market.collaterals,
+            &market.collaterals,
+        )?;
+        let params = LiquidateTrovesParams {
+            liquidation_list: targets.iter().map(|t| t.trove.owner).collect(),
+            collateral_denom: denom.to_string(),
+        };
+        let collateral = find_collateral(&market.collaterals, denom)?;
+        instructions.push(liquidate_troves(&market.config, &liquidator, collateral, params, remaining_accounts));
+        Ok(instructions)
+    }
+
+    fn transaction(&self, instructions: &[Instruction]) -> Result<Transaction> {
+        let request = instructions
+            .iter()
+            .cloned()
+            .fold(self.program.request(), |request, ix| request.instruction(ix));
+        Ok(request.signed_transaction()?)
+    }
+}
+
+/// Print the troves of a pass, riskiest first
+pub fn report(market: &Market, troves: &[TroveRisk]) {
+    println!(
+        "{} trove(s), total debt {}, recovery mode: {}",
+        troves.len(),
+        market.state.total_debt_amount,
+        market.recovery_mode
+    );
+    for trove in troves {
+        let owner: &Pubkey = &trove.target.trove.owner;
+        println!(
+            "  {} {:>6} debt {:>16} ICR {} threshold {}{}",
+            owner,
+            trove.denom,
+            trove.debt,
+            trove.icr,
+            trove.liquidation_threshold,
+            if trove.is_liquidatable() { "  LIQUIDATABLE" } else { "" }
+        );
+    }
+}
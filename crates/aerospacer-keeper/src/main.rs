@@ -0,0 +1,141 @@
+//! Liquidation keeper for the Aerospacer protocol
+//!
+//! `run` loads every trove and the oracle's Pyth prices, prices the troves with the program's
+//! own PriceCalculator and risk ratios, and sends liquidate_troves for those below their
+//! liquidation threshold, riskiest first. With `--dry-run` the transactions are only simulated.
+//!
+//! `mock-pyth` writes a Pyth price account for `solana-test-validator --account`, so the keeper
+//! can be exercised against a local validator:
+//!
+//! ```text
+//! aerospacer-keeper mock-pyth --address <PYTH_ACCOUNT> --price 15000000000 --expo -8 --out sol.json
+//! solana-test-validator --account <PYTH_ACCOUNT> sol.json ...
+//! aerospacer-keeper run --dry-run --once
+//! ```
+
+mod liquidate;
+mod mock_pyth;
+mod prices;
+mod scan;
+
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use anchor_client::solana_sdk::commitment_config::CommitmentConfig;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anchor_client::solana_sdk::signature::read_keypair_file;
+use anchor_client::{Client, Cluster};
+use clap::{Args, Parser, Subcommand};
+use crate::liquidate::{report, Liquidator};
+use crate::scan::Market;
+
+/// The devnet Pyth oracle program, which owns the price accounts the tests use
+const PYTH_PROGRAM: &str = "gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s";
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Find and liquidate undercollateralized troves
+    Run(RunArgs),
+    /// Write a mock Pyth price account for solana-test-validator
+    MockPyth(MockPythArgs),
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// RPC endpoint
+    #[arg(long, default_value = "http://127.0.0.1:8899")]
+    rpc_url: String,
+    /// Keypair paying for and signing the liquidations (default ~/.config/solana/id.json)
+    #[arg(long)]
+    keypair: Option<PathBuf>,
+    /// Simulate the liquidations instead of sending them
+    #[arg(long)]
+    dry_run: bool,
+    /// Most troves per liquidate_troves (batches too large for a transaction are split)
+    #[arg(long, default_value_t = 5)]
+    batch_size: usize,
+    /// Seconds between passes
+    #[arg(long, default_value_t = 30)]
+    interval: u64,
+    /// Run a single pass and exit
+    #[arg(long)]
+    once: bool,
+}
+
+#[derive(Args)]
+struct MockPythArgs {
+    /// Address of the price account
+    #[arg(long)]
+    address: Pubkey,
+    /// Aggregate price, scaled by 10^expo
+    #[arg(long)]
+    price: i64,
+    #[arg(long, default_value_t = -8, allow_hyphen_values = true)]
+    expo: i32,
+    /// Confidence interval (the oracle rejects less than 100)
+    #[arg(long, default_value_t = 1_000)]
+    conf: u64,
+    #[arg(long, default_value_t = 0)]
+    publish_time: i64,
+    /// Program owning the account
+    #[arg(long, default_value = PYTH_PROGRAM)]
+    owner: Pubkey,
+    /// Output file
+    #[arg(long)]
+    out: PathBuf,
+}
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Run(args) => run(args),
+        Command::MockPyth(args) => mock_pyth(args),
+    }
+}
+
+fn run(args: RunArgs) -> Result<()> {
+    let keypair_path = match args.keypair {
+        Some(path) => path,
+        None => PathBuf::from(std::env::var("HOME")?).join(".config/solana/id.json"),
+    };
+    let payer = read_keypair_file(&keypair_path)
+        .map_err(|e| anyhow!("reading keypair {}: {}", keypair_path.display(), e))?;
+    let cluster: Cluster = args.rpc_url.parse().map_err(|e| anyhow!("invalid RPC url: {}", e))?;
+    let client = Client::new_with_options(cluster, Rc::new(payer), CommitmentConfig::confirmed());
+    let program = client.program(aerospacer_client::aerospacer_protocol::ID)?;
+
+    loop {
+        let market = Market::load(&program)?;
+        let troves = market.scan_troves(&program)?;
+        report(&market, &troves);
+
+        Liquidator {
+            program: &program,
+            market: &market,
+            batch_size: args.batch_size,
+            dry_run: args.dry_run,
+        }
+        .run(&troves)?;
+
+        if args.once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(args.interval));
+    }
+}
+
+fn mock_pyth(args: MockPythArgs) -> Result<()> {
+    let data = mock_pyth::price_account_data(args.price, args.expo, args.conf, args.publish_time);
+    let json = mock_pyth::account_json(&args.address, &args.owner, &data)?;
+    std::fs::write(&args.out, json).with_context(|| format!("writing {}", args.out.display()))?;
+    println!("wrote Pyth price account {} to {}", args.address, args.out.display());
+    Ok(())
+}
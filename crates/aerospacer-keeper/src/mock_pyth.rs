@@ -0,0 +1,78 @@
+//! Mock Pyth price accounts for a local test validator
+//!
+//! Writes the account as JSON in the format `solana-test-validator --account <ADDRESS> <FILE>`
+//! loads, so a local validator can serve prices the oracle accepts without a Pyth publisher.
+
+use std::mem::size_of;
+use anyhow::Result;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anchor_client::solana_sdk::rent::Rent;
+use base64::Engine;
+use pyth_sdk_solana::state::{
+    AccountType, CorpAction, PriceInfo, PriceStatus, PriceType, SolanaPriceAccount, MAGIC, VERSION_2,
+};
+use serde_json::json;
+
+/// Data of a trading Pyth price account quoting `price` × 10^`expo` ± `conf`
+pub fn price_account_data(price: i64, expo: i32, conf: u64, publish_time: i64) -> Vec<u8> {
+    let account = SolanaPriceAccount {
+        magic: MAGIC,
+        ver: VERSION_2,
+        atype: AccountType::Price as u32,
+        size: size_of::<SolanaPriceAccount>() as u32,
+        ptype: PriceType::Price,
+        expo,
+        timestamp: publish_time,
+        agg: PriceInfo {
+            price,
+            conf,
+            status: PriceStatus::Trading,
+            corp_act: CorpAction::NoCorpAct,
+            pub_slot: 0,
+        },
+        ..Default::default()
+    };
+    bytemuck::bytes_of(&account).to_vec()
+}
+
+/// The price account as a test validator account file
+pub fn account_json(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<String> {
+    let account = json!({
+        "pubkey": address.to_string(),
+        "account": {
+            "lamports": Rent::default().minimum_balance(data.len()),
+            "data": [base64::engine::general_purpose::STANDARD.encode(data), "base64"],
+            "owner": owner.to_string(),
+            "executable": false,
+            "rentEpoch": 0,
+            "space": data.len(),
+        },
+    });
+    Ok(serde_json::to_string_pretty(&account)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aerospacer_client::aerospacer_oracle::state::CollateralData;
+    use crate::prices::read_price;
+
+    #[test]
+    fn test_mock_price_reads_like_the_oracle() {
+        let address = Pubkey::new_unique();
+        let collateral = CollateralData {
+            denom: "SOL".to_string(),
+            decimal: 9,
+            price_id: String::new(),
+            configured_at: 0,
+            pyth_price_account: address,
+        };
+
+        // $150.00000000 for one SOL: 9 + 8 - 6 implied decimals per lamport in aUSD base units
+        let data = price_account_data(15_000_000_000, -8, 1_000, 0);
+        assert_eq!(read_price(&collateral, &address, &data).unwrap(), ("SOL".to_string(), 15_000_000_000, 11));
+
+        let low_confidence = price_account_data(15_000_000_000, -8, 10, 0);
+        assert!(read_price(&collateral, &address, &low_confidence).is_err());
+    }
+}
@@ -0,0 +1,29 @@
+//! Collateral prices read straight from the Pyth accounts the oracle is configured with
+//!
+//! Mirrors the oracle's get_price and the protocol's OracleContext: the aggregate price must be
+//! positive with a confidence of at least 100, its decimals are the Pyth exponent, and it is
+//! normalized with the denom's configured token decimals before any ICR is computed.
+
+use anyhow::{anyhow, ensure, Result};
+use aerospacer_client::aerospacer_oracle::state::CollateralData;
+use aerospacer_client::aerospacer_protocol::oracle::PriceCalculator;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use pyth_sdk_solana::state::{load_price_account, SolanaPriceAccount};
+
+/// A normalized price as the protocol caches it: (denom, price, decimal)
+pub type Price = (String, u64, u8);
+
+/// Decode a Pyth price account and normalize its price for `collateral`
+pub fn read_price(collateral: &CollateralData, price_account: &Pubkey, data: &[u8]) -> Result<Price> {
+    let account: &SolanaPriceAccount = load_price_account(data)
+        .map_err(|e| anyhow!("{}: invalid Pyth price account {}: {:?}", collateral.denom, price_account, e))?;
+    let price = account.to_price_feed(price_account).get_price_unchecked();
+
+    ensure!(price.price > 0, "{}: non-positive Pyth price {}", collateral.denom, price.price);
+    ensure!(price.conf >= 100, "{}: Pyth confidence {} below 100", collateral.denom, price.conf);
+
+    let price_decimal = u8::try_from(-price.expo)
+        .map_err(|_| anyhow!("{}: unsupported Pyth exponent {}", collateral.denom, price.expo))?;
+    let (normalized, decimal) = PriceCalculator::normalize_price(price.price, price_decimal, collateral.decimal)?;
+    Ok((collateral.denom.clone(), normalized as u64, decimal))
+}
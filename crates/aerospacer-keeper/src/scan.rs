@@ -0,0 +1,176 @@
+//! Loading the protocol and finding liquidatable troves
+//!
+//! Troves are judged the way liquidate_troves judges them: pending redistribution rewards are
+//! applied first, the ICR comes from PriceCalculator over every denom the trove holds, and the
+//! threshold from trove_risk_ratios, raised to the CCR while the system is in Recovery Mode.
+
+use anyhow::{anyhow, Context, Result};
+use aerospacer_client::aerospacer_fees::state::FeeStateAccount;
+use aerospacer_client::aerospacer_oracle::state::OracleStateAccount;
+use aerospacer_client::aerospacer_protocol::decimal::Decimal;
+use aerospacer_client::aerospacer_protocol::oracle::PriceCalculator;
+use aerospacer_client::aerospacer_protocol::redistribution::apply_position_rewards;
+use aerospacer_client::aerospacer_protocol::state::*;
+use aerospacer_client::aerospacer_protocol::trove_management::{trove_risk_ratios, SystemCollateral};
+use aerospacer_client::aerospacer_protocol::utils::get_liquidation_threshold;
+use aerospacer_client::pda;
+use aerospacer_client::protocol::{ProtocolConfig, RegistryEntry, TroveTarget};
+use aerospacer_client::remaining::Collateral;
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anchor_client::solana_sdk::signature::Signer;
+use anchor_client::Program;
+use anchor_spl::token::TokenAccount;
+use std::ops::Deref;
+use crate::prices::{read_price, Price};
+
+/// Protocol-wide state a liquidation pass works from
+pub struct Market {
+    pub state: StateAccount,
+    pub config: ProtocolConfig,
+    pub collaterals: Vec<Collateral>, // Every denom the oracle supports, in its stored order
+    pub prices: Vec<Price>,
+    pub configs: Vec<CollateralConfig>,
+    pub recovery_mode: bool,
+}
+
+/// A trove priced against the current market
+#[derive(Clone)]
+pub struct TroveRisk {
+    pub target: TroveTarget,
+    pub denom: String,         // The denom liquidate_troves is called with for this trove
+    pub debt: u64,             // Including pending redistributed debt
+    pub icr: Decimal,
+    pub liquidation_threshold: Decimal,
+}
+
+impl TroveRisk {
+    pub fn is_liquidatable(&self) -> bool {
+        self.icr < self.liquidation_threshold
+    }
+}
+
+impl Market {
+    pub fn load<C: Deref<Target = impl Signer> + Clone>(program: &Program<C>) -> Result<Self> {
+        let state: StateAccount = program.account(pda::state()).context("loading protocol state")?;
+        let oracle_state: OracleStateAccount = program
+            .account(state.oracle_state_addr)
+            .context("loading oracle state")?;
+        let fee_state: FeeStateAccount = program
+            .account(state.fee_state_addr)
+            .context("loading fee state")?;
+        let config = ProtocolConfig::new(&state, &fee_state);
+
+        let rpc = program.rpc();
+        let mut collaterals = Vec::new();
+        let mut prices = Vec::new();
+        for collateral in &oracle_state.collateral_data {
+            let price_account = rpc
+                .get_account(&collateral.pyth_price_account)
+                .with_context(|| format!("loading the {} Pyth price account", collateral.denom))?;
+            prices.push(read_price(collateral, &collateral.pyth_price_account, &price_account.data)?);
+
+            // No vault yet means no trove can hold the denom, so its mint is never used
+            let mint = program
+                .account::<TokenAccount>(pda::protocol_collateral_vault(&collateral.denom))
+                .map(|vault| vault.mint)
+                .unwrap_or_default();
+            collaterals.push(Collateral {
+                denom: collateral.denom.clone(),
+                mint,
+                pyth_price_account: collateral.pyth_price_account,
+            });
+        }
+
+        let totals: Vec<(Pubkey, TotalCollateralAmount)> = program.accounts(Vec::new())?;
+        let system_collateral = SystemCollateral {
+            amounts: collaterals
+                .iter()
+                .map(|c| {
+                    let amount = totals.iter().find(|(_, t)| t.denom == c.denom).map_or(0, |(_, t)| t.amount);
+                    (c.denom.clone(), amount)
+                })
+                .collect(),
+            prices: prices.clone(),
+        };
+        let recovery_mode = system_collateral.is_recovery_mode(state.total_debt_amount)?;
+
+        let configs = program
+            .accounts::<CollateralConfig>(Vec::new())?
+            .into_iter()
+            .map(|(_, c)| c)
+            .collect();
+
+        Ok(Self { state, config, collaterals, prices, configs, recovery_mode })
+    }
+
+    /// Price every open trove, riskiest first
+    pub fn scan_troves<C: Deref<Target = impl Signer> + Clone>(&self, program: &Program<C>) -> Result<Vec<TroveRisk>> {
+        let troves: Vec<(Pubkey, UserDebtAmount)> = program.accounts(Vec::new())?;
+        let positions: Vec<(Pubkey, UserCollateralAmount)> = program.accounts(Vec::new())?;
+        let snapshots: Vec<(Pubkey, RedistributionSnapshot)> = program.accounts(Vec::new())?;
+        let pages: Vec<(Pubkey, TroveRegistryPage)> = program.accounts(Vec::new())?;
+
+        let mut risks = Vec::new();
+        for (_, trove) in troves.into_iter().filter(|(_, t)| t.amount > 0) {
+            let Some(page) = pages.iter().map(|(_, p)| p).find(|p| p.owners.contains(&trove.owner)) else {
+                println!("skipping trove {}: not listed in any TroveRegistryPage", trove.owner);
+                continue;
+            };
+            let registry = RegistryEntry { denom: page.denom.clone(), page_index: page.page_index };
+            risks.push(self.price_trove(trove, registry, &positions, &snapshots)?);
+        }
+
+        risks.sort_by_key(|r| r.icr);
+        Ok(risks)
+    }
+
+    fn price_trove(
+        &self,
+        trove: UserDebtAmount,
+        registry: RegistryEntry,
+        positions: &[(Pubkey, UserCollateralAmount)],
+        snapshots: &[(Pubkey, RedistributionSnapshot)],
+    ) -> Result<TroveRisk> {
+        let mut debt = trove.amount;
+        let mut collateral_amounts = Vec::new();
+        let mut configs = Vec::new();
+        for denom in &trove.collateral_denoms {
+            let mut position = positions
+                .iter()
+                .find(|(_, p)| p.owner == trove.owner && p.denom == *denom)
+                .map(|(_, p)| p.clone())
+                .ok_or_else(|| anyhow!("trove {} has no {} UserCollateralAmount", trove.owner, denom))?;
+            if let Some((_, snapshot)) = snapshots.iter().find(|(_, s)| s.denom == *denom) {
+                let pending_debt = apply_position_rewards(&mut position, &mut snapshot.clone())?;
+                debt = debt.saturating_add(pending_debt);
+            }
+            collateral_amounts.push((denom.clone(), position.amount));
+
+            let config = self
+                .configs
+                .iter()
+                .find(|c| c.denom == *denom)
+                .ok_or_else(|| anyhow!("no CollateralConfig for {}", denom))?;
+            configs.push(config.clone());
+        }
+
+        let icr = PriceCalculator::calculate_trove_icr(&collateral_amounts, debt, &self.prices)?;
+        let risk_ratios = trove_risk_ratios(&collateral_amounts, &self.prices, &configs, self.state.minimum_collateral_ratio)?;
+        let liquidation_threshold = get_liquidation_threshold(self.recovery_mode, risk_ratios.liquidation_threshold)?;
+
+        // Liquidate through the registry denom when the trove still holds it
+        let denom = if trove.holds_denom(&registry.denom) {
+            registry.denom.clone()
+        } else {
+            trove.collateral_denoms.first().cloned().unwrap_or_default()
+        };
+
+        Ok(TroveRisk {
+            target: TroveTarget { trove, registry },
+            denom,
+            debt,
+            icr,
+            liquidation_threshold,
+        })
+    }
+}
@@ -0,0 +1,12 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct FeeDistributed {
+    pub payer: Pubkey,
+    pub mint: Pubkey,
+    pub fee_amount: u64,
+    pub stability_pool_amount: u64,  // Paid to the stake contract while staking is enabled
+    pub fee_address_1_amount: u64,
+    pub fee_address_2_amount: u64,
+    pub total_fees_collected: u64,
+}
@@ -2,6 +2,7 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount, transfer, Transfer};
 use crate::state::FeeStateAccount;
 use crate::error::AerospacerFeesError;
+use crate::events::FeeDistributed;
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct DistributeFeeParams {
@@ -73,7 +74,7 @@ pub fn handler(ctx: Context<DistributeFee>, params: DistributeFeeParams) -> Resu
     msg!("Distributing fee amount: {}", fee_amount);
     msg!("Total fees collected: {}", state.total_fees_collected);
     
-    if state.is_stake_enabled {
+    let (stability_pool_amount, fee_address_1_amount, fee_address_2_amount) = if state.is_stake_enabled {
         // Validate stake contract address is set
         require!(
             state.stake_contract_address != Pubkey::default(),
@@ -100,6 +101,7 @@ pub fn handler(ctx: Context<DistributeFee>, params: DistributeFeeParams) -> Resu
         transfer(transfer_ctx, fee_amount)?;
         
         msg!("Fees distributed to stability pool successfully: {}", fee_amount);
+        (fee_amount, 0, 0)
     } else {
         // Validate fee address token account owners using state values
         require!(
@@ -147,7 +149,18 @@ pub fn handler(ctx: Context<DistributeFee>, params: DistributeFeeParams) -> Resu
         }
         
         msg!("Fees distributed to fee addresses successfully");
-    }
+        (0, half_amount, remaining_amount)
+    };
+    
+    emit!(FeeDistributed {
+        payer: ctx.accounts.payer.key(),
+        mint: payer_mint,
+        fee_amount,
+        stability_pool_amount,
+        fee_address_1_amount,
+        fee_address_2_amount,
+        total_fees_collected: state.total_fees_collected,
+    });
     
     Ok(())
 }
@@ -0,0 +1,33 @@
+use anchor_lang::prelude::*;
+
+/// Emitted by set_data and set_data_batch for every collateral asset configured
+#[event]
+pub struct CollateralDataSet {
+    /// Asset denomination
+    pub denom: String,
+
+    /// Decimal precision of the asset
+    pub decimal: u8,
+
+    /// Pyth Network price feed identifier (hex format)
+    pub price_id: String,
+
+    /// Pyth price account address for this asset
+    pub pyth_price_account: Pubkey,
+
+    /// Timestamp the asset was configured at
+    pub configured_at: i64,
+
+    /// Whether an existing configuration was replaced
+    pub is_update: bool,
+}
+
+/// Emitted by remove_data
+#[event]
+pub struct CollateralDataRemoved {
+    /// Asset denomination
+    pub denom: String,
+
+    /// Pyth price account the asset was configured with
+    pub pyth_price_account: Pubkey,
+}
@@ -23,7 +23,6 @@ pub struct GetAllPrices<'info> {
 
 pub fn handler(ctx: Context<GetAllPrices>, _params: GetAllPricesParams) -> Result<Vec<PriceResponse>> {
     let state = &ctx.accounts.state;
-    let clock = &ctx.accounts.clock;
     
     // Get remaining accounts (should contain Pyth price accounts for each asset)
     let remaining_accounts = &ctx.remaining_accounts;
@@ -41,7 +40,11 @@ pub fn handler(ctx: Context<GetAllPrices>, _params: GetAllPricesParams) -> Resul
     for (index, collateral_data) in state.collateral_data.iter().enumerate() {
         // Get the corresponding Pyth price account from remaining_accounts
         let pyth_price_account = &remaining_accounts[index];
-        
+        require!(
+            pyth_price_account.key() == collateral_data.pyth_price_account,
+            AerospacerOracleError::PythPriceAccountValidationFailed
+        );
+
         // Use Pyth SDK to load and validate price feed data (reusing get_price logic)
         let price_feed = SolanaPriceAccount::account_info_to_feed(pyth_price_account)
             .map_err(|_| AerospacerOracleError::PythPriceFeedLoadFailed)?;
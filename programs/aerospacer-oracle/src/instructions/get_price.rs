@@ -26,7 +26,6 @@ pub struct GetPrice<'info> {
 
 pub fn handler(ctx: Context<GetPrice>, params: GetPriceParams) -> Result<PriceResponse> {
     let state = &ctx.accounts.state;
-    let clock = &ctx.accounts.clock;
     
     // Find the collateral data for the requested denom
     let collateral_data = state.collateral_data
@@ -34,6 +33,12 @@ pub fn handler(ctx: Context<GetPrice>, params: GetPriceParams) -> Result<PriceRe
         .find(|d| d.denom == params.denom)
         .ok_or(AerospacerOracleError::PriceFeedNotFound)?;
 
+    // Callers pick the Pyth account, so it must be the feed configured for this denom
+    require!(
+        ctx.accounts.pyth_price_account.key() == collateral_data.pyth_price_account,
+        AerospacerOracleError::PythPriceAccountValidationFailed
+    );
+
     // PRODUCTION PYTH INTEGRATION CODE
     // Use Pyth SDK to load and validate price feed data
     let price_feed = SolanaPriceAccount::account_info_to_feed(&ctx.accounts.pyth_price_account)
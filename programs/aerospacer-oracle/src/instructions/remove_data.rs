@@ -1,6 +1,7 @@
 use anchor_lang::prelude::*;
 use crate::state::*;
 use crate::error::AerospacerOracleError;
+use crate::events::CollateralDataRemoved;
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct RemoveDataParams {
@@ -40,6 +41,11 @@ pub fn handler(ctx: Context<RemoveData>, params: RemoveDataParams) -> Result<()>
         msg!("Removed price ID: {}", removed_data.price_id);
         msg!("Removed decimal: {}", removed_data.decimal);
         msg!("Removed Pyth price account: {}", removed_data.pyth_price_account);
+        
+        emit!(CollateralDataRemoved {
+            denom: removed_data.denom,
+            pyth_price_account: removed_data.pyth_price_account,
+        });
     } else {
         return Err(AerospacerOracleError::CollateralDataNotFound.into());
     }
@@ -1,6 +1,7 @@
 use anchor_lang::prelude::*;
 use crate::state::*;
 use crate::error::AerospacerOracleError;
+use crate::events::CollateralDataSet;
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct SetDataParams {
@@ -58,6 +59,15 @@ pub fn handler(ctx: Context<SetData>, params: SetDataParams) -> Result<()> {
         pyth_price_account: params.pyth_price_account,
     };
     
+    emit!(CollateralDataSet {
+        denom: params.denom.clone(),
+        decimal: params.decimal,
+        price_id: params.price_id.clone(),
+        pyth_price_account: params.pyth_price_account,
+        configured_at: clock.unix_timestamp,
+        is_update: state.collateral_data.iter().any(|d| d.denom == params.denom),
+    });
+    
     // Check if denom already exists and update, otherwise add new
     if let Some(index) = state.collateral_data.iter().position(|d| d.denom == params.denom) {
         state.collateral_data[index] = collateral_data;
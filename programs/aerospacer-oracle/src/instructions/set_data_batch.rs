@@ -1,6 +1,7 @@
 use anchor_lang::prelude::*;
 use crate::state::*;
 use crate::error::AerospacerOracleError;
+use crate::events::CollateralDataSet;
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct SetDataBatchParams {
@@ -57,6 +58,15 @@ pub fn handler(ctx: Context<SetDataBatch>, params: SetDataBatchParams) -> Result
             pyth_price_account: collateral_data.pyth_price_account,
         };
         
+        emit!(CollateralDataSet {
+            denom: collateral_data.denom.clone(),
+            decimal: collateral_data.decimal,
+            price_id: collateral_data.price_id.clone(),
+            pyth_price_account: collateral_data.pyth_price_account,
+            configured_at: clock.unix_timestamp,
+            is_update: state.collateral_data.iter().any(|d| d.denom == collateral_data.denom),
+        });
+        
         // Check if denom already exists and update, otherwise add new
         if let Some(index) = state.collateral_data.iter().position(|d| d.denom == collateral_data.denom) {
             state.collateral_data[index] = new_collateral_data;
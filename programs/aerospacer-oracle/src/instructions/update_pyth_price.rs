@@ -35,10 +35,10 @@ pub fn handler(ctx: Context<UpdatePythPrice>, params: UpdatePythPriceParams) ->
     let clock = &ctx.accounts.clock;
     
     // Find the collateral data for the requested denom
-    let collateral_data = state.collateral_data
-        .iter_mut()
-        .find(|d| d.denom == params.denom)
-        .ok_or(AerospacerOracleError::PriceFeedNotFound)?;
+    require!(
+        state.collateral_data.iter().any(|d| d.denom == params.denom),
+        AerospacerOracleError::PriceFeedNotFound
+    );
 
     // PRODUCTION PYTH INTEGRATION CODE
     let price_feed = SolanaPriceAccount::account_info_to_feed(&ctx.accounts.pyth_price_account)
@@ -1,6 +1,10 @@
+// Anchor 0.31's #[program] expansion still calls the deprecated AccountInfo::realloc
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
 
 pub mod error;
+pub mod events;
 pub mod instructions;
 pub mod state;
 pub mod msg;
@@ -1,10 +1,11 @@
+//! Account management utilities for the protocol
+//! This module provides clean, type-safe account loading and management
+
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount, Mint, Transfer, Burn};
 use crate::state::*;
 use crate::error::*;
-
-/// Account management utilities for the protocol
-/// This module provides clean, type-safe account loading and management
+use crate::decimal::Decimal;
 
 /// Context for managing user trove accounts
 #[derive(Accounts)]
@@ -72,9 +73,6 @@ pub struct LiquidationContext<'info> {
     #[account(mut)]
     pub liquidator: Signer<'info>,
     
-    #[account(mut)]
-    pub state: Account<'info, StateAccount>,
-    
     #[account(mut)]
     pub stable_coin_mint: Account<'info, Mint>,
     
@@ -126,7 +124,7 @@ impl<'info> TroveContext<'info> {
     }
     
     /// Update liquidity threshold
-    pub fn update_liquidity_threshold(&mut self, new_ratio: u64) -> Result<()> {
+    pub fn update_liquidity_threshold(&mut self, new_ratio: Decimal) -> Result<()> {
         self.liquidity_threshold.ratio = new_ratio;
         Ok(())
     }
@@ -137,7 +135,7 @@ impl<'info> TroveContext<'info> {
 pub struct TroveInfo {
     pub user: Pubkey,
     pub debt_amount: u64,
-    pub liquidity_ratio: u64,
+    pub liquidity_ratio: Decimal,
 }
 
 /// Collateral information structure
@@ -184,7 +182,7 @@ impl<'info> CollateralContext<'info> {
     pub fn transfer_to_user(&self, amount: u64, collateral_denom: &str, bump: u8) -> Result<()> {
         // Derive the PDA seeds for the protocol_collateral_account
         let transfer_seeds = &[
-            b"protocol_collateral_vault".as_ref(),
+            PROTOCOL_COLLATERAL_VAULT_SEED,
             collateral_denom.as_bytes(),
             &[bump],  // ← Use the passed bump instead of trying to access .bumps
         ];
@@ -221,9 +219,6 @@ impl<'info> LiquidationContext<'info> {
             total_collateral_gain = total_collateral_gain.saturating_add(*amount);
         }
         
-        // Update global state
-        self.state.total_debt_amount = self.state.total_debt_amount.saturating_sub(debt_amount);
-        
         // Update total collateral amounts for each denomination
         for (denom, amount) in &collateral_amounts {
             self.update_total_collateral_amount(denom, *amount)?;
@@ -231,8 +226,8 @@ impl<'info> LiquidationContext<'info> {
         
         // Burn stablecoins from protocol vault (PDA signer)
         let burn_seeds = &[
-            b"protocol_stablecoin_vault".as_ref(),
-            &[Pubkey::find_program_address(&[b"protocol_stablecoin_vault"], &crate::ID).1],
+            PROTOCOL_STABLECOIN_VAULT_SEED,
+            &[Pubkey::find_program_address(&[PROTOCOL_STABLECOIN_VAULT_SEED], &crate::ID).1],
         ];
         let burn_signer = &[&burn_seeds[..]];
 
@@ -279,5 +274,161 @@ impl<'info> LiquidationContext<'info> {
     }
 }
 
+/// Accounts that pay out one collateral denom from its protocol vault
+pub struct DenomPayout<'info> {
+    pub denom: String,
+    pub vault: AccountInfo<'info>,
+    pub vault_bump: u8,
+    pub recipient: AccountInfo<'info>,
+    pub total_collateral: AccountInfo<'info>,
+    pub sent: u64,
+}
+
+impl<'info> DenomPayout<'info> {
+    /// Locate a denom's vault, total and the recipient's token account by address
+    pub fn from_remaining_accounts(
+        denom: &str,
+        recipient_owner: &Pubkey,
+        accounts: &[AccountInfo<'info>],
+    ) -> Result<Self> {
+        let (vault_pda, vault_bump) = Pubkey::find_program_address(
+            &[PROTOCOL_COLLATERAL_VAULT_SEED, denom.as_bytes()],
+            &crate::ID,
+        );
+        let (total_pda, _bump) = Pubkey::find_program_address(&TotalCollateralAmount::seeds(denom), &crate::ID);
+        
+        let vault = accounts.iter()
+            .find(|a| a.key() == vault_pda)
+            .ok_or(AerospacerProtocolError::MissingCollateralAccount)?;
+        let total_collateral = accounts.iter()
+            .find(|a| a.key() == total_pda)
+            .ok_or(AerospacerProtocolError::MissingCollateralAccount)?;
+        
+        // The recipient's token account is the one they own for the vault's mint
+        let vault_mint = TokenAccount::try_deserialize(&mut &vault.try_borrow_data()?[..])?.mint;
+        let recipient = find_token_account(recipient_owner, &vault_mint, accounts)?;
+        
+        Ok(Self {
+            denom: denom.to_string(),
+            vault: vault.clone(),
+            vault_bump,
+            recipient: recipient.clone(),
+            total_collateral: total_collateral.clone(),
+            sent: 0,
+        })
+    }
+    
+    /// Transfer `amount` from the vault to the recipient
+    pub fn pay(&mut self, token_program: &AccountInfo<'info>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        
+        let seeds = &[
+            PROTOCOL_COLLATERAL_VAULT_SEED,
+            self.denom.as_bytes(),
+            &[self.vault_bump],
+        ];
+        let signer = &[&seeds[..]];
+        
+        let transfer_ctx = CpiContext::new_with_signer(
+            token_program.clone(),
+            Transfer {
+                from: self.vault.clone(),
+                to: self.recipient.clone(),
+                authority: self.vault.clone(),
+            },
+            signer,
+        );
+        anchor_spl::token::transfer(transfer_ctx, amount)?;
+        
+        self.record_sent(amount)
+    }
+    
+    /// Take collateral that has left the vault out of the denom's TotalCollateralAmount
+    pub fn record_sent(&mut self, amount: u64) -> Result<()> {
+        let mut data = self.total_collateral.try_borrow_mut_data()?;
+        let mut total_collateral = TotalCollateralAmount::try_deserialize(&mut &data[..])?;
+        total_collateral.amount = total_collateral.amount
+            .checked_sub(amount)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+        total_collateral.try_serialize(&mut &mut data[..])?;
+        
+        self.sent = self.sent.saturating_add(amount);
+        Ok(())
+    }
+}
+
+/// Find the token account `owner` holds for `mint` anywhere in `accounts`
+pub fn find_token_account<'a, 'info>(
+    owner: &Pubkey,
+    mint: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a AccountInfo<'info>> {
+    accounts.iter()
+        .filter(|a| a.owner == &anchor_spl::token::ID)
+        .find(|a| {
+            a.try_borrow_data()
+                .ok()
+                .and_then(|data| TokenAccount::try_deserialize(&mut &data[..]).ok())
+                .is_some_and(|token| token.owner == *owner && token.mint == *mint)
+        })
+        .ok_or(AerospacerProtocolError::MissingCollateralAccount.into())
+}
+
+/// Burn a trove's gas compensation reserve once the trove no longer needs it
+pub fn burn_gas_compensation<'info>(
+    token_program: &AccountInfo<'info>,
+    stable_coin_mint: &AccountInfo<'info>,
+    gas_compensation_vault: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    
+    let bump = Pubkey::find_program_address(&[GAS_COMPENSATION_VAULT_SEED], &crate::ID).1;
+    let seeds = &[GAS_COMPENSATION_VAULT_SEED, &[bump]];
+    let signer = &[&seeds[..]];
+    
+    let burn_ctx = CpiContext::new_with_signer(
+        token_program.clone(),
+        Burn {
+            mint: stable_coin_mint.clone(),
+            from: gas_compensation_vault.clone(),
+            authority: gas_compensation_vault.clone(),
+        },
+        signer,
+    );
+    anchor_spl::token::burn(burn_ctx, amount)
+}
+
+/// Pay the gas compensation reserve of liquidated troves to the liquidator
+pub fn pay_gas_compensation<'info>(
+    token_program: &AccountInfo<'info>,
+    gas_compensation_vault: &AccountInfo<'info>,
+    liquidator_stablecoin_account: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    
+    let bump = Pubkey::find_program_address(&[GAS_COMPENSATION_VAULT_SEED], &crate::ID).1;
+    let seeds = &[GAS_COMPENSATION_VAULT_SEED, &[bump]];
+    let signer = &[&seeds[..]];
+    
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.clone(),
+        Transfer {
+            from: gas_compensation_vault.clone(),
+            to: liquidator_stablecoin_account.clone(),
+            authority: gas_compensation_vault.clone(),
+        },
+        signer,
+    );
+    anchor_spl::token::transfer(transfer_ctx, amount)
+}
+
 // NOTE: SortedTrovesManager removed - using off-chain sorting architecture
 // All sorted list management logic has been moved to the client side
@@ -0,0 +1,270 @@
+//! Fixed-point decimal arithmetic
+//!
+//! `Decimal` is an unsigned 18-decimal fixed-point number stored as a raw `u128`
+//! (1.5 is stored as 1_500_000_000_000_000_000). It is used for collateral ratios,
+//! prices, fee rates and the Product-Sum / redistribution factors, so that no consensus
+//! code relies on whole percentages or floating point.
+//!
+//! Every operation is checked, and every operation that divides takes an explicit
+//! `Rounding` so callers decide which side of a truncation the protocol lands on.
+
+use std::fmt;
+
+use anchor_lang::prelude::*;
+use crate::error::AerospacerProtocolError;
+
+/// Which way a result that is not exactly representable is rounded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+    HalfUp,
+}
+
+/// Unsigned 18-decimal fixed-point number
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Decimal(u128);
+
+impl Decimal {
+    /// Raw value of 1.0
+    pub const SCALE: u128 = 1_000_000_000_000_000_000;
+
+    pub const ZERO: Decimal = Decimal(0);
+    pub const ONE: Decimal = Decimal(Self::SCALE);
+    pub const MAX: Decimal = Decimal(u128::MAX);
+
+    /// Serialized size in bytes
+    pub const LEN: usize = 16;
+
+    pub const fn from_raw(raw: u128) -> Self {
+        Decimal(raw)
+    }
+
+    pub const fn raw(self) -> u128 {
+        self.0
+    }
+
+    /// Whole number (u64 always fits: u64::MAX × 1e18 < u128::MAX)
+    pub const fn from_integer(value: u64) -> Self {
+        Decimal(value as u128 * Self::SCALE)
+    }
+
+    /// Whole percentage, e.g. 150 → 1.5
+    pub const fn from_percent(percent: u64) -> Self {
+        Decimal(percent as u128 * (Self::SCALE / 100))
+    }
+
+    /// Basis points, e.g. 50 → 0.005
+    pub const fn from_bps(bps: u64) -> Self {
+        Decimal(bps as u128 * (Self::SCALE / 10_000))
+    }
+
+    /// `numerator / denominator`
+    pub fn from_ratio(numerator: u128, denominator: u128, rounding: Rounding) -> Result<Self> {
+        mul_div(numerator, Self::SCALE, denominator, rounding).map(Decimal)
+    }
+
+    /// An integer carrying `decimals` implied decimals, e.g. an oracle price of 15_025 with 2 decimals → 150.25
+    pub fn from_scaled(value: u128, decimals: u8) -> Result<Self> {
+        let factor = 10u128
+            .checked_pow(decimals as u32)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+        Self::from_ratio(value, factor, Rounding::Down)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Decimal) -> Result<Self> {
+        self.0
+            .checked_add(other.0)
+            .map(Decimal)
+            .ok_or(AerospacerProtocolError::OverflowError.into())
+    }
+
+    pub fn checked_sub(self, other: Decimal) -> Result<Self> {
+        self.0
+            .checked_sub(other.0)
+            .map(Decimal)
+            .ok_or(AerospacerProtocolError::OverflowError.into())
+    }
+
+    pub fn saturating_add(self, other: Decimal) -> Self {
+        Decimal(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Decimal) -> Self {
+        Decimal(self.0.saturating_sub(other.0))
+    }
+
+    pub fn checked_mul(self, other: Decimal, rounding: Rounding) -> Result<Self> {
+        mul_div(self.0, other.0, Self::SCALE, rounding).map(Decimal)
+    }
+
+    pub fn checked_div(self, other: Decimal, rounding: Rounding) -> Result<Self> {
+        mul_div(self.0, Self::SCALE, other.0, rounding).map(Decimal)
+    }
+
+    /// Raise to an integer power by squaring, rounding each step half up
+    pub fn checked_pow(self, exponent: u64) -> Result<Self> {
+        let mut n = exponent;
+        if n == 0 {
+            return Ok(Self::ONE);
+        }
+
+        let mut x = self;
+        let mut y = Self::ONE;
+        while n > 1 {
+            if n % 2 == 0 {
+                x = x.checked_mul(x, Rounding::HalfUp)?;
+                n /= 2;
+            } else {
+                y = x.checked_mul(y, Rounding::HalfUp)?;
+                x = x.checked_mul(x, Rounding::HalfUp)?;
+                n = (n - 1) / 2;
+            }
+        }
+
+        x.checked_mul(y, Rounding::HalfUp)
+    }
+
+    /// Apply to a token amount, e.g. a fee rate to a loan or a ratio to a deposit
+    pub fn mul_amount(self, amount: u64, rounding: Rounding) -> Result<u64> {
+        let value = mul_div(amount as u128, self.0, Self::SCALE, rounding)?;
+        u64::try_from(value).map_err(|_| AerospacerProtocolError::OverflowError.into())
+    }
+
+    /// Divide a token amount by this value, e.g. an aUSD value by a price
+    pub fn div_amount(self, amount: u64, rounding: Rounding) -> Result<u64> {
+        let value = mul_div(amount as u128, Self::SCALE, self.0, rounding)?;
+        u64::try_from(value).map_err(|_| AerospacerProtocolError::OverflowError.into())
+    }
+
+    /// Whole basis points, rounded down (for logs and client-facing limits)
+    pub fn to_bps(self) -> u128 {
+        self.0 / (Self::SCALE / 10_000)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let integer = self.0 / Self::SCALE;
+        let fraction = self.0 % Self::SCALE;
+        if fraction == 0 {
+            write!(f, "{}", integer)
+        } else {
+            let digits = format!("{:018}", fraction);
+            write!(f, "{}.{}", integer, digits.trim_end_matches('0'))
+        }
+    }
+}
+
+/// `a × b / d` with a 256-bit intermediate product, so only the result has to fit in a u128
+pub fn mul_div(a: u128, b: u128, d: u128, rounding: Rounding) -> Result<u128> {
+    require!(d != 0, AerospacerProtocolError::DivideByZeroError);
+
+    let (quotient, remainder) = match a.checked_mul(b) {
+        Some(product) => (product / d, product % d),
+        None => wide_div(wide_mul(a, b), d)?,
+    };
+
+    let round_up = match rounding {
+        Rounding::Down => false,
+        Rounding::Up => remainder > 0,
+        Rounding::HalfUp => remainder >= d - remainder,
+    };
+    if round_up {
+        quotient
+            .checked_add(1)
+            .ok_or(AerospacerProtocolError::OverflowError.into())
+    } else {
+        Ok(quotient)
+    }
+}
+
+/// Full 256-bit product of two u128s as (high, low)
+fn wide_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & MASK);
+    let (b_hi, b_lo) = (b >> 64, b & MASK);
+
+    let lo_lo = a_lo * b_lo;
+    let hi_lo = a_hi * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_hi = a_hi * b_hi;
+
+    let cross = (lo_lo >> 64) + (hi_lo & MASK) + (lo_hi & MASK);
+    let low = (cross << 64) | (lo_lo & MASK);
+    let high = hi_hi + (hi_lo >> 64) + (lo_hi >> 64) + (cross >> 64);
+    (high, low)
+}
+
+/// Divide a 256-bit (high, low) value by a u128, returning (quotient, remainder)
+fn wide_div((high, low): (u128, u128), d: u128) -> Result<(u128, u128)> {
+    // The quotient only fits in a u128 while the high half is below the divisor
+    require!(high < d, AerospacerProtocolError::OverflowError);
+
+    let mut quotient = 0u128;
+    let mut remainder = high;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= d {
+            remainder = remainder.wrapping_sub(d);
+            quotient |= 1;
+        }
+    }
+
+    Ok((quotient, remainder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rounding_modes() {
+        let one_third = |rounding| Decimal::from_ratio(1, 3, rounding).unwrap().raw();
+        assert_eq!(one_third(Rounding::Down), 333_333_333_333_333_333);
+        assert_eq!(one_third(Rounding::Up), 333_333_333_333_333_334);
+        assert_eq!(one_third(Rounding::HalfUp), 333_333_333_333_333_333);
+
+        let two_thirds = Decimal::from_ratio(2, 3, Rounding::HalfUp).unwrap();
+        assert_eq!(two_thirds.raw(), 666_666_666_666_666_667);
+    }
+
+    #[test]
+    fn test_percent_precision() {
+        // 110.9% is no longer indistinguishable from 110%
+        let icr = Decimal::from_ratio(1_109, 1_000, Rounding::Down).unwrap();
+        assert!(icr > Decimal::from_percent(110));
+        assert!(icr < Decimal::from_percent(111));
+        assert_eq!(icr.to_string(), "1.109");
+    }
+
+    #[test]
+    fn test_wide_intermediate_product() {
+        // 1e30 × 1e18 overflows a u128 but the quotient does not
+        let value = 1_000_000_000_000_000_000_000_000_000_000u128;
+        assert_eq!(mul_div(value, Decimal::SCALE, Decimal::SCALE, Rounding::Down).unwrap(), value);
+        assert_eq!(mul_div(u128::MAX, 3, 3, Rounding::Down).unwrap(), u128::MAX);
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX, Rounding::Up).unwrap(), u128::MAX);
+        assert!(mul_div(u128::MAX, 2, 1, Rounding::Down).is_err());
+        assert!(mul_div(1, 1, 0, Rounding::Down).is_err());
+    }
+
+    #[test]
+    fn test_pow_and_amounts() {
+        let half = Decimal::from_percent(50);
+        assert_eq!(half.checked_pow(3).unwrap(), Decimal::from_ratio(1, 8, Rounding::Down).unwrap());
+        assert_eq!(half.checked_pow(0).unwrap(), Decimal::ONE);
+
+        let fee_rate = Decimal::from_bps(50);
+        assert_eq!(fee_rate.mul_amount(1_000_000, Rounding::Down).unwrap(), 5_000);
+        assert_eq!(fee_rate.mul_amount(199, Rounding::Down).unwrap(), 0);
+        assert_eq!(fee_rate.mul_amount(199, Rounding::Up).unwrap(), 1);
+        assert_eq!(fee_rate.to_bps(), 50);
+    }
+}
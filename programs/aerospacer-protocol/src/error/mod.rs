@@ -80,4 +80,76 @@ pub enum AerospacerProtocolError {
     
     #[msg("Invalid snapshot")]
     InvalidSnapshot,
+    
+    #[msg("Parameter value is outside the allowed bounds")]
+    ParameterOutOfBounds,
+    
+    #[msg("Timelock has not elapsed for this parameter change")]
+    TimelockNotExpired,
+    
+    #[msg("Trove already holds the maximum number of collateral denoms")]
+    TooManyCollateralDenoms,
+    
+    #[msg("Collateral account or price feed for one of the trove's denoms is missing")]
+    MissingCollateralAccount,
+    
+    #[msg("Trove holds several collateral denoms - use close_trove to repay in full")]
+    MultiCollateralTroveMustClose,
+    
+    #[msg("Operation not allowed while the protocol is in Recovery Mode")]
+    NotAllowedInRecoveryMode,
+    
+    #[msg("Operation would bring the total collateral ratio below the critical ratio")]
+    TcrBelowCriticalRatio,
+    
+    #[msg("No active trove holds this collateral to take on the redistributed liquidation")]
+    NoTrovesToRedistributeTo,
+    
+    #[msg("Repayment exceeds the debt owed on top of the gas compensation reserve")]
+    DebtBelowGasCompensation,
+    
+    #[msg("Collateral config for one of the trove's denoms is missing")]
+    MissingCollateralConfig,
+    
+    #[msg("Collateral denom is disabled")]
+    CollateralDisabled,
+    
+    #[msg("Operation would exceed the collateral's deposit cap")]
+    DepositCapExceeded,
+    
+    #[msg("Operation would exceed the collateral's debt ceiling")]
+    DebtCeilingExceeded,
+    
+    #[msg("Fee rate exceeds the maximum accepted by the user")]
+    FeeExceedsMaximum,
+    
+    #[msg("Redemption returns less collateral than the minimum accepted by the user")]
+    CollateralOutputBelowMinimum,
+    
+    #[msg("Redemption target's ICR is below 100%")]
+    RedemptionTargetUndercollateralized,
+    
+    #[msg("Trove registry page is full")]
+    TroveRegistryPageFull,
+    
+    #[msg("Trove registry page must be an existing page or the next one in the chain")]
+    InvalidTroveRegistryPage,
+    
+    #[msg("Trove is not listed in this trove registry page")]
+    TroveNotRegistered,
+    
+    #[msg("Stability pool withdrawals are blocked while troves are liquidatable")]
+    LiquidatableTrovesExist,
+    
+    #[msg("Stability pool deposit is still in its unstake cooldown")]
+    UnstakeCooldownActive,
+    
+    #[msg("Share pool has outstanding shares but nothing left to back them")]
+    SharePoolDepleted,
+    
+    #[msg("Troves opened in Recovery Mode need an ICR of at least the critical ratio")]
+    IcrBelowCriticalRatio,
+    
+    #[msg("Collateral denom is longer than 32 bytes")]
+    DenomTooLong,
 }
\ No newline at end of file
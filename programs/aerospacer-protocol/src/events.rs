@@ -0,0 +1,274 @@
+use anchor_lang::prelude::*;
+use crate::decimal::Decimal;
+use crate::state::{CollateralConfig, PendingParameterChange, ProtocolParameter};
+use crate::redistribution::LiquidationSplit;
+use crate::trove_management::LiquidationPenalty;
+
+// Typed events for indexers, decoded from the IDL instead of parsing msg! logs
+
+/// An amount of one collateral denom
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DenomAmount {
+    pub denom: String,
+    pub amount: u64,
+}
+
+/// The instruction behind a TroveAdjusted event
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TroveOperation {
+    AddCollateral,
+    RemoveCollateral,
+    BorrowLoan,
+    RepayLoan,
+    AdjustTrove,
+    StabilityGainToTrove,
+}
+
+pub fn denom_amounts(amounts: &[(String, u64)]) -> Vec<DenomAmount> {
+    amounts
+        .iter()
+        .map(|(denom, amount)| DenomAmount { denom: denom.clone(), amount: *amount })
+        .collect()
+}
+
+#[event]
+pub struct TroveOpened {
+    pub owner: Pubkey,
+    pub collateral_denom: String,
+    pub collateral_amount: u64,
+    pub loan_amount: u64,      // Minted to the owner, borrowing fee included
+    pub borrowing_fee: u64,
+    pub debt_amount: u64,      // Net loan plus the gas compensation reserve
+    pub icr: Decimal,
+}
+
+#[event]
+pub struct TroveAdjusted {
+    pub owner: Pubkey,
+    pub operation: TroveOperation,
+    pub collateral_denom: String,
+    pub collateral_change: u64,
+    pub is_collateral_increase: bool,
+    pub debt_change: u64,       // Net of the borrowing fee
+    pub is_debt_increase: bool,
+    pub borrowing_fee: u64,
+    pub collateral_amount: u64, // The trove's collateral in collateral_denom afterwards
+    pub debt_amount: u64,
+    pub icr: Decimal,
+}
+
+#[event]
+pub struct TroveClosed {
+    pub owner: Pubkey,
+    pub debt_repaid: u64,            // Burned from the owner, gas compensation excluded
+    pub collateral: Vec<DenomAmount>, // Returned to the owner
+}
+
+#[event]
+pub struct TroveLiquidated {
+    pub owner: Pubkey,
+    pub liquidator: Pubkey,
+    pub debt_amount: u64,
+    pub collateral: Vec<DenomAmount>,
+    pub debt_offset: u64,                       // Burned from the stability pool
+    pub debt_redistributed: u64,
+    pub collateral_to_stability_pool: Vec<DenomAmount>,
+    pub collateral_redistributed: Vec<DenomAmount>,
+    pub liquidator_collateral: Vec<DenomAmount>,
+    pub treasury_collateral: Vec<DenomAmount>,
+    pub gas_compensation: u64,
+    pub recovery_mode: bool,
+}
+
+impl TroveLiquidated {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        owner: Pubkey,
+        liquidator: Pubkey,
+        debt_amount: u64,
+        collateral_amounts: &[(String, u64)],
+        split: &LiquidationSplit,
+        penalty: &LiquidationPenalty,
+        gas_compensation: u64,
+        recovery_mode: bool,
+    ) -> Self {
+        Self {
+            owner,
+            liquidator,
+            debt_amount,
+            collateral: denom_amounts(collateral_amounts),
+            debt_offset: split.debt_to_offset,
+            debt_redistributed: split.debt_to_redistribute.iter().map(|(_, amount)| *amount).sum(),
+            collateral_to_stability_pool: denom_amounts(&split.collateral_to_send_to_sp),
+            collateral_redistributed: denom_amounts(&split.collateral_to_redistribute),
+            liquidator_collateral: denom_amounts(&penalty.to_liquidator),
+            treasury_collateral: denom_amounts(&penalty.to_treasury),
+            gas_compensation,
+            recovery_mode,
+        }
+    }
+}
+
+/// One trove's share of a redemption
+#[event]
+pub struct TroveRedeemed {
+    pub owner: Pubkey,
+    pub redeemer: Pubkey,
+    pub debt_redeemed: u64,
+    pub collateral: Vec<DenomAmount>, // Sent to the redeemer
+    pub debt_amount: u64,             // The trove's debt afterwards
+    pub icr: Decimal,
+}
+
+#[event]
+pub struct Redemption {
+    pub redeemer: Pubkey,
+    pub amount: u64,                  // Burned from the redeemer, fee included
+    pub redemption_fee: u64,
+    pub collateral: Vec<DenomAmount>, // Sent to the redeemer
+    pub troves_redeemed: u32,
+    pub base_rate: Decimal,
+}
+
+#[event]
+pub struct Staked {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub deposit: u64,            // The user's compounded deposit afterwards
+    pub total_stake_amount: u64,
+}
+
+#[event]
+pub struct Unstaked {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub deposit: u64,            // The user's compounded deposit afterwards
+    pub total_stake_amount: u64,
+}
+
+#[event]
+pub struct SharesDeposited {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub locked_shares: u64,      // The user's shares in escrow afterwards
+    pub unlock_slot: u64,        // Slot from which release_shares hands them over
+    pub pool_deposit: u64,       // The share pool's compounded deposit afterwards
+}
+
+#[event]
+pub struct SharesReleased {
+    pub user: Pubkey,
+    pub shares: u64,
+}
+
+#[event]
+pub struct SharesRedeemed {
+    pub user: Pubkey,
+    pub shares: u64,
+    pub amount: u64,
+    pub collateral: Vec<DenomAmount>,
+    pub pool_deposit: u64,       // The share pool's compounded deposit afterwards
+}
+
+#[event]
+pub struct LiquidationGainsWithdrawn {
+    pub user: Pubkey,
+    pub collateral_denom: String,
+    pub amount: u64,
+}
+
+/// A trove's stored ICR rewritten by the refresh_icrs crank
+#[event]
+pub struct TroveIcrRefreshed {
+    pub owner: Pubkey,
+    pub debt_amount: u64,
+    pub icr: Decimal,
+}
+
+#[event]
+pub struct SharePoolInitialized {
+    pub share_pool: Pubkey,
+    pub share_mint: Pubkey,
+}
+
+/// A collateral config as created or updated by the admin
+#[event]
+pub struct CollateralConfigSet {
+    pub denom: String,
+    pub minimum_collateral_ratio: u8,
+    pub liquidation_threshold: u8,
+    pub liquidation_penalty_bps: u16,
+    pub treasury_share_bps: u16,
+    pub debt_ceiling: u64,
+    pub deposit_cap: u64,
+    pub decimals: u8,
+    pub enabled: bool,
+}
+
+impl CollateralConfigSet {
+    pub fn new(config: &CollateralConfig) -> Self {
+        Self {
+            denom: config.denom.clone(),
+            minimum_collateral_ratio: config.minimum_collateral_ratio,
+            liquidation_threshold: config.liquidation_threshold,
+            liquidation_penalty_bps: config.liquidation_penalty_bps,
+            treasury_share_bps: config.treasury_share_bps,
+            debt_ceiling: config.debt_ceiling,
+            deposit_cap: config.deposit_cap,
+            decimals: config.decimals,
+            enabled: config.enabled,
+        }
+    }
+}
+
+#[event]
+pub struct ParameterChangeQueued {
+    pub parameter: ProtocolParameter,
+    pub new_value: u64,
+    pub queued_at: i64,
+    pub executable_at: i64,
+}
+
+impl ParameterChangeQueued {
+    pub fn new(pending: &PendingParameterChange) -> Self {
+        Self {
+            parameter: pending.parameter,
+            new_value: pending.new_value,
+            queued_at: pending.queued_at,
+            executable_at: pending.executable_at,
+        }
+    }
+}
+
+#[event]
+pub struct ParameterChangeExecuted {
+    pub parameter: ProtocolParameter,
+    pub new_value: u64,
+}
+
+#[event]
+pub struct ParameterChangeCancelled {
+    pub parameter: ProtocolParameter,
+    pub discarded_value: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denom_amounts_keeps_every_denom_in_order() {
+        let amounts = vec![("SOL".to_string(), 1_000), ("ETH".to_string(), 0), ("SOL".to_string(), 5)];
+
+        assert_eq!(
+            denom_amounts(&amounts),
+            vec![
+                DenomAmount { denom: "SOL".to_string(), amount: 1_000 },
+                DenomAmount { denom: "ETH".to_string(), amount: 0 },
+                DenomAmount { denom: "SOL".to_string(), amount: 5 },
+            ]
+        );
+        assert!(denom_amounts(&[]).is_empty());
+    }
+}
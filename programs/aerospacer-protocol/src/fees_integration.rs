@@ -1,15 +1,18 @@
 use anchor_lang::prelude::*;
-use crate::utils::*;
 use crate::error::*;
+use crate::state::PROTOCOL_COLLATERAL_VAULT_SEED;
+use crate::account_management::{find_token_account, DenomPayout};
+use aerospacer_fees::state::FeeStateAccount;
 
 /// Process protocol fee collection and distribution via CPI to aerospacer-fees
 /// This function handles the complete fee flow:
-/// 1. Calculate fee amount
+/// 1. Take the fee amount derived from the base rate (see utils::get_borrowing_rate / get_redemption_rate)
 /// 2. Call distribute_fee instruction via CPI (which handles token transfers)
 /// 3. Return net amount after fee
+#[allow(clippy::too_many_arguments)]
 pub fn process_protocol_fee<'info>(
     operation_amount: u64,
-    protocol_fee_percentage: u8,
+    fee_amount: u64,
     fees_program: AccountInfo<'info>,
     payer: AccountInfo<'info>,
     fees_state: AccountInfo<'info>,
@@ -19,14 +22,11 @@ pub fn process_protocol_fee<'info>(
     fee_address_2_token_account: AccountInfo<'info>,
     token_program: AccountInfo<'info>,
 ) -> Result<u64> {
-    // Calculate fee amount
-    let fee_amount = calculate_protocol_fee(operation_amount, protocol_fee_percentage)?;
-    
     if fee_amount == 0 {
         return Ok(operation_amount);
     }
     
-    msg!("Processing protocol fee: {} aUSD ({}%)", fee_amount, protocol_fee_percentage);
+    msg!("Processing protocol fee: {} aUSD", fee_amount);
     msg!("Operation amount: {} aUSD", operation_amount);
     
     // Call distribute_fee instruction via CPI
@@ -42,12 +42,15 @@ pub fn process_protocol_fee<'info>(
         &fee_address_2_token_account,
         &token_program,
         fee_amount,
+        &[],
     )?;
     
     msg!("Fee distributed successfully: {} aUSD", fee_amount);
     
     // Return net amount after fee
-    calculate_net_amount_after_fee(operation_amount, protocol_fee_percentage)
+    operation_amount
+        .checked_sub(fee_amount)
+        .ok_or(AerospacerProtocolError::OverflowError.into())
 }
 
 /// Validate fees contract accounts
@@ -97,8 +100,85 @@ pub fn validate_fees_accounts<'info>(
     Ok(())
 }
 
+/// Token accounts of the aerospacer-fees recipients for one mint
+/// 
+/// distribute_fee only pays the stability pool account while staking is enabled and only the
+/// fee address accounts otherwise, so the accounts it does not pay are filled with ones it does.
+pub struct FeeRecipientAccounts<'info> {
+    pub stability_pool_token_account: AccountInfo<'info>,
+    pub fee_address_1_token_account: AccountInfo<'info>,
+    pub fee_address_2_token_account: AccountInfo<'info>,
+}
+
+impl<'info> FeeRecipientAccounts<'info> {
+    /// Locate the recipients' token accounts for `mint` by owner, as configured in the fees state
+    pub fn from_remaining_accounts(
+        fees_state: &AccountInfo<'info>,
+        mint: &Pubkey,
+        accounts: &[AccountInfo<'info>],
+    ) -> Result<Self> {
+        let fee_state = FeeStateAccount::try_deserialize(&mut &fees_state.try_borrow_data()?[..])?;
+        
+        if fee_state.is_stake_enabled {
+            let stability_pool = find_token_account(&fee_state.stake_contract_address, mint, accounts)?;
+            Ok(Self {
+                stability_pool_token_account: stability_pool.clone(),
+                fee_address_1_token_account: stability_pool.clone(),
+                fee_address_2_token_account: stability_pool.clone(),
+            })
+        } else {
+            let fee_address_1 = find_token_account(&fee_state.fee_address_1, mint, accounts)?;
+            let fee_address_2 = find_token_account(&fee_state.fee_address_2, mint, accounts)?;
+            Ok(Self {
+                stability_pool_token_account: fee_address_1.clone(),
+                fee_address_1_token_account: fee_address_1.clone(),
+                fee_address_2_token_account: fee_address_2.clone(),
+            })
+        }
+    }
+}
+
+/// Send collateral held in a protocol vault to the treasury through aerospacer-fees
+/// The vault PDA signs as the fee payer and the amount is taken out of the denom's total
+pub fn distribute_collateral_fee<'info>(
+    fees_program: &AccountInfo<'info>,
+    fees_state: &AccountInfo<'info>,
+    payout: &mut DenomPayout<'info>,
+    recipients: &FeeRecipientAccounts<'info>,
+    token_program: &AccountInfo<'info>,
+    fee_amount: u64,
+) -> Result<()> {
+    if fee_amount == 0 {
+        return Ok(());
+    }
+    
+    let seeds = &[
+        PROTOCOL_COLLATERAL_VAULT_SEED,
+        payout.denom.as_bytes(),
+        &[payout.vault_bump],
+    ];
+    
+    distribute_fee_via_cpi(
+        fees_program,
+        &payout.vault,
+        fees_state,
+        &payout.vault,
+        &recipients.stability_pool_token_account,
+        &recipients.fee_address_1_token_account,
+        &recipients.fee_address_2_token_account,
+        token_program,
+        fee_amount,
+        &[&seeds[..]],
+    )?;
+    
+    msg!("Treasury fee distributed: {} {}", fee_amount, payout.denom);
+    payout.record_sent(fee_amount)
+}
+
 /// Call distribute_fee instruction on aerospacer-fees contract via CPI
 /// The fee contract will transfer tokens from payer to destinations directly
+/// `signer_seeds` lets a program-owned vault act as the payer (empty for a user payer)
+#[allow(clippy::too_many_arguments)]
 fn distribute_fee_via_cpi<'info>(
     fees_program: &AccountInfo<'info>,
     payer: &AccountInfo<'info>,
@@ -109,9 +189,10 @@ fn distribute_fee_via_cpi<'info>(
     fee_address_2_token_account: &AccountInfo<'info>,
     token_program: &AccountInfo<'info>,
     fee_amount: u64,
+    signer_seeds: &[&[&[u8]]],
 ) -> Result<()> {
     use anchor_lang::solana_program::instruction::Instruction;
-    use anchor_lang::solana_program::program::invoke;
+    use anchor_lang::solana_program::program::invoke_signed;
     use anchor_lang::solana_program::hash::hash;
     
     msg!("Distributing fee via aerospacer-fees contract CPI");
@@ -169,7 +250,7 @@ fn distribute_fee_via_cpi<'info>(
         token_program.to_account_info(),
     ];
     
-    invoke(&ix, &account_infos)?;
+    invoke_signed(&ix, &account_infos, signer_seeds)?;
     
     msg!("Fee distribution CPI completed successfully");
     Ok(())
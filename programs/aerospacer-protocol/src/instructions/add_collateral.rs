@@ -5,6 +5,8 @@ use crate::error::*;
 use crate::trove_management::*;
 use crate::account_management::*;
 use crate::oracle::*;
+use crate::redistribution::apply_trove_rewards;
+use crate::events::{TroveAdjusted, TroveOperation};
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct AddCollateralParams {
@@ -28,11 +30,13 @@ pub struct AddCollateral<'info> {
     )]
     pub user_debt_amount: Account<'info, UserDebtAmount>,
 
+    // Created on the first deposit of a new denom into the trove
     #[account(
-        mut,
+        init_if_needed,
+        payer = user,
+        space = 8 + UserCollateralAmount::LEN,
         seeds = [b"user_collateral_amount", user.key().as_ref(), params.collateral_denom.as_bytes()],
-        bump,
-        constraint = user_collateral_amount.owner == user.key() @ AerospacerProtocolError::Unauthorized
+        bump
     )]
     pub user_collateral_amount: Account<'info, UserCollateralAmount>,
 
@@ -67,12 +71,31 @@ pub struct AddCollateral<'info> {
 
     /// CHECK: Per-denom collateral total PDA
     #[account(
-        mut,
+        init_if_needed,
+        payer = user,
+        space = 8 + TotalCollateralAmount::LEN,
         seeds = [b"total_collateral_amount", params.collateral_denom.as_bytes()],
         bump
     )]
     pub total_collateral_amount: Account<'info, TotalCollateralAmount>,
 
+    // Created on the first deposit of this denom into any trove
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RedistributionSnapshot::LEN,
+        seeds = [b"redistribution_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub redistribution_snapshot: Box<Account<'info, RedistributionSnapshot>>,
+
+    // Risk limits of collateral_denom, set by the admin
+    #[account(
+        seeds = [b"collateral_config", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub collateral_config: Box<Account<'info, CollateralConfig>>,
+
     // Oracle context - UncheckedAccount to reduce stack usage
     /// CHECK: Our oracle program - validated against state in handler
     pub oracle_program: UncheckedAccount<'info>,
@@ -97,7 +120,11 @@ impl<'info> AddCollateral<'info> {
     }
 }
 
-pub fn handler(ctx: Context<AddCollateral>, params: AddCollateralParams) -> Result<()> {
+// remaining_accounts:
+// - One [UserCollateralAmount, RedistributionSnapshot, pyth_price_account, CollateralConfig] group
+//   per other denom the trove holds, in UserDebtAmount.collateral_denoms order (see trove_management::load_other_collateral)
+// - Followed by the optional neighbor hints [prev_LiquidityThreshold, next_LiquidityThreshold]
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, AddCollateral<'info>>, params: AddCollateralParams) -> Result<()> {
     // Validate oracle accounts
     require!(
         ctx.accounts.oracle_program.key() == ctx.accounts.state.oracle_helper_addr,
@@ -124,8 +151,37 @@ pub fn handler(ctx: Context<AddCollateral>, params: AddCollateralParams) -> Resu
         AerospacerProtocolError::InvalidAmount
     );
     
+    // First deposit of this denom - initialize the position and record it on the trove
+    if ctx.accounts.user_collateral_amount.owner == Pubkey::default() {
+        ctx.accounts.user_collateral_amount.owner = ctx.accounts.user.key();
+        ctx.accounts.user_collateral_amount.denom = params.collateral_denom.clone();
+        ctx.accounts.user_collateral_amount.amount = 0;
+    }
+    require!(
+        ctx.accounts.user_collateral_amount.owner == ctx.accounts.user.key(),
+        AerospacerProtocolError::Unauthorized
+    );
+    ctx.accounts.user_debt_amount.add_collateral_denom(&params.collateral_denom)?;
+    
+    if ctx.accounts.total_collateral_amount.denom.is_empty() {
+        ctx.accounts.total_collateral_amount.denom = params.collateral_denom.clone();
+    }
+    if ctx.accounts.redistribution_snapshot.denom.is_empty() {
+        ctx.accounts.redistribution_snapshot.denom = params.collateral_denom.clone();
+    }
+    
+    // Bring the trove up to date with redistributed liquidations before pricing it
+    apply_trove_rewards(
+        &mut ctx.accounts.user_debt_amount,
+        &mut ctx.accounts.user_collateral_amount,
+        &mut ctx.accounts.redistribution_snapshot,
+        ctx.remaining_accounts,
+        OTHER_COLLATERAL_GROUP_LEN,
+    )?;
+    let previous_stake = ctx.accounts.user_collateral_amount.amount;
+    
     // Create contexts in scoped block to reduce stack usage
-    let result = {
+    let (result, consumed, other_collateral) = {
         let mut trove_ctx = TroveContext {
             user: ctx.accounts.user.clone(),
             user_debt_amount: ctx.accounts.user_debt_amount.clone(),
@@ -149,11 +205,21 @@ pub fn handler(ctx: Context<AddCollateral>, params: AddCollateralParams) -> Resu
             clock: ctx.accounts.clock.to_account_info(),
         };
         
+        // Price the trove's other collateral so the ICR covers every denom
+        let (other_collateral, consumed) = load_other_collateral(
+            &ctx.accounts.user_debt_amount,
+            &params.collateral_denom,
+            ctx.remaining_accounts,
+            &oracle_ctx,
+        )?;
+        
         // Use TroveManager for clean implementation
         let result = TroveManager::add_collateral(
             &mut trove_ctx,
             &mut collateral_ctx,
             &oracle_ctx,
+            &ctx.accounts.collateral_config,
+            &other_collateral,
             params.amount,
             params.collateral_denom.clone(),
         )?;
@@ -161,61 +227,27 @@ pub fn handler(ctx: Context<AddCollateral>, params: AddCollateralParams) -> Resu
         // Update state before contexts are dropped
         ctx.accounts.state.total_debt_amount = trove_ctx.state.total_debt_amount;
         
-        Ok::<_, Error>(result)
+        Ok::<_, Error>((result, consumed, other_collateral))
     }?;
     
     // CRITICAL: Validate ICR ordering if neighbor hints provided
     // Production clients MUST provide neighbor hints via remainingAccounts for proper sorted list maintenance
     // Pattern: [prev_LiquidityThreshold, next_LiquidityThreshold] or [prev_LT] or [next_LT] or []
     // Optional for backward compatibility with tests, but REQUIRED in production
-    if !ctx.remaining_accounts.is_empty() {
-        use crate::sorted_troves;
-        
-        msg!("Validating ICR ordering with {} neighbor account(s)", ctx.remaining_accounts.len());
-        
-        let prev_icr = if ctx.remaining_accounts.len() >= 1 {
-            let prev_lt = &ctx.remaining_accounts[0];
-            let prev_data = prev_lt.try_borrow_data()?;
-            let prev_threshold = LiquidityThreshold::try_deserialize(&mut &prev_data[..])?;
-            let prev_owner = prev_threshold.owner;
-            let prev_ratio = prev_threshold.ratio;
-            drop(prev_data);
-            
-            // Verify this is a real PDA, not a fake account
-            sorted_troves::verify_liquidity_threshold_pda(prev_lt, prev_owner, ctx.program_id)?;
-            
-            Some(prev_ratio)
-        } else {
-            None
-        };
-        
-        let next_icr = if ctx.remaining_accounts.len() >= 2 {
-            let next_lt = &ctx.remaining_accounts[1];
-            let next_data = next_lt.try_borrow_data()?;
-            let next_threshold = LiquidityThreshold::try_deserialize(&mut &next_data[..])?;
-            let next_owner = next_threshold.owner;
-            let next_ratio = next_threshold.ratio;
-            drop(next_data);
-            
-            // Verify this is a real PDA, not a fake account
-            sorted_troves::verify_liquidity_threshold_pda(next_lt, next_owner, ctx.program_id)?;
-            
-            Some(next_ratio)
-        } else {
-            None
-        };
-        
-        // Validate ordering BEFORE updating state
-        sorted_troves::validate_icr_ordering(result.new_icr, prev_icr, next_icr)?;
-        msg!("✓ ICR ordering validated successfully");
-    } else {
-        msg!("⚠ WARNING: No neighbor hints provided - skipping ICR ordering validation");
-        msg!("⚠ Production clients MUST provide neighbor hints for sorted list integrity");
-    }
+    let new_nicr = crate::sorted_troves::compute_nominal_icr(result.new_collateral_amount, result.new_debt_amount)?;
+    crate::sorted_troves::validate_neighbor_hints(&params.collateral_denom, new_nicr, &ctx.remaining_accounts[consumed..], ctx.program_id)?;
     
     // Update the actual accounts with the results
     ctx.accounts.user_collateral_amount.amount = result.new_collateral_amount;
     ctx.accounts.liquidity_threshold.ratio = result.new_icr;
+    ctx.accounts.liquidity_threshold.set_nominal_ratios(
+        &other_collateral.collateral_amounts(&params.collateral_denom, result.new_collateral_amount),
+        result.new_debt_amount,
+    )?;
+    ctx.accounts.redistribution_snapshot.update_stake(previous_stake, result.new_collateral_amount)?;
+    ctx.accounts.total_collateral_amount.amount = ctx.accounts.total_collateral_amount.amount
+        .checked_add(params.amount)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
     
     msg!("Collateral added successfully");
     msg!("Added: {} {}", params.amount, params.collateral_denom);
@@ -223,5 +255,19 @@ pub fn handler(ctx: Context<AddCollateral>, params: AddCollateralParams) -> Resu
     msg!("New ICR: {}", result.new_icr);
     msg!("Debt amount: {}", result.new_debt_amount);
 
+    emit!(TroveAdjusted {
+        owner: ctx.accounts.user.key(),
+        operation: TroveOperation::AddCollateral,
+        collateral_denom: params.collateral_denom,
+        collateral_change: params.amount,
+        is_collateral_increase: true,
+        debt_change: 0,
+        is_debt_increase: false,
+        borrowing_fee: 0,
+        collateral_amount: result.new_collateral_amount,
+        debt_amount: result.new_debt_amount,
+        icr: result.new_icr,
+    });
+
     Ok(())
 }
\ No newline at end of file
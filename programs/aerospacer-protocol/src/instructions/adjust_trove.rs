@@ -0,0 +1,437 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount, Mint, MintTo, Burn};
+use crate::state::*;
+use crate::error::*;
+use crate::decimal::Decimal;
+use crate::trove_management::*;
+use crate::account_management::*;
+use crate::oracle::*;
+use crate::redistribution::apply_trove_rewards;
+use crate::fees_integration::*;
+use crate::utils::*;
+use crate::events::{TroveAdjusted, TroveOperation};
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct AdjustTroveParams {
+    pub collateral_denom: String,
+    pub collateral_change: u64,
+    pub is_collateral_increase: bool,
+    pub debt_change: u64,
+    pub is_debt_increase: bool,
+    pub max_fee_bps: u16, // Highest borrowing fee rate accepted when the debt increases
+    pub prev_node_id: Option<Pubkey>,
+    pub next_node_id: Option<Pubkey>,
+}
+
+#[derive(Accounts)]
+#[instruction(params: AdjustTroveParams)]
+pub struct AdjustTrove<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"user_debt_amount", user.key().as_ref()],
+        bump,
+        constraint = user_debt_amount.owner == user.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub user_debt_amount: Box<Account<'info, UserDebtAmount>>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity_threshold", user.key().as_ref()],
+        bump,
+        constraint = liquidity_threshold.owner == user.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub liquidity_threshold: Box<Account<'info, LiquidityThreshold>>,
+
+    #[account(mut)]
+    pub state: Box<Account<'info, StateAccount>>,
+
+    #[account(mut)]
+    pub user_stablecoin_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Stable coin mint - validated against state, minted to or burned from
+    #[account(
+        mut,
+        constraint = stable_coin_mint.key() == state.stable_coin_addr @ AerospacerProtocolError::InvalidMint
+    )]
+    pub stable_coin_mint: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        token::mint = stable_coin_mint,
+        token::authority = protocol_stablecoin_account,
+        seeds = [b"protocol_stablecoin_vault"],
+        bump
+    )]
+    pub protocol_stablecoin_account: Box<Account<'info, TokenAccount>>,
+
+    // Created on the first deposit of a new denom into the trove
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserCollateralAmount::LEN,
+        seeds = [b"user_collateral_amount", user.key().as_ref(), params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub user_collateral_amount: Box<Account<'info, UserCollateralAmount>>,
+
+    #[account(
+        mut,
+        constraint = user_collateral_account.mint == collateral_mint.key() @ AerospacerProtocolError::InvalidMint
+    )]
+    pub user_collateral_account: Box<Account<'info, TokenAccount>>,
+
+    pub collateral_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        token::mint = collateral_mint,
+        token::authority = protocol_collateral_account,
+        seeds = [b"protocol_collateral_vault", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub protocol_collateral_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Per-denom collateral total PDA
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + TotalCollateralAmount::LEN,
+        seeds = [b"total_collateral_amount", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub total_collateral_amount: Box<Account<'info, TotalCollateralAmount>>,
+
+    // Created on the first deposit of this denom into any trove
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RedistributionSnapshot::LEN,
+        seeds = [b"redistribution_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub redistribution_snapshot: Box<Account<'info, RedistributionSnapshot>>,
+
+    // Risk limits of collateral_denom, set by the admin
+    #[account(
+        seeds = [b"collateral_config", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub collateral_config: Box<Account<'info, CollateralConfig>>,
+
+    // Oracle context - integration with our aerospacer-oracle
+    /// CHECK: Our oracle program - validated against state
+    #[account(
+        constraint = oracle_program.key() == state.oracle_helper_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_program: AccountInfo<'info>,
+
+    /// CHECK: Oracle state account - validated against state
+    #[account(
+        mut,
+        constraint = oracle_state.key() == state.oracle_state_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_state: AccountInfo<'info>,
+
+    /// CHECK: Pyth price account for collateral price feed
+    pub pyth_price_account: AccountInfo<'info>,
+
+    /// Clock sysvar for timestamp validation
+    pub clock: Sysvar<'info, Clock>,
+
+    // Fee distribution accounts
+    /// CHECK: Fees program - validated against state
+    #[account(
+        constraint = fees_program.key() == state.fee_distributor_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub fees_program: AccountInfo<'info>,
+
+    /// CHECK: Fees state account - validated against state
+    #[account(
+        mut,
+        constraint = fees_state.key() == state.fee_state_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub fees_state: AccountInfo<'info>,
+
+    /// CHECK: Stability pool token account
+    #[account(mut)]
+    pub stability_pool_token_account: AccountInfo<'info>,
+
+    /// CHECK: Fee address 1 token account
+    #[account(mut)]
+    pub fee_address_1_token_account: AccountInfo<'info>,
+
+    /// CHECK: Fee address 2 token account
+    #[account(mut)]
+    pub fee_address_2_token_account: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+// remaining_accounts:
+// - One [UserCollateralAmount, RedistributionSnapshot, pyth_price_account, CollateralConfig] group
+//   per other denom the trove holds, in UserDebtAmount.collateral_denoms order (see trove_management::load_other_collateral)
+// - One [TotalCollateralAmount, pyth_price_account] pair per denom the oracle supports,
+//   used for the Recovery Mode checks (see trove_management::load_system_collateral_block)
+// - Followed by the optional neighbor hints [prev_LiquidityThreshold, next_LiquidityThreshold]
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, AdjustTrove<'info>>, params: AdjustTroveParams) -> Result<()> {
+    // Validate input parameters
+    require!(
+        params.collateral_change > 0 || params.debt_change > 0,
+        AerospacerProtocolError::InvalidAmount
+    );
+
+    require!(
+        !params.collateral_denom.is_empty(),
+        AerospacerProtocolError::InvalidAmount
+    );
+
+    if params.debt_change > 0 && params.is_debt_increase {
+        require!(
+            params.debt_change >= MINIMUM_LOAN_AMOUNT,
+            AerospacerProtocolError::LoanAmountBelowMinimum
+        );
+    }
+
+    // Check if user has existing trove
+    require!(
+        ctx.accounts.user_debt_amount.amount > 0,
+        AerospacerProtocolError::TroveDoesNotExist
+    );
+
+    // First deposit of this denom - initialize the position and record it on the trove
+    if ctx.accounts.user_collateral_amount.owner == Pubkey::default() {
+        require!(
+            params.collateral_change > 0 && params.is_collateral_increase,
+            AerospacerProtocolError::InsufficientCollateral
+        );
+        ctx.accounts.user_collateral_amount.owner = ctx.accounts.user.key();
+        ctx.accounts.user_collateral_amount.denom = params.collateral_denom.clone();
+        ctx.accounts.user_collateral_amount.amount = 0;
+    }
+    require!(
+        ctx.accounts.user_collateral_amount.owner == ctx.accounts.user.key(),
+        AerospacerProtocolError::Unauthorized
+    );
+    if params.collateral_change > 0 && params.is_collateral_increase {
+        require!(
+            params.collateral_change <= ctx.accounts.user_collateral_account.amount,
+            AerospacerProtocolError::InsufficientCollateral
+        );
+        ctx.accounts.user_debt_amount.add_collateral_denom(&params.collateral_denom)?;
+    }
+
+    if ctx.accounts.total_collateral_amount.denom.is_empty() {
+        ctx.accounts.total_collateral_amount.denom = params.collateral_denom.clone();
+    }
+    if ctx.accounts.redistribution_snapshot.denom.is_empty() {
+        ctx.accounts.redistribution_snapshot.denom = params.collateral_denom.clone();
+    }
+
+    // Bring the trove up to date with redistributed liquidations before pricing it
+    apply_trove_rewards(
+        &mut ctx.accounts.user_debt_amount,
+        &mut ctx.accounts.user_collateral_amount,
+        &mut ctx.accounts.redistribution_snapshot,
+        ctx.remaining_accounts,
+        OTHER_COLLATERAL_GROUP_LEN,
+    )?;
+    let previous_stake = ctx.accounts.user_collateral_amount.amount;
+
+    // Repayments are burned from the user's wallet and may not clear the trove -
+    // the gas compensation reserve is only released by repay_loan or close_trove
+    if params.debt_change > 0 && !params.is_debt_increase {
+        require!(
+            params.debt_change <= ctx.accounts.user_stablecoin_account.amount,
+            AerospacerProtocolError::InsufficientCollateral
+        );
+        check_repayment_above_gas_compensation(ctx.accounts.user_debt_amount.amount, params.debt_change)?;
+    }
+
+    // Borrowing pays the fee of the decayed base rate, bounded by the user's max fee
+    let (fee_rate, fee_amount) = if params.debt_change > 0 && params.is_debt_increase {
+        decay_base_rate_from_borrowing(&mut ctx.accounts.state, ctx.accounts.clock.unix_timestamp)?;
+        let fee_rate = get_borrowing_rate(&ctx.accounts.state);
+        check_max_fee(fee_rate, params.max_fee_bps)?;
+        (fee_rate, calculate_fee(params.debt_change, fee_rate)?)
+    } else {
+        (Decimal::ZERO, 0)
+    };
+
+    // The trove's debt moves by the net loan amount, as in borrow_loan
+    let adjustment = TroveAdjustment {
+        collateral_change: params.collateral_change,
+        is_collateral_increase: params.is_collateral_increase,
+        debt_change: params.debt_change - fee_amount,
+        is_debt_increase: params.is_debt_increase,
+    };
+
+    // Create contexts in scoped block to reduce stack usage
+    let (result, consumed, other_collateral) = {
+        let mut trove_ctx = TroveContext {
+            user: ctx.accounts.user.clone(),
+            user_debt_amount: (*ctx.accounts.user_debt_amount).clone(),
+            liquidity_threshold: (*ctx.accounts.liquidity_threshold).clone(),
+            state: (*ctx.accounts.state).clone(),
+        };
+
+        let mut collateral_ctx = CollateralContext {
+            user: ctx.accounts.user.clone(),
+            user_collateral_amount: (*ctx.accounts.user_collateral_amount).clone(),
+            user_collateral_account: (*ctx.accounts.user_collateral_account).clone(),
+            protocol_collateral_account: (*ctx.accounts.protocol_collateral_account).clone(),
+            total_collateral_amount: (*ctx.accounts.total_collateral_amount).clone(),
+            token_program: ctx.accounts.token_program.clone(),
+        };
+
+        let oracle_ctx = OracleContext {
+            oracle_program: ctx.accounts.oracle_program.clone(),
+            oracle_state: ctx.accounts.oracle_state.clone(),
+            pyth_price_account: ctx.accounts.pyth_price_account.clone(),
+            clock: ctx.accounts.clock.to_account_info(),
+        };
+
+        // Price the trove's other collateral so the ICR covers every denom
+        let (other_collateral, consumed) = load_other_collateral(
+            &ctx.accounts.user_debt_amount,
+            &params.collateral_denom,
+            ctx.remaining_accounts,
+            &oracle_ctx,
+        )?;
+
+        // Price the protocol's total collateral for the TCR
+        let (system_collateral, system_accounts) = load_system_collateral_block(
+            &oracle_ctx,
+            &ctx.remaining_accounts[consumed..],
+        )?;
+        let consumed = consumed + system_accounts;
+
+        // Use TroveManager for clean implementation - only the final state is validated
+        let result = TroveManager::adjust_trove(
+            &mut trove_ctx,
+            &mut collateral_ctx,
+            &oracle_ctx,
+            &ctx.accounts.collateral_config,
+            &other_collateral,
+            &system_collateral,
+            &adjustment,
+            ctx.bumps.protocol_collateral_account,
+        )?;
+
+        // Update state before contexts are dropped
+        ctx.accounts.state.total_debt_amount = trove_ctx.state.total_debt_amount;
+
+        Ok::<_, Error>((result, consumed, other_collateral))
+    }?;
+
+    // CRITICAL: Validate ICR ordering if neighbor hints provided
+    // Production clients MUST provide neighbor hints via remainingAccounts for proper sorted list maintenance
+    // Pattern: [prev_LiquidityThreshold, next_LiquidityThreshold] or [prev_LT] or [next_LT] or []
+    // Optional for backward compatibility with tests, but REQUIRED in production
+    let new_nicr = crate::sorted_troves::compute_nominal_icr(result.new_collateral_amount, result.new_debt_amount)?;
+    crate::sorted_troves::validate_neighbor_hints(&params.collateral_denom, new_nicr, &ctx.remaining_accounts[consumed..], ctx.program_id)?;
+
+    // Update the actual accounts with the results
+    ctx.accounts.user_debt_amount.amount = result.new_debt_amount;
+    ctx.accounts.user_collateral_amount.amount = result.new_collateral_amount;
+    ctx.accounts.liquidity_threshold.ratio = result.new_icr;
+    ctx.accounts.liquidity_threshold.set_nominal_ratios(
+        &other_collateral.collateral_amounts(&params.collateral_denom, result.new_collateral_amount),
+        result.new_debt_amount,
+    )?;
+    ctx.accounts.redistribution_snapshot.update_stake(previous_stake, result.new_collateral_amount)?;
+    ctx.accounts.total_collateral_amount.amount = if params.is_collateral_increase {
+        ctx.accounts.total_collateral_amount.amount
+            .checked_add(params.collateral_change)
+            .ok_or(AerospacerProtocolError::OverflowError)?
+    } else {
+        ctx.accounts.total_collateral_amount.amount
+            .checked_sub(params.collateral_change)
+            .ok_or(AerospacerProtocolError::OverflowError)?
+    };
+
+    // Withdrawing a denom completely drops it from the trove
+    if result.new_collateral_amount == 0 {
+        ctx.accounts.user_debt_amount.remove_collateral_denom(&params.collateral_denom);
+    }
+
+    if params.debt_change > 0 && params.is_debt_increase {
+        // Mint total loan amount (including fee)
+        let mint_seeds = &[
+            PROTOCOL_STABLECOIN_VAULT_SEED,
+            &[ctx.bumps.protocol_stablecoin_account],
+        ];
+        let mint_signer = &[&mint_seeds[..]];
+
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.stable_coin_mint.to_account_info(),
+                to: ctx.accounts.user_stablecoin_account.to_account_info(),
+                authority: ctx.accounts.protocol_stablecoin_account.to_account_info(),
+            },
+            mint_signer,
+        );
+        anchor_spl::token::mint_to(mint_ctx, params.debt_change)?;
+
+        // Distribute fee via CPI to aerospacer-fees
+        if fee_amount > 0 {
+            process_protocol_fee(
+                params.debt_change,
+                fee_amount,
+                ctx.accounts.fees_program.to_account_info(),
+                ctx.accounts.user.to_account_info(),
+                ctx.accounts.fees_state.to_account_info(),
+                ctx.accounts.user_stablecoin_account.to_account_info(),
+                ctx.accounts.stability_pool_token_account.to_account_info(),
+                ctx.accounts.fee_address_1_token_account.to_account_info(),
+                ctx.accounts.fee_address_2_token_account.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+            )?;
+        }
+    } else if params.debt_change > 0 {
+        // Burn the repaid stablecoin
+        let burn_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Burn {
+                mint: ctx.accounts.stable_coin_mint.to_account_info(),
+                from: ctx.accounts.user_stablecoin_account.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            },
+        );
+        anchor_spl::token::burn(burn_ctx, params.debt_change)?;
+    }
+
+    msg!("Trove adjusted successfully");
+    msg!("Collateral {}: {} {}",
+         if params.is_collateral_increase { "added" } else { "removed" },
+         params.collateral_change, params.collateral_denom);
+    msg!("Debt {}: {} aUSD",
+         if params.is_debt_increase { "borrowed" } else { "repaid" },
+         params.debt_change);
+    msg!("Fee amount: {} aUSD ({} bps)", fee_amount, fee_rate_to_bps(fee_rate));
+    msg!("New debt amount: {}", result.new_debt_amount);
+    msg!("New collateral amount: {}", result.new_collateral_amount);
+    msg!("New ICR: {}", result.new_icr);
+
+    emit!(TroveAdjusted {
+        owner: ctx.accounts.user.key(),
+        operation: TroveOperation::AdjustTrove,
+        collateral_denom: params.collateral_denom,
+        collateral_change: params.collateral_change,
+        is_collateral_increase: params.is_collateral_increase,
+        debt_change: adjustment.debt_change,
+        is_debt_increase: params.is_debt_increase,
+        borrowing_fee: fee_amount,
+        collateral_amount: result.new_collateral_amount,
+        debt_amount: result.new_debt_amount,
+        icr: result.new_icr,
+    });
+
+    Ok(())
+}
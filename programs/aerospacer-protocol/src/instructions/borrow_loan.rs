@@ -5,13 +5,16 @@ use crate::error::*;
 use crate::trove_management::*;
 use crate::account_management::*;
 use crate::oracle::*;
+use crate::redistribution::apply_trove_rewards;
 use crate::fees_integration::*;
 use crate::utils::*;
+use crate::events::{TroveAdjusted, TroveOperation};
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct BorrowLoanParams {
     pub loan_amount: u64,
     pub collateral_denom: String,
+    pub max_fee_bps: u16, // Highest borrowing fee rate accepted
     pub prev_node_id: Option<Pubkey>,
     pub next_node_id: Option<Pubkey>,
 }
@@ -93,6 +96,20 @@ pub struct BorrowLoan<'info> {
     )]
     pub total_collateral_amount: Account<'info, TotalCollateralAmount>,
 
+    #[account(
+        mut,
+        seeds = [b"redistribution_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub redistribution_snapshot: Box<Account<'info, RedistributionSnapshot>>,
+
+    // Risk limits of collateral_denom, set by the admin
+    #[account(
+        seeds = [b"collateral_config", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub collateral_config: Box<Account<'info, CollateralConfig>>,
+
     // Oracle context - integration with our aerospacer-oracle
     /// CHECK: Our oracle program - validated against state
     #[account(
@@ -146,7 +163,13 @@ pub struct BorrowLoan<'info> {
 
 
 
-pub fn handler(ctx: Context<BorrowLoan>, params: BorrowLoanParams) -> Result<()> {
+// remaining_accounts:
+// - One [UserCollateralAmount, RedistributionSnapshot, pyth_price_account, CollateralConfig] group
+//   per other denom the trove holds, in UserDebtAmount.collateral_denoms order (see trove_management::load_other_collateral)
+// - One [TotalCollateralAmount, pyth_price_account] pair per denom the oracle supports,
+//   used for the Recovery Mode checks (see trove_management::load_system_collateral_block)
+// - Followed by the optional neighbor hints [prev_LiquidityThreshold, next_LiquidityThreshold]
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, BorrowLoan<'info>>, params: BorrowLoanParams) -> Result<()> {
     // Validate input parameters
     require!(
         params.loan_amount > 0,
@@ -169,6 +192,15 @@ pub fn handler(ctx: Context<BorrowLoan>, params: BorrowLoanParams) -> Result<()>
         AerospacerProtocolError::TroveDoesNotExist
     );
     
+    // Bring the trove up to date with redistributed liquidations before pricing it
+    apply_trove_rewards(
+        &mut ctx.accounts.user_debt_amount,
+        &mut ctx.accounts.user_collateral_amount,
+        &mut ctx.accounts.redistribution_snapshot,
+        ctx.remaining_accounts,
+        OTHER_COLLATERAL_GROUP_LEN,
+    )?;
+    
     // Create context structs for clean architecture
     let mut trove_ctx = TroveContext {
         user: ctx.accounts.user.clone(),
@@ -193,15 +225,36 @@ pub fn handler(ctx: Context<BorrowLoan>, params: BorrowLoanParams) -> Result<()>
         clock: ctx.accounts.clock.to_account_info(),
     };
     
-    // Calculate fee and net loan amount
-    let fee_amount = calculate_protocol_fee(params.loan_amount, ctx.accounts.state.protocol_fee)?;
+    // Calculate fee and net loan amount - the borrowing fee follows the decayed base rate
+    decay_base_rate_from_borrowing(&mut ctx.accounts.state, ctx.accounts.clock.unix_timestamp)?;
+    let fee_rate = get_borrowing_rate(&ctx.accounts.state);
+    check_max_fee(fee_rate, params.max_fee_bps)?;
+    let fee_amount = calculate_fee(params.loan_amount, fee_rate)?;
     let net_loan_amount = params.loan_amount - fee_amount;
     
+    // Price the trove's other collateral so the ICR covers every denom
+    let (other_collateral, consumed) = load_other_collateral(
+        &ctx.accounts.user_debt_amount,
+        &params.collateral_denom,
+        ctx.remaining_accounts,
+        &oracle_ctx,
+    )?;
+    
+    // Price the protocol's total collateral for the TCR
+    let (system_collateral, system_accounts) = load_system_collateral_block(
+        &oracle_ctx,
+        &ctx.remaining_accounts[consumed..],
+    )?;
+    let consumed = consumed + system_accounts;
+    
     // Use TroveManager for clean implementation (with net amount)
     let result = TroveManager::borrow_loan(
         &mut trove_ctx,
         &mut collateral_ctx,
         &oracle_ctx,
+        &ctx.accounts.collateral_config,
+        &other_collateral,
+        &system_collateral,
         net_loan_amount,
     )?;
     
@@ -209,60 +262,22 @@ pub fn handler(ctx: Context<BorrowLoan>, params: BorrowLoanParams) -> Result<()>
     // Production clients MUST provide neighbor hints via remainingAccounts for proper sorted list maintenance
     // Pattern: [prev_LiquidityThreshold, next_LiquidityThreshold] or [prev_LT] or [next_LT] or []
     // Optional for backward compatibility with tests, but REQUIRED in production
-    if !ctx.remaining_accounts.is_empty() {
-        use crate::sorted_troves;
-        
-        msg!("Validating ICR ordering with {} neighbor account(s)", ctx.remaining_accounts.len());
-        
-        let prev_icr = if ctx.remaining_accounts.len() >= 1 {
-            let prev_lt = &ctx.remaining_accounts[0];
-            let prev_data = prev_lt.try_borrow_data()?;
-            let prev_threshold = LiquidityThreshold::try_deserialize(&mut &prev_data[..])?;
-            let prev_owner = prev_threshold.owner;
-            let prev_ratio = prev_threshold.ratio;
-            drop(prev_data);
-            
-            // Verify this is a real PDA, not a fake account
-            sorted_troves::verify_liquidity_threshold_pda(prev_lt, prev_owner, ctx.program_id)?;
-            
-            Some(prev_ratio)
-        } else {
-            None
-        };
-        
-        let next_icr = if ctx.remaining_accounts.len() >= 2 {
-            let next_lt = &ctx.remaining_accounts[1];
-            let next_data = next_lt.try_borrow_data()?;
-            let next_threshold = LiquidityThreshold::try_deserialize(&mut &next_data[..])?;
-            let next_owner = next_threshold.owner;
-            let next_ratio = next_threshold.ratio;
-            drop(next_data);
-            
-            // Verify this is a real PDA, not a fake account
-            sorted_troves::verify_liquidity_threshold_pda(next_lt, next_owner, ctx.program_id)?;
-            
-            Some(next_ratio)
-        } else {
-            None
-        };
-        
-        // Validate ordering BEFORE updating state
-        sorted_troves::validate_icr_ordering(result.new_icr, prev_icr, next_icr)?;
-        msg!("✓ ICR ordering validated successfully");
-    } else {
-        msg!("⚠ WARNING: No neighbor hints provided - skipping ICR ordering validation");
-        msg!("⚠ Production clients MUST provide neighbor hints for sorted list integrity");
-    }
+    let new_nicr = crate::sorted_troves::compute_nominal_icr(result.new_collateral_amount, result.new_debt_amount)?;
+    crate::sorted_troves::validate_neighbor_hints(&params.collateral_denom, new_nicr, &ctx.remaining_accounts[consumed..], ctx.program_id)?;
     
     // Update the actual accounts with the results
     ctx.accounts.user_debt_amount.amount = result.new_debt_amount;
     ctx.accounts.liquidity_threshold.ratio = result.new_icr;
+    ctx.accounts.liquidity_threshold.set_nominal_ratios(
+        &other_collateral.collateral_amounts(&params.collateral_denom, result.new_collateral_amount),
+        result.new_debt_amount,
+    )?;
     ctx.accounts.state.total_debt_amount = trove_ctx.state.total_debt_amount;
     
     // Mint total loan amount (including fee)
     // Use invoke_signed for PDA authority
     let mint_seeds = &[
-        b"protocol_stablecoin_vault".as_ref(),
+        PROTOCOL_STABLECOIN_VAULT_SEED,
         &[ctx.bumps.protocol_stablecoin_account],
     ];
     let mint_signer = &[&mint_seeds[..]];
@@ -282,7 +297,7 @@ pub fn handler(ctx: Context<BorrowLoan>, params: BorrowLoanParams) -> Result<()>
     if fee_amount > 0 {
         let net_amount = process_protocol_fee(
             params.loan_amount,
-            ctx.accounts.state.protocol_fee,
+            fee_amount,
             ctx.accounts.fees_program.to_account_info(),
             ctx.accounts.user.to_account_info(),
             ctx.accounts.fees_state.to_account_info(),
@@ -300,11 +315,25 @@ pub fn handler(ctx: Context<BorrowLoan>, params: BorrowLoanParams) -> Result<()>
     msg!("Loan borrowed successfully");
     msg!("Total amount: {} aUSD", params.loan_amount);
     msg!("Net loan amount: {} aUSD", net_loan_amount);
-    msg!("Fee amount: {} aUSD", fee_amount);
+    msg!("Fee amount: {} aUSD ({} bps)", fee_amount, fee_rate_to_bps(fee_rate));
     msg!("Collateral denom: {}", params.collateral_denom);
     msg!("New debt amount: {}", result.new_debt_amount);
     msg!("New ICR: {}", result.new_icr);
     msg!("Collateral amount: {}", result.new_collateral_amount);
     
+    emit!(TroveAdjusted {
+        owner: ctx.accounts.user.key(),
+        operation: TroveOperation::BorrowLoan,
+        collateral_denom: params.collateral_denom,
+        collateral_change: 0,
+        is_collateral_increase: false,
+        debt_change: net_loan_amount,
+        is_debt_increase: true,
+        borrowing_fee: fee_amount,
+        collateral_amount: result.new_collateral_amount,
+        debt_amount: result.new_debt_amount,
+        icr: result.new_icr,
+    });
+    
     Ok(())
 }
\ No newline at end of file
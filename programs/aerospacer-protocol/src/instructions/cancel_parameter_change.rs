@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::AerospacerProtocolError;
+use crate::events::ParameterChangeCancelled;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct CancelParameterChangeParams {
+    pub parameter: ProtocolParameter,
+}
+
+#[derive(Accounts)]
+#[instruction(params: CancelParameterChangeParams)]
+pub struct CancelParameterChange<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [b"state"],
+        bump,
+        constraint = state.admin == admin.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub state: Account<'info, StateAccount>,
+    
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"pending_parameter_change", params.parameter.seed()],
+        bump
+    )]
+    pub pending_parameter_change: Account<'info, PendingParameterChange>,
+}
+
+pub fn handler(ctx: Context<CancelParameterChange>, params: CancelParameterChangeParams) -> Result<()> {
+    // Pending account is closed via Anchor's `close` constraint
+    msg!("Parameter change cancelled: {:?}", params.parameter);
+    msg!("Discarded value: {}", ctx.accounts.pending_parameter_change.new_value);
+    
+    emit!(ParameterChangeCancelled {
+        parameter: params.parameter,
+        discarded_value: ctx.accounts.pending_parameter_change.new_value,
+    });
+    
+    Ok(())
+}
@@ -2,6 +2,11 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount, Transfer, Burn};
 use crate::state::*;
 use crate::error::*;
+use crate::trove_management::{load_user_collateral_amount, set_user_collateral_amount};
+use crate::account_management::burn_gas_compensation;
+use crate::redistribution::{apply_trove_rewards, modify_redistribution_snapshot};
+use crate::trove_registry::deregister_trove;
+use crate::events::{DenomAmount, TroveClosed};
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct CloseTroveParams {
@@ -40,6 +45,21 @@ pub struct CloseTrove<'info> {
     )]
     pub liquidity_threshold: Box<Account<'info, LiquidityThreshold>>,
 
+    // The registry page listing the trove (under the denom it was opened with)
+    #[account(
+        mut,
+        seeds = [b"trove_registry_page", trove_registry_page.denom.as_bytes(), &trove_registry_page.page_index.to_le_bytes()],
+        bump
+    )]
+    pub trove_registry_page: Box<Account<'info, TroveRegistryPage>>,
+
+    #[account(
+        mut,
+        seeds = [b"trove_registry", trove_registry_page.denom.as_bytes()],
+        bump
+    )]
+    pub trove_registry: Box<Account<'info, TroveRegistry>>,
+
     #[account(mut)]
     pub state: Box<Account<'info, StateAccount>>,
 
@@ -81,28 +101,61 @@ pub struct CloseTrove<'info> {
     )]
     pub total_collateral_amount: AccountInfo<'info>,
 
+    #[account(
+        mut,
+        seeds = [b"redistribution_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub redistribution_snapshot: Box<Account<'info, RedistributionSnapshot>>,
+
+    /// CHECK: Gas compensation vault PDA - the trove's reserve is burned from it
+    #[account(
+        mut,
+        seeds = [b"gas_compensation_vault"],
+        bump
+    )]
+    pub gas_compensation_vault: AccountInfo<'info>,
+
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
-pub fn handler(ctx: Context<CloseTrove>, params: CloseTroveParams) -> Result<()> {
+// remaining_accounts: for every other denom the trove holds, in UserDebtAmount.collateral_denoms order:
+// [UserCollateralAmount, RedistributionSnapshot, protocol_collateral_vault, user's token account for that denom,
+//  TotalCollateralAmount]
+const CLOSE_GROUP_LEN: usize = 5;
+
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, CloseTrove<'info>>, params: CloseTroveParams) -> Result<()> {
     // Validate collateral denomination
     require!(
         !params.collateral_denom.is_empty(),
         AerospacerProtocolError::InvalidAmount
     );
     
+    // Redistributed debt and collateral are settled together with the rest of the trove
+    apply_trove_rewards(
+        &mut ctx.accounts.user_debt_amount,
+        &mut ctx.accounts.user_collateral_amount,
+        &mut ctx.accounts.redistribution_snapshot,
+        ctx.remaining_accounts,
+        CLOSE_GROUP_LEN,
+    )?;
+    
     let debt_amount = ctx.accounts.user_debt_amount.amount;
     let collateral_amount = ctx.accounts.user_collateral_amount.amount;
     
+    // The gas compensation reserve already sits in the protocol, the user repays the rest
+    let gas_compensation = debt_amount.min(GAS_COMPENSATION);
+    let user_repayment = debt_amount - gas_compensation;
+    
     // Validate user has sufficient stablecoins to repay full debt
     require!(
-        ctx.accounts.user_stablecoin_account.amount >= debt_amount,
+        ctx.accounts.user_stablecoin_account.amount >= user_repayment,
         AerospacerProtocolError::InsufficientCollateral
     );
     
     msg!("Closing trove for user: {}", ctx.accounts.user.key());
-    msg!("Debt to repay: {} aUSD", debt_amount);
+    msg!("Debt to repay: {} aUSD (gas compensation: {})", debt_amount, gas_compensation);
     msg!("Collateral to return: {} {}", collateral_amount, params.collateral_denom);
     
     // STEP 1: Update global state BEFORE token operations (for atomicity)
@@ -113,17 +166,18 @@ pub fn handler(ctx: Context<CloseTrove>, params: CloseTroveParams) -> Result<()>
     
     // Update total collateral for this denomination
     let mut total_collateral_data = ctx.accounts.total_collateral_amount.try_borrow_mut_data()?;
-    let mut total_collateral: TotalCollateralAmount = TotalCollateralAmount::try_from_slice(&total_collateral_data[8..])?;
+    let mut total_collateral = TotalCollateralAmount::try_deserialize(&mut &total_collateral_data[..])?;
     total_collateral.amount = total_collateral.amount
         .checked_sub(collateral_amount)
         .ok_or(AerospacerProtocolError::OverflowError)?;
-    total_collateral.try_serialize(&mut &mut total_collateral_data[8..])?;
+    total_collateral.try_serialize(&mut &mut total_collateral_data[..])?;
     drop(total_collateral_data);
+    ctx.accounts.redistribution_snapshot.update_stake(collateral_amount, 0)?;
     
     msg!("Updated global state - debt: {}, collateral tracked", ctx.accounts.state.total_debt_amount);
     
     // STEP 2: Burn stablecoins to repay debt
-    if debt_amount > 0 {
+    if user_repayment > 0 {
         let burn_ctx = CpiContext::new(
             ctx.accounts.token_program.to_account_info(),
             Burn {
@@ -132,17 +186,23 @@ pub fn handler(ctx: Context<CloseTrove>, params: CloseTroveParams) -> Result<()>
                 authority: ctx.accounts.user.to_account_info(),
             },
         );
-        anchor_spl::token::burn(burn_ctx, debt_amount)?;
+        anchor_spl::token::burn(burn_ctx, user_repayment)?;
         
-        msg!("Burned {} aUSD to repay debt", debt_amount);
+        msg!("Burned {} aUSD to repay debt", user_repayment);
     }
+    burn_gas_compensation(
+        &ctx.accounts.token_program.to_account_info(),
+        &ctx.accounts.stable_coin_mint.to_account_info(),
+        &ctx.accounts.gas_compensation_vault,
+        gas_compensation,
+    )?;
     
     // STEP 3: Transfer collateral back to user
     if collateral_amount > 0 {
         // Get PDA seeds for signing
         let collateral_denom_bytes = params.collateral_denom.as_bytes();
         let seeds = &[
-            b"protocol_collateral_vault",
+            PROTOCOL_COLLATERAL_VAULT_SEED,
             collateral_denom_bytes,
             &[ctx.bumps.protocol_collateral_vault],
         ];
@@ -162,9 +222,37 @@ pub fn handler(ctx: Context<CloseTrove>, params: CloseTroveParams) -> Result<()>
         msg!("Transferred {} {} back to user", collateral_amount, params.collateral_denom);
     }
     
-    // STEP 4: Zero out user accounts AFTER successful token operations
+    // STEP 4: Return the trove's collateral in every other denom it holds
+    let other_denoms: Vec<String> = ctx.accounts.user_debt_amount.collateral_denoms
+        .iter()
+        .filter(|d| **d != params.collateral_denom)
+        .cloned()
+        .collect();
+    require!(
+        ctx.remaining_accounts.len() >= other_denoms.len() * CLOSE_GROUP_LEN,
+        AerospacerProtocolError::MissingCollateralAccount
+    );
+    let mut returned_collateral = vec![DenomAmount { denom: params.collateral_denom.clone(), amount: collateral_amount }];
+    for (denom, accounts) in other_denoms.iter().zip(ctx.remaining_accounts.chunks(CLOSE_GROUP_LEN)) {
+        let returned = return_other_collateral(
+            &ctx.accounts.user,
+            denom,
+            accounts,
+            &ctx.accounts.token_program,
+        )?;
+        msg!("Transferred {} {} back to user", returned, denom);
+        returned_collateral.push(DenomAmount { denom: denom.clone(), amount: returned });
+    }
+    
+    // STEP 5: Zero out user accounts AFTER successful token operations
     ctx.accounts.user_debt_amount.amount = 0;
+    ctx.accounts.user_debt_amount.collateral_denoms.clear();
     ctx.accounts.user_collateral_amount.amount = 0;
+    deregister_trove(
+        &mut ctx.accounts.trove_registry,
+        &mut ctx.accounts.trove_registry_page,
+        &ctx.accounts.user.key(),
+    )?;
     
     // NOTE: Sorted troves management moved off-chain
     // LiquidityThreshold account is automatically closed via Anchor's `close` constraint
@@ -176,5 +264,97 @@ pub fn handler(ctx: Context<CloseTrove>, params: CloseTroveParams) -> Result<()>
     msg!("  Collateral returned: {} {}", collateral_amount, params.collateral_denom);
     msg!("  Total protocol debt: {}", ctx.accounts.state.total_debt_amount);
     
+    emit!(TroveClosed {
+        owner: ctx.accounts.user.key(),
+        debt_repaid: user_repayment,
+        collateral: returned_collateral,
+    });
+    
     Ok(())
 }
+
+/// Return a trove's collateral in one of its other denoms and zero the position
+/// 
+/// `accounts` is [UserCollateralAmount, RedistributionSnapshot, protocol_collateral_vault, user token account,
+/// TotalCollateralAmount]
+fn return_other_collateral<'info>(
+    user: &Signer<'info>,
+    denom: &str,
+    accounts: &[AccountInfo<'info>],
+    token_program: &Program<'info, Token>,
+) -> Result<u64> {
+    let collateral_account = &accounts[0];
+    let redistribution_snapshot = &accounts[1];
+    let protocol_collateral_vault = &accounts[2];
+    let user_collateral_account = &accounts[3];
+    let total_collateral_account = &accounts[4];
+    
+    let collateral_amount = load_user_collateral_amount(collateral_account, &user.key(), denom)?.amount;
+    
+    // Vault and total must be this denom's PDAs
+    let (vault_pda, vault_bump) = Pubkey::find_program_address(
+        &[PROTOCOL_COLLATERAL_VAULT_SEED, denom.as_bytes()],
+        &crate::ID,
+    );
+    require!(
+        protocol_collateral_vault.key() == vault_pda,
+        AerospacerProtocolError::MissingCollateralAccount
+    );
+    let (total_pda, _bump) = Pubkey::find_program_address(&TotalCollateralAmount::seeds(denom), &crate::ID);
+    require!(
+        total_collateral_account.key() == total_pda,
+        AerospacerProtocolError::MissingCollateralAccount
+    );
+    
+    // Collateral can only go back to the user's own account for the vault's mint
+    require!(
+        user_collateral_account.owner == &anchor_spl::token::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+    let vault_mint = TokenAccount::try_deserialize(&mut &protocol_collateral_vault.try_borrow_data()?[..])?.mint;
+    let recipient = TokenAccount::try_deserialize(&mut &user_collateral_account.try_borrow_data()?[..])?;
+    require!(
+        recipient.owner == user.key(),
+        AerospacerProtocolError::Unauthorized
+    );
+    require!(
+        recipient.mint == vault_mint,
+        AerospacerProtocolError::InvalidMint
+    );
+    
+    // Update total collateral for this denomination
+    let mut total_collateral_data = total_collateral_account.try_borrow_mut_data()?;
+    let mut total_collateral = TotalCollateralAmount::try_deserialize(&mut &total_collateral_data[..])?;
+    total_collateral.amount = total_collateral.amount
+        .checked_sub(collateral_amount)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+    total_collateral.try_serialize(&mut &mut total_collateral_data[..])?;
+    drop(total_collateral_data);
+    
+    // The position's stake leaves with it (the snapshot was checked by apply_trove_rewards)
+    modify_redistribution_snapshot(redistribution_snapshot, |snapshot| snapshot.update_stake(collateral_amount, 0))?;
+    
+    if collateral_amount > 0 {
+        let seeds = &[
+            PROTOCOL_COLLATERAL_VAULT_SEED,
+            denom.as_bytes(),
+            &[vault_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        
+        let transfer_ctx = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: protocol_collateral_vault.clone(),
+                to: user_collateral_account.clone(),
+                authority: protocol_collateral_vault.clone(),
+            },
+            signer_seeds,
+        );
+        anchor_spl::token::transfer(transfer_ctx, collateral_amount)?;
+    }
+    
+    set_user_collateral_amount(collateral_account, 0)?;
+    
+    Ok(collateral_amount)
+}
\ No newline at end of file
@@ -0,0 +1,89 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Mint;
+use crate::state::*;
+use crate::error::AerospacerProtocolError;
+use crate::utils::validate_collateral_config;
+use crate::events::CollateralConfigSet;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct CreateCollateralConfigParams {
+    pub collateral_denom: String,
+    pub minimum_collateral_ratio: u8,
+    pub liquidation_threshold: u8,
+    pub liquidation_penalty_bps: u16,
+    pub treasury_share_bps: u16,
+    pub debt_ceiling: u64,
+    pub deposit_cap: u64,
+    pub enabled: bool,
+}
+
+#[derive(Accounts)]
+#[instruction(params: CreateCollateralConfigParams)]
+pub struct CreateCollateralConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [b"state"],
+        bump,
+        constraint = state.admin == admin.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub state: Account<'info, StateAccount>,
+    
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + CollateralConfig::LEN,
+        seeds = [b"collateral_config", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+    
+    // The denom's stability pool S factor, credited by liquidations from here on
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + StabilityPoolSnapshot::LEN,
+        seeds = [b"stability_pool_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub stability_pool_snapshot: Account<'info, StabilityPoolSnapshot>,
+    
+    // The denom's token mint - its decimals are recorded in the config
+    pub collateral_mint: Account<'info, Mint>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<CreateCollateralConfig>, params: CreateCollateralConfigParams) -> Result<()> {
+    let config = CollateralConfig {
+        denom: params.collateral_denom,
+        minimum_collateral_ratio: params.minimum_collateral_ratio,
+        liquidation_threshold: params.liquidation_threshold,
+        liquidation_penalty_bps: params.liquidation_penalty_bps,
+        treasury_share_bps: params.treasury_share_bps,
+        debt_ceiling: params.debt_ceiling,
+        deposit_cap: params.deposit_cap,
+        decimals: ctx.accounts.collateral_mint.decimals,
+        enabled: params.enabled,
+    };
+    validate_collateral_config(&config)?;
+    
+    msg!("Collateral config created for {}", config.denom);
+    msg!("MCR: {}%, liquidation threshold: {}%", config.minimum_collateral_ratio, config.liquidation_threshold);
+    msg!("Liquidation penalty: {} bps (treasury share: {} bps)", config.liquidation_penalty_bps, config.treasury_share_bps);
+    msg!("Debt ceiling: {}, deposit cap: {}", config.debt_ceiling, config.deposit_cap);
+    msg!("Decimals: {}, enabled: {}", config.decimals, config.enabled);
+    
+    let snapshot = &mut ctx.accounts.stability_pool_snapshot;
+    if snapshot.denom.is_empty() {
+        snapshot.denom = config.denom.clone();
+        snapshot.epoch = ctx.accounts.state.epoch;
+        snapshot.scale = ctx.accounts.state.current_scale;
+    }
+    
+    emit!(CollateralConfigSet::new(&config));
+    ctx.accounts.collateral_config.set_inner(config);
+    
+    Ok(())
+}
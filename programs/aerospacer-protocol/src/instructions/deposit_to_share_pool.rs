@@ -0,0 +1,219 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount, Mint, Transfer, MintTo};
+use crate::state::*;
+use crate::utils::*;
+use crate::error::*;
+use crate::events::SharesDeposited;
+use crate::oracle::{OracleContext, oracle_supported_denoms};
+use crate::stability_pool::settle_deposit_gains;
+use crate::share_pool::{lock_shares, share_pool_collateral, share_pool_value, shares_for_deposit};
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct DepositToSharePoolParams {
+    pub amount: u64,
+}
+
+#[derive(Accounts)]
+pub struct DepositToSharePool<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"share_pool"],
+        bump = share_pool.bump
+    )]
+    pub share_pool: Box<Account<'info, SharePool>>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake_amount", share_pool.key().as_ref()],
+        bump,
+        constraint = pool_stake_amount.owner == share_pool.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub pool_stake_amount: Box<Account<'info, UserStakeAmount>>,
+
+    #[account(
+        mut,
+        address = share_pool.share_mint @ AerospacerProtocolError::InvalidMint
+    )]
+    pub share_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        constraint = user_share_account.owner == user.key() @ AerospacerProtocolError::Unauthorized,
+        constraint = user_share_account.mint == share_mint.key() @ AerospacerProtocolError::InvalidMint
+    )]
+    pub user_share_account: Box<Account<'info, TokenAccount>>,
+
+    // Fresh shares wait out the unstake cooldown here, recorded against the depositor
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + ShareLock::LEN,
+        seeds = [b"share_lock", user.key().as_ref()],
+        bump
+    )]
+    pub share_lock: Box<Account<'info, ShareLock>>,
+
+    #[account(
+        mut,
+        seeds = [SHARE_ESCROW_SEED],
+        bump
+    )]
+    pub share_escrow: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub state: Box<Account<'info, StateAccount>>,
+
+    #[account(
+        mut,
+        constraint = user_stablecoin_account.owner == user.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub user_stablecoin_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_stablecoin_vault"],
+        bump
+    )]
+    pub protocol_stablecoin_vault: Box<Account<'info, TokenAccount>>,
+
+    // Oracle context - UncheckedAccount to reduce stack usage
+    /// CHECK: Our oracle program - validated against state in handler
+    pub oracle_program: UncheckedAccount<'info>,
+
+    /// CHECK: Oracle state account - validated against state in handler
+    pub oracle_state: UncheckedAccount<'info>,
+
+    /// CHECK: Clock sysvar - validated in handler if needed
+    pub clock: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+// remaining_accounts, located by address: the StabilityPoolSnapshot and the share pool's
+// UserCollateralSnapshot of every denom the oracle supports, the EpochScaleSum PDAs its gains are
+// read from (see stability_pool::settle_deposit_gains), and the Pyth price account of every denom
+// it holds gains in (see share_pool::share_pool_value)
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositToSharePool<'info>>,
+    params: DepositToSharePoolParams,
+) -> Result<()> {
+    require!(
+        params.amount >= MINIMUM_LOAN_AMOUNT, // Same minimum as stake
+        AerospacerProtocolError::InvalidAmount
+    );
+    require!(
+        ctx.accounts.user_stablecoin_account.amount >= params.amount,
+        AerospacerProtocolError::InsufficientCollateral
+    );
+
+    // Validate oracle accounts
+    require!(
+        ctx.accounts.oracle_program.key() == ctx.accounts.state.oracle_helper_addr,
+        AerospacerProtocolError::Unauthorized
+    );
+    require!(
+        ctx.accounts.oracle_state.key() == ctx.accounts.state.oracle_state_addr,
+        AerospacerProtocolError::Unauthorized
+    );
+
+    let share_pool_key = ctx.accounts.share_pool.key();
+    let denoms = oracle_supported_denoms(&ctx.accounts.oracle_state)?;
+    let state = &mut ctx.accounts.state;
+    let pool_stake_amount = &mut ctx.accounts.pool_stake_amount;
+    let compounded_deposit = calculate_compounded_stake(pool_stake_amount, state)?;
+
+    // Bring the pool's collateral gains up to date so new shares buy into them at market
+    settle_deposit_gains(
+        pool_stake_amount,
+        state,
+        &denoms,
+        &share_pool_key,
+        &ctx.accounts.user.to_account_info(),
+        ctx.remaining_accounts,
+        &ctx.accounts.system_program.to_account_info(),
+    )?;
+
+    let collateral = share_pool_collateral(&share_pool_key, &denoms, ctx.remaining_accounts)?;
+    // Every price feed is located in remaining_accounts, so no dedicated Pyth account is needed
+    let oracle_ctx = OracleContext {
+        oracle_program: ctx.accounts.oracle_program.to_account_info(),
+        oracle_state: ctx.accounts.oracle_state.to_account_info(),
+        pyth_price_account: ctx.accounts.oracle_state.to_account_info(),
+        clock: ctx.accounts.clock.to_account_info(),
+    };
+    let pool_value = share_pool_value(compounded_deposit, &collateral, &oracle_ctx, ctx.remaining_accounts)?;
+    let shares = shares_for_deposit(params.amount, pool_value, ctx.accounts.share_mint.supply)?;
+    require!(shares > 0, AerospacerProtocolError::InvalidAmount);
+
+    // Transfer stablecoins from user to protocol vault
+    let transfer_ctx = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        Transfer {
+            from: ctx.accounts.user_stablecoin_account.to_account_info(),
+            to: ctx.accounts.protocol_stablecoin_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        },
+    );
+    anchor_spl::token::transfer(transfer_ctx, params.amount)?;
+
+    // The pool re-deposits its compounded deposit plus the new aUSD at the current P, epoch and scale
+    pool_stake_amount.amount = safe_add(compounded_deposit, params.amount)?;
+    pool_stake_amount.p_snapshot = state.p_factor;
+    pool_stake_amount.epoch_snapshot = state.epoch;
+    pool_stake_amount.scale_snapshot = state.current_scale;
+    pool_stake_amount.last_update_block = Clock::get()?.slot;
+
+    state.total_stake_amount = safe_add(state.total_stake_amount, params.amount)?;
+
+    // Mint the shares into escrow, signed by the SharePool - they wait out the unstake cooldown there
+    let share_pool_seeds = &[b"share_pool".as_ref(), &[ctx.accounts.share_pool.bump]];
+    let share_pool_signer = &[&share_pool_seeds[..]];
+    let mint_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        MintTo {
+            mint: ctx.accounts.share_mint.to_account_info(),
+            to: ctx.accounts.share_escrow.to_account_info(),
+            authority: ctx.accounts.share_pool.to_account_info(),
+        },
+        share_pool_signer,
+    );
+    anchor_spl::token::mint_to(mint_ctx, shares)?;
+
+    let share_lock = &mut ctx.accounts.share_lock;
+    share_lock.owner = ctx.accounts.user.key();
+    let released = lock_shares(share_lock, shares, Clock::get()?.slot, state.unstake_cooldown_slots)?;
+    if released > 0 {
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.share_escrow.to_account_info(),
+                to: ctx.accounts.user_share_account.to_account_info(),
+                authority: ctx.accounts.share_pool.to_account_info(),
+            },
+            share_pool_signer,
+        );
+        anchor_spl::token::transfer(transfer_ctx, released)?;
+    }
+
+    msg!("Deposited to share pool successfully");
+    msg!("User: {}", ctx.accounts.user.key());
+    msg!("Amount: {} aUSD", params.amount);
+    msg!("Pool value before: {} aUSD", pool_value);
+    msg!("Shares minted: {}", shares);
+    msg!("Shares locked until slot {}: {}", share_lock.unlock_slot, share_lock.shares);
+    msg!("Pool deposit: {} aUSD", pool_stake_amount.amount);
+
+    emit!(SharesDeposited {
+        user: ctx.accounts.user.key(),
+        amount: params.amount,
+        shares,
+        locked_shares: share_lock.shares,
+        unlock_slot: share_lock.unlock_slot,
+        pool_deposit: pool_stake_amount.amount,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::AerospacerProtocolError;
+use crate::utils::{apply_parameter_change, check_parameter_change_executable};
+use crate::events::ParameterChangeExecuted;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct ExecuteParameterChangeParams {
+    pub parameter: ProtocolParameter,
+}
+
+#[derive(Accounts)]
+#[instruction(params: ExecuteParameterChangeParams)]
+pub struct ExecuteParameterChange<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"state"],
+        bump,
+        constraint = state.admin == admin.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub state: Account<'info, StateAccount>,
+    
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"pending_parameter_change", params.parameter.seed()],
+        bump,
+        constraint = pending_parameter_change.parameter == params.parameter @ AerospacerProtocolError::InvalidAccountData
+    )]
+    pub pending_parameter_change: Account<'info, PendingParameterChange>,
+}
+
+pub fn handler(ctx: Context<ExecuteParameterChange>, params: ExecuteParameterChangeParams) -> Result<()> {
+    let pending = &ctx.accounts.pending_parameter_change;
+    check_parameter_change_executable(pending, Clock::get()?.unix_timestamp)?;
+    
+    apply_parameter_change(&mut ctx.accounts.state, params.parameter, pending.new_value)?;
+    
+    // Pending account is closed via Anchor's `close` constraint
+    msg!("Parameter change executed: {:?}", params.parameter);
+    msg!("New value: {}", pending.new_value);
+    msg!("Queued at: {}, executable at: {}", pending.queued_at, pending.executable_at);
+    
+    emit!(ParameterChangeExecuted {
+        parameter: params.parameter,
+        new_value: pending.new_value,
+    });
+    
+    Ok(())
+}
@@ -1,6 +1,8 @@
 use anchor_lang::prelude::*;
 use crate::state::*;
-use anchor_spl::token::{Token, SetAuthority, set_authority, spl_token::instruction::AuthorityType};
+use crate::decimal::Decimal;
+use crate::error::*;
+use anchor_spl::token::{Token, Mint, SetAuthority, set_authority, spl_token::instruction::AuthorityType};
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct InitializeParams {
@@ -26,9 +28,12 @@ pub struct Initialize<'info> {
     #[account(mut)]
     pub admin: Signer<'info>,
     
-    /// CHECK: This is the stable coin mint account
-    #[account(mut)]
-    pub stable_coin_mint: UncheckedAccount<'info>,
+    // aUSD amounts are u64 everywhere, so the mint must use 6 decimals
+    #[account(
+        mut,
+        constraint = stable_coin_mint.decimals == AUSD_DECIMALS @ AerospacerProtocolError::InvalidMint
+    )]
+    pub stable_coin_mint: Account<'info, Mint>,
     
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
@@ -50,13 +55,24 @@ pub fn handler(ctx: Context<Initialize>, params: InitializeParams) -> Result<()>
     state.total_stake_amount = 0;
     
     // SNAPSHOT: Initialize P factor and epoch for Liquity Product-Sum algorithm
-    state.p_factor = StateAccount::SCALE_FACTOR; // 10^18
+    state.p_factor = Decimal::ONE;
     state.epoch = 0;
+    state.current_scale = 0;
+    
+    // Admin parameter changes are queued behind this delay
+    state.parameter_timelock_delay = DEFAULT_PARAMETER_TIMELOCK_DELAY;
+    
+    // No stability pool cooldown until the admin sets one
+    state.unstake_cooldown_slots = 0;
+    
+    // Fees start at their floors; redemptions raise the base rate from here
+    state.base_rate = Decimal::ZERO;
+    state.last_fee_operation_time = Clock::get()?.unix_timestamp;
     
     // Move mint authority for the stable coin mint to the protocol PDA (protocol_stablecoin_vault)
     // This matches Injective's model where the protocol contract is the minter.
     let (protocol_stablecoin_vault_pda, _bump) = Pubkey::find_program_address(
-        &[b"protocol_stablecoin_vault"],
+        &[PROTOCOL_STABLECOIN_VAULT_SEED],
         &crate::ID,
     );
 
@@ -84,9 +100,10 @@ pub fn handler(ctx: Context<Initialize>, params: InitializeParams) -> Result<()>
     msg!("Fee Distributor: {}", state.fee_distributor_addr);
     msg!("Fee State: {}", state.fee_state_addr);
     msg!("Minimum Collateral Ratio: {}%", state.minimum_collateral_ratio);
-    msg!("Protocol Fee: {}%", state.protocol_fee);
+    msg!("Max Borrowing Fee: {}%", state.protocol_fee);
     msg!("P factor initialized: {}", state.p_factor);
     msg!("Epoch initialized: {}", state.epoch);
+    msg!("Parameter timelock delay: {}s", state.parameter_timelock_delay);
     
     Ok(())
 } 
\ No newline at end of file
@@ -0,0 +1,88 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, Mint, TokenAccount};
+use crate::state::*;
+use crate::error::AerospacerProtocolError;
+use crate::events::SharePoolInitialized;
+
+#[derive(Accounts)]
+pub struct InitializeSharePool<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump,
+        constraint = state.admin == admin.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub state: Account<'info, StateAccount>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + SharePool::LEN,
+        seeds = [b"share_pool"],
+        bump
+    )]
+    pub share_pool: Account<'info, SharePool>,
+
+    // spAUSD shares carry aUSD's decimals so the first deposit mints them 1:1
+    #[account(
+        init,
+        payer = admin,
+        mint::decimals = AUSD_DECIMALS,
+        mint::authority = share_pool,
+        seeds = [b"share_mint"],
+        bump
+    )]
+    pub share_mint: Account<'info, Mint>,
+
+    // Holds the shares of every ShareLock until their cooldown ends
+    #[account(
+        init,
+        payer = admin,
+        token::mint = share_mint,
+        token::authority = share_pool,
+        seeds = [SHARE_ESCROW_SEED],
+        bump
+    )]
+    pub share_escrow: Account<'info, TokenAccount>,
+
+    // The share pool's stability pool deposit, an ordinary stake owned by the SharePool PDA
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + UserStakeAmount::LEN,
+        seeds = [b"user_stake_amount", share_pool.key().as_ref()],
+        bump
+    )]
+    pub pool_stake_amount: Account<'info, UserStakeAmount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<InitializeSharePool>) -> Result<()> {
+    let share_pool = &mut ctx.accounts.share_pool;
+    share_pool.share_mint = ctx.accounts.share_mint.key();
+    share_pool.bump = ctx.bumps.share_pool;
+
+    let state = &ctx.accounts.state;
+    let pool_stake_amount = &mut ctx.accounts.pool_stake_amount;
+    pool_stake_amount.owner = share_pool.key();
+    pool_stake_amount.amount = 0;
+    pool_stake_amount.p_snapshot = state.p_factor;
+    pool_stake_amount.epoch_snapshot = state.epoch;
+    pool_stake_amount.scale_snapshot = state.current_scale;
+    pool_stake_amount.last_update_block = Clock::get()?.slot;
+
+    msg!("Share pool initialized");
+    msg!("Share pool: {}", share_pool.key());
+    msg!("Share mint: {}", share_pool.share_mint);
+
+    emit!(SharePoolInitialized {
+        share_pool: share_pool.key(),
+        share_mint: share_pool.share_mint,
+    });
+
+    Ok(())
+}
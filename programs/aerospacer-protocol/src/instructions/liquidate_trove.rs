@@ -2,7 +2,17 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount, Mint, Burn};
 use crate::state::*;
 use crate::error::*;
-use crate::oracle::{self, OracleContext, PriceCalculator};
+use crate::oracle::OracleContext;
+use crate::account_management::pay_gas_compensation;
+use crate::trove_management::{
+    load_other_collateral, load_system_collateral_block, pay_liquidation_penalty,
+    set_user_collateral_amount, split_liquidation_penalty, OTHER_COLLATERAL_GROUP_LEN,
+};
+use crate::stability_pool::absorb_liquidation;
+use crate::redistribution::{apply_trove_rewards, modify_redistribution_snapshot, redistribute_to_troves, split_liquidation};
+use crate::utils::get_liquidation_threshold;
+use crate::trove_registry::deregister_trove;
+use crate::events::TroveLiquidated;
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct LiquidateTroveParams {
@@ -45,6 +55,47 @@ pub struct LiquidateTrove<'info> {
     )]
     pub total_collateral_amount: Account<'info, TotalCollateralAmount>,
 
+    #[account(
+        mut,
+        seeds = [b"redistribution_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub redistribution_snapshot: Box<Account<'info, RedistributionSnapshot>>,
+
+    // Stability pool S factor of collateral_denom - created by the first liquidation that needs it
+    // when the denom was configured before create_collateral_config set it up
+    #[account(
+        init_if_needed,
+        payer = liquidator,
+        space = 8 + StabilityPoolSnapshot::LEN,
+        seeds = [b"stability_pool_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub stability_pool_snapshot: Box<Account<'info, StabilityPoolSnapshot>>,
+
+    // Risk limits of collateral_denom, set by the admin
+    #[account(
+        seeds = [b"collateral_config", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub collateral_config: Box<Account<'info, CollateralConfig>>,
+
+    /// CHECK: Gas compensation vault PDA - pays the trove's reserve to the liquidator
+    #[account(
+        mut,
+        seeds = [b"gas_compensation_vault"],
+        bump
+    )]
+    pub gas_compensation_vault: AccountInfo<'info>,
+
+    // Liquidator's aUSD account (receives the gas compensation)
+    #[account(
+        mut,
+        constraint = liquidator_stablecoin_account.owner == liquidator.key() @ AerospacerProtocolError::Unauthorized,
+        constraint = liquidator_stablecoin_account.mint == stable_coin_mint.key() @ AerospacerProtocolError::InvalidMint
+    )]
+    pub liquidator_stablecoin_account: Box<Account<'info, TokenAccount>>,
+
     // Target trove accounts
     #[account(
         mut,
@@ -70,6 +121,21 @@ pub struct LiquidateTrove<'info> {
     )]
     pub liquidity_threshold: Account<'info, LiquidityThreshold>,
 
+    // The registry page listing the trove (under the denom it was opened with)
+    #[account(
+        mut,
+        seeds = [b"trove_registry_page", trove_registry_page.denom.as_bytes(), &trove_registry_page.page_index.to_le_bytes()],
+        bump
+    )]
+    pub trove_registry_page: Box<Account<'info, TroveRegistryPage>>,
+
+    #[account(
+        mut,
+        seeds = [b"trove_registry", trove_registry_page.denom.as_bytes()],
+        bump
+    )]
+    pub trove_registry: Box<Account<'info, TroveRegistry>>,
+
     // User's ATA for seized collateral (must match denom mint implied by vault)
     #[account(mut)]
     pub user_collateral_token_account: Account<'info, TokenAccount>,
@@ -91,13 +157,37 @@ pub struct LiquidateTrove<'info> {
     /// CHECK: Pyth price account for collateral price feed
     pub pyth_price_account: AccountInfo<'info>,
 
+    // Fee distribution accounts - the treasury share of the penalty goes through aerospacer-fees
+    /// CHECK: Fees program - validated against state
+    #[account(
+        constraint = fees_program.key() == state.fee_distributor_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub fees_program: AccountInfo<'info>,
+
+    /// CHECK: Fees state account - validated against state
+    #[account(
+        mut,
+        constraint = fees_state.key() == state.fee_state_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub fees_state: AccountInfo<'info>,
+
     pub clock: Sysvar<'info, Clock>,
 
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
-pub fn handler(ctx: Context<LiquidateTrove>, params: LiquidateTroveParams) -> Result<()> {
+// remaining_accounts:
+// - One [UserCollateralAmount, RedistributionSnapshot, pyth_price_account, CollateralConfig] group
+//   per other denom the trove holds, in UserDebtAmount.collateral_denoms order - every denom of the trove is seized
+// - One [TotalCollateralAmount, pyth_price_account] pair per denom the oracle supports,
+//   used to determine whether the protocol is in Recovery Mode
+// - Located by address, for every denom the trove holds: the liquidator's token account, the fee
+//   recipients' token accounts (see FeeRecipientAccounts), and for denoms other than collateral_denom
+//   the protocol_collateral_vault, TotalCollateralAmount and StabilityPoolSnapshot PDAs
+// - Located by address, for every denom the pool takes collateral in: its EpochScaleSum PDA for the
+//   pool's current epoch and scale
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateTrove<'info>>, params: LiquidateTroveParams) -> Result<()> {
     // Basic input checks
     require!(!params.collateral_denom.is_empty(), AerospacerProtocolError::InvalidAmount);
 
@@ -109,33 +199,70 @@ pub fn handler(ctx: Context<LiquidateTrove>, params: LiquidateTroveParams) -> Re
         clock: ctx.accounts.clock.to_account_info(),
     };
 
-    // Compute ICR and ensure undercollateralized (ICR < 110)
-    let debt_amount = ctx.accounts.user_debt_amount.amount;
-    let coll_info = &ctx.accounts.user_collateral_amount;
-
     // If no debt, nothing to liquidate
-    require!(debt_amount > 0, AerospacerProtocolError::TroveDoesNotExist);
+    require!(ctx.accounts.user_debt_amount.amount > 0, AerospacerProtocolError::TroveDoesNotExist);
 
     // Require denom match
-    require!(coll_info.denom == params.collateral_denom, AerospacerProtocolError::InvalidAmount);
+    require!(ctx.accounts.user_collateral_amount.denom == params.collateral_denom, AerospacerProtocolError::InvalidAmount);
+
+    // Earlier redistributions are part of the trove being liquidated
+    apply_trove_rewards(
+        &mut ctx.accounts.user_debt_amount,
+        &mut ctx.accounts.user_collateral_amount,
+        &mut ctx.accounts.redistribution_snapshot,
+        ctx.remaining_accounts,
+        OTHER_COLLATERAL_GROUP_LEN,
+    )?;
+
+    // Compute ICR and ensure undercollateralized (ICR below the trove's threshold, or < CCR in Recovery Mode)
+    let debt_amount = ctx.accounts.user_debt_amount.amount;
+    let collateral_amount = ctx.accounts.user_collateral_amount.amount;
 
     // Price validation
     let price = oracle_ctx.get_price(&params.collateral_denom)?;
     oracle_ctx.validate_price(&price)?;
 
-    let collateral_value = PriceCalculator::calculate_collateral_value(
-        coll_info.amount,
-        price.price as u64,
-        price.decimal,
+    // ICR covers the trove's collateral in every denom
+    let (other_collateral, consumed) = load_other_collateral(
+        &ctx.accounts.user_debt_amount,
+        &params.collateral_denom,
+        ctx.remaining_accounts,
+        &oracle_ctx,
     )?;
-
-    let current_icr = PriceCalculator::calculate_collateral_ratio(collateral_value, debt_amount)?;
-    // Use micro-percent threshold (110% = 110_000_000)
-    require!(current_icr < 110_000_000, AerospacerProtocolError::CollateralBelowMinimum);
+    let current_icr = other_collateral.trove_icr(&params.collateral_denom, collateral_amount, &price, debt_amount)?;
+
+    // Troves below the critical collateral ratio are liquidatable in Recovery Mode
+    let (system_collateral, _) = load_system_collateral_block(&oracle_ctx, &ctx.remaining_accounts[consumed..])?;
+    let recovery_mode = system_collateral.is_recovery_mode(ctx.accounts.state.total_debt_amount)?;
+    let risk_ratios = other_collateral.risk_ratios(
+        &ctx.accounts.collateral_config,
+        collateral_amount,
+        &price,
+        ctx.accounts.state.minimum_collateral_ratio,
+    )?;
+    let liquidation_threshold = get_liquidation_threshold(recovery_mode, risk_ratios.liquidation_threshold)?;
+
+    // Both are 18-decimal fixed-point ratios (110% = 1.1 = 1.1e18 raw)
+    require!(current_icr < liquidation_threshold, AerospacerProtocolError::CollateralBelowMinimum);
+
+    // The penalty comes off the top, only the rest backs the trove's debt
+    let mut collateral_amounts = other_collateral.amounts.clone();
+    collateral_amounts.push((params.collateral_denom.clone(), collateral_amount));
+    let mut configs = other_collateral.configs.clone();
+    configs.push((**ctx.accounts.collateral_config).clone());
+    let penalty = split_liquidation_penalty(&collateral_amounts, &configs)?;
+
+    // The stability pool offsets as much debt as it holds, the rest is redistributed
+    let mut prices = other_collateral.prices.clone();
+    prices.push((params.collateral_denom.clone(), price.price as u64, price.decimal));
+    let total_stake_amount = ctx.accounts.state.total_stake_amount;
+    let mut split = split_liquidation(&penalty.backing_collateral, debt_amount, total_stake_amount, &prices)?;
+    split.add_stability_pool_penalty(&penalty.to_stability_pool, total_stake_amount > 0);
+    let debt_to_offset = split.debt_to_offset;
 
     // Burn stablecoin from protocol vault (PDA signer)
-    let (_pda, bump) = Pubkey::find_program_address(&[b"protocol_stablecoin_vault"], &crate::ID);
-    let vault_seeds: &[&[u8]] = &[b"protocol_stablecoin_vault", &[bump]];
+    let (_pda, bump) = Pubkey::find_program_address(&[PROTOCOL_STABLECOIN_VAULT_SEED], &crate::ID);
+    let vault_seeds: &[&[u8]] = &[PROTOCOL_STABLECOIN_VAULT_SEED, &[bump]];
     let signer: &[&[&[u8]]] = &[vault_seeds];
 
     let burn_ctx = CpiContext::new_with_signer(
@@ -147,37 +274,107 @@ pub fn handler(ctx: Context<LiquidateTrove>, params: LiquidateTroveParams) -> Re
         },
         signer,
     );
-    anchor_spl::token::burn(burn_ctx, debt_amount)?;
+    anchor_spl::token::burn(burn_ctx, debt_to_offset)?;
+
+    // The pool's share of the trove goes to stakers through the Product-Sum snapshots: S of every
+    // seized denom grows by the collateral per unit staked, then P shrinks by the debt burned
+    if ctx.accounts.stability_pool_snapshot.denom.is_empty() {
+        ctx.accounts.stability_pool_snapshot.denom = params.collateral_denom.clone();
+    }
+    // collateral_denom's snapshot is written out first and read back after, so the update is
+    // made through the same account as the other denoms' and the batch path's
+    ctx.accounts.stability_pool_snapshot.exit(&crate::ID)?;
+    let mut snapshot_accounts = ctx.remaining_accounts.to_vec();
+    snapshot_accounts.push(ctx.accounts.stability_pool_snapshot.to_account_info());
+    absorb_liquidation(
+        &mut ctx.accounts.state,
+        &split.collateral_to_send_to_sp,
+        debt_to_offset,
+        &snapshot_accounts,
+        &ctx.accounts.liquidator.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+    )?;
+    ctx.accounts.stability_pool_snapshot.reload()?;
+
+    // The trove's stakes leave before its remainder is handed to the other troves
+    ctx.accounts.redistribution_snapshot.update_stake(collateral_amount, 0)?;
+    let (collateral, debt) = split.redistributed(&params.collateral_denom);
+    redistribute_to_troves(&mut ctx.accounts.redistribution_snapshot, collateral, debt)?;
+    for (index, (denom, amount)) in other_collateral.amounts.iter().enumerate() {
+        let snapshot_account = &ctx.remaining_accounts[index * OTHER_COLLATERAL_GROUP_LEN + 1];
+        let (collateral, debt) = split.redistributed(denom);
+        modify_redistribution_snapshot(snapshot_account, |snapshot| {
+            snapshot.update_stake(*amount, 0)?;
+            redistribute_to_troves(snapshot, collateral, debt)
+        })?;
+    }
 
     // Zero user trove data (effectively liquidated)
     ctx.accounts.user_debt_amount.amount = 0;
     ctx.accounts.user_collateral_amount.amount = 0;
-    ctx.accounts.liquidity_threshold.ratio = 0;
-
-    // Deplete stability pool stake by burned amount
-    ctx.accounts.state.total_stake_amount = ctx
-        .accounts
-        .state
-        .total_stake_amount
-        .saturating_sub(debt_amount);
+    ctx.accounts.liquidity_threshold.clear();
+    deregister_trove(
+        &mut ctx.accounts.trove_registry,
+        &mut ctx.accounts.trove_registry_page,
+        &params.target_user,
+    )?;
+    for collateral_account in ctx.remaining_accounts[..consumed].iter().step_by(OTHER_COLLATERAL_GROUP_LEN) {
+        set_user_collateral_amount(collateral_account, 0)?;
+    }
+    ctx.accounts.user_debt_amount.collateral_denoms.clear();
 
-    // Update global state
+    // Update global state - redistributed debt stays in the system with the troves that took it on
     ctx.accounts.state.total_debt_amount = ctx
         .accounts
         .state
         .total_debt_amount
-        .saturating_sub(debt_amount);
-
-    // NOTE: For minimal version, we do not transfer seized collateral out of protocol vault
-    // nor update S snapshot here. That can be added by exposing an optional
-    // stability_pool_snapshot account and applying the snapshot algorithm.
+        .checked_sub(debt_to_offset)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+
+    // Pay the liquidator the trove's gas compensation and its slice of the collateral,
+    // and the treasury its share of the penalty
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let gas_compensation = debt_amount.min(GAS_COMPENSATION);
+    pay_gas_compensation(
+        &token_program,
+        &ctx.accounts.gas_compensation_vault,
+        &ctx.accounts.liquidator_stablecoin_account.to_account_info(),
+        gas_compensation,
+    )?;
+    let mut payout_accounts = ctx.remaining_accounts.to_vec();
+    payout_accounts.push(ctx.accounts.protocol_collateral_vault.clone());
+    payout_accounts.push(ctx.accounts.total_collateral_amount.to_account_info());
+    pay_liquidation_penalty(
+        &ctx.accounts.liquidator.key(),
+        &penalty.to_liquidator,
+        &penalty.to_treasury,
+        &ctx.accounts.fees_program,
+        &ctx.accounts.fees_state,
+        &token_program,
+        &payout_accounts,
+    )?;
+    // The payouts wrote collateral_denom's total through its AccountInfo
+    ctx.accounts.total_collateral_amount.reload()?;
 
     msg!(
-        "Single trove liquidated: user={}, denom={}, debt_liquidated={}",
+        "Single trove liquidated: user={}, denom={}, debt_liquidated={}, debt_offset={}, debt_redistributed={}, gas_compensation={}",
         params.target_user,
         params.collateral_denom,
-        debt_amount
+        debt_amount,
+        debt_to_offset,
+        debt_amount - debt_to_offset,
+        gas_compensation
     );
+    emit!(TroveLiquidated::new(
+        params.target_user,
+        ctx.accounts.liquidator.key(),
+        debt_amount,
+        &collateral_amounts,
+        &split,
+        &penalty,
+        gas_compensation,
+        recovery_mode,
+    ));
 
     Ok(())
 }
@@ -1,10 +1,11 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, Mint};
+use anchor_spl::token::{Token, TokenAccount, Mint};
 use crate::state::*;
 use crate::error::*;
 use crate::trove_management::*;
 use crate::account_management::*;
 use crate::oracle::*;
+use crate::trove_registry::deregister_trove_by_address;
 
 // Constants
 const MAX_LIQUIDATION_BATCH_SIZE: usize = 50;
@@ -57,6 +58,36 @@ pub struct LiquidateTroves<'info> {
     )]
     pub total_collateral_amount: Account<'info, TotalCollateralAmount>,
 
+    /// CHECK: Gas compensation vault PDA - pays the liquidated troves' reserves
+    #[account(
+        mut,
+        seeds = [b"gas_compensation_vault"],
+        bump
+    )]
+    pub gas_compensation_vault: AccountInfo<'info>,
+
+    // Liquidator's aUSD account (receives the gas compensation)
+    #[account(
+        mut,
+        constraint = liquidator_stablecoin_account.owner == liquidator.key() @ AerospacerProtocolError::Unauthorized,
+        constraint = liquidator_stablecoin_account.mint == stable_coin_mint.key() @ AerospacerProtocolError::InvalidMint
+    )]
+    pub liquidator_stablecoin_account: Box<Account<'info, TokenAccount>>,
+
+    // Fee distribution accounts - the treasury share of the penalty goes through aerospacer-fees
+    /// CHECK: Fees program - validated against state
+    #[account(
+        constraint = fees_program.key() == state.fee_distributor_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub fees_program: AccountInfo<'info>,
+
+    /// CHECK: Fees state account - validated against state
+    #[account(
+        mut,
+        constraint = fees_state.key() == state.fee_state_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub fees_state: AccountInfo<'info>,
+
     // Oracle context - integration with our aerospacer-oracle
     /// CHECK: Our oracle program - validated against state
     #[account(
@@ -82,12 +113,23 @@ pub struct LiquidateTroves<'info> {
     pub system_program: Program<'info, System>,
     
     // remaining_accounts should contain:
-    // - First 4*N accounts: Per-trove accounts (UserDebtAmount, UserCollateralAmount, LiquidityThreshold, Node)
-    // - Remaining accounts: TotalLiquidationCollateralGain PDAs (one per unique denom being liquidated)
-    //   These PDAs track seized collateral for distribution to stability pool stakers
+    // - First 4*N accounts: Per-trove accounts (UserDebtAmount, UserCollateralAmount, LiquidityThreshold, TokenAccount)
+    // - Remaining accounts, located by address:
+    //   - UserCollateralAmount PDAs for every other denom each trove holds (all of a trove's denoms are seized)
+    //   - TotalCollateralAmount PDAs for every denom the oracle supports (system TCR / Recovery Mode)
+    //   - Pyth price accounts for every denom involved other than pyth_price_account's
+    //   - StabilityPoolSnapshot PDAs (one per unique denom being liquidated - required, or the batch fails)
+    //   - EpochScaleSum PDAs of those denoms for the pool's current epoch and scale (and the next
+    //     scale, in case the batch moves P on to it)
+    //   - RedistributionSnapshot PDAs for every denom the troves hold (debt the pool cannot absorb is redistributed)
+    //   - CollateralConfig PDAs for every denom the troves hold (thresholds and penalties)
+    //   - protocol_collateral_vault PDAs for every denom the troves hold other than collateral_denom
+    //   - The liquidator's token account for every denom the troves hold
+    //   - The fee recipients' token accounts for every denom the troves hold (see FeeRecipientAccounts)
+    //   - The TroveRegistryPage listing each trove and the TroveRegistry of each of those pages
 }
 
-pub fn handler(ctx: Context<LiquidateTroves>, params: LiquidateTrovesParams) -> Result<()> {
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateTroves<'info>>, params: LiquidateTrovesParams) -> Result<()> {
     // Validate input parameters
     require!(
         !params.liquidation_list.is_empty(),
@@ -116,12 +158,11 @@ pub fn handler(ctx: Context<LiquidateTroves>, params: LiquidateTrovesParams) ->
     msg!("Liquidation by: {}", ctx.accounts.liquidator.key());
     
     // Validate remaining accounts for each user
-    validate_remaining_accounts(&params.liquidation_list, &ctx.remaining_accounts)?;
+    validate_remaining_accounts(&params.liquidation_list, ctx.remaining_accounts)?;
     
     // Create context structs for clean architecture
     let mut liquidation_ctx = LiquidationContext {
         liquidator: ctx.accounts.liquidator.clone(),
-        state: ctx.accounts.state.clone(),
         stable_coin_mint: ctx.accounts.stable_coin_mint.clone(),
         protocol_stablecoin_vault: ctx.accounts.protocol_stablecoin_vault.clone(),
         protocol_collateral_vault: ctx.accounts.protocol_collateral_vault.clone(),
@@ -140,14 +181,40 @@ pub fn handler(ctx: Context<LiquidateTroves>, params: LiquidateTrovesParams) ->
     // Use TroveManager for clean implementation
     let result = TroveManager::liquidate_troves(
         &mut liquidation_ctx,
+        &mut ctx.accounts.state,
         &oracle_ctx,
         params.liquidation_list.clone(),
-        &ctx.remaining_accounts,
+        ctx.remaining_accounts,
     )?;
 
-    // Update the actual accounts with the results
-    ctx.accounts.state.total_debt_amount = liquidation_ctx.state.total_debt_amount;
-    ctx.accounts.state.total_stake_amount = liquidation_ctx.state.total_stake_amount;
+    // Liquidated troves are no longer active
+    for user in &params.liquidation_list {
+        deregister_trove_by_address(user, ctx.remaining_accounts)?;
+    }
+    
+    // Pay the liquidator the troves' gas compensation and its slice of the collateral,
+    // and the treasury its share of the penalty
+    let token_program = ctx.accounts.token_program.to_account_info();
+    pay_gas_compensation(
+        &token_program,
+        &ctx.accounts.gas_compensation_vault,
+        &ctx.accounts.liquidator_stablecoin_account.to_account_info(),
+        result.gas_compensation,
+    )?;
+    let mut payout_accounts = ctx.remaining_accounts.to_vec();
+    payout_accounts.push(ctx.accounts.protocol_collateral_vault.clone());
+    payout_accounts.push(ctx.accounts.total_collateral_amount.to_account_info());
+    pay_liquidation_penalty(
+        &ctx.accounts.liquidator.key(),
+        &result.liquidator_collateral,
+        &result.treasury_collateral,
+        &ctx.accounts.fees_program,
+        &ctx.accounts.fees_state,
+        &token_program,
+        &payout_accounts,
+    )?;
+    // The payouts wrote collateral_denom's total through its AccountInfo
+    ctx.accounts.total_collateral_amount.reload()?;
     
     // NOTE: Sorted troves management moved off-chain
     msg!("Troves liquidated successfully");
@@ -156,6 +223,7 @@ pub fn handler(ctx: Context<LiquidateTroves>, params: LiquidateTrovesParams) ->
     msg!("Liquidated troves: {}", result.liquidated_count);
     msg!("Total debt liquidated: {}", result.total_debt_liquidated);
     msg!("Total collateral gained: {}", result.total_collateral_gained);
+    msg!("Gas compensation paid: {} aUSD", result.gas_compensation);
     
     // Log liquidation gains by denomination
     for (denom, amount) in &result.liquidation_gains {
@@ -210,7 +278,7 @@ fn validate_user_debt_account(account_info: &AccountInfo, expected_user: &Pubkey
     );
     
     let account_data = account_info.try_borrow_data()?;
-    let user_debt_amount = UserDebtAmount::try_from_slice(&account_data)?;
+    let user_debt_amount = UserDebtAmount::try_deserialize(&mut &account_data[..])?;
     
     require!(
         user_debt_amount.owner == *expected_user,
@@ -233,7 +301,7 @@ fn validate_user_collateral_account(account_info: &AccountInfo, expected_user: &
     );
     
     let account_data = account_info.try_borrow_data()?;
-    let user_collateral_amount = UserCollateralAmount::try_from_slice(&account_data)?;
+    let user_collateral_amount = UserCollateralAmount::try_deserialize(&mut &account_data[..])?;
     
     require!(
         user_collateral_amount.owner == *expected_user,
@@ -256,7 +324,7 @@ fn validate_liquidity_threshold_account(account_info: &AccountInfo, expected_use
     );
     
     let account_data = account_info.try_borrow_data()?;
-    let liquidity_threshold = LiquidityThreshold::try_from_slice(&account_data)?;
+    let liquidity_threshold = LiquidityThreshold::try_deserialize(&mut &account_data[..])?;
     
     require!(
         liquidity_threshold.owner == *expected_user,
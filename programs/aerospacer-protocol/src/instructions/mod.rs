@@ -4,16 +4,34 @@ pub mod add_collateral;
 pub mod remove_collateral;
 pub mod borrow_loan;
 pub mod repay_loan;
+pub mod adjust_trove;
 pub mod close_trove;
 pub mod liquidate_troves;
 pub mod liquidate_trove;
 pub mod query_liquidatable_troves;
+pub mod query_system_mode;
+pub mod refresh_icrs;
+pub mod query_trove_registry;
 pub mod stake;
 pub mod unstake;
 pub mod withdraw_liquidation_gains;
+pub mod withdraw_all_liquidation_gains;
+pub mod withdraw_liquidation_gains_to_trove;
+pub mod initialize_share_pool;
+pub mod deposit_to_share_pool;
+pub mod release_shares;
+pub mod redeem_from_share_pool;
 pub mod redeem;
 pub mod update_protocol_addresses;
 pub mod transfer_stablecoin;
+pub mod set_minimum_collateral_ratio;
+pub mod set_protocol_fee;
+pub mod set_parameter_timelock_delay;
+pub mod set_unstake_cooldown;
+pub mod execute_parameter_change;
+pub mod cancel_parameter_change;
+pub mod create_collateral_config;
+pub mod update_collateral_config;
 
 #[allow(ambiguous_glob_reexports)]
 pub use initialize::*;
@@ -28,6 +46,8 @@ pub use borrow_loan::*;
 #[allow(ambiguous_glob_reexports)]
 pub use repay_loan::*;
 #[allow(ambiguous_glob_reexports)]
+pub use adjust_trove::*;
+#[allow(ambiguous_glob_reexports)]
 pub use close_trove::*;
 #[allow(ambiguous_glob_reexports)]
 pub use liquidate_troves::*;
@@ -36,14 +56,48 @@ pub use liquidate_trove::*;
 #[allow(ambiguous_glob_reexports)]
 pub use query_liquidatable_troves::*;
 #[allow(ambiguous_glob_reexports)]
+pub use query_system_mode::*;
+#[allow(ambiguous_glob_reexports)]
+pub use refresh_icrs::*;
+#[allow(ambiguous_glob_reexports)]
+pub use query_trove_registry::*;
+#[allow(ambiguous_glob_reexports)]
 pub use stake::*;
 #[allow(ambiguous_glob_reexports)]
 pub use unstake::*;
 #[allow(ambiguous_glob_reexports)]
 pub use withdraw_liquidation_gains::*;
 #[allow(ambiguous_glob_reexports)]
+pub use withdraw_all_liquidation_gains::*;
+#[allow(ambiguous_glob_reexports)]
+pub use withdraw_liquidation_gains_to_trove::*;
+#[allow(ambiguous_glob_reexports)]
+pub use initialize_share_pool::*;
+#[allow(ambiguous_glob_reexports)]
+pub use deposit_to_share_pool::*;
+#[allow(ambiguous_glob_reexports)]
+pub use release_shares::*;
+#[allow(ambiguous_glob_reexports)]
+pub use redeem_from_share_pool::*;
+#[allow(ambiguous_glob_reexports)]
 pub use redeem::*;
 #[allow(ambiguous_glob_reexports)]
 pub use update_protocol_addresses::*;
 #[allow(ambiguous_glob_reexports)]
-pub use transfer_stablecoin::*; 
\ No newline at end of file
+pub use transfer_stablecoin::*; 
+#[allow(ambiguous_glob_reexports)]
+pub use set_minimum_collateral_ratio::*;
+#[allow(ambiguous_glob_reexports)]
+pub use set_protocol_fee::*;
+#[allow(ambiguous_glob_reexports)]
+pub use set_parameter_timelock_delay::*;
+#[allow(ambiguous_glob_reexports)]
+pub use set_unstake_cooldown::*;
+#[allow(ambiguous_glob_reexports)]
+pub use execute_parameter_change::*;
+#[allow(ambiguous_glob_reexports)]
+pub use cancel_parameter_change::*;
+#[allow(ambiguous_glob_reexports)]
+pub use create_collateral_config::*;
+#[allow(ambiguous_glob_reexports)]
+pub use update_collateral_config::*;
\ No newline at end of file
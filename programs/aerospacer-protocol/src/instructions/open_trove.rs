@@ -2,11 +2,15 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount, Mint, MintTo};
 use crate::state::*;
 use crate::error::*;
+use crate::decimal::Decimal;
 use crate::account_management::*;
 use crate::oracle::*;
-use crate::trove_management::TroveManager;
+use crate::trove_management::{load_system_collateral_block, TroveManager};
+use crate::redistribution::apply_position_rewards;
+use crate::trove_registry::register_trove;
 use crate::state::{MINIMUM_LOAN_AMOUNT, MINIMUM_COLLATERAL_AMOUNT};
 use crate::fees_integration::*;
+use crate::events::TroveOpened;
 use crate::utils::*;
 
 // Oracle integration is now handled via our aerospacer-oracle contract
@@ -16,6 +20,8 @@ pub struct OpenTroveParams {
     pub loan_amount: u64,
     pub collateral_denom: String,
     pub collateral_amount: u64,
+    pub max_fee_bps: u16, // Highest borrowing fee rate accepted
+    pub registry_page: u32, // Page of collateral_denom's TroveRegistry with a free slot (page_count starts a new page)
 }
 
 #[derive(Accounts)]
@@ -82,6 +88,43 @@ pub struct OpenTrove<'info> {
     )]
     pub total_collateral_amount: Box<Account<'info, TotalCollateralAmount>>,
     
+    // Created by the first trove opened with this denom
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + RedistributionSnapshot::LEN,
+        seeds = [b"redistribution_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub redistribution_snapshot: Box<Account<'info, RedistributionSnapshot>>,
+
+    // Risk limits of collateral_denom, set by the admin
+    #[account(
+        seeds = [b"collateral_config", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub collateral_config: Box<Account<'info, CollateralConfig>>,
+
+    // Created by the first trove opened with this denom
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + TroveRegistry::LEN,
+        seeds = [b"trove_registry", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub trove_registry: Box<Account<'info, TroveRegistry>>,
+
+    // The page the trove is listed on - created when it is the next page in the chain
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + TroveRegistryPage::LEN,
+        seeds = [b"trove_registry_page", params.collateral_denom.as_bytes(), &params.registry_page.to_le_bytes()],
+        bump
+    )]
+    pub trove_registry_page: Box<Account<'info, TroveRegistryPage>>,
+    
     // State account - Box<> to reduce stack usage
     #[account(mut)]
     pub state: Box<Account<'info, StateAccount>>,
@@ -103,6 +146,17 @@ pub struct OpenTrove<'info> {
     )]
     pub protocol_stablecoin_account: Box<Account<'info, TokenAccount>>,
     
+    // Holds the gas compensation reserved by every open trove for its liquidator
+    #[account(
+        init_if_needed,
+        payer = user,
+        token::mint = stable_coin_mint,
+        token::authority = gas_compensation_vault,
+        seeds = [b"gas_compensation_vault"],
+        bump
+    )]
+    pub gas_compensation_vault: Box<Account<'info, TokenAccount>>,
+    
     #[account(
         mut,
         constraint = stable_coin_mint.key() == state.stable_coin_addr @ AerospacerProtocolError::InvalidMint
@@ -147,7 +201,11 @@ pub struct OpenTrove<'info> {
     pub system_program: Program<'info, System>,
 }
 
-pub fn handler(ctx: Context<OpenTrove>, params: OpenTroveParams) -> Result<()> {
+// remaining_accounts:
+// - One [TotalCollateralAmount, pyth_price_account] pair per denom the oracle supports,
+//   used for the debt ceiling check (see trove_management::load_system_collateral_block)
+// - Followed by the optional neighbor hints [prev_LiquidityThreshold, next_LiquidityThreshold]
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, OpenTrove<'info>>, params: OpenTroveParams) -> Result<()> {
     // Validate oracle accounts
     require!(
         ctx.accounts.oracle_program.key() == ctx.accounts.state.oracle_helper_addr,
@@ -206,29 +264,50 @@ pub fn handler(ctx: Context<OpenTrove>, params: OpenTroveParams) -> Result<()> {
         AerospacerProtocolError::InsufficientCollateral
     );
     
+    // The mint must be the one the denom was configured for
+    require!(
+        ctx.accounts.collateral_mint.decimals == ctx.accounts.collateral_config.decimals,
+        AerospacerProtocolError::InvalidMint
+    );
+    
     // Initialize user debt amount
     ctx.accounts.user_debt_amount.owner = ctx.accounts.user.key();
     ctx.accounts.user_debt_amount.amount = 0; // Will be set below
+    ctx.accounts.user_debt_amount.collateral_denoms = vec![params.collateral_denom.clone()];
     
     // Initialize user collateral amount
     ctx.accounts.user_collateral_amount.owner = ctx.accounts.user.key();
     ctx.accounts.user_collateral_amount.denom = params.collateral_denom.clone();
     ctx.accounts.user_collateral_amount.amount = 0; // Will be set below
     
+    // Start the position at the current redistribution snapshot so it earns no earlier rewards
+    if ctx.accounts.redistribution_snapshot.denom.is_empty() {
+        ctx.accounts.redistribution_snapshot.denom = params.collateral_denom.clone();
+    }
+    apply_position_rewards(&mut ctx.accounts.user_collateral_amount, &mut ctx.accounts.redistribution_snapshot)?;
+    
     // Initialize liquidity threshold
     ctx.accounts.liquidity_threshold.owner = ctx.accounts.user.key();
-    ctx.accounts.liquidity_threshold.ratio = 0; // Will be set below
+    ctx.accounts.liquidity_threshold.ratio = Decimal::ZERO; // Will be set below
     
-    // Calculate opening fee BEFORE trove operations
-    let fee_amount = calculate_protocol_fee(params.loan_amount, ctx.accounts.state.protocol_fee)?;
+    // Calculate opening fee BEFORE trove operations - the borrowing fee follows the decayed base rate
+    decay_base_rate_from_borrowing(&mut ctx.accounts.state, Clock::get()?.unix_timestamp)?;
+    let fee_rate = get_borrowing_rate(&ctx.accounts.state);
+    check_max_fee(fee_rate, params.max_fee_bps)?;
+    let fee_amount = calculate_fee(params.loan_amount, fee_rate)?;
     let net_loan_amount = params.loan_amount.saturating_sub(fee_amount);
     
-    msg!("Opening fee: {} aUSD ({}%)", fee_amount, ctx.accounts.state.protocol_fee);
+    msg!("Opening fee: {} aUSD ({} bps)", fee_amount, fee_rate_to_bps(fee_rate));
     msg!("Net loan amount: {} aUSD", net_loan_amount);
     
+    // The trove also owes the gas compensation reserved for whoever liquidates it
+    let debt_amount = net_loan_amount
+        .checked_add(GAS_COMPENSATION)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+    
     // Create contexts in scoped block to reduce stack usage
     // Execute trove operations and capture results
-    let result = {
+    let (result, consumed) = {
         let mut trove_ctx = TroveContext {
             user: ctx.accounts.user.clone(),
             user_debt_amount: (*ctx.accounts.user_debt_amount).clone(),
@@ -252,12 +331,17 @@ pub fn handler(ctx: Context<OpenTrove>, params: OpenTroveParams) -> Result<()> {
             clock: ctx.accounts.clock.to_account_info(),
         };
         
-        // Use TroveManager with NET loan amount (after fee)
+        // Price the protocol's total collateral for the debt ceiling
+        let (system_collateral, consumed) = load_system_collateral_block(&oracle_ctx, ctx.remaining_accounts)?;
+        
+        // Use TroveManager with NET loan amount (after fee) plus the gas compensation
         let result = TroveManager::open_trove(
             &mut trove_ctx,
             &mut collateral_ctx,
             &oracle_ctx,
-            net_loan_amount,  // Use net amount for debt recording
+            &ctx.accounts.collateral_config,
+            &system_collateral,
+            debt_amount,
             params.collateral_amount,
             params.collateral_denom.clone(),
         )?;
@@ -265,66 +349,34 @@ pub fn handler(ctx: Context<OpenTrove>, params: OpenTroveParams) -> Result<()> {
         // Update state total debt before contexts are dropped
         ctx.accounts.state.total_debt_amount = trove_ctx.state.total_debt_amount;
         
-        Ok::<_, Error>(result)
+        Ok::<_, Error>((result, consumed))
     }?;
     
     // CRITICAL: Validate ICR ordering if neighbor hints provided
     // Production clients MUST provide neighbor hints via remainingAccounts for proper sorted list maintenance
     // Pattern: [prev_LiquidityThreshold, next_LiquidityThreshold] or [prev_LT] or [next_LT] or []
     // Optional for backward compatibility with tests, but REQUIRED in production
-    if !ctx.remaining_accounts.is_empty() {
-        use crate::sorted_troves;
-        
-        msg!("Validating ICR ordering with {} neighbor account(s)", ctx.remaining_accounts.len());
-        
-        let prev_icr = if ctx.remaining_accounts.len() >= 1 {
-            // First account is previous neighbor's LiquidityThreshold
-            let prev_lt = &ctx.remaining_accounts[0];
-            let prev_data = prev_lt.try_borrow_data()?;
-            let prev_threshold = LiquidityThreshold::try_deserialize(&mut &prev_data[..])?;
-            let prev_owner = prev_threshold.owner;
-            let prev_ratio = prev_threshold.ratio;
-            drop(prev_data);
-            
-            // Verify this is a real PDA, not a fake account
-            sorted_troves::verify_liquidity_threshold_pda(prev_lt, prev_owner, ctx.program_id)?;
-            
-            msg!("Previous neighbor: owner={}, ICR={}", prev_owner, prev_ratio);
-            Some(prev_ratio)
-        } else {
-            None
-        };
-        
-        let next_icr = if ctx.remaining_accounts.len() >= 2 {
-            // Second account is next neighbor's LiquidityThreshold
-            let next_lt = &ctx.remaining_accounts[1];
-            let next_data = next_lt.try_borrow_data()?;
-            let next_threshold = LiquidityThreshold::try_deserialize(&mut &next_data[..])?;
-            let next_owner = next_threshold.owner;
-            let next_ratio = next_threshold.ratio;
-            drop(next_data);
-            
-            // Verify this is a real PDA, not a fake account
-            sorted_troves::verify_liquidity_threshold_pda(next_lt, next_owner, ctx.program_id)?;
-            
-            msg!("Next neighbor: owner={}, ICR={}", next_owner, next_ratio);
-            Some(next_ratio)
-        } else {
-            None
-        };
-        
-        // Validate ordering BEFORE updating state
-        sorted_troves::validate_icr_ordering(result.new_icr, prev_icr, next_icr)?;
-        msg!("✓ ICR ordering validated successfully");
-    } else {
-        msg!("⚠ WARNING: No neighbor hints provided - skipping ICR ordering validation");
-        msg!("⚠ Production clients MUST provide neighbor hints for sorted list integrity");
-    }
+    let new_nicr = crate::sorted_troves::compute_nominal_icr(result.new_collateral_amount, result.new_debt_amount)?;
+    crate::sorted_troves::validate_neighbor_hints(&params.collateral_denom, new_nicr, &ctx.remaining_accounts[consumed..], ctx.program_id)?;
     
     // Update the actual accounts with the results
     ctx.accounts.user_debt_amount.amount = result.new_debt_amount;
     ctx.accounts.liquidity_threshold.ratio = result.new_icr;
+    ctx.accounts.liquidity_threshold.set_nominal_ratios(
+        &[(params.collateral_denom.clone(), result.new_collateral_amount)],
+        result.new_debt_amount,
+    )?;
     ctx.accounts.user_collateral_amount.amount = result.new_collateral_amount;
+    ctx.accounts.redistribution_snapshot.update_stake(0, result.new_collateral_amount)?;
+    
+    // List the trove so clients can enumerate it without scanning the program
+    register_trove(
+        &mut ctx.accounts.trove_registry,
+        &mut ctx.accounts.trove_registry_page,
+        &params.collateral_denom,
+        params.registry_page,
+        ctx.accounts.user.key(),
+    )?;
     
     // Initialize total_collateral_amount if it was just created
     if ctx.accounts.total_collateral_amount.denom.is_empty() {
@@ -340,7 +392,7 @@ pub fn handler(ctx: Context<OpenTrove>, params: OpenTroveParams) -> Result<()> {
     // Mint full loan amount to user first (user requested full amount, will pay fee from it)
     // Use invoke_signed for PDA authority
     let mint_seeds = &[
-        b"protocol_stablecoin_vault".as_ref(),
+        PROTOCOL_STABLECOIN_VAULT_SEED,
         &[ctx.bumps.protocol_stablecoin_account],
     ];
     let mint_signer = &[&mint_seeds[..]];
@@ -356,11 +408,23 @@ pub fn handler(ctx: Context<OpenTrove>, params: OpenTroveParams) -> Result<()> {
     );
     anchor_spl::token::mint_to(mint_ctx, params.loan_amount)?;
     
+    // Set the gas compensation aside - it is paid to the liquidator or burned at close_trove
+    let gas_mint_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        MintTo {
+            mint: ctx.accounts.stable_coin_mint.to_account_info(),
+            to: ctx.accounts.gas_compensation_vault.to_account_info(),
+            authority: ctx.accounts.protocol_stablecoin_account.to_account_info(),
+        },
+        mint_signer,
+    );
+    anchor_spl::token::mint_to(gas_mint_ctx, GAS_COMPENSATION)?;
+    
     // Distribute opening fee via CPI to aerospacer-fees
     if fee_amount > 0 {
         let _net_amount = process_protocol_fee(
             params.loan_amount,
-            ctx.accounts.state.protocol_fee,
+            fee_amount,
             ctx.accounts.fees_program.to_account_info(),
             ctx.accounts.user.to_account_info(),
             ctx.accounts.fees_state.to_account_info(),
@@ -379,8 +443,19 @@ pub fn handler(ctx: Context<OpenTrove>, params: OpenTroveParams) -> Result<()> {
     msg!("Trove opened successfully");
     msg!("User: {}", ctx.accounts.user.key());
     msg!("Loan amount: {} aUSD (fee: {})", params.loan_amount, fee_amount);
+    msg!("Gas compensation reserved: {} aUSD", GAS_COMPENSATION);
     msg!("Collateral: {} {}", params.collateral_amount, params.collateral_denom);
     msg!("ICR: {}", result.new_icr);
     
+    emit!(TroveOpened {
+        owner: ctx.accounts.user.key(),
+        collateral_denom: params.collateral_denom,
+        collateral_amount: params.collateral_amount,
+        loan_amount: params.loan_amount,
+        borrowing_fee: fee_amount,
+        debt_amount: result.new_debt_amount,
+        icr: result.new_icr,
+    });
+    
     Ok(())
 }
\ No newline at end of file
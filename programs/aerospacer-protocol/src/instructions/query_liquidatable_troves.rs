@@ -1,11 +1,12 @@
 use anchor_lang::prelude::*;
 use crate::error::*;
+use crate::decimal::Decimal;
 use crate::sorted_troves::get_liquidatable_troves;
 
 /// Query parameters for finding liquidatable troves
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct QueryLiquidatableTrovesParams {
-    pub liquidation_threshold: u64, // ICR threshold (typically 110 for 110%)
+    pub liquidation_threshold: Decimal, // ICR threshold (typically 1.1 for 110%)
     pub max_troves: u8, // Limit results to avoid huge responses (default 50)
 }
 
@@ -34,7 +35,7 @@ pub struct QueryLiquidatableTroves {
 pub fn handler(ctx: Context<QueryLiquidatableTroves>, params: QueryLiquidatableTrovesParams) -> Result<()> {
     // Validate parameters
     require!(
-        params.liquidation_threshold > 0,
+        !params.liquidation_threshold.is_zero(),
         AerospacerProtocolError::InvalidAmount
     );
     
@@ -43,7 +44,7 @@ pub fn handler(ctx: Context<QueryLiquidatableTroves>, params: QueryLiquidatableT
         AerospacerProtocolError::InvalidList
     );
     
-    msg!("Querying liquidatable troves with threshold: {}%", params.liquidation_threshold);
+    msg!("Querying liquidatable troves with threshold: {}", params.liquidation_threshold);
     msg!("Max troves to return: {}", params.max_troves);
     
     // Validate pre-sorted list provided by client via remainingAccounts
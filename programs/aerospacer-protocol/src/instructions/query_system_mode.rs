@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::*;
+use crate::oracle::OracleContext;
+use crate::query::query_system_mode;
+use crate::trove_management::load_system_collateral;
+
+/// Query context - read-only, no mutations
+#[derive(Accounts)]
+pub struct QuerySystemMode<'info> {
+    #[account(
+        seeds = [b"state"],
+        bump
+    )]
+    pub state: Account<'info, StateAccount>,
+
+    /// CHECK: Our oracle program - validated against state
+    #[account(
+        constraint = oracle_program.key() == state.oracle_helper_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_program: AccountInfo<'info>,
+
+    /// CHECK: Oracle state account - validated against state
+    #[account(
+        constraint = oracle_state.key() == state.oracle_state_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_state: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Handler for query_system_mode instruction
+/// Returns the Total Collateral Ratio and Recovery Mode flag via Anchor return data
+///
+/// # Remaining Accounts Pattern
+/// One [TotalCollateralAmount, pyth_price_account] pair per denom the oracle supports,
+/// located by address (see trove_management::load_system_collateral)
+///
+/// # Returns
+/// SystemModeResponse via AnchorSerialize return data
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, QuerySystemMode<'info>>) -> Result<()> {
+    // Every price feed is located in remaining_accounts, so no dedicated Pyth account is needed
+    let oracle_ctx = OracleContext {
+        oracle_program: ctx.accounts.oracle_program.clone(),
+        oracle_state: ctx.accounts.oracle_state.clone(),
+        pyth_price_account: ctx.accounts.oracle_state.clone(),
+        clock: ctx.accounts.clock.to_account_info(),
+    };
+
+    let system_collateral = load_system_collateral(&oracle_ctx, ctx.remaining_accounts)?;
+    let response = query_system_mode(&ctx.accounts.state, &system_collateral)?;
+
+    msg!("Total collateral ratio: {}", response.total_collateral_ratio);
+    msg!("Critical collateral ratio: {}", response.critical_collateral_ratio);
+    msg!("Recovery Mode: {}", response.recovery_mode);
+
+    // Clients can decode this as SystemModeResponse
+    anchor_lang::solana_program::program::set_return_data(&response.try_to_vec()?);
+
+    Ok(())
+}
\ No newline at end of file
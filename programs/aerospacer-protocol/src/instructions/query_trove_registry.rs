@@ -0,0 +1,60 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::msg::TroveRegistryPageResponse;
+
+/// Query parameters for reading one page of a denom's trove registry
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct QueryTroveRegistryParams {
+    pub denom: String,
+    pub page_index: u32,
+}
+
+/// Query context - read-only, no mutations
+#[derive(Accounts)]
+#[instruction(params: QueryTroveRegistryParams)]
+pub struct QueryTroveRegistry<'info> {
+    #[account(
+        seeds = [b"trove_registry", params.denom.as_bytes()],
+        bump
+    )]
+    pub trove_registry: Account<'info, TroveRegistry>,
+
+    #[account(
+        seeds = [b"trove_registry_page", params.denom.as_bytes(), &params.page_index.to_le_bytes()],
+        bump
+    )]
+    pub trove_registry_page: Account<'info, TroveRegistryPage>,
+}
+
+/// Handler for query_trove_registry instruction
+/// Returns the trove owners listed on one registry page via Anchor return data
+///
+/// Clients start at page 0 and follow next_page_index until it is None
+///
+/// # Returns
+/// TroveRegistryPageResponse via AnchorSerialize return data
+pub fn handler(ctx: Context<QueryTroveRegistry>, params: QueryTroveRegistryParams) -> Result<()> {
+    let registry = &ctx.accounts.trove_registry;
+    let page = &ctx.accounts.trove_registry_page;
+
+    let next_page_index = params
+        .page_index
+        .checked_add(1)
+        .filter(|next| *next < registry.page_count);
+
+    let response = TroveRegistryPageResponse {
+        denom: params.denom,
+        page_index: params.page_index,
+        owners: page.owners.clone(),
+        next_page_index,
+        trove_count: registry.trove_count,
+    };
+
+    msg!("Trove registry {} page {}: {} troves", response.denom, response.page_index, response.owners.len());
+    msg!("Troves listed in total: {}", response.trove_count);
+
+    // Clients can decode this as TroveRegistryPageResponse
+    anchor_lang::solana_program::program::set_return_data(&response.try_to_vec()?);
+
+    Ok(())
+}
@@ -1,13 +1,24 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, TokenAccount, Transfer, Burn};
+use anchor_spl::token::{Token, TokenAccount, Transfer, Burn, Mint};
 use crate::state::*;
 use crate::error::*;
 use crate::fees_integration::*;
+use crate::account_management::DenomPayout;
+use crate::utils::{update_base_rate_from_redemption, get_redemption_rate, calculate_fee, fee_rate_to_bps, check_max_fee, check_min_collateral_out};
+use crate::trove_management::{find_user_collateral_account, set_user_collateral_amount, set_liquidity_threshold, load_trove_collateral};
+use crate::redistribution::{apply_trove_rewards_by_address, find_redistribution_snapshot_account, modify_redistribution_snapshot};
+use crate::sorted_troves::{compute_nominal_icr, validate_icr_ordering, verify_liquidity_threshold_pda};
+use crate::oracle::{OracleContext, PriceCalculator};
+use crate::decimal::Decimal;
+use crate::events::{DenomAmount, Redemption, TroveRedeemed};
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct RedeemParams {
     pub amount: u64, // Equivalent to Uint256
     pub collateral_denom: String, // Which collateral to redeem (SOL, ETH, BTC, etc.)
+    pub num_troves: u8, // Number of 4-account trove groups at the start of remainingAccounts
+    pub max_fee_bps: u16, // Highest redemption fee rate accepted
+    pub min_collateral_out: u64, // Fewest units of collateral_denom the redeemer accepts
     // NOTE: prev_node_id and next_node_id removed - using off-chain sorted list architecture
 }
 
@@ -102,6 +113,9 @@ pub struct Redeem<'info> {
     )]
     pub oracle_state: AccountInfo<'info>,
 
+    /// CHECK: Pyth price account for collateral_denom - validated by the oracle
+    pub pyth_price_account: AccountInfo<'info>,
+
     // Fee distribution accounts
     /// CHECK: Fees program - validated against state
     #[account(
@@ -128,10 +142,12 @@ pub struct Redeem<'info> {
     #[account(mut)]
     pub fee_address_2_token_account: AccountInfo<'info>,
 
+    pub clock: Sysvar<'info, Clock>,
+
     pub token_program: Program<'info, Token>,
 }
 
-pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, Redeem<'info>>, params: RedeemParams) -> Result<()> {
     // PRODUCTION VALIDATION: Input parameter checks
     require!(
         params.amount > 0,
@@ -148,8 +164,11 @@ pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
         AerospacerProtocolError::InvalidAmount
     );
     
-    // Store protocol fee before creating mutable borrow
-    let protocol_fee = ctx.accounts.state.protocol_fee;
+    // aUSD supply before this redemption's burn drives the base rate increase
+    let total_supply = {
+        let mint_data = ctx.accounts.stable_coin_mint.try_borrow_data()?;
+        Mint::try_deserialize(&mut &mint_data[..])?.supply
+    };
     
     let state = &mut ctx.accounts.state;
     
@@ -159,8 +178,7 @@ pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
         AerospacerProtocolError::NotEnoughLiquidityForRedeem
     );
     
-    // NOTE: Sorted list validation removed - using off-chain sorting architecture
-    // Client must provide pre-sorted target list via remainingAccounts
+    // NOTE: Client sorts the target list off-chain - its ordering is validated per trove below
     
     // Validate user has enough stablecoins (including fee)
     require!(
@@ -168,11 +186,22 @@ pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
         AerospacerProtocolError::InvalidAmount
     );
     
+    // Redemptions raise the base rate before their own fee is derived from it
+    let base_rate = update_base_rate_from_redemption(
+        state,
+        params.amount,
+        total_supply,
+        Clock::get()?.unix_timestamp,
+    )?;
+    let fee_rate = get_redemption_rate(base_rate);
+    check_max_fee(fee_rate, params.max_fee_bps)?;
+    let fee_amount = calculate_fee(params.amount, fee_rate)?;
+    
     // Collect redemption fee via CPI to aerospacer-fees
     // This returns the net amount after fee deduction
     let net_redemption_amount = process_protocol_fee(
         params.amount,
-        protocol_fee,
+        fee_amount,
         ctx.accounts.fees_program.to_account_info(),
         ctx.accounts.user.to_account_info(),
         ctx.accounts.fees_state.to_account_info(),
@@ -183,8 +212,7 @@ pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
         ctx.accounts.token_program.to_account_info(),
     )?;
     
-    let fee_amount = params.amount.saturating_sub(net_redemption_amount);
-    msg!("Redemption fee: {} aUSD ({}%)", fee_amount, protocol_fee);
+    msg!("Redemption fee: {} aUSD ({} bps)", fee_amount, fee_rate_to_bps(fee_rate));
     msg!("Net redemption amount: {} aUSD", net_redemption_amount);
     
     // Transfer NET redemption amount from user to protocol (after fee deduction)
@@ -201,7 +229,7 @@ pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
     // Burn NET redemption amount (not including fee)
     // Use invoke_signed for PDA authority
     let burn_seeds = &[
-        b"protocol_stablecoin_vault".as_ref(),
+        PROTOCOL_STABLECOIN_VAULT_SEED,
         &[ctx.bumps.protocol_stablecoin_vault],
     ];
     let burn_signer = &[&burn_seeds[..]];
@@ -219,21 +247,46 @@ pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
 
     // NEW ARCHITECTURE: Core redemption logic using pre-sorted list from remainingAccounts
     // Client provides sorted target troves via remainingAccounts (sorted from riskiest to safest)
-    // Each trove has 4 accounts: UserDebtAmount, UserCollateralAmount, LiquidityThreshold, TokenAccount
+    // Each trove has 4 accounts: UserDebtAmount, UserCollateralAmount (for collateral_denom),
+    // LiquidityThreshold, TokenAccount
+    // After the params.num_troves groups come the accounts for the troves' other denoms, located
+    // by address: each trove's UserCollateralAmount PDAs, the RedistributionSnapshot of every denom
+    // the troves hold, plus the Pyth price account, protocol_collateral_vault, TotalCollateralAmount
+    // and redeemer's token account of every denom besides collateral_denom.
+    // Every denom a trove holds is redeemed in proportion to the debt redeemed from it.
+    // Each trove's ICR is recomputed at the current prices and must be at or above 100%, and the
+    // list must be in ascending NICR order for collateral_denom, so redemptions cannot skip the
+    // riskiest troves.
+    
+    let oracle_ctx = OracleContext {
+        oracle_program: ctx.accounts.oracle_program.to_account_info(),
+        oracle_state: ctx.accounts.oracle_state.to_account_info(),
+        pyth_price_account: ctx.accounts.pyth_price_account.to_account_info(),
+        clock: ctx.accounts.clock.to_account_info(),
+    };
+    let mut prices: Vec<(String, u64, u8)> = Vec::new();
+    let mut prev_nicr: Option<Decimal> = None;
     
     let mut remaining_amount = net_redemption_amount;
-    let mut total_collateral_sent = 0u64;
     let mut troves_redeemed = 0u32;
     
-    // Validate remaining_accounts structure (4 accounts per trove)
+    let num_troves = params.num_troves as usize;
     require!(
-        ctx.remaining_accounts.len() % 4 == 0,
+        ctx.remaining_accounts.len() >= num_troves * 4,
         AerospacerProtocolError::InvalidList
     );
-    
-    let num_troves = ctx.remaining_accounts.len() / 4;
     msg!("Processing redemption across {} pre-sorted troves", num_troves);
     
+    // Payout accounts per denom, starting with the instruction's own collateral_denom accounts
+    let mut payouts = vec![DenomPayout {
+        denom: params.collateral_denom.clone(),
+        vault: ctx.accounts.protocol_collateral_vault.to_account_info(),
+        vault_bump: ctx.bumps.protocol_collateral_vault,
+        recipient: ctx.accounts.user_collateral_account.to_account_info(),
+        total_collateral: ctx.accounts.total_collateral_amount.to_account_info(),
+        sent: 0,
+    }];
+    
     // Iterate through pre-sorted troves provided by client
     for i in 0..num_troves {
         if remaining_amount == 0 {
@@ -245,94 +298,114 @@ pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
         // Get accounts for this trove
         let debt_account = &ctx.remaining_accounts[base_idx];
         let collateral_account = &ctx.remaining_accounts[base_idx + 1];
-        let _lt_account = &ctx.remaining_accounts[base_idx + 2];
+        let lt_account = &ctx.remaining_accounts[base_idx + 2];
         let token_account = &ctx.remaining_accounts[base_idx + 3];
         
-        // Deserialize trove data
-        let debt_data = debt_account.try_borrow_data()?;
-        let user_debt = UserDebtAmount::try_deserialize(&mut &debt_data[..])?;
+        require!(
+            debt_account.owner == &crate::ID,
+            AerospacerProtocolError::Unauthorized
+        );
+        
+        // Deserialize trove data - redistributed debt and collateral are redeemed with the rest of the trove
+        let mut debt_data = debt_account.try_borrow_mut_data()?;
+        let mut user_debt = UserDebtAmount::try_deserialize(&mut &debt_data[..])?;
+        apply_trove_rewards_by_address(&mut user_debt, ctx.remaining_accounts)?;
+        user_debt.try_serialize(&mut &mut debt_data[..])?;
         let trove_user = user_debt.owner;
         let debt_amount = user_debt.amount;
         drop(debt_data);
         
-        let collateral_data = collateral_account.try_borrow_data()?;
-        let user_collateral = UserCollateralAmount::try_deserialize(&mut &collateral_data[..])?;
-        let collateral_denom = user_collateral.denom.clone();
-        let collateral_amount = user_collateral.amount;
-        drop(collateral_data);
+        // Every account in the group must be the trove owner's real PDA
+        verify_trove_pda(debt_account, &UserDebtAmount::seeds(&trove_user))?;
+        verify_trove_pda(collateral_account, &UserCollateralAmount::seeds(&trove_user, &params.collateral_denom))?;
+        verify_liquidity_threshold_pda(lt_account, trove_user, &crate::ID)?;
         
-        // Skip if this trove doesn't have the requested collateral type
-        if collateral_denom != params.collateral_denom {
-            msg!("Trove {} has {} collateral, not {}, skipping", trove_user, collateral_denom, params.collateral_denom);
+        if debt_amount == 0 {
+            msg!("Trove {} has no debt, skipping", trove_user);
             continue;
         }
         
+        // Recompute the ICR at the current prices rather than trusting the stored ratio
+        let collateral_amounts = load_trove_collateral(&user_debt, &oracle_ctx, ctx.remaining_accounts, &mut prices)?;
+        let icr = PriceCalculator::calculate_trove_icr(&collateral_amounts, debt_amount, &prices)?;
+        
+        // Underwater troves are left for liquidation, and no trove may be redeemed
+        // ahead of a riskier one in collateral_denom's sorted list
+        let denom_amount = collateral_amounts
+            .iter()
+            .find(|(denom, _)| *denom == params.collateral_denom)
+            .map(|(_, amount)| *amount)
+            .ok_or(AerospacerProtocolError::InvalidList)?;
+        let nicr = compute_nominal_icr(denom_amount, debt_amount)?;
+        check_redemption_target(icr, nicr, prev_nicr)?;
+        prev_nicr = Some(nicr);
+        
         // Validate token account
         require!(
             token_account.owner == &anchor_spl::token::ID,
             AerospacerProtocolError::Unauthorized
         );
         
-        let trove_data = TroveData {
-            user: trove_user,
-            debt_amount,
-            collateral_amounts: vec![(collateral_denom.clone(), collateral_amount)],
-            liquidity_ratio: 0, // Not needed for redemption
-        };
+        // The gas compensation reserve is left for close_trove or the trove's liquidator
+        let redeemable_debt = debt_amount.saturating_sub(GAS_COMPENSATION);
+        if redeemable_debt == 0 {
+            msg!("Trove {} only holds its gas compensation reserve, skipping", trove_user);
+            continue;
+        }
         
         // Calculate how much to redeem from this trove
-        let redeem_from_trove = remaining_amount.min(trove_data.debt_amount);
-        
-        // Calculate collateral to send (proportional to debt redeemed)
-        let collateral_ratio = if trove_data.debt_amount > 0 {
-            (redeem_from_trove as f64) / (trove_data.debt_amount as f64)
-        } else {
-            0.0
-        };
+        let redeem_from_trove = remaining_amount.min(redeemable_debt);
         
-        let collateral_to_send = ((collateral_amount as f64) * collateral_ratio) as u64;
-        
-        if collateral_to_send > 0 {
-            // Transfer collateral to user
-            let collateral_seeds = &[
-                b"protocol_collateral_vault".as_ref(),
-                params.collateral_denom.as_bytes(),
-                &[ctx.bumps.protocol_collateral_vault],
-            ];
-            let collateral_signer = &[&collateral_seeds[..]];
+        // Send the same share of every denom the trove holds (proportional to debt redeemed)
+        let mut new_collateral_amounts = Vec::with_capacity(collateral_amounts.len());
+        let mut collateral_sent = Vec::new();
+        for (denom, collateral_amount) in &collateral_amounts {
+            let collateral_amount = *collateral_amount;
+            let collateral_account = find_user_collateral_account(&trove_user, denom, ctx.remaining_accounts)?;
+            
+            let collateral_to_send = u64::try_from(
+                (collateral_amount as u128)
+                    .checked_mul(redeem_from_trove as u128)
+                    .ok_or(AerospacerProtocolError::OverflowError)?
+                    / debt_amount as u128,
+            ).map_err(|_| AerospacerProtocolError::OverflowError)?;
+            
+            if collateral_to_send == 0 {
+                new_collateral_amounts.push((denom.clone(), collateral_amount));
+                continue;
+            }
             
-            let collateral_transfer_ctx = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.protocol_collateral_vault.to_account_info(),
-                    to: ctx.accounts.user_collateral_account.to_account_info(),
-                    authority: ctx.accounts.protocol_collateral_vault.to_account_info(),
-                },
-                collateral_signer,
-            );
-            anchor_spl::token::transfer(collateral_transfer_ctx, collateral_to_send)?;
+            let payout_index = match payouts.iter().position(|p| p.denom == *denom) {
+                Some(index) => index,
+                None => {
+                    let payout = DenomPayout::from_remaining_accounts(
+                        denom,
+                        &ctx.accounts.user.key(),
+                        &ctx.remaining_accounts[num_troves * 4..],
+                    )?;
+                    payouts.push(payout);
+                    payouts.len() - 1
+                }
+            };
             
-            // Update UserCollateralAmount to reflect decreased collateral
-            let mut coll_data = collateral_account.try_borrow_mut_data()?;
-            let mut user_coll = UserCollateralAmount::try_deserialize(&mut &coll_data[..])?;
-            user_coll.amount = user_coll.amount.saturating_sub(collateral_to_send);
-            user_coll.try_serialize(&mut &mut coll_data[..])?;
-            drop(coll_data);
+            // Transfer collateral to user and take it out of the denom's global total
+            payouts[payout_index].pay(&ctx.accounts.token_program.to_account_info(), collateral_to_send)?;
             
-            // Update global total_collateral_amount PDA
-            let mut total_coll_data = ctx.accounts.total_collateral_amount.try_borrow_mut_data()?;
-            let mut total_collateral: TotalCollateralAmount = TotalCollateralAmount::try_deserialize(&mut &total_coll_data[..])?;
-            total_collateral.amount = total_collateral.amount.checked_sub(collateral_to_send)
-                .ok_or(AerospacerProtocolError::OverflowError)?;
-            total_collateral.try_serialize(&mut &mut total_coll_data[..])?;
-            drop(total_coll_data);
+            // Update UserCollateralAmount (and the trove's stake) to reflect decreased collateral
+            let new_collateral_amount = collateral_amount.saturating_sub(collateral_to_send);
+            new_collateral_amounts.push((denom.clone(), new_collateral_amount));
+            set_user_collateral_amount(collateral_account, new_collateral_amount)?;
+            modify_redistribution_snapshot(
+                find_redistribution_snapshot_account(denom, ctx.remaining_accounts)?,
+                |snapshot| snapshot.update_stake(collateral_amount, new_collateral_amount),
+            )?;
             
-            total_collateral_sent = total_collateral_sent.saturating_add(collateral_to_send);
-            msg!("Transferred {} {} to user from trove {}", collateral_to_send, params.collateral_denom, trove_user);
+            msg!("Transferred {} {} to user from trove {}", collateral_to_send, denom, trove_user);
+            collateral_sent.push(DenomAmount { denom: denom.clone(), amount: collateral_to_send });
         }
         
         // Update trove debt
-        let new_debt = trove_data.debt_amount.saturating_sub(redeem_from_trove);
+        let new_debt = debt_amount.saturating_sub(redeem_from_trove);
         
         // Update UserDebtAmount account
         let mut debt_data_mut = debt_account.try_borrow_mut_data()?;
@@ -341,12 +414,25 @@ pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
         user_debt_mut.try_serialize(&mut &mut debt_data_mut[..])?;
         drop(debt_data_mut);
         
-        if new_debt == 0 {
-            msg!("Trove fully redeemed and zeroed: {}", trove_user);
+        // Store the trove's new ICR and NICRs so the sorted list and liquidation checks see the redemption
+        let new_icr = PriceCalculator::calculate_trove_icr(&new_collateral_amounts, new_debt, &prices)?;
+        set_liquidity_threshold(lt_account, new_icr, &new_collateral_amounts, new_debt)?;
+        
+        if new_debt <= GAS_COMPENSATION {
+            msg!("Trove redeemed down to its gas compensation reserve: {}", trove_user);
         } else {
-            msg!("Trove partially redeemed: user={}, new_debt={}", trove_user, new_debt);
+            msg!("Trove partially redeemed: user={}, new_debt={}, new_icr={}", trove_user, new_debt, new_icr);
         }
         
+        emit!(TroveRedeemed {
+            owner: trove_user,
+            redeemer: ctx.accounts.user.key(),
+            debt_redeemed: redeem_from_trove,
+            collateral: collateral_sent,
+            debt_amount: new_debt,
+            icr: new_icr,
+        });
+        
         troves_redeemed += 1;
         remaining_amount = remaining_amount.saturating_sub(redeem_from_trove);
     }
@@ -359,6 +445,9 @@ pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
         AerospacerProtocolError::InsufficientCollateral // Not enough troves with requested collateral type
     );
     
+    // The first payout is always collateral_denom
+    check_min_collateral_out(payouts[0].sent, params.min_collateral_out)?;
+    
     // PRODUCTION SAFETY: Update global state with net redeemed amount (which equals net_redemption_amount since remaining is 0)
     state.total_debt_amount = state.total_debt_amount.checked_sub(net_redemption_amount)
         .ok_or(AerospacerProtocolError::OverflowError)?;
@@ -366,22 +455,89 @@ pub fn handler(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
     msg!("Redeemed successfully");
     msg!("User: {}", ctx.accounts.user.key());
     msg!("Gross amount: {} aUSD", params.amount);
-    msg!("Fee: {} aUSD ({}%)", fee_amount, ctx.accounts.state.protocol_fee);
+    msg!("Fee: {} aUSD ({} bps)", fee_amount, fee_rate_to_bps(fee_rate));
+    msg!("Base rate: {}", ctx.accounts.state.base_rate);
     msg!("Net redemption: {} aUSD", net_redemption_amount);
-    msg!("Collateral sent: {} {}", total_collateral_sent, params.collateral_denom);
+    for payout in &payouts {
+        msg!("Collateral sent: {} {}", payout.sent, payout.denom);
+    }
     msg!("Troves redeemed: {}", troves_redeemed);
     msg!("Remaining amount: {} aUSD", remaining_amount);
 
+    emit!(Redemption {
+        redeemer: ctx.accounts.user.key(),
+        amount: params.amount,
+        redemption_fee: fee_amount,
+        collateral: payouts.iter().map(|p| DenomAmount { denom: p.denom.clone(), amount: p.sent }).collect(),
+        troves_redeemed,
+        base_rate: ctx.accounts.state.base_rate,
+    });
+
+    Ok(())
+}
+
+/// Check a redemption target's current-price ICR and its place after the previous target
+fn check_redemption_target(icr: Decimal, nicr: Decimal, prev_nicr: Option<Decimal>) -> Result<()> {
+    require!(
+        icr >= Decimal::ONE,
+        AerospacerProtocolError::RedemptionTargetUndercollateralized
+    );
+    validate_icr_ordering(nicr, prev_nicr, None)
+}
+
+/// Check that a trove account passed via remaining_accounts is the program's PDA for `seeds`
+fn verify_trove_pda(account_info: &AccountInfo, seeds: &[&[u8]]) -> Result<()> {
+    let (expected_pda, _bump) = Pubkey::find_program_address(seeds, &crate::ID);
+    require!(
+        account_info.key() == expected_pda,
+        AerospacerProtocolError::InvalidList
+    );
     Ok(())
 }
 
 // NOTE: Helper functions for sorted list traversal removed - using off-chain sorting architecture
 
-// Trove data structure for redemption
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
-pub struct TroveData {
-    pub user: Pubkey,
-    pub debt_amount: u64,
-    pub collateral_amounts: Vec<(String, u64)>,
-    pub liquidity_ratio: u64,
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_redemption_targets_run_riskiest_first_above_par() {
+        let riskier = compute_nominal_icr(1_500, 1_000).unwrap();
+        let safer = compute_nominal_icr(1_600, 1_000).unwrap();
+        
+        assert!(check_redemption_target(Decimal::from_percent(150), riskier, None).is_ok());
+        assert!(check_redemption_target(Decimal::from_percent(160), safer, Some(riskier)).is_ok());
+        assert!(check_redemption_target(Decimal::from_percent(160), safer, Some(safer)).is_ok());
+        
+        // Skipping past a riskier trove breaks the ordering
+        assert!(check_redemption_target(Decimal::from_percent(150), riskier, Some(safer)).is_err());
+        
+        // Troves at 100% can still be redeemed, underwater ones cannot
+        assert!(check_redemption_target(Decimal::ONE, riskier, None).is_ok());
+        assert!(check_redemption_target(Decimal::from_percent(99), riskier, None).is_err());
+    }
+    
+    #[test]
+    fn test_trove_accounts_must_be_owner_pdas() {
+        let owner = Pubkey::new_unique();
+        let seeds = UserCollateralAmount::seeds(&owner, "SOL");
+        let (pda, _bump) = Pubkey::find_program_address(&seeds, &crate::ID);
+        let impostor = Pubkey::new_unique();
+        
+        let mut lamports = 0;
+        let mut data = [];
+        let account = AccountInfo::new(&pda, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        assert!(verify_trove_pda(&account, &seeds).is_ok());
+        
+        // Another owner's PDA, or the same owner's PDA for another denom, is rejected
+        assert!(verify_trove_pda(&account, &UserCollateralAmount::seeds(&impostor, "SOL")).is_err());
+        assert!(verify_trove_pda(&account, &UserCollateralAmount::seeds(&owner, "ETH")).is_err());
+        
+        let mut lamports = 0;
+        let mut data = [];
+        let account = AccountInfo::new(&impostor, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        assert!(verify_trove_pda(&account, &seeds).is_err());
+    }
+}
+
@@ -0,0 +1,212 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount, Mint, Transfer, Burn};
+use crate::state::*;
+use crate::utils::*;
+use crate::error::*;
+use crate::account_management::DenomPayout;
+use crate::events::{SharesRedeemed, denom_amounts};
+use crate::oracle::OracleContext;
+use crate::stability_pool::settle_deposit_gains;
+use crate::share_pool::{redeemed_amount, share_pool_collateral, take_share_pool_collateral};
+use crate::instructions::unstake::check_no_liquidatable_troves;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RedeemFromSharePoolParams {
+    pub shares: u64,
+}
+
+#[derive(Accounts)]
+pub struct RedeemFromSharePool<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"share_pool"],
+        bump = share_pool.bump
+    )]
+    pub share_pool: Box<Account<'info, SharePool>>,
+
+    #[account(
+        mut,
+        seeds = [b"user_stake_amount", share_pool.key().as_ref()],
+        bump,
+        constraint = pool_stake_amount.owner == share_pool.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub pool_stake_amount: Box<Account<'info, UserStakeAmount>>,
+
+    #[account(
+        mut,
+        address = share_pool.share_mint @ AerospacerProtocolError::InvalidMint
+    )]
+    pub share_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        constraint = user_share_account.owner == user.key() @ AerospacerProtocolError::Unauthorized,
+        constraint = user_share_account.mint == share_mint.key() @ AerospacerProtocolError::InvalidMint
+    )]
+    pub user_share_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub state: Box<Account<'info, StateAccount>>,
+
+    #[account(
+        mut,
+        constraint = user_stablecoin_account.owner == user.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub user_stablecoin_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Protocol stablecoin vault PDA
+    #[account(
+        mut,
+        seeds = [b"protocol_stablecoin_vault"],
+        bump
+    )]
+    pub protocol_stablecoin_vault: AccountInfo<'info>,
+
+    /// CHECK: Our oracle program - validated against state
+    #[account(
+        constraint = oracle_program.key() == state.oracle_helper_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_program: AccountInfo<'info>,
+
+    /// CHECK: Oracle state account - lists the collateral denoms whose troves are checked and gains settled
+    #[account(
+        constraint = oracle_state.key() == state.oracle_state_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_state: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+// remaining_accounts:
+// - The trove proof and the troves' accounts, as for unstake (see unstake::check_no_liquidatable_troves)
+// - Then, located by address: the StabilityPoolSnapshot and the share pool's UserCollateralSnapshot
+//   of every denom the oracle supports, the EpochScaleSum PDAs its gains are read from
+//   (see stability_pool::settle_deposit_gains), and for every denom it holds gains in, the
+//   protocol_collateral_vault, TotalCollateralAmount and the user's token account
+//
+// Shares only reach a holder's account once the deposit that minted them has waited out the unstake
+// cooldown (see release_shares), so redemptions need no cooldown check of their own.
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, RedeemFromSharePool<'info>>,
+    params: RedeemFromSharePoolParams,
+) -> Result<()> {
+    require!(params.shares > 0, AerospacerProtocolError::InvalidAmount);
+    require!(
+        ctx.accounts.user_share_account.amount >= params.shares,
+        AerospacerProtocolError::InvalidAmount
+    );
+
+    let user = ctx.accounts.user.key();
+    let share_pool_key = ctx.accounts.share_pool.key();
+    let share_supply = ctx.accounts.share_mint.supply;
+
+    // Every price feed is located in remaining_accounts, so no dedicated Pyth account is needed
+    let oracle_ctx = OracleContext {
+        oracle_program: ctx.accounts.oracle_program.clone(),
+        oracle_state: ctx.accounts.oracle_state.to_account_info(),
+        pyth_price_account: ctx.accounts.oracle_state.to_account_info(),
+        clock: ctx.accounts.clock.to_account_info(),
+    };
+    let denoms = oracle_ctx.supported_denoms()?;
+
+    let state = &mut ctx.accounts.state;
+    let pool_stake_amount = &mut ctx.accounts.pool_stake_amount;
+
+    // Depositors may not leave the pool ahead of a pending liquidation
+    let consumed = check_no_liquidatable_troves(&oracle_ctx, &denoms, state, ctx.remaining_accounts)?;
+    let accounts = &ctx.remaining_accounts[consumed..];
+
+    let compounded_deposit = calculate_compounded_stake(pool_stake_amount, state)?;
+
+    // Bring the pool's collateral gains up to date before splitting them
+    settle_deposit_gains(
+        pool_stake_amount,
+        state,
+        &denoms,
+        &share_pool_key,
+        &ctx.accounts.user.to_account_info(),
+        accounts,
+        &ctx.accounts.system_program.to_account_info(),
+    )?;
+
+    // The shares' part of the compounded deposit and of each denom's gains, paid in kind
+    let amount = redeemed_amount(params.shares, share_supply, compounded_deposit)?;
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let mut collateral = Vec::new();
+    for (denom, pending) in share_pool_collateral(&share_pool_key, &denoms, accounts)? {
+        let gain = redeemed_amount(params.shares, share_supply, pending)?;
+        if gain == 0 {
+            continue;
+        }
+        take_share_pool_collateral(&share_pool_key, &denom, gain, accounts)?;
+        DenomPayout::from_remaining_accounts(&denom, &user, accounts)?.pay(&token_program, gain)?;
+        collateral.push((denom, gain));
+    }
+    require!(
+        amount > 0 || !collateral.is_empty(),
+        AerospacerProtocolError::InvalidAmount
+    );
+
+    if amount > 0 {
+        // Transfer stablecoin back to user from protocol vault
+        let transfer_seeds = &[
+            PROTOCOL_STABLECOIN_VAULT_SEED,
+            &[ctx.bumps.protocol_stablecoin_vault],
+        ];
+        let transfer_signer = &[&transfer_seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            token_program.clone(),
+            Transfer {
+                from: ctx.accounts.protocol_stablecoin_vault.to_account_info(),
+                to: ctx.accounts.user_stablecoin_account.to_account_info(),
+                authority: ctx.accounts.protocol_stablecoin_vault.to_account_info(),
+            },
+            transfer_signer,
+        );
+        anchor_spl::token::transfer(transfer_ctx, amount)?;
+    }
+
+    let burn_ctx = CpiContext::new(
+        token_program,
+        Burn {
+            mint: ctx.accounts.share_mint.to_account_info(),
+            from: ctx.accounts.user_share_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        },
+    );
+    anchor_spl::token::burn(burn_ctx, params.shares)?;
+
+    // The rest of the compounded deposit is re-deposited at the current P, epoch and scale
+    pool_stake_amount.amount = safe_sub(compounded_deposit, amount)?;
+    pool_stake_amount.p_snapshot = state.p_factor;
+    pool_stake_amount.epoch_snapshot = state.epoch;
+    pool_stake_amount.scale_snapshot = state.current_scale;
+    pool_stake_amount.last_update_block = Clock::get()?.slot;
+
+    state.total_stake_amount = safe_sub(state.total_stake_amount, amount)?;
+
+    msg!("Redeemed from share pool successfully");
+    msg!("User: {}", user);
+    msg!("Shares burned: {} of {}", params.shares, share_supply);
+    msg!("Amount: {} aUSD", amount);
+    for (denom, gain) in &collateral {
+        msg!("Collateral: {} {}", gain, denom);
+    }
+    msg!("Pool deposit: {} aUSD", pool_stake_amount.amount);
+
+    emit!(SharesRedeemed {
+        user,
+        shares: params.shares,
+        amount,
+        collateral: denom_amounts(&collateral),
+        pool_deposit: pool_stake_amount.amount,
+    });
+
+    Ok(())
+}
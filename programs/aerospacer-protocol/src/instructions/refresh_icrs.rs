@@ -0,0 +1,111 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::*;
+use crate::oracle::{OracleContext, PriceCalculator};
+use crate::redistribution::apply_trove_rewards_by_address;
+use crate::sorted_troves::verify_liquidity_threshold_pda;
+use crate::trove_management::{load_trove_collateral, set_liquidity_threshold};
+use crate::events::TroveIcrRefreshed;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RefreshIcrsParams {
+    pub num_troves: u8, // Number of [UserDebtAmount, LiquidityThreshold] pairs at the start of remainingAccounts
+}
+
+/// Permissionless crank - anyone may pay to bring stored ICRs up to date
+#[derive(Accounts)]
+pub struct RefreshIcrs<'info> {
+    pub caller: Signer<'info>,
+
+    #[account(
+        seeds = [b"state"],
+        bump
+    )]
+    pub state: Box<Account<'info, StateAccount>>,
+
+    /// CHECK: Our oracle program - validated against state
+    #[account(
+        constraint = oracle_program.key() == state.oracle_helper_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_program: AccountInfo<'info>,
+
+    /// CHECK: Oracle state account - validated against state
+    #[account(
+        constraint = oracle_state.key() == state.oracle_state_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_state: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+/// Handler for refresh_icrs instruction
+/// Reprices a batch of troves through the oracle and rewrites their LiquidityThreshold ratios,
+/// so off-chain sorting and on-chain liquidation checks work from current prices (the NICRs
+/// are rewritten too, which only changes them if redistribution rewards were pending)
+///
+/// # Remaining Accounts Pattern
+/// - params.num_troves [UserDebtAmount, LiquidityThreshold] pairs (writable)
+/// - Then, located by address: each trove's UserCollateralAmount PDAs, the RedistributionSnapshot
+///   and Pyth price account of every denom the troves hold
+///
+/// Pending redistribution rewards are applied first, so the stored ratio covers them.
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, RefreshIcrs<'info>>, params: RefreshIcrsParams) -> Result<()> {
+    let num_troves = params.num_troves as usize;
+    require!(
+        num_troves > 0 && ctx.remaining_accounts.len() >= num_troves * 2,
+        AerospacerProtocolError::InvalidList
+    );
+
+    // Every price feed is located in remaining_accounts, so no dedicated Pyth account is needed
+    let oracle_ctx = OracleContext {
+        oracle_program: ctx.accounts.oracle_program.clone(),
+        oracle_state: ctx.accounts.oracle_state.clone(),
+        pyth_price_account: ctx.accounts.oracle_state.clone(),
+        clock: ctx.accounts.clock.to_account_info(),
+    };
+    let mut prices: Vec<(String, u64, u8)> = Vec::new();
+    let mut refreshed = 0u32;
+
+    for i in 0..num_troves {
+        let debt_account = &ctx.remaining_accounts[i * 2];
+        let lt_account = &ctx.remaining_accounts[i * 2 + 1];
+
+        require!(
+            debt_account.owner == &crate::ID,
+            AerospacerProtocolError::Unauthorized
+        );
+
+        let mut debt_data = debt_account.try_borrow_mut_data()?;
+        let mut user_debt = UserDebtAmount::try_deserialize(&mut &debt_data[..])?;
+        let (expected_pda, _bump) = Pubkey::find_program_address(&UserDebtAmount::seeds(&user_debt.owner), &crate::ID);
+        require!(
+            debt_account.key() == expected_pda,
+            AerospacerProtocolError::InvalidList
+        );
+        verify_liquidity_threshold_pda(lt_account, user_debt.owner, &crate::ID)?;
+
+        if user_debt.amount == 0 {
+            msg!("Trove {} has no debt, skipping", user_debt.owner);
+            continue;
+        }
+
+        apply_trove_rewards_by_address(&mut user_debt, ctx.remaining_accounts)?;
+        user_debt.try_serialize(&mut &mut debt_data[..])?;
+        drop(debt_data);
+
+        let collateral_amounts = load_trove_collateral(&user_debt, &oracle_ctx, ctx.remaining_accounts, &mut prices)?;
+        let icr = PriceCalculator::calculate_trove_icr(&collateral_amounts, user_debt.amount, &prices)?;
+        set_liquidity_threshold(lt_account, icr, &collateral_amounts, user_debt.amount)?;
+
+        msg!("Trove {}: debt={}, ICR={}", user_debt.owner, user_debt.amount, icr);
+        emit!(TroveIcrRefreshed {
+            owner: user_debt.owner,
+            debt_amount: user_debt.amount,
+            icr,
+        });
+        refreshed += 1;
+    }
+
+    msg!("Refreshed {} trove ICRs", refreshed);
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount, Transfer};
+use crate::state::*;
+use crate::error::*;
+use crate::events::SharesReleased;
+use crate::share_pool::release_matured_shares;
+
+#[derive(Accounts)]
+pub struct ReleaseShares<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"share_pool"],
+        bump = share_pool.bump
+    )]
+    pub share_pool: Box<Account<'info, SharePool>>,
+
+    #[account(
+        mut,
+        seeds = [b"share_lock", user.key().as_ref()],
+        bump,
+        constraint = share_lock.owner == user.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub share_lock: Box<Account<'info, ShareLock>>,
+
+    #[account(
+        mut,
+        seeds = [SHARE_ESCROW_SEED],
+        bump
+    )]
+    pub share_escrow: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = user_share_account.owner == user.key() @ AerospacerProtocolError::Unauthorized,
+        constraint = user_share_account.mint == share_pool.share_mint @ AerospacerProtocolError::InvalidMint
+    )]
+    pub user_share_account: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+pub fn handler(ctx: Context<ReleaseShares>) -> Result<()> {
+    let share_lock = &mut ctx.accounts.share_lock;
+    let shares = release_matured_shares(share_lock, Clock::get()?.slot);
+    require!(shares > 0, AerospacerProtocolError::UnstakeCooldownActive);
+
+    // Hand the shares over from escrow, signed by the SharePool
+    let share_pool_seeds = &[b"share_pool".as_ref(), &[ctx.accounts.share_pool.bump]];
+    let share_pool_signer = &[&share_pool_seeds[..]];
+    let transfer_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        Transfer {
+            from: ctx.accounts.share_escrow.to_account_info(),
+            to: ctx.accounts.user_share_account.to_account_info(),
+            authority: ctx.accounts.share_pool.to_account_info(),
+        },
+        share_pool_signer,
+    );
+    anchor_spl::token::transfer(transfer_ctx, shares)?;
+
+    msg!("Shares released from escrow");
+    msg!("User: {}", ctx.accounts.user.key());
+    msg!("Shares: {}", shares);
+
+    emit!(SharesReleased {
+        user: ctx.accounts.user.key(),
+        shares,
+    });
+
+    Ok(())
+}
@@ -5,6 +5,8 @@ use crate::error::*;
 use crate::trove_management::*;
 use crate::account_management::*;
 use crate::oracle::*;
+use crate::redistribution::apply_trove_rewards;
+use crate::events::{TroveAdjusted, TroveOperation};
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct RemoveCollateralParams {
@@ -73,6 +75,20 @@ pub struct RemoveCollateral<'info> {
     )]
     pub total_collateral_amount: Account<'info, TotalCollateralAmount>,
 
+    #[account(
+        mut,
+        seeds = [b"redistribution_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub redistribution_snapshot: Box<Account<'info, RedistributionSnapshot>>,
+
+    // Risk limits of collateral_denom, set by the admin
+    #[account(
+        seeds = [b"collateral_config", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub collateral_config: Box<Account<'info, CollateralConfig>>,
+
     // Oracle context - UncheckedAccount to reduce stack usage
     /// CHECK: Our oracle program - validated against state in handler
     pub oracle_program: UncheckedAccount<'info>,
@@ -93,7 +109,13 @@ pub struct RemoveCollateral<'info> {
 
 
 
-pub fn handler(ctx: Context<RemoveCollateral>, params: RemoveCollateralParams) -> Result<()> {
+// remaining_accounts:
+// - One [UserCollateralAmount, RedistributionSnapshot, pyth_price_account, CollateralConfig] group
+//   per other denom the trove holds, in UserDebtAmount.collateral_denoms order (see trove_management::load_other_collateral)
+// - One [TotalCollateralAmount, pyth_price_account] pair per denom the oracle supports,
+//   used for the Recovery Mode checks (see trove_management::load_system_collateral_block)
+// - Followed by the optional neighbor hints [prev_LiquidityThreshold, next_LiquidityThreshold]
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, RemoveCollateral<'info>>, params: RemoveCollateralParams) -> Result<()> {
     // Validate oracle accounts
     require!(
         ctx.accounts.oracle_program.key() == ctx.accounts.state.oracle_helper_addr,
@@ -115,13 +137,23 @@ pub fn handler(ctx: Context<RemoveCollateral>, params: RemoveCollateralParams) -
         AerospacerProtocolError::InvalidAmount
     );
     
+    // Bring the trove up to date with redistributed liquidations before pricing it
+    apply_trove_rewards(
+        &mut ctx.accounts.user_debt_amount,
+        &mut ctx.accounts.user_collateral_amount,
+        &mut ctx.accounts.redistribution_snapshot,
+        ctx.remaining_accounts,
+        OTHER_COLLATERAL_GROUP_LEN,
+    )?;
+    let previous_stake = ctx.accounts.user_collateral_amount.amount;
+    
     require!(
         params.collateral_amount <= ctx.accounts.user_collateral_amount.amount,
         AerospacerProtocolError::InsufficientCollateral
     );
     
     // Create contexts in scoped block to reduce stack usage
-    let result = {
+    let (result, consumed, other_collateral) = {
         let mut trove_ctx = TroveContext {
             user: ctx.accounts.user.clone(),
             user_debt_amount: ctx.accounts.user_debt_amount.clone(),
@@ -145,11 +177,29 @@ pub fn handler(ctx: Context<RemoveCollateral>, params: RemoveCollateralParams) -
             clock: ctx.accounts.clock.to_account_info(),
         };
         
+        // Price the trove's other collateral so the ICR covers every denom
+        let (other_collateral, consumed) = load_other_collateral(
+            &ctx.accounts.user_debt_amount,
+            &params.collateral_denom,
+            ctx.remaining_accounts,
+            &oracle_ctx,
+        )?;
+        
+        // Price the protocol's total collateral for the TCR
+        let (system_collateral, system_accounts) = load_system_collateral_block(
+            &oracle_ctx,
+            &ctx.remaining_accounts[consumed..],
+        )?;
+        let consumed = consumed + system_accounts;
+        
         // Use TroveManager for clean implementation
         let result = TroveManager::remove_collateral(
             &mut trove_ctx,
             &mut collateral_ctx,
             &oracle_ctx,
+            &ctx.accounts.collateral_config,
+            &other_collateral,
+            &system_collateral,
             params.collateral_amount,
             params.collateral_denom.clone(),
             ctx.bumps.protocol_collateral_account,
@@ -158,61 +208,32 @@ pub fn handler(ctx: Context<RemoveCollateral>, params: RemoveCollateralParams) -
         // Update state before contexts are dropped
         ctx.accounts.state.total_debt_amount = trove_ctx.state.total_debt_amount;
         
-        Ok::<_, Error>(result)
+        Ok::<_, Error>((result, consumed, other_collateral))
     }?;
     
     // CRITICAL: Validate ICR ordering if neighbor hints provided
     // Production clients MUST provide neighbor hints via remainingAccounts for proper sorted list maintenance
     // Pattern: [prev_LiquidityThreshold, next_LiquidityThreshold] or [prev_LT] or [next_LT] or []
     // Optional for backward compatibility with tests, but REQUIRED in production
-    if !ctx.remaining_accounts.is_empty() {
-        use crate::sorted_troves;
-        
-        msg!("Validating ICR ordering with {} neighbor account(s)", ctx.remaining_accounts.len());
-        
-        let prev_icr = if ctx.remaining_accounts.len() >= 1 {
-            let prev_lt = &ctx.remaining_accounts[0];
-            let prev_data = prev_lt.try_borrow_data()?;
-            let prev_threshold = LiquidityThreshold::try_deserialize(&mut &prev_data[..])?;
-            let prev_owner = prev_threshold.owner;
-            let prev_ratio = prev_threshold.ratio;
-            drop(prev_data);
-            
-            // Verify this is a real PDA, not a fake account
-            sorted_troves::verify_liquidity_threshold_pda(prev_lt, prev_owner, ctx.program_id)?;
-            
-            Some(prev_ratio)
-        } else {
-            None
-        };
-        
-        let next_icr = if ctx.remaining_accounts.len() >= 2 {
-            let next_lt = &ctx.remaining_accounts[1];
-            let next_data = next_lt.try_borrow_data()?;
-            let next_threshold = LiquidityThreshold::try_deserialize(&mut &next_data[..])?;
-            let next_owner = next_threshold.owner;
-            let next_ratio = next_threshold.ratio;
-            drop(next_data);
-            
-            // Verify this is a real PDA, not a fake account
-            sorted_troves::verify_liquidity_threshold_pda(next_lt, next_owner, ctx.program_id)?;
-            
-            Some(next_ratio)
-        } else {
-            None
-        };
-        
-        // Validate ordering BEFORE updating state
-        sorted_troves::validate_icr_ordering(result.new_icr, prev_icr, next_icr)?;
-        msg!("✓ ICR ordering validated successfully");
-    } else {
-        msg!("⚠ WARNING: No neighbor hints provided - skipping ICR ordering validation");
-        msg!("⚠ Production clients MUST provide neighbor hints for sorted list integrity");
-    }
+    let new_nicr = crate::sorted_troves::compute_nominal_icr(result.new_collateral_amount, result.new_debt_amount)?;
+    crate::sorted_troves::validate_neighbor_hints(&params.collateral_denom, new_nicr, &ctx.remaining_accounts[consumed..], ctx.program_id)?;
     
     // Update the actual accounts with the results
     ctx.accounts.user_collateral_amount.amount = result.new_collateral_amount;
     ctx.accounts.liquidity_threshold.ratio = result.new_icr;
+    ctx.accounts.liquidity_threshold.set_nominal_ratios(
+        &other_collateral.collateral_amounts(&params.collateral_denom, result.new_collateral_amount),
+        result.new_debt_amount,
+    )?;
+    ctx.accounts.redistribution_snapshot.update_stake(previous_stake, result.new_collateral_amount)?;
+    ctx.accounts.total_collateral_amount.amount = ctx.accounts.total_collateral_amount.amount
+        .checked_sub(params.collateral_amount)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+    
+    // Withdrawing a denom completely drops it from the trove
+    if result.new_collateral_amount == 0 {
+        ctx.accounts.user_debt_amount.remove_collateral_denom(&params.collateral_denom);
+    }
     
     msg!("Collateral removed successfully");
     msg!("Removed: {} {}", params.collateral_amount, params.collateral_denom);
@@ -220,5 +241,19 @@ pub fn handler(ctx: Context<RemoveCollateral>, params: RemoveCollateralParams) -
     msg!("New ICR: {}", result.new_icr);
     msg!("Debt amount: {}", result.new_debt_amount);
 
+    emit!(TroveAdjusted {
+        owner: ctx.accounts.user.key(),
+        operation: TroveOperation::RemoveCollateral,
+        collateral_denom: params.collateral_denom,
+        collateral_change: params.collateral_amount,
+        is_collateral_increase: false,
+        debt_change: 0,
+        is_debt_increase: false,
+        borrowing_fee: 0,
+        collateral_amount: result.new_collateral_amount,
+        debt_amount: result.new_debt_amount,
+        icr: result.new_icr,
+    });
+
     Ok(())
 }
\ No newline at end of file
@@ -5,6 +5,8 @@ use crate::error::*;
 use crate::trove_management::*;
 use crate::account_management::*;
 use crate::oracle::*;
+use crate::redistribution::apply_trove_rewards;
+use crate::events::{TroveAdjusted, TroveOperation};
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct RepayLoanParams {
@@ -80,6 +82,21 @@ pub struct RepayLoan<'info> {
     )]
     pub total_collateral_amount: Account<'info, TotalCollateralAmount>,
 
+    #[account(
+        mut,
+        seeds = [b"redistribution_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub redistribution_snapshot: Box<Account<'info, RedistributionSnapshot>>,
+
+    /// CHECK: Gas compensation vault PDA - the reserve is burned on full repayment
+    #[account(
+        mut,
+        seeds = [b"gas_compensation_vault"],
+        bump
+    )]
+    pub gas_compensation_vault: AccountInfo<'info>,
+
     // Oracle context - UncheckedAccount to reduce stack usage
     /// CHECK: Our oracle program - validated against state in handler
     pub oracle_program: UncheckedAccount<'info>,
@@ -98,7 +115,9 @@ pub struct RepayLoan<'info> {
     pub system_program: Program<'info, System>,
 }
 
-pub fn handler(ctx: Context<RepayLoan>, params: RepayLoanParams) -> Result<()> {
+// remaining_accounts: one [UserCollateralAmount, RedistributionSnapshot, pyth_price_account, CollateralConfig]
+// group per other denom the trove holds, in UserDebtAmount.collateral_denoms order (see trove_management::load_other_collateral)
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, RepayLoan<'info>>, params: RepayLoanParams) -> Result<()> {
     // Validate oracle accounts
     require!(
         ctx.accounts.oracle_program.key() == ctx.accounts.state.oracle_helper_addr,
@@ -126,20 +145,38 @@ pub fn handler(ctx: Context<RepayLoan>, params: RepayLoanParams) -> Result<()> {
         AerospacerProtocolError::TroveDoesNotExist
     );
     
+    // Bring the trove up to date with redistributed liquidations before pricing it
+    apply_trove_rewards(
+        &mut ctx.accounts.user_debt_amount,
+        &mut ctx.accounts.user_collateral_amount,
+        &mut ctx.accounts.redistribution_snapshot,
+        ctx.remaining_accounts,
+        OTHER_COLLATERAL_GROUP_LEN,
+    )?;
+    let previous_stake = ctx.accounts.user_collateral_amount.amount;
+    
     // Check if user has sufficient stablecoins
     require!(
         params.amount <= ctx.accounts.user_stablecoin_account.amount,
         AerospacerProtocolError::InsufficientCollateral
     );
     
-    // Check if repayment amount doesn't exceed debt
+    // The gas compensation reserve is already held by the protocol - repaying everything
+    // else clears it too, so the trove is fully repaid
+    let gas_compensation = ctx.accounts.user_debt_amount.amount.min(GAS_COMPENSATION);
     require!(
-        params.amount <= ctx.accounts.user_debt_amount.amount,
-        AerospacerProtocolError::InvalidAmount
+        params.amount <= ctx.accounts.user_debt_amount.amount - gas_compensation,
+        AerospacerProtocolError::DebtBelowGasCompensation
     );
+    let full_repayment = params.amount == ctx.accounts.user_debt_amount.amount - gas_compensation;
+    let debt_repaid = if full_repayment {
+        ctx.accounts.user_debt_amount.amount
+    } else {
+        params.amount
+    };
     
     // Create contexts in scoped block to reduce stack usage
-    let result = {
+    let (result, other_collateral) = {
         let mut trove_ctx = TroveContext {
             user: ctx.accounts.user.clone(),
             user_debt_amount: ctx.accounts.user_debt_amount.clone(),
@@ -163,29 +200,44 @@ pub fn handler(ctx: Context<RepayLoan>, params: RepayLoanParams) -> Result<()> {
             clock: ctx.accounts.clock.to_account_info(),
         };
         
+        // Price the trove's other collateral so the ICR covers every denom
+        let (other_collateral, _consumed) = load_other_collateral(
+            &ctx.accounts.user_debt_amount,
+            &params.collateral_denom,
+            ctx.remaining_accounts,
+            &oracle_ctx,
+        )?;
+        
         // Use TroveManager for clean implementation
         let result = TroveManager::repay_loan(
             &mut trove_ctx,
             &mut collateral_ctx,
             &oracle_ctx,
-            params.amount,
+            &other_collateral,
+            debt_repaid,
             ctx.bumps.protocol_collateral_account,
         )?;
         
         // Update state before contexts are dropped
         ctx.accounts.state.total_debt_amount = trove_ctx.state.total_debt_amount;
         
-        Ok::<_, Error>(result)
+        Ok::<_, Error>((result, other_collateral))
     }?;
     
     // Update the actual accounts with the results
     ctx.accounts.user_debt_amount.amount = result.new_debt_amount;
     ctx.accounts.liquidity_threshold.ratio = result.new_icr;
+    ctx.accounts.liquidity_threshold.set_nominal_ratios(
+        &other_collateral.collateral_amounts(&params.collateral_denom, result.new_collateral_amount),
+        result.new_debt_amount,
+    )?;
     ctx.accounts.user_collateral_amount.amount = result.new_collateral_amount;
+    ctx.accounts.redistribution_snapshot.update_stake(previous_stake, result.new_collateral_amount)?;
 
     // NOTE: Sorted troves management moved off-chain
     // If debt is fully repaid, trove is automatically removed from off-chain sorted list
     if result.new_debt_amount == 0 {
+        ctx.accounts.user_debt_amount.remove_collateral_denom(&params.collateral_denom);
         msg!("Trove fully repaid - ready for off-chain list cleanup");
     }
 
@@ -199,6 +251,14 @@ pub fn handler(ctx: Context<RepayLoan>, params: RepayLoanParams) -> Result<()> {
         },
     );
     anchor_spl::token::burn(burn_ctx, params.amount)?;
+    if full_repayment {
+        burn_gas_compensation(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.stable_coin_mint.to_account_info(),
+            &ctx.accounts.gas_compensation_vault,
+            gas_compensation,
+        )?;
+    }
     
     msg!("Loan repaid successfully");
     msg!("Amount: {} aUSD", params.amount);
@@ -207,5 +267,19 @@ pub fn handler(ctx: Context<RepayLoan>, params: RepayLoanParams) -> Result<()> {
     msg!("New ICR: {}", result.new_icr);
     msg!("Collateral amount: {}", result.new_collateral_amount);
     
+    emit!(TroveAdjusted {
+        owner: ctx.accounts.user.key(),
+        operation: TroveOperation::RepayLoan,
+        collateral_denom: params.collateral_denom,
+        collateral_change: 0,
+        is_collateral_increase: false,
+        debt_change: params.amount,
+        is_debt_increase: false,
+        borrowing_fee: 0,
+        collateral_amount: result.new_collateral_amount,
+        debt_amount: result.new_debt_amount,
+        icr: result.new_icr,
+    });
+    
     Ok(())
 }
\ No newline at end of file
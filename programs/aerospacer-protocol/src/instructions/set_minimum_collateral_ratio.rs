@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::AerospacerProtocolError;
+use crate::utils::queue_parameter_change;
+use crate::events::ParameterChangeQueued;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SetMinimumCollateralRatioParams {
+    pub ratio: u8,
+}
+
+#[derive(Accounts)]
+pub struct SetMinimumCollateralRatio<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [b"state"],
+        bump,
+        constraint = state.admin == admin.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub state: Account<'info, StateAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + PendingParameterChange::LEN,
+        seeds = [b"pending_parameter_change", ProtocolParameter::MinimumCollateralRatio.seed()],
+        bump
+    )]
+    pub pending_parameter_change: Account<'info, PendingParameterChange>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<SetMinimumCollateralRatio>, params: SetMinimumCollateralRatioParams) -> Result<()> {
+    let executable_at = queue_parameter_change(
+        &mut ctx.accounts.pending_parameter_change,
+        &ctx.accounts.state,
+        ProtocolParameter::MinimumCollateralRatio,
+        params.ratio as u64,
+        Clock::get()?.unix_timestamp,
+    )?;
+    
+    msg!("Minimum collateral ratio change queued");
+    msg!("Current: {}%", ctx.accounts.state.minimum_collateral_ratio);
+    msg!("New: {}%", params.ratio);
+    msg!("Executable at: {}", executable_at);
+    
+    emit!(ParameterChangeQueued::new(&ctx.accounts.pending_parameter_change));
+    
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::AerospacerProtocolError;
+use crate::utils::queue_parameter_change;
+use crate::events::ParameterChangeQueued;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SetProtocolFeeParams {
+    pub fee: u8,
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolFee<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [b"state"],
+        bump,
+        constraint = state.admin == admin.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub state: Account<'info, StateAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + PendingParameterChange::LEN,
+        seeds = [b"pending_parameter_change", ProtocolParameter::ProtocolFee.seed()],
+        bump
+    )]
+    pub pending_parameter_change: Account<'info, PendingParameterChange>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<SetProtocolFee>, params: SetProtocolFeeParams) -> Result<()> {
+    let executable_at = queue_parameter_change(
+        &mut ctx.accounts.pending_parameter_change,
+        &ctx.accounts.state,
+        ProtocolParameter::ProtocolFee,
+        params.fee as u64,
+        Clock::get()?.unix_timestamp,
+    )?;
+    
+    msg!("Protocol fee (borrowing fee cap) change queued");
+    msg!("Current: {}%", ctx.accounts.state.protocol_fee);
+    msg!("New: {}%", params.fee);
+    msg!("Executable at: {}", executable_at);
+    
+    emit!(ParameterChangeQueued::new(&ctx.accounts.pending_parameter_change));
+    
+    Ok(())
+}
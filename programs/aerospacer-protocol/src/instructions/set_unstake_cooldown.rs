@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::AerospacerProtocolError;
+use crate::utils::queue_parameter_change;
+use crate::events::ParameterChangeQueued;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SetUnstakeCooldownParams {
+    pub cooldown_slots: u64,
+}
+
+#[derive(Accounts)]
+pub struct SetUnstakeCooldown<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [b"state"],
+        bump,
+        constraint = state.admin == admin.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub state: Account<'info, StateAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + PendingParameterChange::LEN,
+        seeds = [b"pending_parameter_change", ProtocolParameter::UnstakeCooldown.seed()],
+        bump
+    )]
+    pub pending_parameter_change: Account<'info, PendingParameterChange>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<SetUnstakeCooldown>, params: SetUnstakeCooldownParams) -> Result<()> {
+    let executable_at = queue_parameter_change(
+        &mut ctx.accounts.pending_parameter_change,
+        &ctx.accounts.state,
+        ProtocolParameter::UnstakeCooldown,
+        params.cooldown_slots,
+        Clock::get()?.unix_timestamp,
+    )?;
+    
+    msg!("Unstake cooldown change queued");
+    msg!("Current: {} slots", ctx.accounts.state.unstake_cooldown_slots);
+    msg!("New: {} slots", params.cooldown_slots);
+    msg!("Executable at: {}", executable_at);
+    
+    emit!(ParameterChangeQueued::new(&ctx.accounts.pending_parameter_change));
+    
+    Ok(())
+}
@@ -3,6 +3,9 @@ use anchor_spl::token::{Token, TokenAccount, Transfer};
 use crate::state::*;
 use crate::utils::*;
 use crate::error::*;
+use crate::events::Staked;
+use crate::oracle::oracle_supported_denoms;
+use crate::stability_pool::settle_deposit_gains;
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct StakeParams {
@@ -50,13 +53,20 @@ pub struct Stake<'info> {
     )]
     pub stable_coin_mint: UncheckedAccount<'info>,
 
+    /// CHECK: Oracle state account - lists the collateral denoms whose gains are settled
+    #[account(
+        constraint = oracle_state.key() == state.oracle_state_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_state: UncheckedAccount<'info>,
+
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
-
-
-pub fn handler(ctx: Context<Stake>, params: StakeParams) -> Result<()> {
+// remaining_accounts, located by address: the StabilityPoolSnapshot and UserCollateralSnapshot of
+// every denom the oracle supports, and the EpochScaleSum PDAs the user's gains are read from
+// (see stability_pool::settle_deposit_gains)
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, Stake<'info>>, params: StakeParams) -> Result<()> {
     // Validate input parameters
     require!(
         params.amount > 0,
@@ -90,13 +100,9 @@ pub fn handler(ctx: Context<Stake>, params: StakeParams) -> Result<()> {
 
     // CRITICAL FIX: Compound existing deposit before updating snapshots
     // This ensures amount and p_snapshot stay in sync after liquidations
-    let current_deposit = if user_stake_amount.amount > 0 && user_stake_amount.p_snapshot > 0 {
+    let current_deposit = if user_stake_amount.amount > 0 && !user_stake_amount.p_snapshot.is_zero() {
         // User has existing stake - calculate compounded value first
-        let compounded = calculate_compounded_stake(
-            user_stake_amount.amount,
-            user_stake_amount.p_snapshot,
-            state.p_factor,
-        )?;
+        let compounded = calculate_compounded_stake(user_stake_amount, state)?;
         
         msg!("Compounding existing deposit:");
         msg!("  Original deposit: {}", user_stake_amount.amount);
@@ -110,6 +116,17 @@ pub fn handler(ctx: Context<Stake>, params: StakeParams) -> Result<()> {
         user_stake_amount.amount
     };
     
+    // Set aside the deposit's gains in every denom before its snapshots move on below
+    settle_deposit_gains(
+        user_stake_amount,
+        state,
+        &oracle_supported_denoms(&ctx.accounts.oracle_state)?,
+        &ctx.accounts.user.key(),
+        &ctx.accounts.user.to_account_info(),
+        ctx.remaining_accounts,
+        &ctx.accounts.system_program.to_account_info(),
+    )?;
+    
     // Update user stake amount with compounded value + new stake
     user_stake_amount.owner = ctx.accounts.user.key();
     user_stake_amount.amount = safe_add(current_deposit, params.amount)?;
@@ -117,6 +134,7 @@ pub fn handler(ctx: Context<Stake>, params: StakeParams) -> Result<()> {
     // SNAPSHOT: Update to current P factor (amount is now in current scale)
     user_stake_amount.p_snapshot = state.p_factor;
     user_stake_amount.epoch_snapshot = state.epoch;
+    user_stake_amount.scale_snapshot = state.current_scale;
     user_stake_amount.last_update_block = Clock::get()?.slot;
 
     // Update state
@@ -128,7 +146,14 @@ pub fn handler(ctx: Context<Stake>, params: StakeParams) -> Result<()> {
     msg!("Total staked: {} aUSD", user_stake_amount.amount);
     msg!("Total protocol stake: {} aUSD", state.total_stake_amount);
     msg!("P snapshot: {}", user_stake_amount.p_snapshot);
-    msg!("Epoch snapshot: {}", user_stake_amount.epoch_snapshot);
+    msg!("Epoch snapshot: {}, scale snapshot: {}", user_stake_amount.epoch_snapshot, user_stake_amount.scale_snapshot);
+
+    emit!(Staked {
+        user: ctx.accounts.user.key(),
+        amount: params.amount,
+        deposit: user_stake_amount.amount,
+        total_stake_amount: state.total_stake_amount,
+    });
 
     Ok(())
 }
\ No newline at end of file
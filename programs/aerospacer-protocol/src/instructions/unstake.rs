@@ -3,12 +3,21 @@ use anchor_spl::token::{Token, TokenAccount, Transfer};
 use crate::state::*;
 use crate::utils::*;
 use crate::error::*;
+use crate::events::Unstaked;
+use crate::oracle::{OracleContext, PriceCalculator};
+use crate::stability_pool::settle_deposit_gains;
+use crate::decimal::Decimal;
+use crate::trove_management::{cache_denom_price, load_trove_with_pending_rewards};
+use crate::trove_registry::load_registry_owners;
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct UnstakeParams {
     pub amount: u64, // Equivalent to Uint256
 }
 
+/// A trove's collateral amounts and debt, with its pending redistribution rewards applied
+pub type TroveAmounts = (Vec<(String, u64)>, u64);
+
 #[derive(Accounts)]
 #[instruction(params: UnstakeParams)]
 pub struct Unstake<'info> {
@@ -46,12 +55,33 @@ pub struct Unstake<'info> {
     )]
     pub stable_coin_mint: UncheckedAccount<'info>,
 
-    pub token_program: Program<'info, Token>,
-}
+    /// CHECK: Our oracle program - validated against state
+    #[account(
+        constraint = oracle_program.key() == state.oracle_helper_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_program: AccountInfo<'info>,
+
+    /// CHECK: Oracle state account - lists the collateral denoms whose troves are checked and gains settled
+    #[account(
+        constraint = oracle_state.key() == state.oracle_state_addr @ AerospacerProtocolError::Unauthorized
+    )]
+    pub oracle_state: UncheckedAccount<'info>,
 
+    pub clock: Sysvar<'info, Clock>,
 
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
 
-pub fn handler(ctx: Context<Unstake>, params: UnstakeParams) -> Result<()> {
+// remaining_accounts:
+// - The trove proof: the TroveRegistry of every denom the oracle supports, in oracle order, each
+//   followed by its pages in order (see check_no_liquidatable_troves)
+// - Then, located by address: the UserDebtAmount of every trove listed, the UserCollateralAmount PDAs
+//   of each denom it holds, the RedistributionSnapshot and Pyth price account of every such denom,
+//   and the StabilityPoolSnapshot and UserCollateralSnapshot of every denom
+//   the oracle supports, and the EpochScaleSum PDAs the user's gains are read from
+//   (see stability_pool::settle_deposit_gains)
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, Unstake<'info>>, params: UnstakeParams) -> Result<()> {
     // Validate input parameters
     require!(
         params.amount > 0,
@@ -63,15 +93,29 @@ pub fn handler(ctx: Context<Unstake>, params: UnstakeParams) -> Result<()> {
         AerospacerProtocolError::InvalidAmount
     );
 
+    // Every price feed is located in remaining_accounts, so no dedicated Pyth account is needed
+    let oracle_ctx = OracleContext {
+        oracle_program: ctx.accounts.oracle_program.clone(),
+        oracle_state: ctx.accounts.oracle_state.to_account_info(),
+        pyth_price_account: ctx.accounts.oracle_state.to_account_info(),
+        clock: ctx.accounts.clock.to_account_info(),
+    };
+    let denoms = oracle_ctx.supported_denoms()?;
+
     let user_stake_amount = &mut ctx.accounts.user_stake_amount;
     let state = &mut ctx.accounts.state;
 
+    // A deposit must rest for the cooldown after it last changed
+    require!(
+        Clock::get()?.slot >= user_stake_amount.last_update_block.saturating_add(state.unstake_cooldown_slots),
+        AerospacerProtocolError::UnstakeCooldownActive
+    );
+
+    // Depositors may not leave the pool ahead of a pending liquidation
+    let consumed = check_no_liquidatable_troves(&oracle_ctx, &denoms, state, ctx.remaining_accounts)?;
+
     // SNAPSHOT: Calculate compounded stake accounting for pool depletion
-    let compounded_stake = calculate_compounded_stake(
-        user_stake_amount.amount,
-        user_stake_amount.p_snapshot,
-        state.p_factor,
-    )?;
+    let compounded_stake = calculate_compounded_stake(user_stake_amount, state)?;
 
     // Check if user has enough compounded stake (NOT original deposit)
     require!(
@@ -79,9 +123,20 @@ pub fn handler(ctx: Context<Unstake>, params: UnstakeParams) -> Result<()> {
         AerospacerProtocolError::InvalidAmount
     );
 
+    // Set aside the deposit's gains in every denom before its snapshots move on below
+    settle_deposit_gains(
+        user_stake_amount,
+        state,
+        &denoms,
+        &ctx.accounts.user.key(),
+        &ctx.accounts.user.to_account_info(),
+        &ctx.remaining_accounts[consumed..],
+        &ctx.accounts.system_program.to_account_info(),
+    )?;
+
     // Transfer stablecoin back to user from protocol vault (Injective: CW20 transfer)
     let transfer_seeds = &[
-        b"protocol_stablecoin_vault".as_ref(),
+        PROTOCOL_STABLECOIN_VAULT_SEED,
         &[ctx.bumps.protocol_stablecoin_vault],
     ];
     let transfer_signer = &[&transfer_seeds[..]];
@@ -97,26 +152,12 @@ pub fn handler(ctx: Context<Unstake>, params: UnstakeParams) -> Result<()> {
     );
     anchor_spl::token::transfer(transfer_ctx, params.amount)?;
 
-    // Update user stake amount - subtract from original deposit proportionally
-    let remaining_compounded = safe_sub(compounded_stake, params.amount)?;
-    
-    // Calculate new deposit amount: remaining_compounded / (P_current / P_snapshot)
-    // = remaining_compounded * P_snapshot / P_current
-    let new_deposit = if remaining_compounded == 0 {
-        0u64
-    } else {
-        let remaining_128 = remaining_compounded as u128;
-        let numerator = remaining_128
-            .checked_mul(user_stake_amount.p_snapshot)
-            .ok_or(AerospacerProtocolError::MathOverflow)?;
-        let result = numerator
-            .checked_div(state.p_factor)
-            .ok_or(AerospacerProtocolError::MathOverflow)?;
-        u64::try_from(result)
-            .map_err(|_| AerospacerProtocolError::MathOverflow)?
-    };
-
-    user_stake_amount.amount = new_deposit;
+    // The rest of the compounded deposit is re-deposited at the current P, epoch and scale
+    // (its gains so far were set aside above)
+    user_stake_amount.amount = safe_sub(compounded_stake, params.amount)?;
+    user_stake_amount.p_snapshot = state.p_factor;
+    user_stake_amount.epoch_snapshot = state.epoch;
+    user_stake_amount.scale_snapshot = state.current_scale;
     user_stake_amount.last_update_block = Clock::get()?.slot;
 
     // Update state
@@ -129,5 +170,176 @@ pub fn handler(ctx: Context<Unstake>, params: UnstakeParams) -> Result<()> {
     msg!("Remaining deposit: {} aUSD", user_stake_amount.amount);
     msg!("Total protocol stake: {} aUSD", state.total_stake_amount);
 
+    emit!(Unstaked {
+        user: ctx.accounts.user.key(),
+        amount: params.amount,
+        deposit: user_stake_amount.amount,
+        total_stake_amount: state.total_stake_amount,
+    });
+
+    Ok(())
+}
+
+/// Reject the withdrawal if any trove sits below the protocol MCR at current prices, as in Liquity,
+/// returning the number of remaining_accounts the proof took
+///
+/// Every trove is listed in the registry of the denom it was opened with, so the complete
+/// registries of all of `denoms` (the oracle's supported denoms) cover every open trove, the
+/// riskiest one included.
+pub fn check_no_liquidatable_troves<'info>(
+    oracle_ctx: &OracleContext<'info>,
+    denoms: &[String],
+    state: &StateAccount,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<usize> {
+    let (troves, consumed) = load_all_troves(denoms, remaining_accounts)?;
+
+    let mut prices: Vec<(String, u64, u8)> = Vec::new();
+    for (collateral_amounts, _) in &troves {
+        for (denom, _) in collateral_amounts {
+            cache_denom_price(oracle_ctx, denom, remaining_accounts, &mut prices)?;
+        }
+    }
+
+    check_troves_above_ratio(
+        &troves,
+        &prices,
+        Decimal::from_percent(state.minimum_collateral_ratio as u64),
+    )?;
+    Ok(consumed)
+}
+
+/// Every trove listed in the registries of `denoms`, read with its pending redistribution rewards
+///
+/// remaining_accounts must start with each denom's TroveRegistry and its pages (see
+/// trove_registry::load_registry_owners); the troves' own accounts are located by address. Returns
+/// the troves together with the number of positional accounts read.
+pub fn load_all_troves(denoms: &[String], remaining_accounts: &[AccountInfo]) -> Result<(Vec<TroveAmounts>, usize)> {
+    let mut troves = Vec::new();
+    let mut consumed = 0;
+    for denom in denoms {
+        let (owners, registry_len) = load_registry_owners(denom, &remaining_accounts[consumed..])?;
+        consumed += registry_len;
+        for owner in &owners {
+            troves.push(load_trove_with_pending_rewards(owner, remaining_accounts)?);
+        }
+    }
+    Ok((troves, consumed))
+}
+
+/// Check that every trove's ICR at `prices` is at least `minimum_ratio`
+pub fn check_troves_above_ratio(
+    troves: &[TroveAmounts],
+    prices: &[(String, u64, u8)],
+    minimum_ratio: Decimal,
+) -> Result<()> {
+    for (collateral_amounts, debt_amount) in troves {
+        let icr = PriceCalculator::calculate_trove_icr(collateral_amounts, *debt_amount, prices)?;
+        require!(icr >= minimum_ratio, AerospacerProtocolError::LiquidatableTrovesExist);
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account_data<T: AccountSerialize>(account: &T) -> Vec<u8> {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    fn registry(denom: &str, owners: &[Pubkey], trove_count: u64) -> (TroveRegistry, TroveRegistryPage) {
+        let registry = TroveRegistry { denom: denom.to_string(), page_count: 1, trove_count };
+        let page = TroveRegistryPage { denom: denom.to_string(), page_index: 0, owners: owners.to_vec() };
+        (registry, page)
+    }
+
+    fn position(owner: Pubkey, amount: u64) -> UserCollateralAmount {
+        UserCollateralAmount {
+            owner,
+            denom: "SOL".to_string(),
+            amount,
+            l_collateral_snapshot: Decimal::ZERO,
+            l_debt_snapshot: Decimal::ZERO,
+        }
+    }
+
+    /// Load the troves of a proof for SOL (one registry page) and ETH (never initialized), with
+    /// one SOL trove holding 1000 SOL against 1000 of debt
+    fn load(trove_count: u64, l_debt: Decimal, with_eth: bool) -> Result<(Vec<TroveAmounts>, usize)> {
+        let owner = Pubkey::new_unique();
+        let (sol_registry, sol_page) = registry("SOL", &[owner], trove_count);
+        let debt = UserDebtAmount { owner, amount: 1_000, collateral_denoms: vec!["SOL".to_string()] };
+        let snapshot = RedistributionSnapshot {
+            denom: "SOL".to_string(),
+            l_collateral: Decimal::ZERO,
+            l_debt,
+            total_stakes: 1_000,
+        };
+
+        let page_index_bytes = 0u32.to_le_bytes();
+        let keys = [
+            Pubkey::find_program_address(&TroveRegistry::seeds("SOL"), &crate::ID).0,
+            Pubkey::find_program_address(&TroveRegistryPage::seeds("SOL", &page_index_bytes), &crate::ID).0,
+            Pubkey::find_program_address(&TroveRegistry::seeds("ETH"), &crate::ID).0,
+            Pubkey::find_program_address(&UserDebtAmount::seeds(&owner), &crate::ID).0,
+            Pubkey::find_program_address(&UserCollateralAmount::seeds(&owner, "SOL"), &crate::ID).0,
+            Pubkey::find_program_address(&RedistributionSnapshot::seeds("SOL"), &crate::ID).0,
+        ];
+        let mut data = [
+            account_data(&sol_registry),
+            account_data(&sol_page),
+            Vec::new(),
+            account_data(&debt),
+            account_data(&position(owner, 1_000)),
+            account_data(&snapshot),
+        ];
+        let system_program = Pubkey::default();
+        let owners = [crate::ID, crate::ID, system_program, crate::ID, crate::ID, crate::ID];
+        let mut lamports = [0u64; 6];
+
+        let mut accounts: Vec<AccountInfo> = keys
+            .iter()
+            .zip(data.iter_mut())
+            .zip(lamports.iter_mut())
+            .zip(owners.iter())
+            .map(|(((key, data), lamports), owner)| AccountInfo::new(key, false, false, lamports, data, owner, false, 0))
+            .collect();
+        if !with_eth {
+            accounts.remove(2);
+        }
+
+        load_all_troves(&["SOL".to_string(), "ETH".to_string()], &accounts)
+    }
+
+    #[test]
+    fn test_trove_proof_covers_every_registry_at_current_prices() {
+        let mcr = Decimal::from_percent(115);
+        let (troves, consumed) = load(1, Decimal::ZERO, true).unwrap();
+        // Both SOL accounts and the uninitialized ETH registry are the proof
+        assert_eq!(consumed, 3);
+        assert_eq!(troves, vec![(vec![("SOL".to_string(), 1_000)], 1_000)]);
+
+        // Healthy at a price of 2, liquidatable once SOL falls to 1.1 - whatever the stored ratio says
+        let prices = |price| vec![("SOL".to_string(), price, 1)];
+        check_troves_above_ratio(&troves, &prices(20), mcr).unwrap();
+        assert!(check_troves_above_ratio(&troves, &prices(11), mcr).is_err());
+
+        // Redistributed debt not yet applied to the trove still counts: 0.8 per unit of stake
+        // takes it to 1800 of debt against 2000 of collateral value
+        let (troves, _) = load(1, Decimal::from_percent(80), true).unwrap();
+        assert_eq!(troves[0].1, 1_800);
+        assert!(check_troves_above_ratio(&troves, &prices(20), mcr).is_err());
+    }
+
+    #[test]
+    fn test_trove_proof_must_be_complete() {
+        // A registry page hiding one of the troves it counts
+        assert!(load(2, Decimal::ZERO, true).is_err());
+
+        // A supported denom left out of the proof
+        assert!(load(1, Decimal::ZERO, false).is_err());
+    }
+}
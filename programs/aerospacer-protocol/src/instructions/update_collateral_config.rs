@@ -0,0 +1,85 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::AerospacerProtocolError;
+use crate::utils::validate_collateral_config;
+use crate::events::CollateralConfigSet;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct UpdateCollateralConfigParams {
+    pub collateral_denom: String,
+    pub minimum_collateral_ratio: Option<u8>,
+    pub liquidation_threshold: Option<u8>,
+    pub liquidation_penalty_bps: Option<u16>,
+    pub treasury_share_bps: Option<u16>,
+    pub debt_ceiling: Option<u64>,
+    pub deposit_cap: Option<u64>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Accounts)]
+#[instruction(params: UpdateCollateralConfigParams)]
+pub struct UpdateCollateralConfig<'info> {
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [b"state"],
+        bump,
+        constraint = state.admin == admin.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub state: Account<'info, StateAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"collateral_config", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+}
+
+pub fn handler(ctx: Context<UpdateCollateralConfig>, params: UpdateCollateralConfigParams) -> Result<()> {
+    let config = &mut ctx.accounts.collateral_config;
+    
+    if let Some(ratio) = params.minimum_collateral_ratio {
+        config.minimum_collateral_ratio = ratio;
+        msg!("MCR updated: {}%", ratio);
+    }
+    
+    if let Some(threshold) = params.liquidation_threshold {
+        config.liquidation_threshold = threshold;
+        msg!("Liquidation threshold updated: {}%", threshold);
+    }
+    
+    if let Some(penalty) = params.liquidation_penalty_bps {
+        config.liquidation_penalty_bps = penalty;
+        msg!("Liquidation penalty updated: {} bps", penalty);
+    }
+    
+    if let Some(share) = params.treasury_share_bps {
+        config.treasury_share_bps = share;
+        msg!("Treasury share of penalty updated: {} bps", share);
+    }
+    
+    if let Some(ceiling) = params.debt_ceiling {
+        config.debt_ceiling = ceiling;
+        msg!("Debt ceiling updated: {}", ceiling);
+    }
+    
+    if let Some(cap) = params.deposit_cap {
+        config.deposit_cap = cap;
+        msg!("Deposit cap updated: {}", cap);
+    }
+    
+    if let Some(enabled) = params.enabled {
+        config.enabled = enabled;
+        msg!("Enabled: {}", enabled);
+    }
+    
+    // The combination must still be within bounds (e.g. threshold not above MCR)
+    validate_collateral_config(config)?;
+    
+    msg!("Collateral config updated for {}", params.collateral_denom);
+    
+    emit!(CollateralConfigSet::new(config));
+    
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+use crate::state::*;
+use crate::error::*;
+use crate::account_management::DenomPayout;
+use crate::events::LiquidationGainsWithdrawn;
+use crate::stability_pool::{claim_collateral_gain, load_or_create_user_collateral_snapshot, load_stability_pool_snapshot};
+
+/// Accounts per collateral denom at the start of remaining_accounts
+pub const WITHDRAW_GAINS_GROUP_LEN: usize = 4;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct WithdrawAllLiquidationGainsParams {
+    pub collateral_denoms: Vec<String>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawAllLiquidationGains<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"user_stake_amount", user.key().as_ref()],
+        bump,
+        constraint = user_stake_amount.owner == user.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub user_stake_amount: Account<'info, UserStakeAmount>,
+
+    #[account(
+        seeds = [b"state"],
+        bump
+    )]
+    pub state: Account<'info, StateAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+// remaining_accounts:
+// - One [StabilityPoolSnapshot, UserCollateralSnapshot, protocol_collateral_vault, user's token account]
+//   group per denom, in params.collateral_denoms order - the UserCollateralSnapshot is created
+//   (paid by the user) on their first withdrawal of the denom
+// - Located by address, for every denom: its TotalCollateralAmount PDA and the EpochScaleSum PDAs
+//   the user's gain is read from (see stability_pool::gain_epoch_scale_sums)
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, WithdrawAllLiquidationGains<'info>>,
+    params: WithdrawAllLiquidationGainsParams,
+) -> Result<()> {
+    require!(!params.collateral_denoms.is_empty(), AerospacerProtocolError::InvalidList);
+
+    let groups_len = params.collateral_denoms.len() * WITHDRAW_GAINS_GROUP_LEN;
+    require!(
+        ctx.remaining_accounts.len() >= groups_len,
+        AerospacerProtocolError::MissingCollateralAccount
+    );
+
+    let user = ctx.accounts.user.key();
+    let user_info = ctx.accounts.user.to_account_info();
+    let system_program = ctx.accounts.system_program.to_account_info();
+    let token_program = ctx.accounts.token_program.to_account_info();
+    let stake = &ctx.accounts.user_stake_amount;
+    let state = &ctx.accounts.state;
+
+    let mut withdrawn = Vec::new();
+    for (index, denom) in params.collateral_denoms.iter().enumerate() {
+        require!(
+            !withdrawn.iter().any(|(d, _): &(String, u64)| d == denom),
+            AerospacerProtocolError::InvalidList
+        );
+        let group = &ctx.remaining_accounts[index * WITHDRAW_GAINS_GROUP_LEN..(index + 1) * WITHDRAW_GAINS_GROUP_LEN];
+
+        // The denom's pool snapshot proves it is a stability pool denom
+        let (snapshot_pda, _bump) = Pubkey::find_program_address(&StabilityPoolSnapshot::seeds(denom), &crate::ID);
+        require_keys_eq!(group[0].key(), snapshot_pda, AerospacerProtocolError::InvalidSnapshot);
+        load_stability_pool_snapshot(&group[0], denom)?;
+
+        // The user's S snapshot for the denom, started from their deposit on first withdrawal
+        let user_snapshot_info = &group[1];
+        let mut user_snapshot = load_or_create_user_collateral_snapshot(
+            user_snapshot_info,
+            &user,
+            denom,
+            stake,
+            &user_info,
+            &system_program,
+        )?;
+
+        let gain = claim_collateral_gain(stake, &mut user_snapshot, state, ctx.remaining_accounts)?;
+        user_snapshot.try_serialize(&mut &mut user_snapshot_info.try_borrow_mut_data()?[..])?;
+
+        // Pay from the denom's vault into the group's token account
+        let mut payout = DenomPayout::from_remaining_accounts(denom, &user, ctx.remaining_accounts)?;
+        require_keys_eq!(payout.vault.key(), group[2].key(), AerospacerProtocolError::Unauthorized);
+        require_keys_eq!(payout.recipient.key(), group[3].key(), AerospacerProtocolError::Unauthorized);
+        payout.pay(&token_program, gain)?;
+
+        msg!("Liquidation gains withdrawn: {} {}", gain, denom);
+        if gain > 0 {
+            emit!(LiquidationGainsWithdrawn {
+                user,
+                collateral_denom: denom.clone(),
+                amount: gain,
+            });
+        }
+        withdrawn.push((denom.clone(), gain));
+    }
+
+    msg!("All liquidation gains withdrawn successfully (snapshot-based)");
+    msg!("User: {}", user);
+    msg!("Denoms: {}", withdrawn.len());
+
+    Ok(())
+}
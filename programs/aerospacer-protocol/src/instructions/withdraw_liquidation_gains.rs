@@ -3,6 +3,8 @@ use anchor_spl::token::{Token, TokenAccount, Transfer};
 use crate::state::*;
 use crate::utils::*;
 use crate::error::*;
+use crate::events::LiquidationGainsWithdrawn;
+use crate::stability_pool::{claim_collateral_gain, start_user_collateral_snapshot};
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct WithdrawLiquidationGainsParams {
@@ -66,38 +68,37 @@ pub struct WithdrawLiquidationGains<'info> {
 }
 
 
-
+// remaining_accounts: the denom's EpochScaleSum PDAs for the epoch and scale the user's S snapshot
+// is in and the scale after it (see stability_pool::gain_epoch_scale_sums), located by address
 pub fn handler(ctx: Context<WithdrawLiquidationGains>, params: WithdrawLiquidationGainsParams) -> Result<()> {
     let user_stake_amount = &mut ctx.accounts.user_stake_amount;
     let user_collateral_snapshot = &mut ctx.accounts.user_collateral_snapshot;
-    let stability_pool_snapshot = &ctx.accounts.stability_pool_snapshot;
-    let _state = &mut ctx.accounts.state;
-    
-    // Validate user has stake
-    require!(
-        user_stake_amount.amount > 0,
-        AerospacerProtocolError::InvalidAmount
-    );
+    let state = &ctx.accounts.state;
     
     // SNAPSHOT ALGORITHM: Calculate collateral gain using Product-Sum formula
-    // gain = initial_deposit × (S_current - S_snapshot) / P_snapshot
+    // gain = initial_deposit × (S[epoch][scale] - S_snapshot) / P_snapshot, plus the next scale's share
     
     // Initialize S snapshot metadata if first time (but still calculate and transfer gains!)
-    let is_first_withdrawal = user_collateral_snapshot.s_snapshot == 0;
+    let is_first_withdrawal = user_collateral_snapshot.owner == Pubkey::default();
     if is_first_withdrawal {
-        user_collateral_snapshot.owner = ctx.accounts.user.key();
-        user_collateral_snapshot.denom = params.collateral_denom.clone();
-        user_collateral_snapshot.pending_collateral_gain = 0;
+        start_user_collateral_snapshot(
+            user_collateral_snapshot,
+            ctx.accounts.user.key(),
+            &params.collateral_denom,
+            user_stake_amount,
+        );
         msg!("First withdrawal for {} - calculating full accumulated gains", params.collateral_denom);
     }
+    let s_snapshot = user_collateral_snapshot.s_snapshot;
     
-    // Calculate collateral gain using helper function
+    // Calculate collateral gain and move the S snapshot up to where it was measured, adding the
+    // gains set aside by stake/unstake (still claimable once the deposit is fully withdrawn)
     // If s_snapshot = 0 (first withdrawal), this calculates the full accumulated gain
-    let collateral_gain = calculate_collateral_gain(
-        user_stake_amount.amount,
-        user_collateral_snapshot.s_snapshot, // 0 on first withdrawal = full gain
-        stability_pool_snapshot.s_factor,
-        user_stake_amount.p_snapshot,
+    let collateral_gain = claim_collateral_gain(
+        user_stake_amount,
+        user_collateral_snapshot,
+        state,
+        ctx.remaining_accounts,
     )?;
     
     // Check if user has any gains
@@ -109,13 +110,19 @@ pub fn handler(ctx: Context<WithdrawLiquidationGains>, params: WithdrawLiquidati
     msg!("SNAPSHOT-BASED WITHDRAWAL:");
     msg!("  User deposit: {}", user_stake_amount.amount);
     msg!("  P_snapshot: {}", user_stake_amount.p_snapshot);
-    msg!("  S_snapshot ({}): {}", params.collateral_denom, user_collateral_snapshot.s_snapshot);
-    msg!("  S_current ({}): {}", params.collateral_denom, stability_pool_snapshot.s_factor);
+    msg!("  S_snapshot ({}): {}", params.collateral_denom, s_snapshot);
+    msg!(
+        "  S_current ({}): {} (epoch {}, scale {})",
+        params.collateral_denom,
+        user_collateral_snapshot.s_snapshot,
+        user_collateral_snapshot.epoch_snapshot,
+        user_collateral_snapshot.scale_snapshot
+    );
     msg!("  Calculated gain: {}", collateral_gain);
     
     // Transfer collateral gain from stability pool vault to user
     let transfer_seeds = &[
-        b"protocol_collateral_vault".as_ref(),
+        PROTOCOL_COLLATERAL_VAULT_SEED,
         params.collateral_denom.as_bytes(),
         &[ctx.bumps.protocol_collateral_vault],
     ];
@@ -132,9 +139,6 @@ pub fn handler(ctx: Context<WithdrawLiquidationGains>, params: WithdrawLiquidati
     );
     anchor_spl::token::transfer(transfer_ctx, collateral_gain)?;
 
-    // Update user's S snapshot to current value (marks gains as claimed)
-    user_collateral_snapshot.s_snapshot = stability_pool_snapshot.s_factor;
-
     // Update per-denom collateral total PDA
     update_total_collateral_from_account_info(
         &ctx.accounts.total_collateral_amount,
@@ -144,7 +148,13 @@ pub fn handler(ctx: Context<WithdrawLiquidationGains>, params: WithdrawLiquidati
     msg!("Liquidation gains withdrawn successfully (snapshot-based)");
     msg!("Amount: {} {}", collateral_gain, params.collateral_denom);
     msg!("User: {}", ctx.accounts.user.key());
-    msg!("S snapshot updated to: {}", stability_pool_snapshot.s_factor);
+    msg!("S snapshot updated to: {}", ctx.accounts.user_collateral_snapshot.s_snapshot);
+
+    emit!(LiquidationGainsWithdrawn {
+        user: ctx.accounts.user.key(),
+        collateral_denom: params.collateral_denom,
+        amount: collateral_gain,
+    });
 
     Ok(())
 }
\ No newline at end of file
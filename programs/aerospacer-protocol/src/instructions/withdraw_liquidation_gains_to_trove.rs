@@ -0,0 +1,216 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::*;
+use crate::trove_management::*;
+use crate::oracle::*;
+use crate::redistribution::apply_trove_rewards;
+use crate::events::{TroveAdjusted, TroveOperation};
+use crate::stability_pool::{claim_collateral_gain, start_user_collateral_snapshot};
+
+/// EpochScaleSum PDAs a gain is read from (see stability_pool::gain_epoch_scale_sums)
+pub const GAIN_SUM_ACCOUNTS_LEN: usize = 2;
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct WithdrawLiquidationGainsToTroveParams {
+    pub collateral_denom: String,
+    pub prev_node_id: Option<Pubkey>,
+    pub next_node_id: Option<Pubkey>,
+}
+
+#[derive(Accounts)]
+#[instruction(params: WithdrawLiquidationGainsToTroveParams)]
+pub struct WithdrawLiquidationGainsToTrove<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"user_stake_amount", user.key().as_ref()],
+        bump,
+        constraint = user_stake_amount.owner == user.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub user_stake_amount: Account<'info, UserStakeAmount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserCollateralSnapshot::LEN,
+        seeds = [b"user_collateral_snapshot", user.key().as_ref(), params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub user_collateral_snapshot: Box<Account<'info, UserCollateralSnapshot>>,
+
+    #[account(
+        seeds = [b"state"],
+        bump
+    )]
+    pub state: Box<Account<'info, StateAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"user_debt_amount", user.key().as_ref()],
+        bump,
+        constraint = user_debt_amount.owner == user.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub user_debt_amount: Box<Account<'info, UserDebtAmount>>,
+
+    // Created when the trove does not hold collateral_denom yet
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserCollateralAmount::LEN,
+        seeds = [b"user_collateral_amount", user.key().as_ref(), params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub user_collateral_amount: Box<Account<'info, UserCollateralAmount>>,
+
+    #[account(
+        mut,
+        seeds = [b"liquidity_threshold", user.key().as_ref()],
+        bump,
+        constraint = liquidity_threshold.owner == user.key() @ AerospacerProtocolError::Unauthorized
+    )]
+    pub liquidity_threshold: Box<Account<'info, LiquidityThreshold>>,
+
+    #[account(
+        mut,
+        seeds = [b"redistribution_snapshot", params.collateral_denom.as_bytes()],
+        bump
+    )]
+    pub redistribution_snapshot: Box<Account<'info, RedistributionSnapshot>>,
+
+    // Oracle context - UncheckedAccount to reduce stack usage
+    /// CHECK: Our oracle program - validated against state in handler
+    pub oracle_program: UncheckedAccount<'info>,
+
+    /// CHECK: Oracle state account - validated against state in handler
+    #[account(mut)]
+    pub oracle_state: UncheckedAccount<'info>,
+
+    /// CHECK: Pyth price account for collateral price feed
+    pub pyth_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Clock sysvar - validated in handler if needed
+    pub clock: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// remaining_accounts:
+// - One [UserCollateralAmount, RedistributionSnapshot, pyth_price_account, CollateralConfig] group
+//   per other denom the trove holds, in UserDebtAmount.collateral_denoms order (see trove_management::load_other_collateral)
+// - The denom's two EpochScaleSum PDAs the user's gain is read from (see stability_pool::gain_epoch_scale_sums)
+// - Followed by the optional neighbor hints [prev_LiquidityThreshold, next_LiquidityThreshold]
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, WithdrawLiquidationGainsToTrove<'info>>,
+    params: WithdrawLiquidationGainsToTroveParams,
+) -> Result<()> {
+    // Validate oracle accounts
+    require!(
+        ctx.accounts.oracle_program.key() == ctx.accounts.state.oracle_helper_addr,
+        AerospacerProtocolError::Unauthorized
+    );
+    require!(
+        ctx.accounts.oracle_state.key() == ctx.accounts.state.oracle_state_addr,
+        AerospacerProtocolError::Unauthorized
+    );
+
+    let user = ctx.accounts.user.key();
+    let denom = params.collateral_denom.clone();
+
+    // First deposit of this denom into the trove - initialize the position and record it on the trove
+    if ctx.accounts.user_collateral_amount.owner == Pubkey::default() {
+        ctx.accounts.user_collateral_amount.owner = user;
+        ctx.accounts.user_collateral_amount.denom = denom.clone();
+        ctx.accounts.user_collateral_amount.amount = 0;
+    }
+    ctx.accounts.user_debt_amount.add_collateral_denom(&denom)?;
+
+    // Bring the trove up to date with redistributed liquidations before pricing it
+    apply_trove_rewards(
+        &mut ctx.accounts.user_debt_amount,
+        &mut ctx.accounts.user_collateral_amount,
+        &mut ctx.accounts.redistribution_snapshot,
+        ctx.remaining_accounts,
+        OTHER_COLLATERAL_GROUP_LEN,
+    )?;
+    let previous_stake = ctx.accounts.user_collateral_amount.amount;
+
+    let oracle_ctx = OracleContext {
+        oracle_program: ctx.accounts.oracle_program.to_account_info(),
+        oracle_state: ctx.accounts.oracle_state.to_account_info(),
+        pyth_price_account: ctx.accounts.pyth_price_account.to_account_info(),
+        clock: ctx.accounts.clock.to_account_info(),
+    };
+    let (other_collateral, consumed) = load_other_collateral(
+        &ctx.accounts.user_debt_amount,
+        &denom,
+        ctx.remaining_accounts,
+        &oracle_ctx,
+    )?;
+    require!(
+        ctx.remaining_accounts.len() >= consumed + GAIN_SUM_ACCOUNTS_LEN,
+        AerospacerProtocolError::InvalidSnapshot
+    );
+    let sum_accounts = &ctx.remaining_accounts[consumed..consumed + GAIN_SUM_ACCOUNTS_LEN];
+
+    // Settle the gain the same way withdraw_liquidation_gains does
+    let user_collateral_snapshot = &mut ctx.accounts.user_collateral_snapshot;
+    if user_collateral_snapshot.owner == Pubkey::default() {
+        start_user_collateral_snapshot(user_collateral_snapshot, user, &denom, &ctx.accounts.user_stake_amount);
+    }
+    let collateral_gain = claim_collateral_gain(
+        &ctx.accounts.user_stake_amount,
+        user_collateral_snapshot,
+        &ctx.accounts.state,
+        sum_accounts,
+    )?;
+    require!(collateral_gain > 0, AerospacerProtocolError::InvalidAmount);
+
+    // Adding collateral only raises the ICR, so no minimum ratio check is needed
+    let price_data = oracle_ctx.get_price(&denom)?;
+    oracle_ctx.validate_price(&price_data)?;
+    let debt_amount = ctx.accounts.user_debt_amount.amount;
+    let new_collateral_amount = previous_stake
+        .checked_add(collateral_gain)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+    let new_icr = other_collateral.trove_icr(&denom, new_collateral_amount, &price_data, debt_amount)?;
+
+    let new_nicr = crate::sorted_troves::compute_nominal_icr(new_collateral_amount, debt_amount)?;
+    crate::sorted_troves::validate_neighbor_hints(
+        &denom,
+        new_nicr,
+        &ctx.remaining_accounts[consumed + GAIN_SUM_ACCOUNTS_LEN..],
+        ctx.program_id,
+    )?;
+
+    // The gain already sits in protocol_collateral_vault and is still counted in TotalCollateralAmount
+    // (liquidations only take out the liquidator's and treasury's shares), so only the trove changes
+    ctx.accounts.user_collateral_amount.amount = new_collateral_amount;
+    ctx.accounts.liquidity_threshold.ratio = new_icr;
+    ctx.accounts.liquidity_threshold.set_nominal_ratios(
+        &other_collateral.collateral_amounts(&denom, new_collateral_amount),
+        debt_amount,
+    )?;
+    ctx.accounts.redistribution_snapshot.update_stake(previous_stake, new_collateral_amount)?;
+
+    msg!("Liquidation gains moved to trove successfully");
+    msg!("Amount: {} {}", collateral_gain, denom);
+    msg!("New collateral amount: {}", new_collateral_amount);
+    msg!("New ICR: {}", new_icr);
+
+    emit!(TroveAdjusted {
+        owner: user,
+        operation: TroveOperation::StabilityGainToTrove,
+        collateral_denom: denom,
+        collateral_change: collateral_gain,
+        is_collateral_increase: true,
+        debt_change: 0,
+        is_debt_increase: false,
+        borrowing_fee: 0,
+        collateral_amount: new_collateral_amount,
+        debt_amount,
+        icr: new_icr,
+    });
+
+    Ok(())
+}
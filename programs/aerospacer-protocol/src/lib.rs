@@ -1,4 +1,6 @@
 #![allow(unexpected_cfgs)]
+// Anchor 0.31's #[program] expansion still calls the deprecated AccountInfo::realloc
+#![allow(deprecated)]
 
 use anchor_lang::prelude::*;
 
@@ -7,13 +9,19 @@ pub mod error;
 pub mod state;
 pub mod msg;
 pub mod query;
+pub mod decimal;
+pub mod events;
 
 // New architecture modules
 pub mod account_management;
 pub mod oracle;
 pub mod trove_management;
+pub mod redistribution;
+pub mod stability_pool;
+pub mod share_pool;
 pub mod fees_integration;
 pub mod sorted_troves;
+pub mod trove_registry;
 
 // Core instruction handlers
 pub mod instructions;
@@ -37,48 +45,93 @@ pub mod aerospacer_protocol {
         instructions::update_protocol_addresses::handler(ctx, params)
     }
 
+    // Queue a minimum collateral ratio change behind the parameter timelock (admin only)
+    pub fn set_minimum_collateral_ratio(ctx: Context<SetMinimumCollateralRatio>, params: SetMinimumCollateralRatioParams) -> Result<()> {
+        instructions::set_minimum_collateral_ratio::handler(ctx, params)
+    }
+
+    // Queue a change to the borrowing fee cap behind the parameter timelock (admin only)
+    pub fn set_protocol_fee(ctx: Context<SetProtocolFee>, params: SetProtocolFeeParams) -> Result<()> {
+        instructions::set_protocol_fee::handler(ctx, params)
+    }
+
+    // Queue a change to the parameter timelock delay itself (admin only)
+    pub fn set_parameter_timelock_delay(ctx: Context<SetParameterTimelockDelay>, params: SetParameterTimelockDelayParams) -> Result<()> {
+        instructions::set_parameter_timelock_delay::handler(ctx, params)
+    }
+
+    // Queue a change to the stability pool unstake cooldown behind the parameter timelock (admin only)
+    pub fn set_unstake_cooldown(ctx: Context<SetUnstakeCooldown>, params: SetUnstakeCooldownParams) -> Result<()> {
+        instructions::set_unstake_cooldown::handler(ctx, params)
+    }
+
+    // Apply a queued parameter change once its timelock has elapsed (admin only)
+    pub fn execute_parameter_change(ctx: Context<ExecuteParameterChange>, params: ExecuteParameterChangeParams) -> Result<()> {
+        instructions::execute_parameter_change::handler(ctx, params)
+    }
+
+    // Discard a queued parameter change (admin only)
+    pub fn cancel_parameter_change(ctx: Context<CancelParameterChange>, params: CancelParameterChangeParams) -> Result<()> {
+        instructions::cancel_parameter_change::handler(ctx, params)
+    }
+
+    // Create the risk configuration of a collateral denom (admin only)
+    pub fn create_collateral_config(ctx: Context<CreateCollateralConfig>, params: CreateCollateralConfigParams) -> Result<()> {
+        instructions::create_collateral_config::handler(ctx, params)
+    }
+
+    // Update the risk configuration of a collateral denom (admin only)
+    pub fn update_collateral_config(ctx: Context<UpdateCollateralConfig>, params: UpdateCollateralConfigParams) -> Result<()> {
+        instructions::update_collateral_config::handler(ctx, params)
+    }
+
     // Transfer stablecoins between accounts
     pub fn transfer_stablecoin(ctx: Context<TransferStablecoin>, params: TransferStablecoinParams) -> Result<()> {
         instructions::transfer_stablecoin::handler(ctx, params)
     }
 
     // Open a trove by depositing collateral (equivalent to INJECTIVE's open_trove)
-    pub fn open_trove(ctx: Context<OpenTrove>, params: OpenTroveParams) -> Result<()> {
+    pub fn open_trove<'info>(ctx: Context<'_, '_, '_, 'info, OpenTrove<'info>>, params: OpenTroveParams) -> Result<()> {
         instructions::open_trove::handler(ctx, params)
     }
 
     // Add collateral to an existing trove (equivalent to INJECTIVE's add_collateral)
-    pub fn add_collateral(ctx: Context<AddCollateral>, params: AddCollateralParams) -> Result<()> {
+    pub fn add_collateral<'info>(ctx: Context<'_, '_, '_, 'info, AddCollateral<'info>>, params: AddCollateralParams) -> Result<()> {
         instructions::add_collateral::handler(ctx, params)
     }
 
     // Remove collateral from an existing trove (equivalent to INJECTIVE's remove_collateral)
-    pub fn remove_collateral(ctx: Context<RemoveCollateral>, params: RemoveCollateralParams) -> Result<()> {
+    pub fn remove_collateral<'info>(ctx: Context<'_, '_, '_, 'info, RemoveCollateral<'info>>, params: RemoveCollateralParams) -> Result<()> {
         instructions::remove_collateral::handler(ctx, params)
     }
 
     // Borrow stablecoin from an existing trove (equivalent to INJECTIVE's borrow_loan)
-    pub fn borrow_loan(ctx: Context<BorrowLoan>, params: BorrowLoanParams) -> Result<()> {
+    pub fn borrow_loan<'info>(ctx: Context<'_, '_, '_, 'info, BorrowLoan<'info>>, params: BorrowLoanParams) -> Result<()> {
         instructions::borrow_loan::handler(ctx, params)
     }
 
     // Repay stablecoin to an existing trove (equivalent to INJECTIVE's repay_loan)
-    pub fn repay_loan(ctx: Context<RepayLoan>, params: RepayLoanParams) -> Result<()> {
+    pub fn repay_loan<'info>(ctx: Context<'_, '_, '_, 'info, RepayLoan<'info>>, params: RepayLoanParams) -> Result<()> {
         instructions::repay_loan::handler(ctx, params)
     }
 
+    // Change a trove's collateral and debt together, validating only the final ICR (Liquity's adjustTrove)
+    pub fn adjust_trove<'info>(ctx: Context<'_, '_, '_, 'info, AdjustTrove<'info>>, params: AdjustTroveParams) -> Result<()> {
+        instructions::adjust_trove::handler(ctx, params)
+    }
+
     // Close trove by repaying all debt and withdrawing all collateral (equivalent to INJECTIVE's close_trove)
-    pub fn close_trove(ctx: Context<CloseTrove>, params: CloseTroveParams) -> Result<()> {
+    pub fn close_trove<'info>(ctx: Context<'_, '_, '_, 'info, CloseTrove<'info>>, params: CloseTroveParams) -> Result<()> {
         instructions::close_trove::handler(ctx, params)
     }
 
     // Liquidate undercollateralized troves (equivalent to INJECTIVE's liquidate_troves)
-    pub fn liquidate_troves(ctx: Context<LiquidateTroves>, params: LiquidateTrovesParams) -> Result<()> {
+    pub fn liquidate_troves<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateTroves<'info>>, params: LiquidateTrovesParams) -> Result<()> {
         instructions::liquidate_troves::handler(ctx, params)
     }
 
-    // Liquidate a single undercollateralized trove (no remaining_accounts)
-    pub fn liquidate_trove(ctx: Context<LiquidateTrove>, params: LiquidateTroveParams) -> Result<()> {
+    // Liquidate a single undercollateralized trove across all of its collateral denoms
+    pub fn liquidate_trove<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateTrove<'info>>, params: LiquidateTroveParams) -> Result<()> {
         instructions::liquidate_trove::handler(ctx, params)
     }
 
@@ -87,13 +140,28 @@ pub mod aerospacer_protocol {
         instructions::query_liquidatable_troves::handler(ctx, params)
     }
 
+    // Query the Total Collateral Ratio and whether the protocol is in Recovery Mode
+    pub fn query_system_mode<'info>(ctx: Context<'_, '_, '_, 'info, QuerySystemMode<'info>>) -> Result<()> {
+        instructions::query_system_mode::handler(ctx)
+    }
+
+    // Reprice a batch of troves and rewrite their stored ICRs (permissionless crank)
+    pub fn refresh_icrs<'info>(ctx: Context<'_, '_, '_, 'info, RefreshIcrs<'info>>, params: RefreshIcrsParams) -> Result<()> {
+        instructions::refresh_icrs::handler(ctx, params)
+    }
+
+    // Query one page of the active troves opened with a denom
+    pub fn query_trove_registry(ctx: Context<QueryTroveRegistry>, params: QueryTroveRegistryParams) -> Result<()> {
+        instructions::query_trove_registry::handler(ctx, params)
+    }
+
     // Stake stablecoin to earn liquidation gains (equivalent to INJECTIVE's stake)
-    pub fn stake(ctx: Context<Stake>, params: StakeParams) -> Result<()> {
+    pub fn stake<'info>(ctx: Context<'_, '_, '_, 'info, Stake<'info>>, params: StakeParams) -> Result<()> {
         instructions::stake::handler(ctx, params)
     }
 
     // Unstake stablecoin (equivalent to INJECTIVE's unstake)
-    pub fn unstake(ctx: Context<Unstake>, params: UnstakeParams) -> Result<()> {
+    pub fn unstake<'info>(ctx: Context<'_, '_, '_, 'info, Unstake<'info>>, params: UnstakeParams) -> Result<()> {
         instructions::unstake::handler(ctx, params)
     }
 
@@ -102,8 +170,39 @@ pub mod aerospacer_protocol {
         instructions::withdraw_liquidation_gains::handler(ctx, params)
     }
 
+    // Withdraw liquidation gains of every collateral denom at once
+    pub fn withdraw_all_liquidation_gains<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawAllLiquidationGains<'info>>, params: WithdrawAllLiquidationGainsParams) -> Result<()> {
+        instructions::withdraw_all_liquidation_gains::handler(ctx, params)
+    }
+
+    // Move liquidation gains into the staker's own trove instead of their wallet
+    pub fn withdraw_liquidation_gains_to_trove<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawLiquidationGainsToTrove<'info>>, params: WithdrawLiquidationGainsToTroveParams) -> Result<()> {
+        instructions::withdraw_liquidation_gains_to_trove::handler(ctx, params)
+    }
+
+    // Create the spAUSD share pool and its mint (admin only)
+    pub fn initialize_share_pool(ctx: Context<InitializeSharePool>) -> Result<()> {
+        instructions::initialize_share_pool::handler(ctx)
+    }
+
+    // Deposit stablecoin into the stability pool through the share pool for spAUSD shares,
+    // which stay in escrow until the unstake cooldown has passed
+    pub fn deposit_to_share_pool<'info>(ctx: Context<'_, '_, '_, 'info, DepositToSharePool<'info>>, params: DepositToSharePoolParams) -> Result<()> {
+        instructions::deposit_to_share_pool::handler(ctx, params)
+    }
+
+    // Hand a depositor's spAUSD shares over from escrow once their cooldown has passed
+    pub fn release_shares(ctx: Context<ReleaseShares>) -> Result<()> {
+        instructions::release_shares::handler(ctx)
+    }
+
+    // Burn spAUSD shares for their part of the share pool's deposit and collateral gains
+    pub fn redeem_from_share_pool<'info>(ctx: Context<'_, '_, '_, 'info, RedeemFromSharePool<'info>>, params: RedeemFromSharePoolParams) -> Result<()> {
+        instructions::redeem_from_share_pool::handler(ctx, params)
+    }
+
     // Swap stablecoin for collateral (equivalent to INJECTIVE's redeem)
-    pub fn redeem(ctx: Context<Redeem>, params: RedeemParams) -> Result<()> {
+    pub fn redeem<'info>(ctx: Context<'_, '_, '_, 'info, Redeem<'info>>, params: RedeemParams) -> Result<()> {
         instructions::redeem::handler(ctx, params)
     }
 
@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use crate::decimal::Decimal;
 
 // Exact replication of INJECTIVE msg.rs
 #[derive(AnchorSerialize, AnchorDeserialize)]
@@ -70,9 +71,13 @@ pub enum QueryMsg {
     Stake { user_addr: Pubkey }, // String in Injective, Pubkey in Solana
     // Get liquidation gains
     LiquidationGains { user_addr: Pubkey }, // String in Injective, Pubkey in Solana
+    // Get the Total Collateral Ratio and whether the protocol is in Recovery Mode
+    SystemMode {},
+    // Get one page of the active troves opened with a denom
+    TroveRegistry { denom: String, page_index: u32 },
     // Linked list messages
     FindSortedTroveInsertPosition {
-        icr: u64, // Equivalent to Decimal256
+        icr: Decimal, // Equivalent to Decimal256
         prev_node_id: Option<Pubkey>, // String in Injective, Pubkey in Solana
         next_node_id: Option<Pubkey>, // String in Injective, Pubkey in Solana
     },
@@ -103,7 +108,24 @@ pub struct TroveResponse {
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct StakeResponse {
     pub amount: u64, // Equivalent to Uint256
-    pub percentage: u64, // Equivalent to Decimal256
+    pub percentage: Decimal, // Equivalent to Decimal256
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SystemModeResponse {
+    pub total_collateral_ratio: Decimal,
+    pub critical_collateral_ratio: Decimal,
+    pub total_debt_amount: u64,
+    pub recovery_mode: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct TroveRegistryPageResponse {
+    pub denom: String,
+    pub page_index: u32,
+    pub owners: Vec<Pubkey>,
+    pub next_page_index: Option<u32>, // None on the last page of the chain
+    pub trove_count: u64, // Troves listed across all pages
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize)]
@@ -1,16 +1,19 @@
+//! Oracle integration for price feeds
+//! This module provides clean integration with our aerospacer-oracle contract
+
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::{hash::hash, instruction::{Instruction, AccountMeta}};
 use crate::error::*;
-
-/// Oracle integration for price feeds
-/// This module provides clean integration with our aerospacer-oracle contract
+use crate::state::AUSD_DECIMALS;
+use crate::decimal::{Decimal, Rounding};
+use aerospacer_oracle::state::OracleStateAccount;
 
 /// Price data structure (matches aerospacer-oracle PriceResponse)
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct PriceData {
     pub denom: String,
     pub price: i64, // Oracle returns i64
-    pub decimal: u8,
+    pub decimal: u8, // Implied decimals of `price` per base unit of collateral, in aUSD base units
     pub confidence: u64,
     pub timestamp: i64,
     pub exponent: i32,
@@ -35,20 +38,33 @@ pub struct OracleContext<'info> {
 impl<'info> OracleContext<'info> {
     /// Get price for a specific collateral denom via CPI to our oracle
     pub fn get_price(&self, denom: &str) -> Result<PriceData> {
+        self.get_price_from_feed(denom, &self.pyth_price_account)
+    }
+    
+    /// Get price for a denom whose Pyth account was supplied separately
+    /// (e.g. the other collateral denoms of a multi-collateral trove)
+    pub fn get_price_from_feed(&self, denom: &str, pyth_price_account: &AccountInfo<'info>) -> Result<PriceData> {
         // Build the CPI instruction to call oracle's get_price
         let price_response = get_price_via_cpi(
             denom.to_string(),
             self.oracle_program.to_account_info(),
             self.oracle_state.to_account_info(),
-            self.pyth_price_account.to_account_info(),
+            pyth_price_account.to_account_info(),
             self.clock.to_account_info(),
         )?;
         
+        // Rescale the USD price of one whole token into aUSD base units per collateral base unit
+        let (price, decimal) = PriceCalculator::normalize_price(
+            price_response.price,
+            price_response.decimal,
+            self.collateral_decimals(denom)?,
+        )?;
+        
         // Convert PriceResponse to PriceData
         Ok(PriceData {
             denom: price_response.denom,
-            price: price_response.price,
-            decimal: price_response.decimal,
+            price,
+            decimal,
             confidence: price_response.confidence,
             timestamp: price_response.timestamp,
             exponent: price_response.exponent,
@@ -72,6 +88,35 @@ impl<'info> OracleContext<'info> {
         Ok(prices)
     }
     
+    /// Look up the Pyth price account the oracle has configured for a denom
+    pub fn price_feed_address(&self, denom: &str) -> Result<Pubkey> {
+        let data = self.oracle_state.try_borrow_data()?;
+        let oracle_state = OracleStateAccount::try_deserialize(&mut &data[..])?;
+        
+        oracle_state.collateral_data
+            .iter()
+            .find(|d| d.denom == denom)
+            .map(|d| d.pyth_price_account)
+            .ok_or(AerospacerProtocolError::MissingCollateralAccount.into())
+    }
+
+    /// Token decimals the oracle has configured for a denom
+    pub fn collateral_decimals(&self, denom: &str) -> Result<u8> {
+        let data = self.oracle_state.try_borrow_data()?;
+        let oracle_state = OracleStateAccount::try_deserialize(&mut &data[..])?;
+        
+        oracle_state.collateral_data
+            .iter()
+            .find(|d| d.denom == denom)
+            .map(|d| d.decimal)
+            .ok_or(AerospacerProtocolError::MissingCollateralAccount.into())
+    }
+
+    /// List every collateral denom the oracle has configured, in its stored order
+    pub fn supported_denoms(&self) -> Result<Vec<String>> {
+        oracle_supported_denoms(&self.oracle_state)
+    }
+
     /// Validate price data
     pub fn validate_price(&self, price_data: &PriceData) -> Result<()> {
         // Check if price is within reasonable bounds
@@ -93,76 +138,79 @@ impl<'info> OracleContext<'info> {
     }
 }
 
+/// List every collateral denom configured in `oracle_state`, in its stored order
+pub fn oracle_supported_denoms(oracle_state: &AccountInfo) -> Result<Vec<String>> {
+    let data = oracle_state.try_borrow_data()?;
+    let oracle_state = OracleStateAccount::try_deserialize(&mut &data[..])?;
+
+    Ok(oracle_state.collateral_data.iter().map(|d| d.denom.clone()).collect())
+}
+
 /// Price calculation utilities
 /// 
 /// ICR Convention:
-/// All ICR values are represented as simple percentages (not scaled).
-/// Example: 150% ICR = 150, 200% ICR = 200
-/// This avoids u64 overflow issues while maintaining sufficient precision
+/// All ICR values are fixed-point Decimals (see crate::decimal).
+/// Example: 150% ICR = 1.5, 200% ICR = 2.0
+/// Admin-set ratios stay whole percentages and are converted with Decimal::from_percent
 pub struct PriceCalculator;
 
 impl PriceCalculator {
-    /// Calculate collateral value in USD
+    /// Fold the collateral's token decimals and aUSD's decimals into an oracle price
+    ///
+    /// The oracle quotes one whole token in USD with `price_decimal` implied decimals. The
+    /// result converts base units of collateral straight into aUSD base units, so collateral
+    /// values and debts are directly comparable.
+    pub fn normalize_price(price: i64, price_decimal: u8, collateral_decimals: u8) -> Result<(i64, u8)> {
+        let shift = price_decimal as i32 + collateral_decimals as i32 - AUSD_DECIMALS as i32;
+        if shift >= 0 {
+            let decimal = u8::try_from(shift).map_err(|_| AerospacerProtocolError::OverflowError)?;
+            return Ok((price, decimal));
+        }
+        
+        // Fewer decimals than aUSD - scale the price up instead
+        let price = 10i64
+            .checked_pow(shift.unsigned_abs())
+            .and_then(|factor| price.checked_mul(factor))
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+        Ok((price, 0))
+    }
+    
+    /// Oracle price as a Decimal - `price` carries `decimal` implied decimals
+    pub fn price_to_decimal(price: u64, decimal: u8) -> Result<Decimal> {
+        Decimal::from_scaled(price as u128, decimal)
+    }
+    
+    /// Calculate collateral value in aUSD base units from a normalized price
     pub fn calculate_collateral_value(
         amount: u64,
         price: u64,
         decimal: u8,
     ) -> Result<u64> {
-        let decimal_factor = 10_u128.pow(decimal as u32);
-        let value = (amount as u128)
-            .checked_mul(price as u128)
-            .ok_or(AerospacerProtocolError::OverflowError)?
-            .checked_div(decimal_factor)
-            .ok_or(AerospacerProtocolError::OverflowError)?;
-        
-        // Convert back to u64, ensuring it fits
-        if value > u64::MAX as u128 {
-            return Err(AerospacerProtocolError::OverflowError.into());
-        }
-        
-        Ok(value as u64)
+        // Rounded down so collateral is never overvalued
+        Self::price_to_decimal(price, decimal)?.mul_amount(amount, Rounding::Down)
     }
     
-    /// Calculate collateral ratio as a percentage (100 = 100%)
-    /// Returns ICR as an unscaled percentage for comparison
-    /// Example: 150% ICR = 150
+    /// Calculate collateral ratio as a Decimal (1.0 = 100%)
+    /// Example: 150% ICR = 1.5
     /// 
-    /// Note: Both collateral_value and debt_amount should be in the same units
-    /// For proper ICR calculation, we need to normalize the units
+    /// Both collateral_value and debt_amount are in aUSD base units
     pub fn calculate_collateral_ratio(
         collateral_value: u64,
         debt_amount: u64,
-    ) -> Result<u64> {
+    ) -> Result<Decimal> {
         if debt_amount == 0 {
-            return Ok(u64::MAX);
+            return Ok(Decimal::MAX);
         }
         
-        // Normalize both values to the same units for comparison
-        // Collateral value is typically in micro-USD (6 decimals)
-        // Debt amount is typically in micro-aUSD (18 decimals)
-        // We need to scale them to the same precision
-        
-        // Scale collateral value to match debt amount precision (18 decimals)
-        let scaled_collateral_value = (collateral_value as u128)
-            .checked_mul(1_000_000_000_000) // Scale up by 10^12 to match 18 decimals
-            .ok_or(AerospacerProtocolError::OverflowError)?;
-        
-        // Calculate ratio as percentage (multiply by 100)
-        let ratio = scaled_collateral_value
-            .checked_mul(100)
-            .ok_or(AerospacerProtocolError::OverflowError)?
-            .checked_div(debt_amount as u128)
-            .ok_or(AerospacerProtocolError::OverflowError)?;
-        
-        // Convert back to u64
-        u64::try_from(ratio).map_err(|_| AerospacerProtocolError::OverflowError.into())
+        // Rounded down so a trove is never credited with more collateral than it has
+        Decimal::from_ratio(collateral_value as u128, debt_amount as u128, Rounding::Down)
     }
     
     /// Check if trove is liquidatable
     pub fn is_liquidatable(
         collateral_value: u64,
         debt_amount: u64,
-        minimum_ratio: u64,
+        minimum_ratio: Decimal,
     ) -> Result<bool> {
         if debt_amount == 0 {
             return Ok(false);
@@ -205,9 +253,9 @@ impl PriceCalculator {
         collateral_amounts: &[(String, u64)],
         debt_amount: u64,
         prices: &[(String, u64, u8)],
-    ) -> Result<u64> {
+    ) -> Result<Decimal> {
         if debt_amount == 0 {
-            return Ok(u64::MAX);
+            return Ok(Decimal::MAX);
         }
         
         let total_collateral_value = Self::calculate_multi_collateral_value(
@@ -2,8 +2,10 @@ use anchor_lang::prelude::*;
 use crate::state::*;
 use crate::msg::*;
 use crate::error::*;
+use crate::decimal::{Decimal, Rounding};
 // find_insert_location is now in trove_management.rs
 use crate::utils::get_liquidation_gains;
+use crate::trove_management::SystemCollateral;
 
 // Exact replication of INJECTIVE query/mod.rs
 pub fn query_total_collateral_amounts<'a>(
@@ -48,6 +50,20 @@ pub fn query_trove<'a>(
     Ok(response)
 }
 
+pub fn query_system_mode(
+    state_account: &StateAccount,
+    system_collateral: &SystemCollateral,
+) -> Result<SystemModeResponse> {
+    let total_debt_amount = state_account.total_debt_amount;
+
+    Ok(SystemModeResponse {
+        total_collateral_ratio: system_collateral.tcr(total_debt_amount)?,
+        critical_collateral_ratio: CRITICAL_COLLATERAL_RATIO,
+        total_debt_amount,
+        recovery_mode: system_collateral.is_recovery_mode(total_debt_amount)?,
+    })
+}
+
 pub fn query_total_stake_amount(state_account: &StateAccount) -> Result<u64> {
     Ok(state_account.total_stake_amount)
 }
@@ -60,10 +76,10 @@ pub fn query_stake(
     let total_stake_amount = state_account.total_stake_amount;
     let stake_amount = user_stake_amount_account.amount;
 
-    let percentage = if total_stake_amount > 0 {
-        (stake_amount * 1_000_000_000_000_000_000) / total_stake_amount // Simplified Decimal256
+    let percentage = if total_stake_amount == 0 {
+        Decimal::ZERO
     } else {
-        0
+        Decimal::from_ratio(stake_amount as u128, total_stake_amount as u128, Rounding::Down)?
     };
 
     Ok(StakeResponse {
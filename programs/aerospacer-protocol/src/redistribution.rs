@@ -0,0 +1,395 @@
+//! Redistribution of liquidations the stability pool cannot absorb
+//! Liquity's L_ETH / L_LUSDDebt mechanism, tracked per collateral denom: debt the pool cannot
+//! offset is handed to active troves together with the matching collateral, in proportion to
+//! their stake (collateral held) in each denom. Rewards are applied lazily on a trove's next touch.
+
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::*;
+use crate::decimal::{Decimal, Rounding};
+use crate::oracle::PriceCalculator;
+use crate::trove_management::{accumulate_by_denom, find_user_collateral_account, load_user_collateral_amount};
+
+/// How a liquidated trove is split between the stability pool and active troves
+#[derive(Clone, Debug, Default)]
+pub struct LiquidationSplit {
+    pub debt_to_offset: u64,                              // Burned from the stability pool
+    pub collateral_to_send_to_sp: Vec<(String, u64)>,     // Seized by stability pool stakers
+    pub debt_to_redistribute: Vec<(String, u64)>,         // Handed to troves holding each denom
+    pub collateral_to_redistribute: Vec<(String, u64)>,   // Handed to troves holding each denom
+}
+
+impl LiquidationSplit {
+    /// (collateral, debt) redistributed in `denom`
+    pub fn redistributed(&self, denom: &str) -> (u64, u64) {
+        let collateral = self.collateral_to_redistribute.iter().find(|(d, _)| d == denom).map_or(0, |(_, a)| *a);
+        let debt = self.debt_to_redistribute.iter().find(|(d, _)| d == denom).map_or(0, |(_, a)| *a);
+        (collateral, debt)
+    }
+
+    /// Hand the stability pool's share of the liquidation penalty to its stakers, or to the
+    /// active troves along with the rest of the liquidation when the pool is empty
+    pub fn add_stability_pool_penalty(&mut self, penalty: &[(String, u64)], pool_has_stake: bool) {
+        let target = if pool_has_stake {
+            &mut self.collateral_to_send_to_sp
+        } else {
+            &mut self.collateral_to_redistribute
+        };
+        accumulate_by_denom(target, penalty);
+    }
+}
+
+/// Split a liquidated trove: the stability pool offsets as much debt as it holds and receives
+/// the same share of every collateral denom, the rest is redistributed to active troves
+///
+/// Redistributed debt is assigned to each denom in proportion to the value of the collateral
+/// redistributed in it, so troves take on debt together with the collateral backing it.
+pub fn split_liquidation(
+    collateral_amounts: &[(String, u64)],
+    debt_amount: u64,
+    total_stake_amount: u64,
+    prices: &[(String, u64, u8)],
+) -> Result<LiquidationSplit> {
+    let mut split = LiquidationSplit {
+        debt_to_offset: debt_amount.min(total_stake_amount),
+        ..Default::default()
+    };
+
+    for (denom, amount) in collateral_amounts {
+        let to_sp = if debt_amount == 0 {
+            0
+        } else {
+            u64::try_from(
+                (*amount as u128)
+                    .checked_mul(split.debt_to_offset as u128)
+                    .ok_or(AerospacerProtocolError::OverflowError)?
+                    / debt_amount as u128,
+            ).map_err(|_| AerospacerProtocolError::OverflowError)?
+        };
+        split.collateral_to_send_to_sp.push((denom.clone(), to_sp));
+        split.collateral_to_redistribute.push((denom.clone(), amount - to_sp));
+    }
+
+    let debt_remaining = debt_amount - split.debt_to_offset;
+    if debt_remaining == 0 {
+        split.debt_to_redistribute = collateral_amounts.iter().map(|(d, _)| (d.clone(), 0)).collect();
+        return Ok(split);
+    }
+
+    // Value of the redistributed collateral in each denom
+    let mut values = Vec::with_capacity(split.collateral_to_redistribute.len());
+    for (denom, amount) in &split.collateral_to_redistribute {
+        let (_, price, decimal) = prices
+            .iter()
+            .find(|(d, _, _)| d == denom)
+            .ok_or(AerospacerProtocolError::MissingCollateralAccount)?;
+        values.push(PriceCalculator::calculate_collateral_value(*amount, *price, *decimal)? as u128);
+    }
+    let total_value: u128 = values.iter().sum();
+
+    // The last denom takes the rounding remainder (or all of it if nothing has value)
+    let mut debt_assigned = 0u64;
+    for (index, (denom, _amount)) in split.collateral_to_redistribute.iter().enumerate() {
+        let share = if index + 1 == values.len() {
+            debt_remaining - debt_assigned
+        } else {
+            let share = (debt_remaining as u128)
+                .checked_mul(values[index])
+                .ok_or(AerospacerProtocolError::OverflowError)?
+                .checked_div(total_value)
+                .unwrap_or(0);
+            u64::try_from(share).map_err(|_| AerospacerProtocolError::OverflowError)?
+        };
+        debt_assigned += share;
+        split.debt_to_redistribute.push((denom.clone(), share));
+    }
+
+    Ok(split)
+}
+
+/// Hand `collateral` and `debt` of one denom to every trove holding it, per unit of stake
+///
+/// The liquidated trove's own stake must already have been removed from `total_stakes`.
+pub fn redistribute_to_troves(
+    snapshot: &mut RedistributionSnapshot,
+    collateral: u64,
+    debt: u64,
+) -> Result<()> {
+    if collateral == 0 && debt == 0 {
+        return Ok(());
+    }
+
+    // Nobody to take it on - the liquidation must wait for the stability pool
+    require!(
+        snapshot.total_stakes > 0,
+        AerospacerProtocolError::NoTrovesToRedistributeTo
+    );
+
+    let total_stakes = snapshot.total_stakes as u128;
+    let collateral_per_stake = Decimal::from_ratio(collateral as u128, total_stakes, Rounding::Down)?;
+    let debt_per_stake = Decimal::from_ratio(debt as u128, total_stakes, Rounding::Down)?;
+
+    snapshot.l_collateral = snapshot.l_collateral.checked_add(collateral_per_stake)?;
+    snapshot.l_debt = snapshot.l_debt.checked_add(debt_per_stake)?;
+
+    msg!("Redistributed {} {} and {} aUSD debt across {} stake (L_coll={}, L_debt={})",
+         collateral, snapshot.denom, debt, snapshot.total_stakes, snapshot.l_collateral, snapshot.l_debt);
+
+    Ok(())
+}
+
+/// Remove a liquidated trove's stakes and redistribute its remainder, locating the
+/// RedistributionSnapshot of every denom it held by address in remaining_accounts
+pub fn redistribute_trove_by_address(
+    collateral_amounts: &[(String, u64)],
+    split: &LiquidationSplit,
+    remaining_accounts: &[AccountInfo],
+) -> Result<()> {
+    for (denom, amount) in collateral_amounts {
+        let snapshot_account = find_redistribution_snapshot_account(denom, remaining_accounts)?;
+        let (collateral, debt) = split.redistributed(denom);
+        modify_redistribution_snapshot(snapshot_account, |snapshot| {
+            snapshot.update_stake(*amount, 0)?;
+            redistribute_to_troves(snapshot, collateral, debt)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// The (collateral, debt) redistribution rewards a trove's position in one denom has accrued
+/// since its snapshots were last moved up
+pub fn pending_position_rewards(
+    position: &UserCollateralAmount,
+    snapshot: &RedistributionSnapshot,
+) -> Result<(u64, u64)> {
+    let stake = position.amount;
+    let pending_collateral = snapshot.l_collateral
+        .saturating_sub(position.l_collateral_snapshot)
+        .mul_amount(stake, Rounding::Down)?;
+    let pending_debt = snapshot.l_debt
+        .saturating_sub(position.l_debt_snapshot)
+        .mul_amount(stake, Rounding::Down)?;
+    Ok((pending_collateral, pending_debt))
+}
+
+/// Apply the redistribution rewards a trove's position in one denom has accrued
+///
+/// The pending collateral is added to the position (and to the denom's total stakes) and
+/// its snapshots are moved up to the current L values. Returns the pending debt, which the
+/// caller must add to the trove's debt - it is already part of the system's total debt.
+pub fn apply_position_rewards(
+    position: &mut UserCollateralAmount,
+    snapshot: &mut RedistributionSnapshot,
+) -> Result<u64> {
+    let (pending_collateral, pending_debt) = pending_position_rewards(position, snapshot)?;
+
+    position.l_collateral_snapshot = snapshot.l_collateral;
+    position.l_debt_snapshot = snapshot.l_debt;
+
+    if pending_collateral > 0 {
+        position.amount = position.amount
+            .checked_add(pending_collateral)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+        snapshot.total_stakes = snapshot.total_stakes
+            .checked_add(pending_collateral)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+    }
+
+    if pending_collateral > 0 || pending_debt > 0 {
+        msg!("Applied redistribution rewards: {} {} and {} aUSD debt",
+             pending_collateral, position.denom, pending_debt);
+    }
+
+    Ok(pending_debt)
+}
+
+/// Apply a trove's pending redistribution rewards in every denom it holds
+///
+/// `user_collateral_amount` / `redistribution_snapshot` are the instruction's own denom. For
+/// the trove's other denoms, remaining_accounts must start with one group of `group_len`
+/// accounts per denom, in `UserDebtAmount::collateral_denoms` order, each group beginning with
+/// [UserCollateralAmount, RedistributionSnapshot]. The pending debt is added to the trove.
+pub fn apply_trove_rewards(
+    user_debt_amount: &mut UserDebtAmount,
+    user_collateral_amount: &mut UserCollateralAmount,
+    redistribution_snapshot: &mut RedistributionSnapshot,
+    remaining_accounts: &[AccountInfo],
+    group_len: usize,
+) -> Result<()> {
+    let mut pending_debt = apply_position_rewards(user_collateral_amount, redistribution_snapshot)?;
+
+    let other_denoms = user_debt_amount.collateral_denoms
+        .iter()
+        .filter(|d| **d != user_collateral_amount.denom);
+    for (index, denom) in other_denoms.enumerate() {
+        let start = index * group_len;
+        require!(
+            remaining_accounts.len() >= start + 2,
+            AerospacerProtocolError::MissingCollateralAccount
+        );
+
+        // Positional accounts are checked against this trove's PDAs
+        let collateral_account = &remaining_accounts[start];
+        load_user_collateral_amount(collateral_account, &user_debt_amount.owner, denom)?;
+        let snapshot_account = &remaining_accounts[start + 1];
+        require!(
+            snapshot_account.key() == redistribution_snapshot_address(denom),
+            AerospacerProtocolError::MissingCollateralAccount
+        );
+
+        pending_debt = pending_debt
+            .checked_add(apply_account_rewards(collateral_account, snapshot_account)?)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+    }
+
+    user_debt_amount.amount = user_debt_amount.amount
+        .checked_add(pending_debt)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+
+    Ok(())
+}
+
+/// Apply a trove's pending redistribution rewards, locating its UserCollateralAmount and the
+/// RedistributionSnapshot of every denom it holds by address in remaining_accounts
+///
+/// Used by instructions that touch several troves. The pending debt is added to the trove.
+pub fn apply_trove_rewards_by_address(
+    user_debt_amount: &mut UserDebtAmount,
+    remaining_accounts: &[AccountInfo],
+) -> Result<()> {
+    let mut pending_debt = 0u64;
+
+    for denom in &user_debt_amount.collateral_denoms {
+        let collateral_account = find_user_collateral_account(&user_debt_amount.owner, denom, remaining_accounts)?;
+        let snapshot_account = find_redistribution_snapshot_account(denom, remaining_accounts)?;
+
+        pending_debt = pending_debt
+            .checked_add(apply_account_rewards(collateral_account, snapshot_account)?)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+    }
+
+    user_debt_amount.amount = user_debt_amount.amount
+        .checked_add(pending_debt)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+
+    Ok(())
+}
+
+/// Apply rewards to a UserCollateralAmount passed as an AccountInfo, returning the pending debt
+fn apply_account_rewards(collateral_account: &AccountInfo, snapshot_account: &AccountInfo) -> Result<u64> {
+    let mut collateral_data = collateral_account.try_borrow_mut_data()?;
+    let mut position = UserCollateralAmount::try_deserialize(&mut &collateral_data[..])?;
+
+    let pending_debt = modify_redistribution_snapshot(snapshot_account, |snapshot| {
+        apply_position_rewards(&mut position, snapshot)
+    })?;
+
+    position.try_serialize(&mut &mut collateral_data[..])?;
+    Ok(pending_debt)
+}
+
+/// Address of the RedistributionSnapshot PDA for `denom`
+pub fn redistribution_snapshot_address(denom: &str) -> Pubkey {
+    Pubkey::find_program_address(&RedistributionSnapshot::seeds(denom), &crate::ID).0
+}
+
+/// Find the RedistributionSnapshot PDA for `denom` anywhere in remaining_accounts
+pub fn find_redistribution_snapshot_account<'a, 'info>(
+    denom: &str,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a AccountInfo<'info>> {
+    let expected_pda = redistribution_snapshot_address(denom);
+    remaining_accounts
+        .iter()
+        .find(|a| a.key() == expected_pda)
+        .ok_or(AerospacerProtocolError::MissingCollateralAccount.into())
+}
+
+/// Load a RedistributionSnapshot passed as an AccountInfo, apply `f` and write it back
+pub fn modify_redistribution_snapshot<T>(
+    account_info: &AccountInfo,
+    f: impl FnOnce(&mut RedistributionSnapshot) -> Result<T>,
+) -> Result<T> {
+    require!(
+        account_info.owner == &crate::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+
+    let mut data = account_info.try_borrow_mut_data()?;
+    let mut snapshot = RedistributionSnapshot::try_deserialize(&mut &data[..])?;
+    let result = f(&mut snapshot)?;
+    snapshot.try_serialize(&mut &mut data[..])?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amounts(entries: &[(&str, u64)]) -> Vec<(String, u64)> {
+        entries.iter().map(|(d, a)| (d.to_string(), *a)).collect()
+    }
+
+    fn position(amount: u64) -> UserCollateralAmount {
+        UserCollateralAmount {
+            owner: Pubkey::default(),
+            denom: "SOL".to_string(),
+            amount,
+            l_collateral_snapshot: Decimal::ZERO,
+            l_debt_snapshot: Decimal::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_split_offsets_what_the_pool_holds() {
+        let prices = vec![("SOL".to_string(), 10, 0)];
+
+        // The pool holds 400 of the 1000 debt, so it takes 40% of the collateral
+        let split = split_liquidation(&amounts(&[("SOL", 100)]), 1_000, 400, &prices).unwrap();
+        assert_eq!(split.debt_to_offset, 400);
+        assert_eq!(split.collateral_to_send_to_sp, amounts(&[("SOL", 40)]));
+        assert_eq!(split.redistributed("SOL"), (60, 600));
+
+        // A pool that covers the debt takes everything
+        let split = split_liquidation(&amounts(&[("SOL", 100)]), 1_000, 5_000, &prices).unwrap();
+        assert_eq!(split.collateral_to_send_to_sp, amounts(&[("SOL", 100)]));
+        assert_eq!(split.redistributed("SOL"), (0, 0));
+    }
+
+    #[test]
+    fn test_redistributed_debt_follows_collateral_value() {
+        // 1000 aUSD of SOL and 3000 aUSD of ETH, with an empty pool
+        let prices = vec![("SOL".to_string(), 10, 0), ("ETH".to_string(), 300, 0)];
+        let split = split_liquidation(&amounts(&[("SOL", 100), ("ETH", 10)]), 1_001, 0, &prices).unwrap();
+        assert_eq!(split.redistributed("SOL"), (100, 250));
+        // The last denom takes the rounding remainder
+        assert_eq!(split.redistributed("ETH"), (10, 751));
+    }
+
+    #[test]
+    fn test_redistribution_rewards_follow_stake() {
+        let mut snapshot = RedistributionSnapshot {
+            denom: "SOL".to_string(),
+            l_collateral: Decimal::ZERO,
+            l_debt: Decimal::ZERO,
+            total_stakes: 400,
+        };
+        let mut small = position(100);
+        let mut large = position(300);
+
+        redistribute_to_troves(&mut snapshot, 40, 800).unwrap();
+        assert_eq!(apply_position_rewards(&mut small, &mut snapshot).unwrap(), 200);
+        assert_eq!(apply_position_rewards(&mut large, &mut snapshot).unwrap(), 600);
+        assert_eq!((small.amount, large.amount), (110, 330));
+        assert_eq!(snapshot.total_stakes, 440);
+
+        // Applying again finds nothing new
+        assert_eq!(apply_position_rewards(&mut small, &mut snapshot).unwrap(), 0);
+        assert_eq!(small.amount, 110);
+
+        // With no active troves nothing can be redistributed
+        snapshot.total_stakes = 0;
+        assert!(redistribute_to_troves(&mut snapshot, 1, 1).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! spAUSD - the optional tokenized stability pool
+//! The SharePool holds one ordinary stability pool deposit for every share holder. Its gains are
+//! settled into its UserCollateralSnapshots exactly like a staker's (see stability_pool), so the
+//! Product-Sum accounting is unchanged: a share is a claim on 1/supply of the deposit compounded by
+//! calculate_compounded_stake and of the collateral calculate_collateral_gain has credited to it.
+//! Deposits mint shares at the pool's value, priced by the oracle so they buy into the collateral
+//! gains at market; redemptions burn shares for their part of both, paid in kind.
+
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::*;
+use crate::decimal::{mul_div, Rounding};
+use crate::oracle::{OracleContext, PriceCalculator};
+use crate::stability_pool::find_user_collateral_snapshot_account;
+use crate::trove_management::cache_denom_price;
+
+/// Shares minted for `amount` aUSD deposited into a pool worth `pool_value` aUSD that has
+/// `share_supply` shares out (the first deposit mints one share per aUSD)
+pub fn shares_for_deposit(amount: u64, pool_value: u64, share_supply: u64) -> Result<u64> {
+    if share_supply == 0 {
+        return Ok(amount);
+    }
+    // Nothing backs the old shares - new money must not be shared with them
+    require!(pool_value > 0, AerospacerProtocolError::SharePoolDepleted);
+
+    let shares = mul_div(amount as u128, share_supply as u128, pool_value as u128, Rounding::Down)?;
+    u64::try_from(shares).map_err(|_| AerospacerProtocolError::MathOverflow.into())
+}
+
+/// Add `shares` fresh from a deposit at `slot` to the depositor's lock, releasing whatever had
+/// already matured first
+///
+/// Every locked share then waits out the cooldown from this deposit. A depositor can only hold
+/// back their own shares this way, never anyone else's. Returns the shares to hand to the
+/// depositor now - with no cooldown, these include the fresh ones.
+pub fn lock_shares(lock: &mut ShareLock, shares: u64, slot: u64, cooldown_slots: u64) -> Result<u64> {
+    let mut released = release_matured_shares(lock, slot);
+    lock.shares = lock.shares
+        .checked_add(shares)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+    lock.unlock_slot = slot.saturating_add(cooldown_slots);
+    released += release_matured_shares(lock, slot);
+    Ok(released)
+}
+
+/// Take every share out of the lock once its cooldown has passed, returning how many
+pub fn release_matured_shares(lock: &mut ShareLock, slot: u64) -> u64 {
+    if slot < lock.unlock_slot {
+        return 0;
+    }
+    std::mem::take(&mut lock.shares)
+}
+
+/// The part of `total` that `shares` out of `share_supply` redeem for, rounded down in the pool's favour
+pub fn redeemed_amount(shares: u64, share_supply: u64, total: u64) -> Result<u64> {
+    require!(shares <= share_supply, AerospacerProtocolError::InvalidAmount);
+    if share_supply == 0 {
+        return Ok(0);
+    }
+
+    let amount = mul_div(total as u128, shares as u128, share_supply as u128, Rounding::Down)?;
+    u64::try_from(amount).map_err(|_| AerospacerProtocolError::MathOverflow.into())
+}
+
+/// Collateral gains the share pool holds in each of `denoms`, read from its UserCollateralSnapshots
+/// located by address in `accounts` (a snapshot never created holds nothing)
+pub fn share_pool_collateral(share_pool: &Pubkey, denoms: &[String], accounts: &[AccountInfo]) -> Result<Vec<(String, u64)>> {
+    let mut collateral = Vec::new();
+    for denom in denoms {
+        let account_info = find_user_collateral_snapshot_account(share_pool, denom, accounts)?;
+        if account_info.data_is_empty() {
+            continue;
+        }
+        let snapshot = UserCollateralSnapshot::try_deserialize(&mut &account_info.try_borrow_data()?[..])?;
+        if snapshot.pending_collateral_gain > 0 {
+            collateral.push((denom.clone(), snapshot.pending_collateral_gain));
+        }
+    }
+    Ok(collateral)
+}
+
+/// Take `amount` of `denom` out of the share pool's collateral gains as it is paid to a redeemer
+pub fn take_share_pool_collateral(share_pool: &Pubkey, denom: &str, amount: u64, accounts: &[AccountInfo]) -> Result<()> {
+    let account_info = find_user_collateral_snapshot_account(share_pool, denom, accounts)?;
+    let mut data = account_info.try_borrow_mut_data()?;
+    let mut snapshot = UserCollateralSnapshot::try_deserialize(&mut &data[..])?;
+    snapshot.pending_collateral_gain = snapshot.pending_collateral_gain
+        .checked_sub(amount)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+    snapshot.try_serialize(&mut &mut data[..])?;
+    Ok(())
+}
+
+/// Value in aUSD of the share pool: its compounded deposit plus `collateral` at oracle prices,
+/// whose Pyth price accounts are located by address in `accounts`
+pub fn share_pool_value<'info>(
+    compounded_deposit: u64,
+    collateral: &[(String, u64)],
+    oracle_ctx: &OracleContext<'info>,
+    accounts: &[AccountInfo<'info>],
+) -> Result<u64> {
+    let mut prices = Vec::new();
+    for (denom, _amount) in collateral {
+        cache_denom_price(oracle_ctx, denom, accounts, &mut prices)?;
+    }
+    let collateral_value = PriceCalculator::calculate_multi_collateral_value(collateral, &prices)?;
+
+    compounded_deposit
+        .checked_add(collateral_value)
+        .ok_or(AerospacerProtocolError::OverflowError.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shares_track_pool_value() {
+        // First deposit mints one share per aUSD
+        assert_eq!(shares_for_deposit(1_000, 0, 0).unwrap(), 1_000);
+
+        // Liquidations halved the deposit but left 300 aUSD of collateral: 1000 shares are worth 800
+        assert_eq!(shares_for_deposit(400, 800, 1_000).unwrap(), 500);
+
+        // A pool wiped out with shares outstanding takes no new deposits
+        assert!(shares_for_deposit(400, 0, 1_000).is_err());
+    }
+
+    #[test]
+    fn test_deposited_shares_wait_out_the_cooldown() {
+        let mut lock = ShareLock { owner: Pubkey::new_unique(), shares: 0, unlock_slot: 0 };
+
+        // Shares minted at slot 1000 stay locked until slot 1100
+        assert_eq!(lock_shares(&mut lock, 500, 1_000, 100).unwrap(), 0);
+        assert_eq!(release_matured_shares(&mut lock, 1_099), 0);
+
+        // Another deposit before then restarts the cooldown for all of the depositor's locked shares
+        assert_eq!(lock_shares(&mut lock, 200, 1_050, 100).unwrap(), 0);
+        assert_eq!(release_matured_shares(&mut lock, 1_100), 0);
+        assert_eq!(release_matured_shares(&mut lock, 1_150), 700);
+        assert_eq!(lock.shares, 0);
+
+        // Matured shares are handed over before fresh ones are locked
+        assert_eq!(lock_shares(&mut lock, 300, 2_000, 100).unwrap(), 0);
+        assert_eq!(lock_shares(&mut lock, 100, 2_100, 100).unwrap(), 300);
+        assert_eq!((lock.shares, lock.unlock_slot), (100, 2_200));
+
+        // Without a cooldown nothing stays locked
+        assert_eq!(lock_shares(&mut lock, 50, 2_300, 0).unwrap(), 150);
+        assert_eq!(lock.shares, 0);
+    }
+
+    #[test]
+    fn test_redeemed_amount_rounds_down() {
+        assert_eq!(redeemed_amount(1, 3, 100).unwrap(), 33);
+        assert_eq!(redeemed_amount(3, 3, 100).unwrap(), 100);
+        assert!(redeemed_amount(4, 3, 100).is_err());
+    }
+}
@@ -1,40 +1,61 @@
+//! Simplified sorted troves module - Off-chain sorting with on-chain validation
+//! 
+//! NEW ARCHITECTURE:
+//! - Client fetches all troves via RPC (no on-chain size limits)
+//! - Client sorts by ICR off-chain (efficient, no compute limits)
+//! - Client passes 2-3 neighbor accounts via remainingAccounts for validation
+//! - Contract validates ICR ordering without storing linked list
+//! 
+//! Benefits:
+//! - Reduced on-chain storage (no Node accounts, no SortedTrovesState)
+//! - No transaction size limits from passing full list
+//! - Simpler contract logic (~100 lines vs 668 lines)
+//! - Client has full flexibility for sorting strategy
+//! 
+//! Troves are sorted per collateral denom by their nominal collateral ratio (NICR), which
+//! leaves the price out - a price move scales every ICR in a denom's list alike, so it never
+//! invalidates a neighbour's position.
+
 use anchor_lang::prelude::*;
 use crate::state::*;
 use crate::error::*;
+use crate::decimal::{mul_div, Decimal, Rounding};
 
-/// Simplified sorted troves module - Off-chain sorting with on-chain validation
-/// 
-/// NEW ARCHITECTURE:
-/// - Client fetches all troves via RPC (no on-chain size limits)
-/// - Client sorts by ICR off-chain (efficient, no compute limits)
-/// - Client passes 2-3 neighbor accounts via remainingAccounts for validation
-/// - Contract validates ICR ordering without storing linked list
+/// Scale of the nominal ratio: collateral × 1e20 / debt, as in Liquity
+pub const NICR_PRECISION: u128 = 100_000_000_000_000_000_000;
+
+/// Nominal collateral ratio (NICR) of `collateral_amount` base units of one denom against `debt_amount`
 /// 
-/// Benefits:
-/// - Reduced on-chain storage (no Node accounts, no SortedTrovesState)
-/// - No transaction size limits from passing full list
-/// - Simpler contract logic (~100 lines vs 668 lines)
-/// - Client has full flexibility for sorting strategy
+/// The raw Decimal value is collateral × 1e20 / debt. NICRs are only comparable between
+/// troves holding the same denom. A trove without debt sorts last.
+pub fn compute_nominal_icr(collateral_amount: u64, debt_amount: u64) -> Result<Decimal> {
+    if debt_amount == 0 {
+        return Ok(Decimal::MAX);
+    }
+    
+    mul_div(collateral_amount as u128, NICR_PRECISION, debt_amount as u128, Rounding::Down)
+        .map(Decimal::from_raw)
+}
 
-/// Validate that a trove's ICR is correctly ordered between its neighbors
+/// Validate that a trove's NICR is correctly ordered between its neighbors
 /// 
 /// # Arguments
-/// * `trove_icr` - ICR of the trove being validated
-/// * `prev_icr` - ICR of the previous neighbor (if exists), should be <= trove_icr
-/// * `next_icr` - ICR of the next neighbor (if exists), should be >= trove_icr
+/// * `trove_icr` - NICR of the trove being validated
+/// * `prev_icr` - NICR of the previous neighbor (if exists), should be <= trove_icr
+/// * `next_icr` - NICR of the next neighbor (if exists), should be >= trove_icr
 /// 
 /// # Returns
 /// Ok(()) if ordering is valid, Err otherwise
 /// 
 /// # Ordering Rules
-/// - Lower ICR = riskier = earlier in list
-/// - Higher ICR = safer = later in list
+/// - Lower NICR = riskier = earlier in list
+/// - Higher NICR = safer = later in list
 /// - If prev exists: prev_icr <= trove_icr
 /// - If next exists: trove_icr <= next_icr
 pub fn validate_icr_ordering(
-    trove_icr: u64,
-    prev_icr: Option<u64>,
-    next_icr: Option<u64>,
+    trove_icr: Decimal,
+    prev_icr: Option<Decimal>,
+    next_icr: Option<Decimal>,
 ) -> Result<()> {
     // Validate prev neighbor: prev_icr <= trove_icr
     if let Some(prev) = prev_icr {
@@ -63,10 +84,10 @@ pub fn validate_icr_ordering(
 /// Client passes pre-sorted list of troves via remainingAccounts.
 /// Contract validates:
 /// 1. Each trove's ICR < liquidation_threshold
-/// 2. ICRs are properly sorted (ascending order)
+/// 2. NICRs in the denom of each trove's UserCollateralAmount are properly sorted (ascending order)
 /// 
 /// # Arguments
-/// * `liquidation_threshold` - ICR threshold below which troves are liquidatable (typically 110)
+/// * `liquidation_threshold` - ICR threshold below which troves are liquidatable (typically 1.1)
 /// * `remaining_accounts` - Pre-sorted trove accounts [UserDebtAmount, UserCollateralAmount, LiquidityThreshold] triplets
 /// 
 /// # Returns
@@ -75,15 +96,15 @@ pub fn validate_icr_ordering(
 /// # Remaining Accounts Pattern (per trove)
 /// For each trove in the liquidation list:
 /// - [i*3 + 0]: UserDebtAmount account
-/// - [i*3 + 1]: UserCollateralAmount account
-/// - [i*3 + 2]: LiquidityThreshold account (contains ICR)
+/// - [i*3 + 1]: UserCollateralAmount account (the denom of the sorted list)
+/// - [i*3 + 2]: LiquidityThreshold account (contains ICR and NICRs)
 /// 
 /// # Validation
 /// - Checks ICR < threshold for each trove
-/// - Validates ascending ICR order (sorted from riskiest to safest)
+/// - Validates ascending NICR order (sorted from riskiest to safest)
 /// - Stops at first trove with ICR >= threshold (early termination optimization)
 pub fn get_liquidatable_troves(
-    liquidation_threshold: u64,
+    liquidation_threshold: Decimal,
     remaining_accounts: &[AccountInfo],
 ) -> Result<Vec<Pubkey>> {
     let mut liquidatable = Vec::new();
@@ -103,14 +124,15 @@ pub fn get_liquidatable_troves(
     
     msg!("Checking {} troves for liquidation (threshold: {})", num_troves, liquidation_threshold);
     
-    let mut prev_icr: Option<u64> = None;
+    let mut prev_nicr: Option<Decimal> = None;
+    let mut list_denom: Option<String> = None;
     
     for i in 0..num_troves {
         let base_idx = i * 3;
         
         // Get accounts for this trove
         let debt_account = &remaining_accounts[base_idx];
-        let _collateral_account = &remaining_accounts[base_idx + 1]; // Reserved for future use
+        let collateral_account = &remaining_accounts[base_idx + 1];
         let lt_account = &remaining_accounts[base_idx + 2];
         
         // Deserialize UserDebtAmount to get owner
@@ -119,10 +141,25 @@ pub fn get_liquidatable_troves(
         let owner = debt.owner;
         drop(debt_data);
         
-        // Deserialize LiquidityThreshold to get ICR
+        // Deserialize UserCollateralAmount to get the denom the list is sorted in
+        let collateral_data = collateral_account.try_borrow_data()?;
+        let collateral = UserCollateralAmount::try_deserialize(&mut &collateral_data[..])?;
+        drop(collateral_data);
+        
+        // Every trove must come from the same denom's list
+        let denom = list_denom.get_or_insert_with(|| collateral.denom.clone());
+        require!(
+            collateral.owner == owner && *denom == collateral.denom,
+            AerospacerProtocolError::InvalidList
+        );
+        
+        // Deserialize LiquidityThreshold to get ICR and NICR
         let lt_data = lt_account.try_borrow_data()?;
         let threshold = LiquidityThreshold::try_deserialize(&mut &lt_data[..])?;
         let current_icr = threshold.ratio;
+        let current_nicr = threshold
+            .nominal_ratio(&collateral.denom)
+            .ok_or(AerospacerProtocolError::InvalidList)?;
         
         // Verify LiquidityThreshold matches the debt account owner
         require!(
@@ -131,21 +168,16 @@ pub fn get_liquidatable_troves(
         );
         drop(lt_data);
         
-        msg!("Trove {}: owner={}, ICR={}", i, owner, current_icr);
+        msg!("Trove {}: owner={}, ICR={}, NICR={}", i, owner, current_icr, current_nicr);
         
-        // Validate ascending ICR order (sorted list)
-        if let Some(prev) = prev_icr {
-            require!(
-                prev <= current_icr,
-                AerospacerProtocolError::InvalidList
-            );
-        }
+        // Validate ascending NICR order (sorted list)
+        validate_icr_ordering(current_nicr, prev_nicr, None)?;
         
         // Check if liquidatable
         if current_icr < liquidation_threshold {
             liquidatable.push(owner);
             msg!("  -> Liquidatable (ICR {} < threshold {})", current_icr, liquidation_threshold);
-            prev_icr = Some(current_icr);
+            prev_nicr = Some(current_nicr);
         } else {
             // Sorted list optimization: stop at first safe trove
             msg!("  -> Safe (ICR {} >= threshold {}). Stopping (sorted list optimization)", current_icr, liquidation_threshold);
@@ -165,7 +197,7 @@ pub fn get_liquidatable_troves(
 /// 
 /// # Returns
 /// ICR value from the account
-pub fn get_icr_from_account(account: &AccountInfo, expected_owner: Pubkey) -> Result<u64> {
+pub fn get_icr_from_account(account: &AccountInfo, expected_owner: Pubkey) -> Result<Decimal> {
     let threshold_data = account.try_borrow_data()?;
     let threshold = LiquidityThreshold::try_deserialize(&mut &threshold_data[..])?;
     
@@ -204,34 +236,121 @@ pub fn verify_liquidity_threshold_pda(
     Ok(())
 }
 
+/// Validate a trove's new NICR in `denom` against the neighbor hints passed by the client
+/// 
+/// # Arguments
+/// * `denom` - The collateral denom whose sorted list the trove is inserted into
+/// * `trove_nicr` - New NICR of the trove being inserted/moved
+/// * `hint_accounts` - The part of remainingAccounts holding the hints:
+///   [prev_LiquidityThreshold, next_LiquidityThreshold] or [prev_LT] or []
+/// * `program_id` - The program ID for PDA derivation
+/// 
+/// Hints are optional for backward compatibility with tests, but REQUIRED in production
+pub fn validate_neighbor_hints(
+    denom: &str,
+    trove_nicr: Decimal,
+    hint_accounts: &[AccountInfo],
+    program_id: &Pubkey,
+) -> Result<()> {
+    if hint_accounts.is_empty() {
+        msg!("⚠ WARNING: No neighbor hints provided - skipping ICR ordering validation");
+        msg!("⚠ Production clients MUST provide neighbor hints for sorted list integrity");
+        return Ok(());
+    }
+    
+    msg!("Validating ICR ordering with {} neighbor account(s)", hint_accounts.len());
+    
+    // First account is previous neighbor's LiquidityThreshold
+    let prev_lt = &hint_accounts[0];
+    let prev_data = prev_lt.try_borrow_data()?;
+    let prev_threshold = LiquidityThreshold::try_deserialize(&mut &prev_data[..])?;
+    drop(prev_data);
+    
+    // Verify this is a real PDA, not a fake account
+    verify_liquidity_threshold_pda(prev_lt, prev_threshold.owner, program_id)?;
+    let prev_nicr = prev_threshold
+        .nominal_ratio(denom)
+        .ok_or(AerospacerProtocolError::InvalidList)?;
+    msg!("Previous neighbor: owner={}, NICR={}", prev_threshold.owner, prev_nicr);
+    
+    let next_nicr = if hint_accounts.len() >= 2 {
+        // Second account is next neighbor's LiquidityThreshold
+        let next_lt = &hint_accounts[1];
+        let next_data = next_lt.try_borrow_data()?;
+        let next_threshold = LiquidityThreshold::try_deserialize(&mut &next_data[..])?;
+        drop(next_data);
+        
+        // Verify this is a real PDA, not a fake account
+        verify_liquidity_threshold_pda(next_lt, next_threshold.owner, program_id)?;
+        let next_nicr = next_threshold
+            .nominal_ratio(denom)
+            .ok_or(AerospacerProtocolError::InvalidList)?;
+        msg!("Next neighbor: owner={}, NICR={}", next_threshold.owner, next_nicr);
+        Some(next_nicr)
+    } else {
+        None
+    };
+    
+    // Validate ordering BEFORE updating state
+    validate_icr_ordering(trove_nicr, Some(prev_nicr), next_nicr)?;
+    msg!("✓ NICR ordering validated successfully");
+    
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    fn pct(percent: u64) -> Decimal {
+        Decimal::from_percent(percent)
+    }
+    
     #[test]
     fn test_validate_icr_ordering_valid() {
         // Valid: prev(100) <= trove(150) <= next(200)
-        assert!(validate_icr_ordering(150, Some(100), Some(200)).is_ok());
+        assert!(validate_icr_ordering(pct(150), Some(pct(100)), Some(pct(200))).is_ok());
         
         // Valid: no prev, trove(150) <= next(200)
-        assert!(validate_icr_ordering(150, None, Some(200)).is_ok());
+        assert!(validate_icr_ordering(pct(150), None, Some(pct(200))).is_ok());
         
         // Valid: prev(100) <= trove(150), no next
-        assert!(validate_icr_ordering(150, Some(100), None).is_ok());
+        assert!(validate_icr_ordering(pct(150), Some(pct(100)), None).is_ok());
         
         // Valid: no neighbors
-        assert!(validate_icr_ordering(150, None, None).is_ok());
+        assert!(validate_icr_ordering(pct(150), None, None).is_ok());
         
         // Valid: equal ICRs
-        assert!(validate_icr_ordering(150, Some(150), Some(150)).is_ok());
+        assert!(validate_icr_ordering(pct(150), Some(pct(150)), Some(pct(150))).is_ok());
     }
     
     #[test]
     fn test_validate_icr_ordering_invalid() {
         // Invalid: prev(200) > trove(150)
-        assert!(validate_icr_ordering(150, Some(200), Some(300)).is_err());
+        assert!(validate_icr_ordering(pct(150), Some(pct(200)), Some(pct(300))).is_err());
         
         // Invalid: trove(150) > next(100)
-        assert!(validate_icr_ordering(150, Some(100), Some(100)).is_err());
+        assert!(validate_icr_ordering(pct(150), Some(pct(100)), Some(pct(100))).is_err());
+    }
+    
+    #[test]
+    fn test_validate_icr_ordering_fractional() {
+        // 110.9% sorts above 110% instead of truncating to the same whole percent
+        let icr = Decimal::from_raw(1_109_000_000_000_000_000);
+        assert!(validate_icr_ordering(icr, Some(pct(110)), Some(pct(111))).is_ok());
+        assert!(validate_icr_ordering(pct(110), Some(icr), None).is_err());
+    }
+    
+    #[test]
+    fn test_nominal_icr() {
+        // 10 units of collateral against 5 of debt: 10 × 1e20 / 5
+        assert_eq!(compute_nominal_icr(10, 5).unwrap().raw(), 200_000_000_000_000_000_000);
+        assert_eq!(compute_nominal_icr(10, 0).unwrap(), Decimal::MAX);
+        
+        // The NICR order of two troves in one denom holds at any price
+        let riskier = compute_nominal_icr(1_500_000_000, 1_000_000_000).unwrap();
+        let safer = compute_nominal_icr(1_600_000_000, 1_000_000_000).unwrap();
+        assert!(validate_icr_ordering(riskier, None, Some(safer)).is_ok());
+        assert!(validate_icr_ordering(safer, None, Some(riskier)).is_err());
     }
 }
@@ -0,0 +1,841 @@
+//! Stability pool accounting - Liquity's Product-Sum algorithm
+//! Debt offset against the pool shrinks P, the product every deposit is compounded by, and the
+//! seized collateral grows S, the collateral per unit staked, of its denom. Once P would fall below
+//! 1e-9 it is multiplied by SCALE_FACTOR and the scale bumped; once the pool is emptied a new epoch
+//! starts with P back at 1. S is kept per (denom, epoch, scale) in EpochScaleSum PDAs, so a deposit's
+//! gains are read from the epoch and scale it was made in, however far the pool has moved on since.
+
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::*;
+use crate::decimal::{mul_div, Decimal, Rounding};
+use crate::utils::{calculate_collateral_gain, create_pda_account};
+
+/// Burn `debt_amount` out of the stability pool (the P half of the Product-Sum update)
+///
+/// P_new = P_old × (total_stake - debt_amount) / total_stake. A P that would drop below 1e-9 is
+/// multiplied by SCALE_FACTOR and the scale bumped; a pool emptied to zero starts a new epoch with
+/// P reset to 1. Runs after credit_stability_pool_gain, so the gains of the liquidation that moves
+/// the pool on are credited to the epoch and scale its depositors were in.
+pub fn offset_stability_pool_debt(state: &mut StateAccount, debt_amount: u64) -> Result<()> {
+    let total_stake = state.total_stake_amount;
+
+    msg!("Distributing liquidation gains to stability pool (snapshot algorithm):");
+    msg!("  Total stake in pool: {}", total_stake);
+    msg!("  Debt liquidated: {}", debt_amount);
+    msg!("  Current P factor: {}", state.p_factor);
+    msg!("  Current epoch: {}, scale: {}", state.epoch, state.current_scale);
+
+    // If no stakers, the whole trove was redistributed (nothing for the pool)
+    if total_stake == 0 {
+        msg!("  No stakers - liquidation fully redistributed to active troves");
+        return Ok(());
+    }
+
+    let remaining_stake = total_stake.saturating_sub(debt_amount);
+
+    if remaining_stake == 0 {
+        // Pool completely depleted - start new epoch
+        state.epoch = state.epoch
+            .checked_add(1)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+        state.current_scale = 0;
+        state.p_factor = Decimal::ONE;
+        state.total_stake_amount = 0;
+        msg!("  Pool depleted to 0 - starting epoch {}", state.epoch);
+        msg!("  P factor reset to 1");
+        return Ok(());
+    }
+
+    // Calculate depletion ratio: (remaining_stake / total_stake)
+    let depletion_ratio = Decimal::from_ratio(remaining_stake as u128, total_stake as u128, Rounding::Down)?;
+
+    // Update P: P_new = P_old × depletion_ratio, moving to the next scale before it underflows
+    let p_factor = state.p_factor.checked_mul(depletion_ratio, Rounding::Down)?;
+    if p_factor.raw() < SCALE_FACTOR {
+        let scaled = mul_div(state.p_factor.raw(), depletion_ratio.raw(), Decimal::SCALE / SCALE_FACTOR, Rounding::Down)?;
+        state.p_factor = Decimal::from_raw(scaled);
+        state.current_scale = state.current_scale
+            .checked_add(1)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+        msg!("  P factor scaled by {} - now at scale {}", SCALE_FACTOR, state.current_scale);
+    } else {
+        state.p_factor = p_factor;
+    }
+    require!(!state.p_factor.is_zero(), AerospacerProtocolError::MathOverflow);
+
+    state.total_stake_amount = remaining_stake;
+
+    msg!("  Updated P factor: {} (depletion ratio: {})", state.p_factor, depletion_ratio);
+    msg!("  Remaining stake: {}", remaining_stake);
+
+    Ok(())
+}
+
+/// The stability pool's part of one liquidation - the full Product-Sum update shared by
+/// liquidate_trove and liquidate_troves
+///
+/// S of every denom in `collateral_amounts` grows by the collateral the pool seized, then P shrinks
+/// by `debt_amount`. `state` must be the instruction's StateAccount itself, since P, epoch and scale
+/// live there. The denoms' StabilityPoolSnapshot PDAs and the EpochScaleSum PDAs of the pool's
+/// current (epoch, scale) are located by address in `accounts`; a missing snapshot fails the
+/// liquidation, as the seized collateral would otherwise be stranded in the vault. `payer` funds
+/// the EpochScaleSum of a scale on its first credit.
+pub fn absorb_liquidation<'info>(
+    state: &mut StateAccount,
+    collateral_amounts: &[(String, u64)],
+    debt_amount: u64,
+    accounts: &[AccountInfo<'info>],
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    if state.total_stake_amount > 0 {
+        for (denom, amount) in collateral_amounts {
+            let snapshot_account = find_stability_pool_snapshot_account(denom, accounts)
+                .map_err(|_| error!(AerospacerProtocolError::InvalidSnapshot))?;
+            let epoch_scale_sum = find_epoch_scale_sum_account(denom, state.epoch, state.current_scale, accounts)?;
+            modify_stability_pool_snapshot(snapshot_account, denom, |snapshot| {
+                credit_stability_pool_gain(state, snapshot, *amount, epoch_scale_sum, payer, system_program)
+            })?;
+        }
+    }
+
+    offset_stability_pool_debt(state, debt_amount)
+}
+
+/// Credit `amount` of the snapshot's denom to the stability pool (the S half of the Product-Sum update)
+///
+/// S_new = S_old + amount × P / total_stake in the pool's current (epoch, scale), both in the denom's
+/// StabilityPoolSnapshot and in `epoch_scale_sum`, its EpochScaleSum for that (epoch, scale), which
+/// `payer` funds on the first credit of the scale. Must run before offset_stability_pool_debt, so
+/// P is the one the pool's deposits are compounded by when the collateral is seized - a deposit's
+/// gain is its S difference divided by the P it was made at, which yields its compounded share.
+pub fn credit_stability_pool_gain<'info>(
+    state: &StateAccount,
+    snapshot: &mut StabilityPoolSnapshot,
+    amount: u64,
+    epoch_scale_sum: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let total_stake = state.total_stake_amount;
+    if total_stake == 0 || amount == 0 {
+        return Ok(());
+    }
+    let s_increment = Decimal::from_raw(mul_div(amount as u128, state.p_factor.raw(), total_stake as u128, Rounding::Down)?);
+
+    // The snapshot only carries the latest (epoch, scale) - earlier ones live on in their EpochScaleSum
+    if snapshot.epoch != state.epoch || snapshot.scale != state.current_scale {
+        snapshot.s_factor = Decimal::ZERO;
+        snapshot.epoch = state.epoch;
+        snapshot.scale = state.current_scale;
+    }
+    snapshot.s_factor = snapshot.s_factor.checked_add(s_increment)?;
+    snapshot.total_collateral_gained = snapshot.total_collateral_gained
+        .checked_add(amount)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+
+    if epoch_scale_sum.data_is_empty() {
+        init_epoch_scale_sum(epoch_scale_sum, &snapshot.denom, state.epoch, state.current_scale, payer, system_program)?;
+    }
+    let mut sum = load_epoch_scale_sum(epoch_scale_sum, &snapshot.denom, state.epoch, state.current_scale)?;
+    sum.s_factor = sum.s_factor.checked_add(s_increment)?;
+    sum.try_serialize(&mut &mut epoch_scale_sum.try_borrow_mut_data()?[..])?;
+
+    msg!(
+        "  Updated S factor for {} (epoch {}, scale {}): +{} (new S: {})",
+        snapshot.denom,
+        state.epoch,
+        state.current_scale,
+        s_increment,
+        snapshot.s_factor
+    );
+
+    Ok(())
+}
+
+/// Collateral `stake` has gained in the snapshot's denom since `user_snapshot`, moving the
+/// snapshot up to the S it was measured against (the caller pays the gain out)
+///
+/// The gain is read from the denom's EpochScaleSum PDAs for the snapshot's scale and the next one,
+/// located by address in `sum_accounts` (see gain_epoch_scale_sums). A snapshot older than the
+/// deposit restarts from the deposit's epoch and scale.
+pub fn settle_collateral_gain(
+    stake: &UserStakeAmount,
+    user_snapshot: &mut UserCollateralSnapshot,
+    state: &StateAccount,
+    sum_accounts: &[AccountInfo],
+) -> Result<u64> {
+    let behind_deposit = (user_snapshot.epoch_snapshot, user_snapshot.scale_snapshot)
+        < (stake.epoch_snapshot, stake.scale_snapshot);
+    if behind_deposit {
+        user_snapshot.s_snapshot = Decimal::ZERO;
+        user_snapshot.epoch_snapshot = stake.epoch_snapshot;
+        user_snapshot.scale_snapshot = stake.scale_snapshot;
+    }
+
+    // Nothing is gained after the epoch the deposit was wiped out in
+    if user_snapshot.epoch_snapshot > stake.epoch_snapshot || stake.amount == 0 {
+        return Ok(0);
+    }
+
+    let (epoch, scale) = (user_snapshot.epoch_snapshot, user_snapshot.scale_snapshot);
+    let sum_at_scale = read_epoch_scale_sum(
+        find_epoch_scale_sum_account(&user_snapshot.denom, epoch, scale, sum_accounts)?,
+        &user_snapshot.denom,
+        epoch,
+        scale,
+    )?;
+    let sum_at_next_scale = read_epoch_scale_sum(
+        find_epoch_scale_sum_account(&user_snapshot.denom, epoch, scale + 1, sum_accounts)?,
+        &user_snapshot.denom,
+        epoch,
+        scale + 1,
+    )?;
+
+    let gain = calculate_collateral_gain(
+        stake.amount,
+        stake.p_snapshot,
+        user_snapshot.s_snapshot,
+        scale - stake.scale_snapshot,
+        sum_at_scale,
+        sum_at_next_scale,
+    )?;
+
+    // Stay in the snapshot's scale while the pool is, otherwise move on to the next one
+    if (state.epoch, state.current_scale) == (epoch, scale) {
+        user_snapshot.s_snapshot = sum_at_scale;
+    } else {
+        user_snapshot.s_snapshot = sum_at_next_scale;
+        user_snapshot.scale_snapshot = scale + 1;
+    }
+
+    Ok(gain)
+}
+
+/// Start a staker's S snapshot for `denom` from their deposit's epoch and scale, when they have
+/// none for it yet
+pub fn start_user_collateral_snapshot(
+    user_snapshot: &mut UserCollateralSnapshot,
+    owner: Pubkey,
+    denom: &str,
+    stake: &UserStakeAmount,
+) {
+    user_snapshot.owner = owner;
+    user_snapshot.denom = denom.to_string();
+    user_snapshot.s_snapshot = Decimal::ZERO;
+    user_snapshot.pending_collateral_gain = 0;
+    user_snapshot.epoch_snapshot = stake.epoch_snapshot;
+    user_snapshot.scale_snapshot = stake.scale_snapshot;
+}
+
+/// Everything a staker can withdraw in the snapshot's denom: the gain settle_collateral_gain
+/// measures now plus the pending_collateral_gain set aside by earlier deposit changes, which is cleared
+pub fn claim_collateral_gain(
+    stake: &UserStakeAmount,
+    user_snapshot: &mut UserCollateralSnapshot,
+    state: &StateAccount,
+    sum_accounts: &[AccountInfo],
+) -> Result<u64> {
+    let gain = settle_collateral_gain(stake, user_snapshot, state, sum_accounts)?;
+    let claimable = gain
+        .checked_add(user_snapshot.pending_collateral_gain)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+    user_snapshot.pending_collateral_gain = 0;
+    Ok(claimable)
+}
+
+/// Set aside a staker's gains in every stability pool denom before their deposit changes
+///
+/// For each of `denoms` whose StabilityPoolSnapshot exists, the gain of the deposit as it stands
+/// is added to `owner`'s UserCollateralSnapshot pending_collateral_gain (the snapshot is created,
+/// paid by `payer`, if missing) and the snapshot restarted at the pool's current epoch, scale and
+/// S - where the caller must re-snapshot the deposit. Both snapshots are located by address in
+/// `accounts`, along with the EpochScaleSum PDAs the gains are read from (see gain_epoch_scale_sums).
+pub fn settle_deposit_gains<'info>(
+    stake: &UserStakeAmount,
+    state: &StateAccount,
+    denoms: &[String],
+    owner: &Pubkey,
+    payer: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    for denom in denoms {
+        let pool_snapshot_info = find_stability_pool_snapshot_account(denom, accounts)?;
+        if pool_snapshot_info.data_is_empty() {
+            // No liquidation can have reached a pool without a snapshot
+            continue;
+        }
+        let pool_snapshot = load_stability_pool_snapshot(pool_snapshot_info, denom)?;
+
+        let user_snapshot_info = find_user_collateral_snapshot_account(owner, denom, accounts)?;
+        let mut user_snapshot = load_or_create_user_collateral_snapshot(
+            user_snapshot_info,
+            owner,
+            denom,
+            stake,
+            payer,
+            system_program,
+        )?;
+        let gain = settle_collateral_gain(stake, &mut user_snapshot, state, accounts)?;
+        user_snapshot.pending_collateral_gain = user_snapshot.pending_collateral_gain
+            .checked_add(gain)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+
+        // The pool snapshot only holds S while it is still in the pool's current scale
+        let in_current_scale = (pool_snapshot.epoch, pool_snapshot.scale) == (state.epoch, state.current_scale);
+        user_snapshot.s_snapshot = if in_current_scale { pool_snapshot.s_factor } else { Decimal::ZERO };
+        user_snapshot.epoch_snapshot = state.epoch;
+        user_snapshot.scale_snapshot = state.current_scale;
+        user_snapshot.try_serialize(&mut &mut user_snapshot_info.try_borrow_mut_data()?[..])?;
+
+        msg!(
+            "Settled {} {} (pending: {})",
+            gain,
+            denom,
+            user_snapshot.pending_collateral_gain
+        );
+    }
+    Ok(())
+}
+
+/// Find `owner`'s UserCollateralSnapshot PDA for `denom` anywhere in remaining_accounts
+pub fn find_user_collateral_snapshot_account<'a, 'info>(
+    owner: &Pubkey,
+    denom: &str,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a AccountInfo<'info>> {
+    let (expected_pda, _bump) = Pubkey::find_program_address(&UserCollateralSnapshot::seeds(owner, denom), &crate::ID);
+    remaining_accounts
+        .iter()
+        .find(|a| a.key() == expected_pda)
+        .ok_or(AerospacerProtocolError::MissingCollateralAccount.into())
+}
+
+/// Load `owner`'s UserCollateralSnapshot for `denom` passed as an AccountInfo, creating it
+/// (paid by `payer`) and starting it from `stake` when they have none yet
+pub fn load_or_create_user_collateral_snapshot<'info>(
+    account_info: &AccountInfo<'info>,
+    owner: &Pubkey,
+    denom: &str,
+    stake: &UserStakeAmount,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<UserCollateralSnapshot> {
+    if account_info.data_is_empty() {
+        create_pda_account(
+            account_info,
+            &UserCollateralSnapshot::seeds(owner, denom),
+            8 + UserCollateralSnapshot::LEN,
+            payer,
+            system_program,
+        )?;
+        let mut user_snapshot = UserCollateralSnapshot {
+            owner: *owner,
+            denom: denom.to_string(),
+            s_snapshot: Decimal::ZERO,
+            pending_collateral_gain: 0,
+            epoch_snapshot: 0,
+            scale_snapshot: 0,
+        };
+        start_user_collateral_snapshot(&mut user_snapshot, *owner, denom, stake);
+        return Ok(user_snapshot);
+    }
+
+    let (expected_pda, _bump) = Pubkey::find_program_address(
+        &UserCollateralSnapshot::seeds(owner, denom),
+        &crate::ID,
+    );
+    require_keys_eq!(account_info.key(), expected_pda, AerospacerProtocolError::InvalidSnapshot);
+    require!(
+        account_info.owner == &crate::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+    UserCollateralSnapshot::try_deserialize(&mut &account_info.try_borrow_data()?[..])
+}
+
+/// The (epoch, scale) pairs whose EpochScaleSum settle_collateral_gain reads for `user_snapshot`
+pub fn gain_epoch_scale_sums(stake: &UserStakeAmount, user_snapshot: &UserCollateralSnapshot) -> [(u64, u64); 2] {
+    let (epoch, scale) = (user_snapshot.epoch_snapshot, user_snapshot.scale_snapshot)
+        .max((stake.epoch_snapshot, stake.scale_snapshot));
+    [(epoch, scale), (epoch, scale + 1)]
+}
+
+pub fn epoch_scale_sum_address(denom: &str, epoch: u64, scale: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &EpochScaleSum::seeds(denom, &epoch.to_le_bytes(), &scale.to_le_bytes()),
+        &crate::ID,
+    )
+}
+
+/// Find the EpochScaleSum PDA for (`denom`, `epoch`, `scale`) anywhere in remaining_accounts
+pub fn find_epoch_scale_sum_account<'a, 'info>(
+    denom: &str,
+    epoch: u64,
+    scale: u64,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a AccountInfo<'info>> {
+    let (expected_pda, _bump) = epoch_scale_sum_address(denom, epoch, scale);
+    remaining_accounts
+        .iter()
+        .find(|a| a.key() == expected_pda)
+        .ok_or(AerospacerProtocolError::MissingCollateralAccount.into())
+}
+
+/// S of `denom` in (`epoch`, `scale`) - zero while no liquidation has credited that scale
+pub fn read_epoch_scale_sum(account_info: &AccountInfo, denom: &str, epoch: u64, scale: u64) -> Result<Decimal> {
+    require_keys_eq!(
+        account_info.key(),
+        epoch_scale_sum_address(denom, epoch, scale).0,
+        AerospacerProtocolError::InvalidSnapshot
+    );
+    if account_info.data_is_empty() {
+        return Ok(Decimal::ZERO);
+    }
+    Ok(load_epoch_scale_sum(account_info, denom, epoch, scale)?.s_factor)
+}
+
+/// Load the EpochScaleSum for (`denom`, `epoch`, `scale`) passed as an AccountInfo
+fn load_epoch_scale_sum(account_info: &AccountInfo, denom: &str, epoch: u64, scale: u64) -> Result<EpochScaleSum> {
+    require!(
+        account_info.owner == &crate::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+
+    let sum = EpochScaleSum::try_deserialize(&mut &account_info.try_borrow_data()?[..])?;
+    require!(
+        sum.denom == denom && sum.epoch == epoch && sum.scale == scale,
+        AerospacerProtocolError::InvalidSnapshot
+    );
+    Ok(sum)
+}
+
+/// Create the EpochScaleSum PDA for (`denom`, `epoch`, `scale`) with a zero S
+fn init_epoch_scale_sum<'info>(
+    account_info: &AccountInfo<'info>,
+    denom: &str,
+    epoch: u64,
+    scale: u64,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let epoch_bytes = epoch.to_le_bytes();
+    let scale_bytes = scale.to_le_bytes();
+    create_pda_account(
+        account_info,
+        &EpochScaleSum::seeds(denom, &epoch_bytes, &scale_bytes),
+        8 + EpochScaleSum::LEN,
+        payer,
+        system_program,
+    )?;
+
+    let sum = EpochScaleSum {
+        denom: denom.to_string(),
+        epoch,
+        scale,
+        s_factor: Decimal::ZERO,
+    };
+    sum.try_serialize(&mut &mut account_info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+/// Find the StabilityPoolSnapshot PDA for `denom` anywhere in remaining_accounts
+pub fn find_stability_pool_snapshot_account<'a, 'info>(
+    denom: &str,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a AccountInfo<'info>> {
+    let (expected_pda, _bump) = Pubkey::find_program_address(&StabilityPoolSnapshot::seeds(denom), &crate::ID);
+    remaining_accounts
+        .iter()
+        .find(|a| a.key() == expected_pda)
+        .ok_or(AerospacerProtocolError::MissingCollateralAccount.into())
+}
+
+/// Load `denom`'s StabilityPoolSnapshot passed as an AccountInfo
+pub fn load_stability_pool_snapshot(account_info: &AccountInfo, denom: &str) -> Result<StabilityPoolSnapshot> {
+    require!(
+        account_info.owner == &crate::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+
+    let snapshot = StabilityPoolSnapshot::try_deserialize(&mut &account_info.try_borrow_data()?[..])?;
+    require!(snapshot.denom == denom, AerospacerProtocolError::InvalidSnapshot);
+    Ok(snapshot)
+}
+
+/// Load `denom`'s StabilityPoolSnapshot passed as an AccountInfo, apply `f` and write it back
+pub fn modify_stability_pool_snapshot<T>(
+    account_info: &AccountInfo,
+    denom: &str,
+    f: impl FnOnce(&mut StabilityPoolSnapshot) -> Result<T>,
+) -> Result<T> {
+    require!(
+        account_info.owner == &crate::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+
+    let mut data = account_info.try_borrow_mut_data()?;
+    let mut snapshot = StabilityPoolSnapshot::try_deserialize(&mut &data[..])?;
+    require!(snapshot.denom == denom, AerospacerProtocolError::InvalidSnapshot);
+    let result = f(&mut snapshot)?;
+    snapshot.try_serialize(&mut &mut data[..])?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::calculate_compounded_stake;
+
+    fn pool(total_stake_amount: u64) -> StateAccount {
+        StateAccount {
+            admin: Pubkey::default(),
+            oracle_helper_addr: Pubkey::default(),
+            oracle_state_addr: Pubkey::default(),
+            fee_distributor_addr: Pubkey::default(),
+            fee_state_addr: Pubkey::default(),
+            minimum_collateral_ratio: DEFAULT_MINIMUM_COLLATERAL_RATIO,
+            protocol_fee: DEFAULT_PROTOCOL_FEE,
+            stable_coin_addr: Pubkey::default(),
+            total_debt_amount: 0,
+            total_stake_amount,
+            p_factor: Decimal::ONE,
+            epoch: 0,
+            current_scale: 0,
+            parameter_timelock_delay: 0,
+            base_rate: Decimal::ZERO,
+            last_fee_operation_time: 0,
+            unstake_cooldown_slots: 0,
+        }
+    }
+
+    fn deposit(amount: u64, state: &StateAccount) -> UserStakeAmount {
+        UserStakeAmount {
+            owner: Pubkey::default(),
+            amount,
+            p_snapshot: state.p_factor,
+            epoch_snapshot: state.epoch,
+            scale_snapshot: state.current_scale,
+            last_update_block: 0,
+        }
+    }
+
+    fn account_data<T: AccountSerialize>(account: &T) -> Vec<u8> {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    fn epoch_scale_sum(denom: &str, epoch: u64, scale: u64, s_factor: Decimal) -> EpochScaleSum {
+        EpochScaleSum { denom: denom.to_string(), epoch, scale, s_factor }
+    }
+
+    #[test]
+    fn test_offset_shrinks_p_by_the_debt_share() {
+        let mut state = pool(1_000);
+        let first = deposit(600, &state);
+        let second = deposit(400, &state);
+
+        // Burning 250 of 1000 leaves every deposit at 75%
+        offset_stability_pool_debt(&mut state, 250).unwrap();
+        assert_eq!(state.p_factor, Decimal::from_percent(75));
+        assert_eq!(state.total_stake_amount, 750);
+        assert_eq!((state.epoch, state.current_scale), (0, 0));
+        assert_eq!(calculate_compounded_stake(&first, &state).unwrap(), 450);
+        assert_eq!(calculate_compounded_stake(&second, &state).unwrap(), 300);
+
+        // A second offset compounds on the first: 750 → 375
+        offset_stability_pool_debt(&mut state, 375).unwrap();
+        assert_eq!(state.p_factor, Decimal::from_ratio(375, 1_000, Rounding::Down).unwrap());
+        assert_eq!(calculate_compounded_stake(&first, &state).unwrap(), 225);
+        assert_eq!(calculate_compounded_stake(&second, &state).unwrap(), 150);
+    }
+
+    #[test]
+    fn test_offset_without_stakers_changes_nothing() {
+        let mut state = pool(0);
+        offset_stability_pool_debt(&mut state, 500).unwrap();
+        assert_eq!(state.p_factor, Decimal::ONE);
+        assert_eq!((state.epoch, state.current_scale, state.total_stake_amount), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_offset_moves_to_next_scale_before_p_underflows() {
+        let mut state = pool(1_000_000_000_000);
+        state.p_factor = Decimal::from_ratio(1, 100_000, Rounding::Down).unwrap();
+        let stake = deposit(1_000_000_000_000, &state);
+
+        // P would drop to 1e-10, so it is scaled up by 1e9 instead
+        offset_stability_pool_debt(&mut state, 999_990_000_000).unwrap();
+        assert_eq!(state.current_scale, 1);
+        assert_eq!(state.p_factor, Decimal::from_ratio(1, 10, Rounding::Down).unwrap());
+        assert_eq!(calculate_compounded_stake(&stake, &state).unwrap(), 10_000_000);
+
+        // A deposit made at the new scale compounds against it
+        let later = deposit(10_000_000, &state);
+        state.total_stake_amount += 10_000_000;
+        offset_stability_pool_debt(&mut state, 10_000_000).unwrap();
+        assert_eq!(state.current_scale, 1);
+        assert_eq!(calculate_compounded_stake(&later, &state).unwrap(), 5_000_000);
+        assert_eq!(calculate_compounded_stake(&stake, &state).unwrap(), 5_000_000);
+    }
+
+    #[test]
+    fn test_emptied_pool_starts_new_epoch() {
+        let mut state = pool(1_000);
+        let stake = deposit(1_000, &state);
+
+        offset_stability_pool_debt(&mut state, 1_000).unwrap();
+        assert_eq!((state.epoch, state.current_scale), (1, 0));
+        assert_eq!(state.p_factor, Decimal::ONE);
+        assert_eq!(state.total_stake_amount, 0);
+        assert_eq!(calculate_compounded_stake(&stake, &state).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_absorb_liquidation_moves_p_on_the_state_it_is_given() {
+        // A batch of two liquidations against one 1000 aUSD deposit
+        let mut state = pool(1_000);
+        let stake = deposit(1_000, &state);
+
+        let snapshot = |denom: &str| StabilityPoolSnapshot {
+            denom: denom.to_string(),
+            s_factor: Decimal::ZERO,
+            total_collateral_gained: 0,
+            epoch: 0,
+            scale: 0,
+        };
+        let keys = [
+            Pubkey::find_program_address(&StabilityPoolSnapshot::seeds("SOL"), &crate::ID).0,
+            Pubkey::find_program_address(&StabilityPoolSnapshot::seeds("ETH"), &crate::ID).0,
+            epoch_scale_sum_address("SOL", 0, 0).0,
+            epoch_scale_sum_address("ETH", 0, 0).0,
+        ];
+        let mut data = [
+            account_data(&snapshot("SOL")),
+            account_data(&snapshot("ETH")),
+            account_data(&epoch_scale_sum("SOL", 0, 0, Decimal::ZERO)),
+            account_data(&epoch_scale_sum("ETH", 0, 0, Decimal::ZERO)),
+        ];
+        let mut lamports = [0u64; 4];
+        let accounts: Vec<AccountInfo> = keys
+            .iter()
+            .zip(data.iter_mut())
+            .zip(lamports.iter_mut())
+            .map(|((key, data), lamports)| AccountInfo::new(key, false, true, lamports, data, &crate::ID, false, 0))
+            .collect();
+        let unused = Pubkey::default();
+        let (mut unused_lamports, mut unused_data) = (0, []);
+        let payer = AccountInfo::new(&unused, true, true, &mut unused_lamports, &mut unused_data, &unused, false, 0);
+
+        // 250 of debt for 300 SOL and 10 ETH, then 375 for 100 SOL
+        let first = vec![("SOL".to_string(), 300), ("ETH".to_string(), 10)];
+        absorb_liquidation(&mut state, &first, 250, &accounts, &payer, &payer).unwrap();
+        assert_eq!(state.p_factor, Decimal::from_percent(75));
+        absorb_liquidation(&mut state, &[("SOL".to_string(), 100)], 375, &accounts, &payer, &payer).unwrap();
+
+        // P, the stake and the deposit all reflect both liquidations
+        assert_eq!(state.p_factor, Decimal::from_bps(3_750));
+        assert_eq!(state.total_stake_amount, 375);
+        assert_eq!(calculate_compounded_stake(&stake, &state).unwrap(), 375);
+        let sol = load_stability_pool_snapshot(&accounts[0], "SOL").unwrap();
+        let eth = load_stability_pool_snapshot(&accounts[1], "ETH").unwrap();
+        assert_eq!((sol.total_collateral_gained, eth.total_collateral_gained), (400, 10));
+        // S grew by 300 x 1 / 1000 for the first liquidation and 100 x 0.75 / 750 for the second
+        assert_eq!(sol.s_factor, Decimal::from_ratio(400, 1_000, Rounding::Down).unwrap());
+
+        // Collateral of a denom without a snapshot fails the liquidation rather than being stranded
+        assert!(absorb_liquidation(&mut state, &[("BTC".to_string(), 1)], 1, &accounts, &payer, &payer).is_err());
+    }
+
+    #[test]
+    fn test_collateral_gains_never_exceed_seized_collateral() {
+        let denom = "SOL";
+        let mut state = pool(1_000);
+        let first = deposit(600, &state);
+        let second = deposit(400, &state);
+
+        let (sum_key, _) = epoch_scale_sum_address(denom, 0, 0);
+        let (next_sum_key, _) = epoch_scale_sum_address(denom, 0, 1);
+        let mut sum_data = account_data(&epoch_scale_sum(denom, 0, 0, Decimal::ZERO));
+        let mut next_sum_data = [];
+        let unused = Pubkey::default();
+        let (mut sum_lamports, mut next_sum_lamports, mut unused_lamports) = (0, 0, 0);
+        let mut unused_data = [];
+        let sums = [
+            AccountInfo::new(&sum_key, false, true, &mut sum_lamports, &mut sum_data, &crate::ID, false, 0),
+            AccountInfo::new(&next_sum_key, false, true, &mut next_sum_lamports, &mut next_sum_data, &crate::ID, false, 0),
+        ];
+        let payer = AccountInfo::new(&unused, true, true, &mut unused_lamports, &mut unused_data, &unused, false, 0);
+        let mut snapshot = StabilityPoolSnapshot {
+            denom: denom.to_string(),
+            s_factor: Decimal::ZERO,
+            total_collateral_gained: 0,
+            epoch: 0,
+            scale: 0,
+        };
+        let user_snapshot = |stake: &UserStakeAmount, s_snapshot: Decimal| {
+            let mut user_snapshot = UserCollateralSnapshot {
+                owner: stake.owner,
+                denom: String::new(),
+                s_snapshot: Decimal::ZERO,
+                pending_collateral_gain: 0,
+                epoch_snapshot: 0,
+                scale_snapshot: 0,
+            };
+            start_user_collateral_snapshot(&mut user_snapshot, stake.owner, denom, stake);
+            user_snapshot.s_snapshot = s_snapshot;
+            user_snapshot
+        };
+
+        // 250 of debt burned for 300 SOL: P falls to 0.75
+        credit_stability_pool_gain(&state, &mut snapshot, 300, &sums[0], &payer, &payer).unwrap();
+        offset_stability_pool_debt(&mut state, 250).unwrap();
+        assert_eq!(state.p_factor, Decimal::from_percent(75));
+
+        // A deposit made at P = 0.75 only shares in later liquidations
+        let third = deposit(250, &state);
+        state.total_stake_amount += 250;
+        let third_snapshot = user_snapshot(&third, snapshot.s_factor);
+
+        // 400 of the 1000 now staked burned for 500 SOL
+        credit_stability_pool_gain(&state, &mut snapshot, 500, &sums[0], &payer, &payer).unwrap();
+        offset_stability_pool_debt(&mut state, 400).unwrap();
+
+        // Each deposit gains in proportion to its compounded stake at each liquidation
+        let deposits = [
+            (&first, user_snapshot(&first, Decimal::ZERO)),
+            (&second, user_snapshot(&second, Decimal::ZERO)),
+            (&third, third_snapshot),
+        ];
+        let gains: Vec<u64> = deposits
+            .iter()
+            .map(|(stake, user_snapshot)| claim_collateral_gain(stake, &mut user_snapshot.clone(), &state, &sums).unwrap())
+            .collect();
+        assert_eq!(gains, vec![180 + 225, 120 + 150, 125]);
+        assert_eq!(gains.iter().sum::<u64>(), 800);
+
+        // Uneven amounts round down, so the pool never pays out more than it seized
+        credit_stability_pool_gain(&state, &mut snapshot, 157, &sums[0], &payer, &payer).unwrap();
+        offset_stability_pool_debt(&mut state, 123).unwrap();
+        let claimed: u64 = deposits
+            .iter()
+            .map(|(stake, user_snapshot)| claim_collateral_gain(stake, &mut user_snapshot.clone(), &state, &sums).unwrap())
+            .sum();
+        assert_eq!(snapshot.total_collateral_gained, 957);
+        assert!((957 - 3..=957).contains(&claimed), "claimed {}", claimed);
+        let compounded: u64 = deposits
+            .iter()
+            .map(|(stake, _)| calculate_compounded_stake(stake, &state).unwrap())
+            .sum();
+        assert!(compounded <= state.total_stake_amount);
+    }
+
+    #[test]
+    fn test_claim_collateral_gain_adds_pending_gains() {
+        let denom = "SOL";
+        let state = pool(1_000);
+        let stake = deposit(100, &state);
+        let mut user_snapshot = UserCollateralSnapshot {
+            owner: stake.owner,
+            denom: String::new(),
+            s_snapshot: Decimal::ZERO,
+            pending_collateral_gain: 0,
+            epoch_snapshot: 0,
+            scale_snapshot: 0,
+        };
+        start_user_collateral_snapshot(&mut user_snapshot, stake.owner, denom, &stake);
+        user_snapshot.pending_collateral_gain = 7;
+        assert_eq!(gain_epoch_scale_sums(&stake, &user_snapshot), [(0, 0), (0, 1)]);
+
+        let (sum_key, _) = epoch_scale_sum_address(denom, 0, 0);
+        let (next_sum_key, _) = epoch_scale_sum_address(denom, 0, 1);
+        let mut sum_data = account_data(&epoch_scale_sum(denom, 0, 0, Decimal::from_percent(50)));
+        let mut next_sum_data = [];
+        let (mut sum_lamports, mut next_sum_lamports) = (0, 0);
+        let sums = [
+            AccountInfo::new(&sum_key, false, false, &mut sum_lamports, &mut sum_data, &crate::ID, false, 0),
+            AccountInfo::new(&next_sum_key, false, false, &mut next_sum_lamports, &mut next_sum_data, &crate::ID, false, 0),
+        ];
+
+        // Half a unit of collateral per unit staked, on top of what was set aside earlier
+        assert_eq!(claim_collateral_gain(&stake, &mut user_snapshot, &state, &sums).unwrap(), 50 + 7);
+        assert_eq!(user_snapshot.pending_collateral_gain, 0);
+        assert_eq!(user_snapshot.s_snapshot, Decimal::from_percent(50));
+
+        // Claiming again pays nothing
+        assert_eq!(claim_collateral_gain(&stake, &mut user_snapshot, &state, &sums).unwrap(), 0);
+
+        // The sum of the snapshot's own scale must be passed
+        assert!(claim_collateral_gain(&stake, &mut user_snapshot, &state, &sums[1..]).is_err());
+
+        // Snapshots from before the deposit read the deposit's epoch and scale
+        let mut later = deposit(100, &state);
+        later.epoch_snapshot = 1;
+        assert_eq!(gain_epoch_scale_sums(&later, &user_snapshot), [(1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn test_settle_deposit_gains_sets_aside_every_denom() {
+        let mut state = pool(1_000);
+        state.current_scale = 2;
+        let owner = Pubkey::new_unique();
+        let mut stake = deposit(100, &state);
+        stake.owner = owner;
+
+        // SOL has paid out 0.5 per unit staked this scale, 0.2 of it before the staker's snapshot;
+        // ETH has never been liquidated, so it has no pool snapshot
+        let sol = "SOL";
+        let (pool_key, _) = Pubkey::find_program_address(&StabilityPoolSnapshot::seeds(sol), &crate::ID);
+        let (user_key, _) = Pubkey::find_program_address(&UserCollateralSnapshot::seeds(&owner, sol), &crate::ID);
+        let (eth_pool_key, _) = Pubkey::find_program_address(&StabilityPoolSnapshot::seeds("ETH"), &crate::ID);
+        let (sum_key, _) = epoch_scale_sum_address(sol, 0, 2);
+        let (next_sum_key, _) = epoch_scale_sum_address(sol, 0, 3);
+        let mut pool_data = account_data(&StabilityPoolSnapshot {
+            denom: sol.to_string(),
+            s_factor: Decimal::from_percent(50),
+            total_collateral_gained: 500,
+            epoch: 0,
+            scale: 2,
+        });
+        let mut user_data = account_data(&UserCollateralSnapshot {
+            owner,
+            denom: sol.to_string(),
+            s_snapshot: Decimal::from_percent(20),
+            pending_collateral_gain: 3,
+            epoch_snapshot: 0,
+            scale_snapshot: 2,
+        });
+        let mut sum_data = account_data(&epoch_scale_sum(sol, 0, 2, Decimal::from_percent(50)));
+        let (mut eth_pool_data, mut next_sum_data, mut payer_data) = ([], [], []);
+        let mut lamports = [0u64; 6];
+        let [pool_lamports, user_lamports, eth_pool_lamports, sum_lamports, next_sum_lamports, payer_lamports] = &mut lamports;
+        let payer_key = Pubkey::new_unique();
+        let accounts = [
+            AccountInfo::new(&pool_key, false, false, pool_lamports, &mut pool_data, &crate::ID, false, 0),
+            AccountInfo::new(&user_key, false, true, user_lamports, &mut user_data, &crate::ID, false, 0),
+            AccountInfo::new(&eth_pool_key, false, false, eth_pool_lamports, &mut eth_pool_data, &crate::ID, false, 0),
+            AccountInfo::new(&sum_key, false, false, sum_lamports, &mut sum_data, &crate::ID, false, 0),
+            AccountInfo::new(&next_sum_key, false, false, next_sum_lamports, &mut next_sum_data, &crate::ID, false, 0),
+        ];
+        let payer = AccountInfo::new(&payer_key, true, true, payer_lamports, &mut payer_data, &payer_key, false, 0);
+
+        let denoms = vec![sol.to_string(), "ETH".to_string()];
+        settle_deposit_gains(&stake, &state, &denoms, &owner, &payer, &accounts, &payer).unwrap();
+
+        // 100 × (0.5 - 0.2) is set aside, and the snapshot restarts at the pool's current S
+        let user_snapshot = UserCollateralSnapshot::try_deserialize(&mut &accounts[1].try_borrow_data().unwrap()[..]).unwrap();
+        assert_eq!(user_snapshot.pending_collateral_gain, 3 + 30);
+        assert_eq!(user_snapshot.s_snapshot, Decimal::from_percent(50));
+        assert_eq!((user_snapshot.epoch_snapshot, user_snapshot.scale_snapshot), (0, 2));
+
+        // Settling again before any liquidation sets nothing more aside
+        settle_deposit_gains(&stake, &state, &denoms, &owner, &payer, &accounts, &payer).unwrap();
+        let user_snapshot = UserCollateralSnapshot::try_deserialize(&mut &accounts[1].try_borrow_data().unwrap()[..]).unwrap();
+        assert_eq!(user_snapshot.pending_collateral_gain, 33);
+
+        // Every denom needs its pool snapshot passed
+        assert!(settle_deposit_gains(&stake, &state, &denoms, &owner, &payer, &accounts[1..], &payer).is_err());
+    }
+}
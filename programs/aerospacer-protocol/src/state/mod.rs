@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use crate::decimal::Decimal;
 
 // Exact replication of INJECTIVE state.rs
 // Main state account (equivalent to INJECTIVE's ADMIN, ORACLE_HELPER_ADDR, FEE_DISTRIBUTOR_ADDR, MINIMUM_COLLATERAL_RATIO, PROTOCOL_FEE, STABLE_COIN_ADDR, TOTAL_DEBT_AMOUNT, TOTAL_STAKE_AMOUNT)
@@ -10,21 +11,29 @@ pub struct StateAccount {
     pub fee_distributor_addr: Pubkey,        // aerospacer-fees program ID
     pub fee_state_addr: Pubkey,              // aerospacer-fees state account address
     pub minimum_collateral_ratio: u8,
-    pub protocol_fee: u8,
+    pub protocol_fee: u8,                    // Cap on the borrowing fee, in percent
     pub stable_coin_addr: Pubkey,
     pub total_debt_amount: u64, // Equivalent to Uint256
     pub total_stake_amount: u64, // Equivalent to Uint256
     
     // Stability Pool Snapshot Variables (Liquity Product-Sum Algorithm)
-    pub p_factor: u128,  // Product/depletion factor - tracks cumulative pool depletion from debt burns (starts at SCALE_FACTOR)
+    pub p_factor: Decimal,  // Product/depletion factor - tracks cumulative pool depletion from debt burns (starts at 1.0)
     pub epoch: u64,      // Current epoch - increments when pool is completely depleted to 0
+    pub current_scale: u64, // Times P was multiplied by SCALE_FACTOR this epoch to keep it from underflowing
+
+    // Admin parameter timelock
+    pub parameter_timelock_delay: i64, // Seconds a queued MCR/fee change must wait before it can be executed
+
+    // Liquity-style base rate driving the borrowing and redemption fees
+    pub base_rate: Decimal,              // Fraction raised by redemptions and decaying per minute
+    pub last_fee_operation_time: i64,    // Unix timestamp the base rate was last decayed from
+
+    // Slots a stability pool deposit must rest after it last changed before it can be unstaked (0 disables)
+    pub unstake_cooldown_slots: u64,
 }
 
 impl StateAccount {
-    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 32 + 1 + 1 + 32 + 8 + 8 + 16 + 8; // Added oracle_state_addr + fee_state_addr
-    
-    // Scale factor for precision in P/S calculations (10^18, same as Liquity)
-    pub const SCALE_FACTOR: u128 = 1_000_000_000_000_000_000;
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 32 + 1 + 1 + 32 + 8 + 8 + Decimal::LEN + 8 + 8 + 8 + Decimal::LEN + 8 + 8; // Added unstake_cooldown_slots
     
     pub fn seeds() -> [&'static [u8]; 1] {
         [b"state"]
@@ -36,13 +45,40 @@ impl StateAccount {
 pub struct UserDebtAmount {
     pub owner: Pubkey,
     pub amount: u64, // Equivalent to Uint256
+    pub collateral_denoms: Vec<String>, // Every denom this trove holds collateral in (ICR is aggregated across all of them)
 }
 
 impl UserDebtAmount {
-    pub const LEN: usize = 8 + 32 + 8;
+    pub const LEN: usize = 8 + 32 + 8 + 4 + MAX_TROVE_COLLATERAL_DENOMS * (4 + 32); // collateral_denoms: vec prefix + up to MAX denoms of 32 bytes
     pub fn seeds(owner: &Pubkey) -> [&[u8]; 2] {
         [b"user_debt_amount", owner.as_ref()]
     }
+
+    pub fn holds_denom(&self, denom: &str) -> bool {
+        self.collateral_denoms.iter().any(|d| d == denom)
+    }
+
+    // Record a newly deposited collateral denom for this trove
+    pub fn add_collateral_denom(&mut self, denom: &str) -> Result<()> {
+        if self.holds_denom(denom) {
+            return Ok(());
+        }
+        require!(
+            self.collateral_denoms.len() < MAX_TROVE_COLLATERAL_DENOMS,
+            crate::error::AerospacerProtocolError::TooManyCollateralDenoms
+        );
+        require!(
+            denom.len() <= MAX_DENOM_LEN,
+            crate::error::AerospacerProtocolError::DenomTooLong
+        );
+        self.collateral_denoms.push(denom.to_string());
+        Ok(())
+    }
+
+    // Forget a denom once the trove's balance in it reaches zero
+    pub fn remove_collateral_denom(&mut self, denom: &str) {
+        self.collateral_denoms.retain(|d| d != denom);
+    }
 }
 
 // User collateral amount (equivalent to INJECTIVE's USER_COLLATERAL_AMOUNT: Map<(Addr, String), Uint256>)
@@ -51,10 +87,12 @@ pub struct UserCollateralAmount {
     pub owner: Pubkey,
     pub denom: String,
     pub amount: u64, // Equivalent to Uint256
+    pub l_collateral_snapshot: Decimal, // RedistributionSnapshot.l_collateral when redistribution rewards were last applied
+    pub l_debt_snapshot: Decimal,       // RedistributionSnapshot.l_debt when redistribution rewards were last applied
 }
 
 impl UserCollateralAmount {
-    pub const LEN: usize = 8 + 32 + 32 + 8; // String length needs to be considered
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 16 + 16; // String length needs to be considered; added L snapshots
     pub fn seeds<'a>(owner: &'a Pubkey, denom: &'a str) -> [&'a [u8]; 3] {
         [b"user_collateral_amount", owner.as_ref(), denom.as_bytes()]
     }
@@ -65,13 +103,14 @@ impl UserCollateralAmount {
 pub struct UserStakeAmount {
     pub owner: Pubkey,
     pub amount: u64,                    // Current staked amount
-    pub p_snapshot: u128,               // User's P factor snapshot at last deposit (for compounded stake calculation)
+    pub p_snapshot: Decimal,            // User's P factor snapshot at last deposit (for compounded stake calculation)
     pub epoch_snapshot: u64,            // Epoch when user last deposited (for epoch transition tracking)
+    pub scale_snapshot: u64,            // StateAccount.current_scale when user last deposited
     pub last_update_block: u64,         // Last block when stake was updated
 }
 
 impl UserStakeAmount {
-    pub const LEN: usize = 8 + 32 + 8 + 16 + 8 + 8; // Added p_snapshot(16) + epoch_snapshot(8) + last_update_block(8)
+    pub const LEN: usize = 8 + 32 + 8 + 16 + 8 + 8 + 8; // Added p_snapshot(16) + epoch_snapshot(8) + scale_snapshot(8) + last_update_block(8)
     pub fn seeds(owner: &Pubkey) -> [&[u8]; 2] {
         [b"user_stake_amount", owner.as_ref()]
     }
@@ -81,14 +120,56 @@ impl UserStakeAmount {
 #[account]
 pub struct LiquidityThreshold {
     pub owner: Pubkey,
-    pub ratio: u64, // Equivalent to Decimal256
+    pub ratio: Decimal, // The trove's ICR (1.5 = 150%)
+    pub nominal_ratios: Vec<NominalRatio>, // The trove's NICR in every denom it holds - the sorted list order
 }
 
 impl LiquidityThreshold {
-    pub const LEN: usize = 8 + 32 + 8;
+    pub const LEN: usize = 8 + 32 + Decimal::LEN + 4 + MAX_TROVE_COLLATERAL_DENOMS * NominalRatio::LEN;
     pub fn seeds(owner: &Pubkey) -> [&[u8]; 2] {
         [b"liquidity_threshold", owner.as_ref()]
     }
+
+    pub fn nominal_ratio(&self, denom: &str) -> Option<Decimal> {
+        self.nominal_ratios.iter().find(|n| n.denom == denom).map(|n| n.ratio)
+    }
+
+    // Recompute the NICR of every denom the trove holds once its collateral or debt changes
+    // (a trove without debt is in no sorted list)
+    pub fn set_nominal_ratios(&mut self, collateral_amounts: &[(String, u64)], debt_amount: u64) -> Result<()> {
+        if debt_amount == 0 {
+            self.nominal_ratios.clear();
+            return Ok(());
+        }
+        self.nominal_ratios = collateral_amounts
+            .iter()
+            .filter(|(_, amount)| *amount > 0)
+            .map(|(denom, amount)| {
+                Ok(NominalRatio {
+                    denom: denom.clone(),
+                    ratio: crate::sorted_troves::compute_nominal_icr(*amount, debt_amount)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(())
+    }
+
+    // Forget both ratios once the trove is closed or liquidated
+    pub fn clear(&mut self) {
+        self.ratio = Decimal::ZERO;
+        self.nominal_ratios.clear();
+    }
+}
+
+// A trove's nominal collateral ratio in one denom (see sorted_troves::compute_nominal_icr)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NominalRatio {
+    pub denom: String,
+    pub ratio: Decimal,
+}
+
+impl NominalRatio {
+    pub const LEN: usize = 4 + 32 + Decimal::LEN;
 }
 
 // Total collateral amount (equivalent to INJECTIVE's TOTAL_COLLATERAL_AMOUNT: Map<String, Uint256>)
@@ -145,47 +226,279 @@ impl TotalLiquidationCollateralGain {
 // - Contract validates ICR ordering without storing linked list
 
 // Stability Pool Snapshot - tracks cumulative collateral rewards per denomination
-// This is the global "S" factor from Liquity's Product-Sum algorithm
+// This is the global "S" factor from Liquity's Product-Sum algorithm, for the latest (epoch, scale)
+// the denom was credited in; every (epoch, scale) keeps its own S in an EpochScaleSum
 #[account]
 pub struct StabilityPoolSnapshot {
     pub denom: String,                  // Collateral denomination (e.g., "SOL", "USDC")
-    pub s_factor: u128,                 // Sum: cumulative collateral-per-unit-staked (scaled by SCALE_FACTOR)
-    pub total_collateral_gained: u64,  // Total collateral seized and distributed this epoch
-    pub epoch: u64,                     // Current epoch (resets when pool depletes to 0)
+    pub s_factor: Decimal,              // Sum: cumulative collateral-per-unit-staked in (epoch, scale)
+    pub total_collateral_gained: u64,  // Total collateral seized and distributed to the pool
+    pub epoch: u64,                     // Epoch s_factor belongs to
+    pub scale: u64,                     // Scale s_factor belongs to
 }
 
 impl StabilityPoolSnapshot {
-    pub const LEN: usize = 8 + 32 + 16 + 8 + 8; // denom(32) + s_factor(16) + total(8) + epoch(8)
+    pub const LEN: usize = 8 + 32 + 16 + 8 + 8 + 8; // denom(32) + s_factor(16) + total(8) + epoch(8) + scale(8)
     
     pub fn seeds(denom: &str) -> [&[u8]; 2] {
         [b"stability_pool_snapshot", denom.as_bytes()]
     }
 }
 
+// S of one collateral denomination for one (epoch, scale) of the stability pool
+// (Liquity's epochToScaleToSum). Created by the first liquidation crediting the denom in that scale.
+#[account]
+pub struct EpochScaleSum {
+    pub denom: String,
+    pub epoch: u64,
+    pub scale: u64,
+    pub s_factor: Decimal,              // Cumulative collateral-per-unit-staked within this (epoch, scale)
+}
+
+impl EpochScaleSum {
+    pub const LEN: usize = 8 + 4 + 32 + 8 + 8 + 16; // denom(32) + epoch(8) + scale(8) + s_factor(16)
+
+    pub fn seeds<'a>(denom: &'a str, epoch_bytes: &'a [u8; 8], scale_bytes: &'a [u8; 8]) -> [&'a [u8]; 4] {
+        [b"epoch_scale_sum", denom.as_bytes(), epoch_bytes, scale_bytes]
+    }
+}
+
+// Optional spAUSD wrapper around the stability pool: one deposit (the UserStakeAmount seeded by this
+// account's address) held for every holder of share_mint, whose SPL shares redeem pro rata for the
+// compounded deposit and the collateral gains it has earned
+#[account]
+pub struct SharePool {
+    pub share_mint: Pubkey,
+    pub bump: u8,
+}
+
+impl SharePool {
+    pub const LEN: usize = 32 + 1; // share_mint(32) + bump(1)
+
+    pub fn seeds() -> [&'static [u8]; 1] {
+        [b"share_pool"]
+    }
+}
+
+// Shares a depositor minted from the share pool that still wait out the unstake cooldown, held in the
+// SHARE_ESCROW_SEED token account so they can neither be redeemed nor passed on before it ends
+#[account]
+pub struct ShareLock {
+    pub owner: Pubkey,
+    pub shares: u64,      // Shares held in escrow for owner
+    pub unlock_slot: u64, // Slot from which they may be released to owner
+}
+
+impl ShareLock {
+    pub const LEN: usize = 32 + 8 + 8; // owner(32) + shares(8) + unlock_slot(8)
+
+    pub fn seeds(owner: &Pubkey) -> [&[u8]; 2] {
+        [b"share_lock", owner.as_ref()]
+    }
+}
+
 // User Collateral Snapshot - tracks user's S snapshot for each collateral type
 // Captures the S value when user stakes, enabling gain calculation on withdrawal
 #[account]
 pub struct UserCollateralSnapshot {
     pub owner: Pubkey,
     pub denom: String,
-    pub s_snapshot: u128,               // User's S factor snapshot at last deposit
+    pub s_snapshot: Decimal,            // User's S factor snapshot at last deposit
     pub pending_collateral_gain: u64,  // Unclaimed gains from previous epochs
+    pub epoch_snapshot: u64,            // Epoch s_snapshot was taken in
+    pub scale_snapshot: u64,            // Scale s_snapshot was taken in
 }
 
 impl UserCollateralSnapshot {
-    pub const LEN: usize = 8 + 32 + 32 + 16 + 8; // owner(32) + denom(32) + s_snapshot(16) + pending(8)
+    pub const LEN: usize = 8 + 32 + 32 + 16 + 8 + 8 + 8; // owner(32) + denom(32) + s_snapshot(16) + pending(8) + epoch(8) + scale(8)
     
     pub fn seeds<'a>(owner: &'a Pubkey, denom: &'a str) -> [&'a [u8]; 3] {
         [b"user_collateral_snapshot", owner.as_ref(), denom.as_bytes()]
     }
 }
 
+// Redistribution Snapshot - tracks liquidated debt and collateral redistributed to active troves per denomination
+// These are the global "L" factors from Liquity (L_ETH / L_LUSDDebt); a trove's stake in a denom is its collateral in it
+#[account]
+pub struct RedistributionSnapshot {
+    pub denom: String,                  // Collateral denomination (e.g., "SOL", "USDC")
+    pub l_collateral: Decimal,          // Cumulative redistributed collateral per unit of stake
+    pub l_debt: Decimal,                // Cumulative redistributed debt per unit of stake
+    pub total_stakes: u64,              // Collateral held in this denom by active troves, with rewards applied
+}
+
+impl RedistributionSnapshot {
+    pub const LEN: usize = 8 + 32 + 16 + 16 + 8; // denom(32) + l_collateral(16) + l_debt(16) + total_stakes(8)
+
+    pub fn seeds(denom: &str) -> [&[u8]; 2] {
+        [b"redistribution_snapshot", denom.as_bytes()]
+    }
+
+    // Replace a trove's stake in this denom once its collateral changes
+    pub fn update_stake(&mut self, old_stake: u64, new_stake: u64) -> Result<()> {
+        self.total_stakes = self.total_stakes
+            .saturating_sub(old_stake)
+            .checked_add(new_stake)
+            .ok_or(crate::error::AerospacerProtocolError::OverflowError)?;
+        Ok(())
+    }
+}
+
+// Directory of the active troves opened with one denom, so clients can enumerate them without
+// getProgramAccounts. The owners live in TroveRegistryPage PDAs chained by page_index (0..page_count).
+#[account]
+pub struct TroveRegistry {
+    pub denom: String,
+    pub page_count: u32,   // Pages created so far - the next page is page_count
+    pub trove_count: u64,  // Troves listed across all pages
+}
+
+impl TroveRegistry {
+    pub const LEN: usize = 8 + 4 + 32 + 4 + 8; // denom(32) + page_count(4) + trove_count(8)
+
+    pub fn seeds(denom: &str) -> [&[u8]; 2] {
+        [b"trove_registry", denom.as_bytes()]
+    }
+}
+
+// One fixed-size page of a TroveRegistry. Pages are never closed - slots freed by closed or
+// liquidated troves are reused by later open_trove calls.
+#[account]
+pub struct TroveRegistryPage {
+    pub denom: String,
+    pub page_index: u32,
+    pub owners: Vec<Pubkey>, // Up to TROVE_REGISTRY_PAGE_SIZE trove owners
+}
+
+impl TroveRegistryPage {
+    pub const LEN: usize = 8 + 4 + 32 + 4 + 4 + TROVE_REGISTRY_PAGE_SIZE * 32;
+
+    pub fn seeds<'a>(denom: &'a str, page_index_bytes: &'a [u8; 4]) -> [&'a [u8]; 3] {
+        [b"trove_registry_page", denom.as_bytes(), page_index_bytes]
+    }
+}
+
+// Risk configuration per collateral denomination (created and updated by the admin)
+// Volatile and stable collateral need different ratios, so every trove instruction reads its limits from here
+#[account]
+#[derive(Debug)]
+pub struct CollateralConfig {
+    pub denom: String,                   // Collateral denomination (e.g., "SOL", "USDC")
+    pub minimum_collateral_ratio: u8,    // Ratio a trove must keep after opening, borrowing or withdrawing (percent)
+    pub liquidation_threshold: u8,       // Troves below this ratio can be liquidated (percent)
+    pub liquidation_penalty_bps: u16,    // Share of a liquidated trove's collateral taken as penalty
+    pub treasury_share_bps: u16,         // Share of the penalty, after the liquidator's slice, sent to the treasury
+    pub debt_ceiling: u64,               // Most aUSD debt the denom may back
+    pub deposit_cap: u64,                // Most collateral of this denom the protocol may hold
+    pub decimals: u8,                    // Decimals of the collateral mint
+    pub enabled: bool,                   // Disabled denoms cannot back new troves, deposits or borrows
+}
+
+impl CollateralConfig {
+    pub const LEN: usize = 8 + 32 + 1 + 1 + 2 + 2 + 8 + 8 + 1 + 1; // denom(32) + ratios(2) + bps(4) + caps(16) + decimals(1) + enabled(1)
+
+    pub fn seeds(denom: &str) -> [&[u8]; 2] {
+        [b"collateral_config", denom.as_bytes()]
+    }
+}
+
+// Protocol parameters that the admin can change through the timelock
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtocolParameter {
+    MinimumCollateralRatio,
+    ProtocolFee,
+    ParameterTimelockDelay,
+    UnstakeCooldown,
+}
+
+impl ProtocolParameter {
+    pub fn seed(&self) -> &'static [u8] {
+        match self {
+            ProtocolParameter::MinimumCollateralRatio => b"minimum_collateral_ratio",
+            ProtocolParameter::ProtocolFee => b"protocol_fee",
+            ProtocolParameter::ParameterTimelockDelay => b"parameter_timelock_delay",
+            ProtocolParameter::UnstakeCooldown => b"unstake_cooldown",
+        }
+    }
+}
+
+// Pending parameter change - queued by the admin, applied by execute_parameter_change once executable_at has passed
+#[account]
+pub struct PendingParameterChange {
+    pub parameter: ProtocolParameter,
+    pub new_value: u64,
+    pub queued_at: i64,       // Unix timestamp when the change was queued
+    pub executable_at: i64,   // Unix timestamp after which the change can be applied
+}
+
+impl PendingParameterChange {
+    pub const LEN: usize = 1 + 8 + 8 + 8; // parameter(1) + new_value(8) + queued_at(8) + executable_at(8)
+}
+
 // Constants to match INJECTIVE exactly
-pub const MINIMUM_LOAN_AMOUNT: u64 = 1_000_000_000_000_000; // 0.001 aUSD with 18 decimals
+pub const AUSD_DECIMALS: u8 = 6; // u64 amounts then hold up to ~18.4 trillion aUSD
+pub const MINIMUM_LOAN_AMOUNT: u64 = 1_000; // 0.001 aUSD with 6 decimals
 pub const MINIMUM_COLLATERAL_AMOUNT: u64 = 1_000_000; // 0.001 SOL with 9 decimals
 pub const DEFAULT_MINIMUM_COLLATERAL_RATIO: u8 = 115; // 115%
 pub const DEFAULT_PROTOCOL_FEE: u8 = 5; // 5%
+pub const CRITICAL_COLLATERAL_RATIO: Decimal = Decimal::from_percent(150); // System TCR below 150% puts the protocol in Recovery Mode
+pub const MAX_TROVE_COLLATERAL_DENOMS: usize = 4; // Bounded so every denom of a trove fits in one transaction
+pub const MAX_DENOM_LEN: usize = 32; // Bytes allowed per denom in the trove accounts' LEN
+pub const TROVE_REGISTRY_PAGE_SIZE: usize = 25; // A full page fits in one query's 1024-byte return data
+pub const SCALE_FACTOR: u128 = 1_000_000_000; // P is multiplied by this (and the scale bumped) once it drops below 1e-9
+
+// Token vault PDA seeds - the vaults are SPL token accounts, so there is no account struct to hold them
+pub const PROTOCOL_STABLECOIN_VAULT_SEED: &[u8] = b"protocol_stablecoin_vault";
+pub const PROTOCOL_COLLATERAL_VAULT_SEED: &[u8] = b"protocol_collateral_vault"; // Followed by the denom
+pub const GAS_COMPENSATION_VAULT_SEED: &[u8] = b"gas_compensation_vault";
+pub const SHARE_MINT_SEED: &[u8] = b"share_mint"; // spAUSD, minted by the SharePool
+pub const SHARE_ESCROW_SEED: &[u8] = b"share_escrow"; // spAUSD token account holding every ShareLock's shares
+
+// Liquidation incentives
+pub const GAS_COMPENSATION: u64 = 1_000_000; // 1 aUSD reserved at open_trove and paid to the liquidator
+pub const LIQUIDATOR_COLLATERAL_BPS: u16 = 50; // 0.5% of a liquidated trove's collateral goes to the liquidator
+pub const MAX_LIQUIDATION_PENALTY_BPS: u16 = 2_000; // 20%
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+// Hard bounds for admin-controlled parameters
+pub const MIN_MINIMUM_COLLATERAL_RATIO: u8 = 110; // Protocol-wide floor under every denom's own MCR
+pub const MAX_MINIMUM_COLLATERAL_RATIO: u8 = 250; // 250%
+pub const MIN_LIQUIDATION_THRESHOLD: u8 = 101; // A liquidated trove must still cover its debt
+pub const MIN_PROTOCOL_FEE: u8 = 1; // Never below the 0.5% borrowing fee floor
+pub const MAX_PROTOCOL_FEE: u8 = 10; // 10%
+pub const DEFAULT_PARAMETER_TIMELOCK_DELAY: i64 = 172_800; // 48 hours
+pub const MIN_PARAMETER_TIMELOCK_DELAY: i64 = 3_600; // 1 hour
+pub const MAX_PARAMETER_TIMELOCK_DELAY: i64 = 2_592_000; // 30 days
+pub const MAX_UNSTAKE_COOLDOWN_SLOTS: u64 = 216_000; // ~1 day of 400ms slots
+
+// Base rate fee model (same as Liquity)
+pub const BORROWING_FEE_FLOOR: Decimal = Decimal::from_bps(50); // 0.5%
+pub const REDEMPTION_FEE_FLOOR: Decimal = Decimal::from_bps(50); // 0.5%
+pub const MAX_REDEMPTION_FEE: Decimal = Decimal::ONE; // 100%
+pub const MINUTE_DECAY_FACTOR: Decimal = Decimal::from_raw(999_037_758_833_783_000); // Base rate half-life of 12 hours
+pub const BASE_RATE_BETA: u128 = 2; // A redemption raises the base rate by half its share of the supply
+pub const MAX_DECAY_MINUTES: u64 = 525_600_000; // 1000 years - bounds the decay exponentiation
 
 // Decimal fractions to match INJECTIVE
 pub const DECIMAL_FRACTION_6: u128 = 1_000_000;
-pub const DECIMAL_FRACTION_18: u128 = 1_000_000_000_000_000_000;
\ No newline at end of file
+pub const DECIMAL_FRACTION_18: u128 = 1_000_000_000_000_000_000;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collateral_denoms_fit_user_debt_amount_len() {
+        let mut trove = UserDebtAmount { owner: Pubkey::new_unique(), amount: 0, collateral_denoms: vec![] };
+        assert!(trove.add_collateral_denom(&"X".repeat(MAX_DENOM_LEN + 1)).is_err());
+
+        // A trove holding the most denoms of the longest length still fits its account
+        for i in 0..MAX_TROVE_COLLATERAL_DENOMS {
+            trove.add_collateral_denom(&format!("{:0>32}", i)).unwrap();
+        }
+        assert!(trove.add_collateral_denom("SOL").is_err());
+        let mut data = Vec::new();
+        trove.try_serialize(&mut data).unwrap();
+        assert!(data.len() <= UserDebtAmount::LEN);
+    }
+}
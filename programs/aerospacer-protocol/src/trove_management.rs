@@ -1,11 +1,21 @@
+//! Trove management utilities
+//! This module provides clean, type-safe trove operations
+
 use anchor_lang::prelude::*;
 use crate::state::*;
 use crate::error::*;
 use crate::oracle::*;
 use crate::account_management::*;
-
-/// Trove management utilities
-/// This module provides clean, type-safe trove operations
+use crate::decimal::{Decimal, Rounding};
+use crate::fees_integration::{distribute_collateral_fee, FeeRecipientAccounts};
+use anchor_spl::token::TokenAccount;
+use crate::utils::get_liquidation_threshold;
+use crate::events::TroveLiquidated;
+use crate::redistribution::{
+    apply_trove_rewards_by_address, find_redistribution_snapshot_account, pending_position_rewards,
+    redistribute_trove_by_address, split_liquidation,
+};
+use crate::stability_pool::absorb_liquidation;
 
 /// Trove operation result
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
@@ -13,10 +23,39 @@ pub struct TroveOperationResult {
     pub success: bool,
     pub new_debt_amount: u64,
     pub new_collateral_amount: u64,
-    pub new_icr: u64,
+    pub new_icr: Decimal,
     pub message: String,
 }
 
+/// Collateral and debt changes applied together by adjust_trove
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TroveAdjustment {
+    pub collateral_change: u64,
+    pub is_collateral_increase: bool,
+    pub debt_change: u64, // Debt added to or repaid from the trove
+    pub is_debt_increase: bool,
+}
+
+impl TroveAdjustment {
+    /// Whether the adjustment can only lower the trove's ICR and the TCR
+    pub fn lowers_collateralization(&self) -> bool {
+        (self.collateral_change > 0 && !self.is_collateral_increase)
+            || (self.debt_change > 0 && self.is_debt_increase)
+    }
+}
+
+/// Check that a partial repayment leaves the trove's gas compensation reserve in place
+///
+/// The reserve is only released by repay_loan or close_trove clearing the trove.
+pub fn check_repayment_above_gas_compensation(debt_amount: u64, repayment: u64) -> Result<()> {
+    let gas_compensation = debt_amount.min(GAS_COMPENSATION);
+    require!(
+        repayment < debt_amount - gas_compensation,
+        AerospacerProtocolError::DebtBelowGasCompensation
+    );
+    Ok(())
+}
+
 /// Liquidation operation result
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct LiquidationResult {
@@ -24,6 +63,9 @@ pub struct LiquidationResult {
     pub total_debt_liquidated: u64,
     pub total_collateral_gained: u64,
     pub liquidation_gains: Vec<(String, u64)>, // Changed from HashMap to Vec for Anchor compatibility
+    pub liquidator_collateral: Vec<(String, u64)>, // Liquidator's slice of the penalty, per denom
+    pub treasury_collateral: Vec<(String, u64)>,   // Treasury's share of the penalty, per denom
+    pub gas_compensation: u64,                     // aUSD reserve owed to the liquidator
 }
 
 /// Trove manager for handling all trove operations
@@ -31,10 +73,13 @@ pub struct TroveManager;
 
 impl TroveManager {
     /// Open a new trove
+    #[allow(clippy::too_many_arguments)]
     pub fn open_trove(
         trove_ctx: &mut TroveContext,
         collateral_ctx: &mut CollateralContext,
         oracle_ctx: &OracleContext,
+        config: &CollateralConfig,
+        system_collateral: &SystemCollateral,
         loan_amount: u64,
         collateral_amount: u64,
         collateral_denom: String,
@@ -50,6 +95,10 @@ impl TroveManager {
             AerospacerProtocolError::CollateralBelowMinimum
         );
         
+        // The denom must be accepting new collateral and have room under its deposit cap
+        require!(config.enabled, AerospacerProtocolError::CollateralDisabled);
+        check_deposit_cap(config, collateral_ctx.total_collateral_amount.amount, collateral_amount)?;
+        
         // Get collateral price
         let price_data = oracle_ctx.get_price(&collateral_denom)?;
         oracle_ctx.validate_price(&price_data)?;
@@ -61,37 +110,51 @@ impl TroveManager {
             price_data.decimal,
         )?;
         
-        msg!("DEBUG - Collateral amount: {}", collateral_amount);
-        msg!("DEBUG - Price: {}", price_data.price);
-        msg!("DEBUG - Price decimal: {}", price_data.decimal);
-        msg!("DEBUG - Calculated collateral value: {}", collateral_value);
-        msg!("DEBUG - Loan amount: {}", loan_amount);
-        
         // Calculate ICR using proper calculation
         let icr = PriceCalculator::calculate_collateral_ratio(
             collateral_value,
             loan_amount,
         )?;
         
-        msg!("DEBUG - Calculated ICR: {}", icr);
-        msg!("DEBUG - Minimum ICR required: {}", trove_ctx.state.minimum_collateral_ratio);
+        // Check the denom's minimum collateral ratio (never below the protocol-wide one)
+        let minimum_ratio = Decimal::from_percent(trove_ctx.state.minimum_collateral_ratio.max(config.minimum_collateral_ratio) as u64);
         
-        // Check minimum collateral ratio
-        let minimum_ratio = trove_ctx.state.minimum_collateral_ratio as u64;
         require!(
             icr >= minimum_ratio,
             AerospacerProtocolError::CollateralBelowMinimum
         );
         
+        let total_debt_amount = trove_ctx.state.total_debt_amount;
+        let new_total_debt_amount = total_debt_amount
+            .checked_add(loan_amount)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+        let mut system_after = system_collateral.clone();
+        system_after.add_collateral(&collateral_denom, collateral_amount, &price_data)?;
+        
+        // In Recovery Mode a trove may only be opened at an ICR of at least the CCR;
+        // outside it the new trove may not push the system into Recovery Mode
+        if system_collateral.is_recovery_mode(total_debt_amount)? {
+            require!(
+                icr >= CRITICAL_COLLATERAL_RATIO,
+                AerospacerProtocolError::IcrBelowCriticalRatio
+            );
+        } else {
+            require!(
+                system_after.tcr(new_total_debt_amount)? >= CRITICAL_COLLATERAL_RATIO,
+                AerospacerProtocolError::TcrBelowCriticalRatio
+            );
+        }
+        
+        // The new debt may not push the denom's share of the system debt over its ceiling
+        system_after.check_debt_ceiling(config, new_total_debt_amount)?;
+        
         // Update accounts
         trove_ctx.update_debt_amount(loan_amount)?;
         trove_ctx.update_liquidity_threshold(icr)?;
         collateral_ctx.update_collateral_amount(collateral_amount)?;
         
         // Update state
-        trove_ctx.state.total_debt_amount = trove_ctx.state.total_debt_amount
-            .checked_add(loan_amount)
-            .ok_or(AerospacerProtocolError::OverflowError)?;
+        trove_ctx.state.total_debt_amount = new_total_debt_amount;
         
         // Transfer collateral to protocol
         collateral_ctx.transfer_to_protocol(collateral_amount)?;
@@ -113,6 +176,8 @@ impl TroveManager {
         trove_ctx: &mut TroveContext,
         collateral_ctx: &mut CollateralContext,
         oracle_ctx: &OracleContext,
+        config: &CollateralConfig,
+        other_collateral: &OtherCollateral,
         additional_amount: u64,
         collateral_denom: String,
     ) -> Result<TroveOperationResult> {
@@ -120,6 +185,10 @@ impl TroveManager {
         let trove_info = trove_ctx.get_trove_info()?;
         let collateral_info = collateral_ctx.get_collateral_info()?;
         
+        // The denom must be accepting new collateral and have room under its deposit cap
+        require!(config.enabled, AerospacerProtocolError::CollateralDisabled);
+        check_deposit_cap(config, collateral_ctx.total_collateral_amount.amount, additional_amount)?;
+        
         // Get collateral price
         let price_data = oracle_ctx.get_price(&collateral_denom)?;
         oracle_ctx.validate_price(&price_data)?;
@@ -129,21 +198,18 @@ impl TroveManager {
             .checked_add(additional_amount)
             .ok_or(AerospacerProtocolError::OverflowError)?;
         
-        // Calculate new collateral value
-        let new_collateral_value = PriceCalculator::calculate_collateral_value(
+        // Calculate new ICR across all of the trove's collateral
+        let new_icr = other_collateral.trove_icr(
+            &collateral_denom,
             new_collateral_amount,
-            price_data.price as u64, // Convert i64 to u64
-            price_data.decimal,
-        )?;
-        
-        // Calculate new ICR
-        let new_icr = PriceCalculator::calculate_collateral_ratio(
-            new_collateral_value,
+            &price_data,
             trove_info.debt_amount,
         )?;
         
-        // Check minimum collateral ratio (both are simple percentages)
-        let minimum_ratio = trove_ctx.state.minimum_collateral_ratio as u64;
+        // Check the trove's minimum collateral ratio (both are 18-decimal fixed-point ratios)
+        let minimum_ratio = other_collateral
+            .risk_ratios(config, new_collateral_amount, &price_data, trove_ctx.state.minimum_collateral_ratio)?
+            .minimum_collateral_ratio;
         require!(
             new_icr >= minimum_ratio,
             AerospacerProtocolError::CollateralBelowMinimum
@@ -161,17 +227,21 @@ impl TroveManager {
         Ok(TroveOperationResult {
             success: true,
             new_debt_amount: trove_info.debt_amount,
-            new_collateral_amount: new_collateral_amount,
-            new_icr: new_icr,
+            new_collateral_amount,
+            new_icr,
             message: "Collateral added successfully".to_string(),
         })
     }
     
     /// Remove collateral from existing trove
+    #[allow(clippy::too_many_arguments)]
     pub fn remove_collateral(
         trove_ctx: &mut TroveContext,
         collateral_ctx: &mut CollateralContext,
         oracle_ctx: &OracleContext,
+        config: &CollateralConfig,
+        other_collateral: &OtherCollateral,
+        system_collateral: &SystemCollateral,
         remove_amount: u64,
         collateral_denom: String,
         bump: u8,
@@ -195,32 +265,44 @@ impl TroveManager {
             .checked_sub(remove_amount)
             .ok_or(AerospacerProtocolError::OverflowError)?;
         
-        // Check minimum collateral amount
+        // Check minimum collateral amount - a denom may only be emptied
+        // entirely while the trove still holds collateral in another denom
         require!(
-            new_collateral_amount >= MINIMUM_COLLATERAL_AMOUNT,
+            new_collateral_amount >= MINIMUM_COLLATERAL_AMOUNT
+                || (new_collateral_amount == 0 && !other_collateral.is_empty()),
             AerospacerProtocolError::CollateralBelowMinimum
         );
         
-        // Calculate new collateral value
-        let new_collateral_value = PriceCalculator::calculate_collateral_value(
+        // Calculate new ICR across all of the trove's collateral
+        let new_icr = other_collateral.trove_icr(
+            &collateral_denom,
             new_collateral_amount,
-            price_data.price as u64, // Convert i64 to u64
-            price_data.decimal,
-        )?;
-        
-        // Calculate new ICR
-        let new_icr = PriceCalculator::calculate_collateral_ratio(
-            new_collateral_value,
+            &price_data,
             trove_info.debt_amount,
         )?;
         
-        // Check minimum collateral ratio (both are simple percentages)
-        let minimum_ratio = trove_ctx.state.minimum_collateral_ratio as u64;
+        // Check the trove's minimum collateral ratio (both are 18-decimal fixed-point ratios)
+        let minimum_ratio = other_collateral
+            .risk_ratios(config, new_collateral_amount, &price_data, trove_ctx.state.minimum_collateral_ratio)?
+            .minimum_collateral_ratio;
         require!(
             new_icr >= minimum_ratio,
             AerospacerProtocolError::CollateralBelowMinimum
         );
         
+        // Withdrawals always lower the TCR: none are allowed in Recovery Mode,
+        // and outside it a withdrawal may not push the system into Recovery Mode
+        let total_debt_amount = trove_ctx.state.total_debt_amount;
+        require!(
+            !system_collateral.is_recovery_mode(total_debt_amount)?,
+            AerospacerProtocolError::NotAllowedInRecoveryMode
+        );
+        let new_tcr = system_collateral.tcr_after_withdrawal(&collateral_denom, remove_amount, total_debt_amount)?;
+        require!(
+            new_tcr >= CRITICAL_COLLATERAL_RATIO,
+            AerospacerProtocolError::TcrBelowCriticalRatio
+        );
+        
         // Update accounts
         collateral_ctx.update_collateral_amount(new_collateral_amount)?;
         trove_ctx.update_liquidity_threshold(new_icr)?;
@@ -232,8 +314,8 @@ impl TroveManager {
         Ok(TroveOperationResult {
             success: true,
             new_debt_amount: trove_info.debt_amount,
-            new_collateral_amount: new_collateral_amount,
-            new_icr: new_icr,
+            new_collateral_amount,
+            new_icr,
             message: "Collateral removed successfully".to_string(),
         })
     }
@@ -243,6 +325,9 @@ impl TroveManager {
         trove_ctx: &mut TroveContext,
         collateral_ctx: &mut CollateralContext,
         oracle_ctx: &OracleContext,
+        config: &CollateralConfig,
+        other_collateral: &OtherCollateral,
+        system_collateral: &SystemCollateral,
         additional_loan_amount: u64,
     ) -> Result<TroveOperationResult> {
         // Get current trove info
@@ -254,55 +339,236 @@ impl TroveManager {
             .checked_add(additional_loan_amount)
             .ok_or(AerospacerProtocolError::OverflowError)?;
         
+        // No new debt may be drawn against a disabled denom
+        require!(
+            config.enabled && other_collateral.configs.iter().all(|c| c.enabled),
+            AerospacerProtocolError::CollateralDisabled
+        );
+        
         // Get collateral price
         let price_data = oracle_ctx.get_price(&collateral_info.denom)?;
         oracle_ctx.validate_price(&price_data)?;
         
-        // Calculate collateral value
-        let collateral_value = PriceCalculator::calculate_collateral_value(
+        // Calculate new ICR across all of the trove's collateral
+        let new_icr = other_collateral.trove_icr(
+            &collateral_info.denom,
             collateral_info.amount,
-            price_data.price as u64, // Convert i64 to u64
-            price_data.decimal,
-        )?;
-        
-        // Calculate new ICR
-        let new_icr = PriceCalculator::calculate_collateral_ratio(
-            collateral_value,
+            &price_data,
             new_debt_amount,
         )?;
         
-        // Check minimum collateral ratio
-        let minimum_ratio = trove_ctx.state.minimum_collateral_ratio as u64;
+        // Check the trove's minimum collateral ratio
+        let minimum_ratio = other_collateral
+            .risk_ratios(config, collateral_info.amount, &price_data, trove_ctx.state.minimum_collateral_ratio)?
+            .minimum_collateral_ratio;
         require!(
             new_icr >= minimum_ratio,
             AerospacerProtocolError::CollateralBelowMinimum
         );
         
+        // Borrowing always lowers the TCR: no borrows are allowed in Recovery Mode,
+        // and outside it a borrow may not push the system into Recovery Mode
+        let total_debt_amount = trove_ctx.state.total_debt_amount;
+        require!(
+            !system_collateral.is_recovery_mode(total_debt_amount)?,
+            AerospacerProtocolError::NotAllowedInRecoveryMode
+        );
+        let new_total_debt_amount = total_debt_amount
+            .checked_add(additional_loan_amount)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+        require!(
+            system_collateral.tcr(new_total_debt_amount)? >= CRITICAL_COLLATERAL_RATIO,
+            AerospacerProtocolError::TcrBelowCriticalRatio
+        );
+        
+        // Nor push any of the trove's denoms over its debt ceiling
+        system_collateral.check_debt_ceiling(config, new_total_debt_amount)?;
+        for other_config in &other_collateral.configs {
+            system_collateral.check_debt_ceiling(other_config, new_total_debt_amount)?;
+        }
+        
         // Update accounts
         trove_ctx.update_debt_amount(new_debt_amount)?;
         trove_ctx.update_liquidity_threshold(new_icr)?;
         
         // Update state
-        trove_ctx.state.total_debt_amount = trove_ctx.state.total_debt_amount
-            .checked_add(additional_loan_amount)
-            .ok_or(AerospacerProtocolError::OverflowError)?;
+        trove_ctx.state.total_debt_amount = new_total_debt_amount;
         
         // Note: Sorted list operations happen in instruction handler via sorted_troves_simple
         
         Ok(TroveOperationResult {
             success: true,
-            new_debt_amount: new_debt_amount,
+            new_debt_amount,
             new_collateral_amount: collateral_info.amount,
-            new_icr: new_icr,
+            new_icr,
             message: "Loan borrowed successfully".to_string(),
         })
     }
     
+    /// Change a trove's collateral in one denom and its debt in a single step
+    ///
+    /// Only the final state is checked: the ICR against the trove's minimum collateral ratio
+    /// and, for withdrawals and borrows, the Recovery Mode rules and debt ceilings. Deposits
+    /// are transferred in and withdrawals paid out here; minting and burning aUSD is left to
+    /// the instruction handler.
+    #[allow(clippy::too_many_arguments)]
+    pub fn adjust_trove(
+        trove_ctx: &mut TroveContext,
+        collateral_ctx: &mut CollateralContext,
+        oracle_ctx: &OracleContext,
+        config: &CollateralConfig,
+        other_collateral: &OtherCollateral,
+        system_collateral: &SystemCollateral,
+        adjustment: &TroveAdjustment,
+        bump: u8,
+    ) -> Result<TroveOperationResult> {
+        // Get current trove info
+        let trove_info = trove_ctx.get_trove_info()?;
+        let collateral_info = collateral_ctx.get_collateral_info()?;
+        let collateral_denom = collateral_info.denom.clone();
+        
+        let depositing = adjustment.collateral_change > 0 && adjustment.is_collateral_increase;
+        let withdrawing = adjustment.collateral_change > 0 && !adjustment.is_collateral_increase;
+        let borrowing = adjustment.debt_change > 0 && adjustment.is_debt_increase;
+        
+        // Deposits need the denom enabled and room under its deposit cap,
+        // and no new debt may be drawn against a disabled denom
+        if depositing {
+            require!(config.enabled, AerospacerProtocolError::CollateralDisabled);
+            check_deposit_cap(config, collateral_ctx.total_collateral_amount.amount, adjustment.collateral_change)?;
+        }
+        if borrowing {
+            require!(
+                config.enabled && other_collateral.configs.iter().all(|c| c.enabled),
+                AerospacerProtocolError::CollateralDisabled
+            );
+        }
+        
+        // Get collateral price
+        let price_data = oracle_ctx.get_price(&collateral_denom)?;
+        oracle_ctx.validate_price(&price_data)?;
+        
+        // Calculate the final collateral and debt amounts
+        let new_collateral_amount = if adjustment.is_collateral_increase {
+            collateral_info.amount
+                .checked_add(adjustment.collateral_change)
+                .ok_or(AerospacerProtocolError::OverflowError)?
+        } else {
+            collateral_info.amount
+                .checked_sub(adjustment.collateral_change)
+                .ok_or(AerospacerProtocolError::InsufficientCollateral)?
+        };
+        let new_debt_amount = if adjustment.is_debt_increase {
+            trove_info.debt_amount
+                .checked_add(adjustment.debt_change)
+                .ok_or(AerospacerProtocolError::OverflowError)?
+        } else {
+            trove_info.debt_amount
+                .checked_sub(adjustment.debt_change)
+                .ok_or(AerospacerProtocolError::InvalidAmount)?
+        };
+        
+        // A denom may only be emptied entirely while the trove still holds another one
+        if withdrawing {
+            require!(
+                new_collateral_amount >= MINIMUM_COLLATERAL_AMOUNT
+                    || (new_collateral_amount == 0 && !other_collateral.is_empty()),
+                AerospacerProtocolError::CollateralBelowMinimum
+            );
+        }
+        
+        // Calculate the final ICR across all of the trove's collateral
+        let new_icr = other_collateral.trove_icr(
+            &collateral_denom,
+            new_collateral_amount,
+            &price_data,
+            new_debt_amount,
+        )?;
+        
+        // Adjustments that weaken the trove must leave it above its minimum collateral ratio
+        if adjustment.lowers_collateralization() {
+            let minimum_ratio = other_collateral
+                .risk_ratios(config, new_collateral_amount, &price_data, trove_ctx.state.minimum_collateral_ratio)?
+                .minimum_collateral_ratio;
+            require!(
+                new_icr >= minimum_ratio,
+                AerospacerProtocolError::CollateralBelowMinimum
+            );
+        }
+        
+        let total_debt_amount = trove_ctx.state.total_debt_amount;
+        let new_total_debt_amount = if adjustment.is_debt_increase {
+            total_debt_amount
+                .checked_add(adjustment.debt_change)
+                .ok_or(AerospacerProtocolError::OverflowError)?
+        } else {
+            total_debt_amount
+                .checked_sub(adjustment.debt_change)
+                .ok_or(AerospacerProtocolError::OverflowError)?
+        };
+        
+        // Withdrawals and borrows lower the TCR: neither is allowed in Recovery Mode,
+        // and outside it the adjustment may not push the system into Recovery Mode
+        if adjustment.lowers_collateralization() {
+            require!(
+                !system_collateral.is_recovery_mode(total_debt_amount)?,
+                AerospacerProtocolError::NotAllowedInRecoveryMode
+            );
+            
+            let mut system_after = system_collateral.clone();
+            let new_tcr = if depositing {
+                system_after.add_collateral(&collateral_denom, adjustment.collateral_change, &price_data)?;
+                system_after.tcr(new_total_debt_amount)?
+            } else {
+                system_after.tcr_after_withdrawal(&collateral_denom, adjustment.collateral_change, new_total_debt_amount)?
+            };
+            require!(
+                new_tcr >= CRITICAL_COLLATERAL_RATIO,
+                AerospacerProtocolError::TcrBelowCriticalRatio
+            );
+            
+            // Nor push any of the trove's denoms over its debt ceiling
+            if borrowing {
+                system_after.check_debt_ceiling(config, new_total_debt_amount)?;
+                for other_config in &other_collateral.configs {
+                    system_after.check_debt_ceiling(other_config, new_total_debt_amount)?;
+                }
+            }
+        }
+        
+        // Update accounts
+        collateral_ctx.update_collateral_amount(new_collateral_amount)?;
+        trove_ctx.update_debt_amount(new_debt_amount)?;
+        trove_ctx.update_liquidity_threshold(new_icr)?;
+        
+        // Update state
+        trove_ctx.state.total_debt_amount = new_total_debt_amount;
+        
+        // Move the collateral
+        if depositing {
+            collateral_ctx.transfer_to_protocol(adjustment.collateral_change)?;
+        }
+        if withdrawing {
+            collateral_ctx.transfer_to_user(adjustment.collateral_change, &collateral_denom, bump)?;
+        }
+        
+        // Note: Sorted list operations happen in instruction handler via sorted_troves_simple
+        
+        Ok(TroveOperationResult {
+            success: true,
+            new_debt_amount,
+            new_collateral_amount,
+            new_icr,
+            message: "Trove adjusted successfully".to_string(),
+        })
+    }
+    
     /// Repay loan
     pub fn repay_loan(
         trove_ctx: &mut TroveContext,
         collateral_ctx: &mut CollateralContext,
         oracle_ctx: &OracleContext,
+        other_collateral: &OtherCollateral,
         repay_amount: u64,
         bump: u8,
     ) -> Result<TroveOperationResult> {
@@ -327,9 +593,16 @@ impl TroveManager {
             .ok_or(AerospacerProtocolError::OverflowError)?;
         
         if new_debt_amount == 0 {
+            // Full repayment only returns this denom, so the rest of a
+            // multi-collateral trove has to go through close_trove
+            require!(
+                other_collateral.is_empty(),
+                AerospacerProtocolError::MultiCollateralTroveMustClose
+            );
+            
             // Full repayment - close trove
             trove_ctx.update_debt_amount(0)?;
-            trove_ctx.update_liquidity_threshold(0)?;
+            trove_ctx.update_liquidity_threshold(Decimal::ZERO)?;
             collateral_ctx.update_collateral_amount(0)?;
 
             // Return collateral to user
@@ -341,7 +614,7 @@ impl TroveManager {
                 success: true,
                 new_debt_amount: 0,
                 new_collateral_amount: 0,
-                new_icr: 0,
+                new_icr: Decimal::ZERO,
                 message: "Trove fully repaid and closed".to_string(),
             })
         } else {
@@ -350,16 +623,11 @@ impl TroveManager {
             let price_data = oracle_ctx.get_price(&collateral_info.denom)?;
             oracle_ctx.validate_price(&price_data)?;
             
-            // Calculate collateral value
-            let collateral_value = PriceCalculator::calculate_collateral_value(
+            // Calculate new ICR across all of the trove's collateral
+            let new_icr = other_collateral.trove_icr(
+                &collateral_info.denom,
                 collateral_info.amount,
-                price_data.price as u64, // Convert i64 to u64
-                price_data.decimal,
-            )?;
-            
-            // Calculate new ICR
-            let new_icr = PriceCalculator::calculate_collateral_ratio(
-                collateral_value,
+                &price_data,
                 new_debt_amount,
             )?;
             
@@ -371,33 +639,77 @@ impl TroveManager {
             
             Ok(TroveOperationResult {
                 success: true,
-                new_debt_amount: new_debt_amount,
+                new_debt_amount,
                 new_collateral_amount: collateral_info.amount,
-                new_icr: new_icr,
+                new_icr,
                 message: "Partial repayment successful".to_string(),
             })
         }
     }
     
     /// Liquidate undercollateralized troves
-    pub fn liquidate_troves(
-        liquidation_ctx: &mut LiquidationContext,
-        oracle_ctx: &OracleContext,
+    /// 
+    /// Each trove is priced and seized across every collateral denom it holds, so the
+    /// UserCollateralAmount PDA and Pyth price account of each of those denoms must be
+    /// present in remaining_accounts (they are located by address after the 4n trove accounts)
+    /// 
+    /// The TotalCollateralAmount PDA of every supported denom must be present as well: the
+    /// system mode is re-evaluated before each trove, and in Recovery Mode troves below the
+    /// critical collateral ratio are liquidatable
+    /// 
+    /// The stability pool offsets as much of each trove's debt as it holds; the remaining debt and
+    /// collateral are redistributed to active troves, so the RedistributionSnapshot PDA of every
+    /// denom involved must be present too
+    /// 
+    /// Each trove's liquidation threshold and penalty come from the CollateralConfig PDAs of its
+    /// denoms, which must be present too. The penalty comes off the trove's collateral first. The pool's share of the penalty is added to its S factor,
+    /// while the liquidator's and treasury's shares are returned for the handler to pay out
+    /// together with the troves' gas compensation.
+    pub fn liquidate_troves<'info>(
+        liquidation_ctx: &mut LiquidationContext<'info>,
+        state: &mut StateAccount,
+        oracle_ctx: &OracleContext<'info>,
         liquidation_list: Vec<Pubkey>,
-        remaining_accounts: &[AccountInfo],
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<LiquidationResult> {
         let mut liquidated_count = 0u32;
         let mut total_debt_liquidated = 0u64;
         let mut total_collateral_gained = 0u64;
         let mut liquidation_gains = Vec::new();
+        let mut liquidator_collateral = Vec::new();
+        let mut treasury_collateral = Vec::new();
+        let mut gas_compensation = 0u64;
+        
+        // Prices are fetched once per denom for the whole batch
+        let mut system_collateral = load_system_collateral(oracle_ctx, remaining_accounts)?;
+        let mut prices = system_collateral.prices.clone();
         
         // Process each trove in the liquidation list
         for (i, user) in liquidation_list.iter().enumerate() {
+            // Earlier redistributions (including ones from this batch) are part of the trove
+            apply_rewards_before_liquidation(i, remaining_accounts)?;
+            
             // Parse real trove data from remaining accounts
             let trove_data = parse_trove_data(user, i, remaining_accounts)?;
             
+            let configs = trove_data.collateral_amounts
+                .iter()
+                .map(|(denom, _)| load_collateral_config(denom, remaining_accounts))
+                .collect::<Result<Vec<_>>>()?;
+            
+            // Earlier liquidations in the batch may have restored normal mode
+            let recovery_mode = system_collateral.is_recovery_mode(state.total_debt_amount)?;
+            
             // Validate trove is actually undercollateralized
-            validate_trove_for_liquidation(&trove_data, oracle_ctx)?;
+            validate_trove_for_liquidation(
+                &trove_data,
+                oracle_ctx,
+                remaining_accounts,
+                &mut prices,
+                &configs,
+                state.minimum_collateral_ratio,
+                recovery_mode,
+            )?;
             
             // Calculate liquidation gains
             let mut trove_collateral_gain = 0u64;
@@ -412,20 +724,51 @@ impl TroveManager {
                 }
             }
             
-            // Process liquidation
-            liquidation_ctx.liquidate_trove(*user, trove_data.debt_amount, trove_data.collateral_amounts.clone())?;
+            // The penalty comes off the top, only the rest backs the trove's debt
+            let penalty = split_liquidation_penalty(&trove_data.collateral_amounts, &configs)?;
             
-            // Distribute seized collateral to stability pool stakers
-            distribute_liquidation_gains_to_stakers(
-                &mut liquidation_ctx.state,
-                &trove_data.collateral_amounts,
+            // The stability pool offsets as much debt as it holds, the rest is redistributed
+            let mut split = split_liquidation(
+                &penalty.backing_collateral,
                 trove_data.debt_amount,
-                remaining_accounts,
-                liquidation_list.len(),
+                state.total_stake_amount,
+                &prices,
             )?;
+            split.add_stability_pool_penalty(&penalty.to_stability_pool, state.total_stake_amount > 0);
+            
+            // Process liquidation
+            liquidation_ctx.liquidate_trove(*user, split.debt_to_offset, split.collateral_to_send_to_sp.clone())?;
+            state.total_debt_amount = state.total_debt_amount.saturating_sub(split.debt_to_offset);
+            
+            // Seized collateral goes to stability pool stakers, the offset debt comes out of P
+            absorb_liquidation(
+                state,
+                &split.collateral_to_send_to_sp,
+                split.debt_to_offset,
+                remaining_accounts.get(liquidation_list.len() * 4..).unwrap_or(&[]),
+                &liquidation_ctx.liquidator.to_account_info(),
+                &liquidation_ctx.system_program.to_account_info(),
+            )?;
+            
+            // Hand the remainder to the troves still holding each denom
+            redistribute_trove_by_address(&trove_data.collateral_amounts, &split, remaining_accounts)?;
+            
+            // Collateral seized by the pool, the liquidator and the treasury no longer backs system debt
+            // (redistributed collateral stays in the system along with its debt)
+            for (denom, amount) in split.collateral_to_send_to_sp.iter()
+                .chain(&penalty.to_liquidator)
+                .chain(&penalty.to_treasury)
+            {
+                system_collateral.remove_collateral(denom, *amount);
+            }
             
             // Update user accounts to zero (trove is closed)
-            update_user_accounts_after_liquidation(user, i, remaining_accounts)?;
+            update_user_accounts_after_liquidation(user, i, &trove_data, remaining_accounts)?;
+            
+            // The liquidator is paid the trove's gas compensation reserve and its slice of the collateral
+            accumulate_by_denom(&mut liquidator_collateral, &penalty.to_liquidator);
+            accumulate_by_denom(&mut treasury_collateral, &penalty.to_treasury);
+            gas_compensation = gas_compensation.saturating_add(trove_data.debt_amount.min(GAS_COMPENSATION));
             
             // Update counters
             liquidated_count += 1;
@@ -436,6 +779,16 @@ impl TroveManager {
             
             msg!("Liquidated trove: user={}, debt={}, collateral={}", 
                  user, trove_data.debt_amount, trove_collateral_gain);
+            emit!(TroveLiquidated::new(
+                *user,
+                liquidation_ctx.liquidator.key(),
+                trove_data.debt_amount,
+                &trove_data.collateral_amounts,
+                &split,
+                &penalty,
+                trove_data.debt_amount.min(GAS_COMPENSATION),
+                recovery_mode,
+            ));
         }
         
         Ok(LiquidationResult {
@@ -443,17 +796,658 @@ impl TroveManager {
             total_debt_liquidated,
             total_collateral_gained,
             liquidation_gains,
+            liquidator_collateral,
+            treasury_collateral,
+            gas_compensation,
         })
     }
 }
 
+/// Collateral a trove holds outside the denom an instruction operates on,
+/// priced through the oracle so the trove's ICR covers all of its collateral
+#[derive(Clone, Debug, Default)]
+pub struct OtherCollateral {
+    pub amounts: Vec<(String, u64)>,
+    pub prices: Vec<(String, u64, u8)>, // (denom, price, decimal)
+    pub configs: Vec<CollateralConfig>,
+}
+
+impl OtherCollateral {
+    /// Whether the trove holds no collateral besides the instruction's denom
+    pub fn is_empty(&self) -> bool {
+        self.amounts.iter().all(|(_, amount)| *amount == 0)
+    }
+    
+    /// Every collateral amount of the trove, with `amount` of `denom` on top of the other collateral
+    pub fn collateral_amounts(&self, denom: &str, amount: u64) -> Vec<(String, u64)> {
+        let mut collateral_amounts = self.amounts.clone();
+        collateral_amounts.push((denom.to_string(), amount));
+        collateral_amounts
+    }
+    
+    /// Aggregate ICR of the trove with `amount` of `denom` on top of the other collateral
+    pub fn trove_icr(
+        &self,
+        denom: &str,
+        amount: u64,
+        price_data: &PriceData,
+        debt_amount: u64,
+    ) -> Result<Decimal> {
+        let collateral_amounts = self.collateral_amounts(denom, amount);
+        
+        let mut prices = self.prices.clone();
+        prices.push((denom.to_string(), price_data.price as u64, price_data.decimal));
+        
+        PriceCalculator::calculate_trove_icr(&collateral_amounts, debt_amount, &prices)
+    }
+    
+    /// Risk ratios of the trove with `amount` of `config`'s denom on top of the other collateral
+    pub fn risk_ratios(
+        &self,
+        config: &CollateralConfig,
+        amount: u64,
+        price_data: &PriceData,
+        protocol_minimum_ratio: u8,
+    ) -> Result<TroveRiskRatios> {
+        let collateral_amounts = self.collateral_amounts(&config.denom, amount);
+        
+        let mut prices = self.prices.clone();
+        prices.push((config.denom.clone(), price_data.price as u64, price_data.decimal));
+        
+        let mut configs = self.configs.clone();
+        configs.push(config.clone());
+        
+        trove_risk_ratios(&collateral_amounts, &prices, &configs, protocol_minimum_ratio)
+    }
+}
+
+/// Accounts per other denom in the remaining_accounts of single-trove instructions
+pub const OTHER_COLLATERAL_GROUP_LEN: usize = 4;
+
+/// Load and price the collateral a trove holds in denoms other than `current_denom`
+/// 
+/// remaining_accounts must start with one [UserCollateralAmount, RedistributionSnapshot,
+/// pyth_price_account, CollateralConfig] group per other denom, in the order they are recorded in
+/// `UserDebtAmount::collateral_denoms` (the snapshots are used by
+/// redistribution::apply_trove_rewards, which must run first). Returns the collateral together
+/// with the number of accounts consumed, so callers can read any neighbor hints that follow.
+pub fn load_other_collateral<'info>(
+    user_debt_amount: &UserDebtAmount,
+    current_denom: &str,
+    remaining_accounts: &[AccountInfo<'info>],
+    oracle_ctx: &OracleContext<'info>,
+) -> Result<(OtherCollateral, usize)> {
+    let mut other_collateral = OtherCollateral::default();
+    let mut consumed = 0usize;
+    
+    for denom in user_debt_amount.collateral_denoms.iter().filter(|d| d.as_str() != current_denom) {
+        require!(
+            remaining_accounts.len() >= consumed + OTHER_COLLATERAL_GROUP_LEN,
+            AerospacerProtocolError::MissingCollateralAccount
+        );
+        
+        let collateral_account = &remaining_accounts[consumed];
+        let pyth_price_account = &remaining_accounts[consumed + 2];
+        let config_account = &remaining_accounts[consumed + 3];
+        
+        let user_collateral = load_user_collateral_amount(collateral_account, &user_debt_amount.owner, denom)?;
+        let config = load_collateral_config(denom, std::slice::from_ref(config_account))?;
+        
+        // The oracle rejects any Pyth account not configured for this denom
+        let price_data = oracle_ctx.get_price_from_feed(denom, pyth_price_account)?;
+        oracle_ctx.validate_price(&price_data)?;
+        
+        other_collateral.amounts.push((denom.clone(), user_collateral.amount));
+        other_collateral.prices.push((denom.clone(), price_data.price as u64, price_data.decimal));
+        other_collateral.configs.push(config);
+        consumed += OTHER_COLLATERAL_GROUP_LEN;
+    }
+    
+    Ok((other_collateral, consumed))
+}
+
+/// Deserialize a UserCollateralAmount passed via remaining_accounts,
+/// checking that it is the PDA for (owner, denom)
+pub fn load_user_collateral_amount(
+    account_info: &AccountInfo,
+    owner: &Pubkey,
+    denom: &str,
+) -> Result<UserCollateralAmount> {
+    let (expected_pda, _bump) = Pubkey::find_program_address(
+        &UserCollateralAmount::seeds(owner, denom),
+        &crate::ID,
+    );
+    require!(
+        account_info.key() == expected_pda,
+        AerospacerProtocolError::MissingCollateralAccount
+    );
+    require!(
+        account_info.owner == &crate::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+    
+    let data = account_info.try_borrow_data()?;
+    UserCollateralAmount::try_deserialize(&mut &data[..])
+}
+
+/// Overwrite the amount stored in a UserCollateralAmount account
+pub fn set_user_collateral_amount(account_info: &AccountInfo, amount: u64) -> Result<()> {
+    let mut data = account_info.try_borrow_mut_data()?;
+    let mut user_collateral = UserCollateralAmount::try_deserialize(&mut &data[..])?;
+    user_collateral.amount = amount;
+    user_collateral.try_serialize(&mut &mut data[..])?;
+    Ok(())
+}
+
+/// Overwrite the ICR and NICRs stored in a LiquidityThreshold account passed via remaining_accounts
+pub fn set_liquidity_threshold(
+    account_info: &AccountInfo,
+    ratio: Decimal,
+    collateral_amounts: &[(String, u64)],
+    debt_amount: u64,
+) -> Result<()> {
+    require!(
+        account_info.owner == &crate::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+    
+    let mut data = account_info.try_borrow_mut_data()?;
+    let mut liquidity_threshold = LiquidityThreshold::try_deserialize(&mut &data[..])?;
+    liquidity_threshold.ratio = ratio;
+    liquidity_threshold.set_nominal_ratios(collateral_amounts, debt_amount)?;
+    liquidity_threshold.try_serialize(&mut &mut data[..])?;
+    Ok(())
+}
+
+/// Load every denom a trove holds and price it once per instruction, for handlers that touch
+/// several troves
+///
+/// The trove's UserCollateralAmount PDAs and the Pyth price accounts are located by address in
+/// remaining_accounts (see cache_denom_price). Returns the trove's collateral amounts, with
+/// `prices` holding a price for each of their denoms.
+pub fn load_trove_collateral<'info>(
+    user_debt_amount: &UserDebtAmount,
+    oracle_ctx: &OracleContext<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    prices: &mut Vec<(String, u64, u8)>,
+) -> Result<Vec<(String, u64)>> {
+    let mut collateral_amounts = Vec::with_capacity(user_debt_amount.collateral_denoms.len());
+    
+    for denom in &user_debt_amount.collateral_denoms {
+        let account_info = find_user_collateral_account(&user_debt_amount.owner, denom, remaining_accounts)?;
+        let amount = {
+            let data = account_info.try_borrow_data()?;
+            UserCollateralAmount::try_deserialize(&mut &data[..])?.amount
+        };
+        cache_denom_price(oracle_ctx, denom, remaining_accounts, prices)?;
+        collateral_amounts.push((denom.clone(), amount));
+    }
+    
+    Ok(collateral_amounts)
+}
+
+/// Read an owner's trove as it stands once its pending redistribution rewards are applied,
+/// without writing anything back
+///
+/// The trove's UserDebtAmount and UserCollateralAmount PDAs and the RedistributionSnapshot of
+/// every denom it holds are located by address in remaining_accounts. Returns the trove's
+/// collateral amounts and its debt.
+pub fn load_trove_with_pending_rewards(
+    owner: &Pubkey,
+    remaining_accounts: &[AccountInfo],
+) -> Result<(Vec<(String, u64)>, u64)> {
+    let (debt_pda, _bump) = Pubkey::find_program_address(&UserDebtAmount::seeds(owner), &crate::ID);
+    let debt_account = remaining_accounts
+        .iter()
+        .find(|a| a.key() == debt_pda)
+        .ok_or(AerospacerProtocolError::InvalidList)?;
+    require!(
+        debt_account.owner == &crate::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+    let user_debt = UserDebtAmount::try_deserialize(&mut &debt_account.try_borrow_data()?[..])?;
+    
+    let mut debt_amount = user_debt.amount;
+    let mut collateral_amounts = Vec::with_capacity(user_debt.collateral_denoms.len());
+    for denom in &user_debt.collateral_denoms {
+        let collateral_account = find_user_collateral_account(owner, denom, remaining_accounts)?;
+        let position = UserCollateralAmount::try_deserialize(&mut &collateral_account.try_borrow_data()?[..])?;
+        let snapshot_account = find_redistribution_snapshot_account(denom, remaining_accounts)?;
+        require!(
+            snapshot_account.owner == &crate::ID,
+            AerospacerProtocolError::Unauthorized
+        );
+        let snapshot = RedistributionSnapshot::try_deserialize(&mut &snapshot_account.try_borrow_data()?[..])?;
+        
+        let (pending_collateral, pending_debt) = pending_position_rewards(&position, &snapshot)?;
+        collateral_amounts.push((
+            denom.clone(),
+            position.amount
+                .checked_add(pending_collateral)
+                .ok_or(AerospacerProtocolError::OverflowError)?,
+        ));
+        debt_amount = debt_amount
+            .checked_add(pending_debt)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+    }
+    
+    Ok((collateral_amounts, debt_amount))
+}
+
+/// Find an owner's UserCollateralAmount PDA for `denom` anywhere in remaining_accounts
+pub fn find_user_collateral_account<'a, 'info>(
+    owner: &Pubkey,
+    denom: &str,
+    remaining_accounts: &'a [AccountInfo<'info>],
+) -> Result<&'a AccountInfo<'info>> {
+    let (expected_pda, _bump) = Pubkey::find_program_address(
+        &UserCollateralAmount::seeds(owner, denom),
+        &crate::ID,
+    );
+    
+    let account_info = remaining_accounts
+        .iter()
+        .find(|a| a.key() == expected_pda)
+        .ok_or(AerospacerProtocolError::MissingCollateralAccount)?;
+    
+    require!(
+        account_info.owner == &crate::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+    
+    Ok(account_info)
+}
+
+/// Price a denom once per instruction for handlers that touch several troves
+/// 
+/// The Pyth account is the instruction's own pyth_price_account when that is the feed the
+/// oracle has configured for `denom`, otherwise it is located by address in remaining_accounts.
+pub fn cache_denom_price<'info>(
+    oracle_ctx: &OracleContext<'info>,
+    denom: &str,
+    remaining_accounts: &[AccountInfo<'info>],
+    prices: &mut Vec<(String, u64, u8)>,
+) -> Result<()> {
+    if prices.iter().any(|(d, _, _)| d == denom) {
+        return Ok(());
+    }
+    
+    let feed_address = oracle_ctx.price_feed_address(denom)?;
+    let price_data = if feed_address == oracle_ctx.pyth_price_account.key() {
+        oracle_ctx.get_price(denom)?
+    } else {
+        let pyth_price_account = remaining_accounts
+            .iter()
+            .find(|a| a.key() == feed_address)
+            .ok_or(AerospacerProtocolError::MissingCollateralAccount)?;
+        oracle_ctx.get_price_from_feed(denom, pyth_price_account)?
+    };
+    oracle_ctx.validate_price(&price_data)?;
+    
+    prices.push((denom.to_string(), price_data.price as u64, price_data.decimal));
+    Ok(())
+}
+
+/// Protocol-wide collateral taken from the TotalCollateralAmount PDAs, priced
+/// through the oracle to compute the Total Collateral Ratio (TCR)
+#[derive(Clone, Debug, Default)]
+pub struct SystemCollateral {
+    pub amounts: Vec<(String, u64)>,
+    pub prices: Vec<(String, u64, u8)>, // (denom, price, decimal)
+}
+
+impl SystemCollateral {
+    /// TCR against `total_debt_amount`
+    pub fn tcr(&self, total_debt_amount: u64) -> Result<Decimal> {
+        PriceCalculator::calculate_trove_icr(&self.amounts, total_debt_amount, &self.prices)
+    }
+
+    /// TCR once `amount` of `denom` has been withdrawn from the system
+    pub fn tcr_after_withdrawal(&self, denom: &str, amount: u64, total_debt_amount: u64) -> Result<Decimal> {
+        let mut system_after = self.clone();
+        system_after.remove_collateral(denom, amount);
+        system_after.tcr(total_debt_amount)
+    }
+
+    /// Stop counting `amount` of `denom` that has left the system
+    pub fn remove_collateral(&mut self, denom: &str, amount: u64) {
+        if let Some(entry) = self.amounts.iter_mut().find(|(d, _)| d == denom) {
+            entry.1 = entry.1.saturating_sub(amount);
+        }
+    }
+
+    /// Recovery Mode is active while the TCR is below the critical collateral ratio
+    pub fn is_recovery_mode(&self, total_debt_amount: u64) -> Result<bool> {
+        Ok(self.tcr(total_debt_amount)? < CRITICAL_COLLATERAL_RATIO)
+    }
+
+    /// Count a deposit of `amount` of `denom` that is not in the TotalCollateralAmount PDAs yet
+    pub fn add_collateral(&mut self, denom: &str, amount: u64, price_data: &PriceData) -> Result<()> {
+        if let Some(entry) = self.amounts.iter_mut().find(|(d, _)| d == denom) {
+            entry.1 = entry.1
+                .checked_add(amount)
+                .ok_or(AerospacerProtocolError::OverflowError)?;
+        } else {
+            self.amounts.push((denom.to_string(), amount));
+        }
+        if !self.prices.iter().any(|(d, _, _)| d == denom) {
+            self.prices.push((denom.to_string(), price_data.price as u64, price_data.decimal));
+        }
+        Ok(())
+    }
+
+    /// Share of `total_debt_amount` backed by `denom`, in proportion to its share of the collateral value
+    pub fn debt_backed_by(&self, denom: &str, total_debt_amount: u64) -> Result<u64> {
+        let mut denom_value = 0u128;
+        let mut total_value = 0u128;
+        for (d, amount) in &self.amounts {
+            let value = collateral_value(d, *amount, &self.prices)? as u128;
+            if d == denom {
+                denom_value = value;
+            }
+            total_value = total_value.saturating_add(value);
+        }
+
+        let backed = (total_debt_amount as u128)
+            .checked_mul(denom_value)
+            .ok_or(AerospacerProtocolError::OverflowError)?
+            .checked_div(total_value)
+            .unwrap_or(0);
+        u64::try_from(backed).map_err(|_| AerospacerProtocolError::OverflowError.into())
+    }
+
+    /// Check that the debt backed by `config`'s denom stays within its debt ceiling
+    pub fn check_debt_ceiling(&self, config: &CollateralConfig, total_debt_amount: u64) -> Result<()> {
+        require!(
+            self.debt_backed_by(&config.denom, total_debt_amount)? <= config.debt_ceiling,
+            AerospacerProtocolError::DebtCeilingExceeded
+        );
+        Ok(())
+    }
+}
+
+/// Load and price the TotalCollateralAmount of every denom the oracle supports
+///
+/// Each denom's TotalCollateralAmount PDA and Pyth price account are located by address in
+/// `accounts`. A PDA that has never been initialized counts as zero collateral, and denoms
+/// with no collateral are not priced. Every supported denom must be present so that the
+/// TCR cannot be understated by leaving collateral out.
+pub fn load_system_collateral<'info>(
+    oracle_ctx: &OracleContext<'info>,
+    accounts: &[AccountInfo<'info>],
+) -> Result<SystemCollateral> {
+    let mut system_collateral = SystemCollateral::default();
+
+    for denom in oracle_ctx.supported_denoms()? {
+        let (expected_pda, _bump) = Pubkey::find_program_address(
+            &TotalCollateralAmount::seeds(&denom),
+            &crate::ID,
+        );
+        let account_info = accounts
+            .iter()
+            .find(|a| a.key() == expected_pda)
+            .ok_or(AerospacerProtocolError::MissingCollateralAccount)?;
+
+        let amount = if account_info.owner == &crate::ID {
+            let data = account_info.try_borrow_data()?;
+            TotalCollateralAmount::try_deserialize(&mut &data[..])?.amount
+        } else {
+            require!(
+                account_info.data_is_empty(),
+                AerospacerProtocolError::Unauthorized
+            );
+            0
+        };
+
+        if amount > 0 {
+            cache_denom_price(oracle_ctx, &denom, accounts, &mut system_collateral.prices)?;
+            system_collateral.amounts.push((denom, amount));
+        }
+    }
+
+    Ok(system_collateral)
+}
+
+/// Load the system collateral from a positional block of remaining_accounts
+///
+/// The block holds one [TotalCollateralAmount, pyth_price_account] pair per denom the oracle
+/// supports (in any order). Returns the collateral together with the size of the block, so
+/// callers can read any neighbor hints that follow.
+pub fn load_system_collateral_block<'info>(
+    oracle_ctx: &OracleContext<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<(SystemCollateral, usize)> {
+    let block_len = oracle_ctx.supported_denoms()?.len() * 2;
+    require!(
+        remaining_accounts.len() >= block_len,
+        AerospacerProtocolError::MissingCollateralAccount
+    );
+
+    let system_collateral = load_system_collateral(oracle_ctx, &remaining_accounts[..block_len])?;
+    Ok((system_collateral, block_len))
+}
+
+/// Load the CollateralConfig of `denom`, located by address in `accounts`
+///
+/// Every denom a trove holds must have been configured by the admin
+pub fn load_collateral_config(denom: &str, accounts: &[AccountInfo]) -> Result<CollateralConfig> {
+    let (expected_pda, _bump) = Pubkey::find_program_address(
+        &CollateralConfig::seeds(denom),
+        &crate::ID,
+    );
+    let account_info = accounts
+        .iter()
+        .find(|a| a.key() == expected_pda)
+        .ok_or(AerospacerProtocolError::MissingCollateralConfig)?;
+    require!(
+        account_info.owner == &crate::ID,
+        AerospacerProtocolError::MissingCollateralConfig
+    );
+
+    let data = account_info.try_borrow_data()?;
+    CollateralConfig::try_deserialize(&mut &data[..])
+}
+
+/// Collateral ratios a trove is held to
+#[derive(Clone, Copy, Debug)]
+pub struct TroveRiskRatios {
+    pub minimum_collateral_ratio: Decimal,
+    pub liquidation_threshold: Decimal,
+}
+
+/// Weigh each denom's CollateralConfig ratios by its share of the trove's collateral value
+///
+/// Weighted ratios round up, and a trove with no collateral value is held to the strictest
+/// of its denoms. The protocol-wide minimum collateral ratio acts as a floor.
+pub fn trove_risk_ratios(
+    collateral_amounts: &[(String, u64)],
+    prices: &[(String, u64, u8)],
+    configs: &[CollateralConfig],
+    protocol_minimum_ratio: u8,
+) -> Result<TroveRiskRatios> {
+    let mut total_value = 0u128;
+    let mut weighted_minimum = 0u128;
+    let mut weighted_threshold = 0u128;
+    let mut strictest_minimum = 0u8;
+    let mut strictest_threshold = 0u8;
+
+    for (denom, amount) in collateral_amounts {
+        let config = configs
+            .iter()
+            .find(|c| c.denom == *denom)
+            .ok_or(AerospacerProtocolError::MissingCollateralConfig)?;
+        let value = collateral_value(denom, *amount, prices)? as u128;
+
+        total_value = total_value.saturating_add(value);
+        weighted_minimum = weighted_minimum.saturating_add(value * config.minimum_collateral_ratio as u128);
+        weighted_threshold = weighted_threshold.saturating_add(value * config.liquidation_threshold as u128);
+        strictest_minimum = strictest_minimum.max(config.minimum_collateral_ratio);
+        strictest_threshold = strictest_threshold.max(config.liquidation_threshold);
+    }
+
+    let (minimum_collateral_ratio, liquidation_threshold) = if total_value == 0 {
+        (
+            Decimal::from_percent(strictest_minimum as u64),
+            Decimal::from_percent(strictest_threshold as u64),
+        )
+    } else {
+        // The weighted sums are in percent, so divide by 100 × total value
+        let denominator = total_value
+            .checked_mul(100)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+        (
+            Decimal::from_ratio(weighted_minimum, denominator, Rounding::Up)?,
+            Decimal::from_ratio(weighted_threshold, denominator, Rounding::Up)?,
+        )
+    };
+
+    Ok(TroveRiskRatios {
+        minimum_collateral_ratio: minimum_collateral_ratio.max(Decimal::from_percent(protocol_minimum_ratio as u64)),
+        liquidation_threshold,
+    })
+}
+
+/// Value of `amount` of `denom` in aUSD at the given prices
+fn collateral_value(denom: &str, amount: u64, prices: &[(String, u64, u8)]) -> Result<u64> {
+    let (_, price, decimal) = prices
+        .iter()
+        .find(|(d, _, _)| d == denom)
+        .ok_or(AerospacerProtocolError::InvalidAmount)?;
+    PriceCalculator::calculate_collateral_value(amount, *price, *decimal)
+}
+
+/// Check that depositing `amount` keeps the denom's total collateral within its deposit cap
+pub fn check_deposit_cap(config: &CollateralConfig, total_collateral: u64, amount: u64) -> Result<()> {
+    let new_total = total_collateral
+        .checked_add(amount)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+    require!(
+        new_total <= config.deposit_cap,
+        AerospacerProtocolError::DepositCapExceeded
+    );
+    Ok(())
+}
+
+/// Collateral a liquidation takes off the top of a trove, per denom
+///
+/// The liquidator receives LIQUIDATOR_COLLATERAL_BPS of the collateral (never more than the
+/// penalty), the rest of the penalty is shared between the treasury and the stability pool.
+/// Only `backing_collateral` is offset against the trove's debt or redistributed.
+#[derive(Clone, Debug, Default)]
+pub struct LiquidationPenalty {
+    pub to_liquidator: Vec<(String, u64)>,
+    pub to_treasury: Vec<(String, u64)>,
+    pub to_stability_pool: Vec<(String, u64)>,
+    pub backing_collateral: Vec<(String, u64)>,
+}
+
+/// Apply each denom's CollateralConfig penalty to a liquidated trove's collateral
+pub fn split_liquidation_penalty(
+    collateral_amounts: &[(String, u64)],
+    configs: &[CollateralConfig],
+) -> Result<LiquidationPenalty> {
+    let mut penalty = LiquidationPenalty::default();
+
+    for (denom, amount) in collateral_amounts {
+        let config = configs
+            .iter()
+            .find(|c| c.denom == *denom)
+            .ok_or(AerospacerProtocolError::MissingCollateralConfig)?;
+
+        let penalty_amount = bps_of(*amount, config.liquidation_penalty_bps as u64)?;
+        let to_liquidator = bps_of(*amount, LIQUIDATOR_COLLATERAL_BPS as u64)?.min(penalty_amount);
+        let shared = penalty_amount - to_liquidator;
+        let to_treasury = bps_of(shared, config.treasury_share_bps as u64)?;
+
+        penalty.to_liquidator.push((denom.clone(), to_liquidator));
+        penalty.to_treasury.push((denom.clone(), to_treasury));
+        penalty.to_stability_pool.push((denom.clone(), shared - to_treasury));
+        penalty.backing_collateral.push((denom.clone(), amount - penalty_amount));
+    }
+
+    Ok(penalty)
+}
+
+/// Pay the liquidator's and the treasury's shares of liquidation penalties out of the vaults
+///
+/// Each denom's protocol_collateral_vault and TotalCollateralAmount, the liquidator's token
+/// account and the fee recipients' token accounts are located by address in `accounts`.
+/// The treasury share goes through aerospacer-fees with the vault signing as the payer.
+pub fn pay_liquidation_penalty<'info>(
+    liquidator: &Pubkey,
+    liquidator_collateral: &[(String, u64)],
+    treasury_collateral: &[(String, u64)],
+    fees_program: &AccountInfo<'info>,
+    fees_state: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    for (denom, to_liquidator) in liquidator_collateral {
+        let to_treasury = treasury_collateral
+            .iter()
+            .find(|(d, _)| d == denom)
+            .map_or(0, |(_, amount)| *amount);
+        if *to_liquidator == 0 && to_treasury == 0 {
+            continue;
+        }
+
+        let mut payout = DenomPayout::from_remaining_accounts(denom, liquidator, accounts)?;
+        payout.pay(token_program, *to_liquidator)?;
+
+        if to_treasury > 0 {
+            let mint = TokenAccount::try_deserialize(&mut &payout.vault.try_borrow_data()?[..])?.mint;
+            let recipients = FeeRecipientAccounts::from_remaining_accounts(fees_state, &mint, accounts)?;
+            distribute_collateral_fee(fees_program, fees_state, &mut payout, &recipients, token_program, to_treasury)?;
+        }
+
+        msg!("Liquidation penalty paid in {}: liquidator={}, treasury={}", denom, to_liquidator, to_treasury);
+    }
+
+    Ok(())
+}
+
+fn bps_of(amount: u64, bps: u64) -> Result<u64> {
+    let value = (amount as u128)
+        .checked_mul(bps as u128)
+        .ok_or(AerospacerProtocolError::OverflowError)?
+        / BPS_DENOMINATOR as u128;
+    u64::try_from(value).map_err(|_| AerospacerProtocolError::OverflowError.into())
+}
+
+/// Add `(denom, amount)` pairs into a running per-denom total
+pub fn accumulate_by_denom(totals: &mut Vec<(String, u64)>, amounts: &[(String, u64)]) {
+    for (denom, amount) in amounts {
+        if let Some(existing) = totals.iter_mut().find(|(d, _)| d == denom) {
+            existing.1 = existing.1.saturating_add(*amount);
+        } else {
+            totals.push((denom.clone(), *amount));
+        }
+    }
+}
+
 /// Trove data structure for liquidation
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
 pub struct TroveData {
     pub user: Pubkey,
     pub debt_amount: u64,
     pub collateral_amounts: Vec<(String, u64)>,
-    pub liquidity_ratio: u64,
+    pub liquidity_ratio: Decimal,
+}
+
+/// Apply a trove's pending redistribution rewards to its accounts before it is liquidated
+fn apply_rewards_before_liquidation(user_index: usize, remaining_accounts: &[AccountInfo]) -> Result<()> {
+    let debt_account = &remaining_accounts[user_index * 4];
+    require!(
+        debt_account.owner == &crate::ID,
+        AerospacerProtocolError::Unauthorized
+    );
+    
+    let mut debt_data = debt_account.try_borrow_mut_data()?;
+    let mut user_debt_amount = UserDebtAmount::try_deserialize(&mut &debt_data[..])?;
+    apply_trove_rewards_by_address(&mut user_debt_amount, remaining_accounts)?;
+    user_debt_amount.try_serialize(&mut &mut debt_data[..])?;
+    
+    Ok(())
 }
 
 /// Parse trove data from remaining accounts
@@ -472,11 +1466,20 @@ fn parse_trove_data(
     
     // Parse UserDebtAmount account
     let debt_account = &remaining_accounts[account_start];
-    let debt_amount = parse_user_debt_amount(debt_account, user)?;
+    let user_debt_amount = parse_user_debt_amount(debt_account, user)?;
     
     // Parse UserCollateralAmount account
     let collateral_account = &remaining_accounts[account_start + 1];
-    let collateral_amounts = parse_user_collateral_amount(collateral_account, user)?;
+    parse_user_collateral_amount(collateral_account, user)?;
+    
+    // Collect every denom the trove holds - located by PDA so none can be left out
+    let mut collateral_amounts = Vec::new();
+    for denom in &user_debt_amount.collateral_denoms {
+        let account_info = find_user_collateral_account(user, denom, remaining_accounts)?;
+        let data = account_info.try_borrow_data()?;
+        let user_collateral_amount = UserCollateralAmount::try_deserialize(&mut &data[..])?;
+        collateral_amounts.push((denom.clone(), user_collateral_amount.amount));
+    }
     
     // Parse LiquidityThreshold account
     let liquidity_account = &remaining_accounts[account_start + 2];
@@ -488,14 +1491,14 @@ fn parse_trove_data(
     
     Ok(TroveData {
         user: *user,
-        debt_amount,
+        debt_amount: user_debt_amount.amount,
         collateral_amounts,
         liquidity_ratio,
     })
 }
 
 /// Parse UserDebtAmount from account info
-fn parse_user_debt_amount(account_info: &AccountInfo, expected_user: &Pubkey) -> Result<u64> {
+fn parse_user_debt_amount(account_info: &AccountInfo, expected_user: &Pubkey) -> Result<UserDebtAmount> {
     // Validate account is owned by our program
     require!(
         account_info.owner == &crate::ID,
@@ -510,7 +1513,7 @@ fn parse_user_debt_amount(account_info: &AccountInfo, expected_user: &Pubkey) ->
     
     // Parse account data
     let account_data = account_info.try_borrow_data()?;
-    let user_debt_amount = UserDebtAmount::try_from_slice(&account_data)?;
+    let user_debt_amount = UserDebtAmount::try_deserialize(&mut &account_data[..])?;
     
     // Validate ownership
     require!(
@@ -518,11 +1521,11 @@ fn parse_user_debt_amount(account_info: &AccountInfo, expected_user: &Pubkey) ->
         AerospacerProtocolError::Unauthorized
     );
     
-    Ok(user_debt_amount.amount)
+    Ok(user_debt_amount)
 }
 
 /// Parse UserCollateralAmount from account info
-fn parse_user_collateral_amount(account_info: &AccountInfo, expected_user: &Pubkey) -> Result<Vec<(String, u64)>> {
+fn parse_user_collateral_amount(account_info: &AccountInfo, expected_user: &Pubkey) -> Result<UserCollateralAmount> {
     // Validate account is owned by our program
     require!(
         account_info.owner == &crate::ID,
@@ -537,7 +1540,7 @@ fn parse_user_collateral_amount(account_info: &AccountInfo, expected_user: &Pubk
     
     // Parse account data
     let account_data = account_info.try_borrow_data()?;
-    let user_collateral_amount = UserCollateralAmount::try_from_slice(&account_data)?;
+    let user_collateral_amount = UserCollateralAmount::try_deserialize(&mut &account_data[..])?;
     
     // Validate ownership
     require!(
@@ -545,11 +1548,11 @@ fn parse_user_collateral_amount(account_info: &AccountInfo, expected_user: &Pubk
         AerospacerProtocolError::Unauthorized
     );
     
-    Ok(vec![(user_collateral_amount.denom, user_collateral_amount.amount)])
+    Ok(user_collateral_amount)
 }
 
 /// Parse LiquidityThreshold from account info
-fn parse_liquidity_threshold(account_info: &AccountInfo, expected_user: &Pubkey) -> Result<u64> {
+fn parse_liquidity_threshold(account_info: &AccountInfo, expected_user: &Pubkey) -> Result<Decimal> {
     // Validate account is owned by our program
     require!(
         account_info.owner == &crate::ID,
@@ -564,7 +1567,7 @@ fn parse_liquidity_threshold(account_info: &AccountInfo, expected_user: &Pubkey)
     
     // Parse account data
     let account_data = account_info.try_borrow_data()?;
-    let liquidity_threshold = LiquidityThreshold::try_from_slice(&account_data)?;
+    let liquidity_threshold = LiquidityThreshold::try_deserialize(&mut &account_data[..])?;
     
     // Validate ownership
     require!(
@@ -587,29 +1590,31 @@ fn validate_token_account(account_info: &AccountInfo, _expected_user: &Pubkey) -
 }
 
 /// Validate that a trove is actually undercollateralized and can be liquidated
-fn validate_trove_for_liquidation(trove_data: &TroveData, oracle_ctx: &OracleContext) -> Result<()> {
-    // Calculate current collateral value
-    let mut total_collateral_value = 0u64;
-    
-    for (denom, amount) in &trove_data.collateral_amounts {
-        let price_data = oracle_ctx.get_price(denom)?;
-        let collateral_value = PriceCalculator::calculate_collateral_value(
-            *amount,
-            price_data.price as u64,
-            price_data.decimal,
-        )?;
-        total_collateral_value = total_collateral_value.saturating_add(collateral_value);
+fn validate_trove_for_liquidation<'info>(
+    trove_data: &TroveData,
+    oracle_ctx: &OracleContext<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    prices: &mut Vec<(String, u64, u8)>,
+    configs: &[CollateralConfig],
+    protocol_minimum_ratio: u8,
+    recovery_mode: bool,
+) -> Result<()> {
+    // Price every denom the trove holds
+    for (denom, _amount) in &trove_data.collateral_amounts {
+        cache_denom_price(oracle_ctx, denom, remaining_accounts, prices)?;
     }
     
-    // Calculate current ICR
-    let current_icr = PriceCalculator::calculate_collateral_ratio(
-        total_collateral_value,
+    // Calculate current ICR across all of the trove's collateral
+    let current_icr = PriceCalculator::calculate_trove_icr(
+        &trove_data.collateral_amounts,
         trove_data.debt_amount,
+        prices,
     )?;
     
-    // Check if trove is undercollateralized (ICR < 110%)
-    // Both current_icr and threshold are simple percentages
-    let liquidation_threshold = 110u64; // 110%
+    let risk_ratios = trove_risk_ratios(&trove_data.collateral_amounts, prices, configs, protocol_minimum_ratio)?;
+    let liquidation_threshold = get_liquidation_threshold(recovery_mode, risk_ratios.liquidation_threshold)?;
+    
+    // Check if trove is undercollateralized (ICR below its threshold, or < CCR in Recovery Mode)
     require!(
         current_icr < liquidation_threshold,
         AerospacerProtocolError::CollateralBelowMinimum // Reuse error for now
@@ -625,179 +1630,340 @@ fn validate_trove_for_liquidation(trove_data: &TroveData, oracle_ctx: &OracleCon
 fn update_user_accounts_after_liquidation(
     user: &Pubkey,
     user_index: usize,
+    trove_data: &TroveData,
     remaining_accounts: &[AccountInfo],
 ) -> Result<()> {
     let account_start = user_index * 4;
     
-    // Update UserDebtAmount to zero
+    // Update UserDebtAmount to zero - the trove no longer holds any collateral
     let debt_account = &remaining_accounts[account_start];
     let mut debt_data = debt_account.try_borrow_mut_data()?;
-    let mut user_debt_amount = UserDebtAmount::try_from_slice(&debt_data)?;
+    let mut user_debt_amount = UserDebtAmount::try_deserialize(&mut &debt_data[..])?;
     user_debt_amount.amount = 0;
-    user_debt_amount.serialize(&mut &mut debt_data[..])?;
+    user_debt_amount.collateral_denoms.clear();
+    user_debt_amount.try_serialize(&mut &mut debt_data[..])?;
+    drop(debt_data);
     
-    // Update UserCollateralAmount to zero
-    let collateral_account = &remaining_accounts[account_start + 1];
-    let mut collateral_data = collateral_account.try_borrow_mut_data()?;
-    let mut user_collateral_amount = UserCollateralAmount::try_from_slice(&collateral_data)?;
-    user_collateral_amount.amount = 0;
-    user_collateral_amount.serialize(&mut &mut collateral_data[..])?;
+    // Update every UserCollateralAmount of the trove to zero
+    for (denom, _amount) in &trove_data.collateral_amounts {
+        let collateral_account = find_user_collateral_account(user, denom, remaining_accounts)?;
+        set_user_collateral_amount(collateral_account, 0)?;
+    }
     
-    // Update LiquidityThreshold to zero
+    // Update LiquidityThreshold to zero and take the trove out of the sorted lists
     let liquidity_account = &remaining_accounts[account_start + 2];
     let mut liquidity_data = liquidity_account.try_borrow_mut_data()?;
-    let mut liquidity_threshold = LiquidityThreshold::try_from_slice(&liquidity_data)?;
-    liquidity_threshold.ratio = 0;
-    liquidity_threshold.serialize(&mut &mut liquidity_data[..])?;
+    let mut liquidity_threshold = LiquidityThreshold::try_deserialize(&mut &liquidity_data[..])?;
+    liquidity_threshold.clear();
+    liquidity_threshold.try_serialize(&mut &mut liquidity_data[..])?;
     
     msg!("Updated user accounts after liquidation: user={}", user);
     
     Ok(())
 }
 
-/// Distribute liquidation gains to stability pool stakers using Liquity's Product-Sum snapshot algorithm
-/// 
-/// This function updates global P and S factors to track:
-/// - P factor: Pool depletion from debt burns (used to calculate compounded stakes)
-/// - S factors: Cumulative collateral rewards per denomination (used to calculate gains)
-/// 
-/// The snapshot mechanism prevents post-liquidation gaming by capturing state at deposit time.
-/// Actual per-user distribution is "lazy" - happens when users call withdraw_liquidation_gains.
-/// 
-/// # Arguments
-/// * `state` - Mutable protocol state to update P factor and epoch
-/// * `collateral_amounts` - Vector of (denom, amount) pairs seized from liquidation
-/// * `debt_amount` - The debt amount that was liquidated (burned from pool)
-/// * `remaining_accounts` - Must include StabilityPoolSnapshot PDAs after the 4n trove accounts
-/// * `num_troves` - Number of troves being liquidated (to calculate where snapshot PDAs start)
-fn distribute_liquidation_gains_to_stakers(
-    state: &mut StateAccount,
-    collateral_amounts: &Vec<(String, u64)>,
-    debt_amount: u64,
-    remaining_accounts: &[AccountInfo],
-    num_troves: usize,
-) -> Result<()> {
-    let total_stake = state.total_stake_amount;
+#[cfg(test)]
+mod tests {
+    use super::*;
     
-    msg!("Distributing liquidation gains to stability pool (snapshot algorithm):");
-    msg!("  Total stake in pool: {}", total_stake);
-    msg!("  Debt liquidated: {}", debt_amount);
-    msg!("  Current P factor: {}", state.p_factor);
-    msg!("  Current epoch: {}", state.epoch);
+    // 1000 SOL at 2 aUSD base units each
+    fn system() -> SystemCollateral {
+        SystemCollateral {
+            amounts: vec![("SOL".to_string(), 1_000)],
+            prices: vec![("SOL".to_string(), 2, 0)],
+        }
+    }
     
-    // If no stakers, collateral stays in vault (no distribution needed)
-    if total_stake == 0 {
-        msg!("  No stakers - seized collateral remains in protocol vault");
-        return Ok(());
+    fn config(denom: &str, liquidation_penalty_bps: u16, treasury_share_bps: u16) -> CollateralConfig {
+        CollateralConfig {
+            denom: denom.to_string(),
+            minimum_collateral_ratio: 130,
+            liquidation_threshold: 120,
+            liquidation_penalty_bps,
+            treasury_share_bps,
+            debt_ceiling: u64::MAX,
+            deposit_cap: u64::MAX,
+            decimals: 0,
+            enabled: true,
+        }
     }
     
-    // STEP 1: Update P factor (tracks pool depletion from debt burn)
-    // Formula: P_new = P_old × (total_stake - debt_liquidated) / total_stake
-    let remaining_stake = total_stake.saturating_sub(debt_amount);
+    #[test]
+    fn test_recovery_mode_below_critical_ratio() {
+        let system = system();
+        
+        // 2000 of collateral value against 1000 of debt: TCR 200%
+        assert_eq!(system.tcr(1_000).unwrap(), Decimal::from_percent(200));
+        assert!(!system.is_recovery_mode(1_000).unwrap());
+        
+        // At exactly the 150% CCR the system is still in normal mode
+        assert!(!system.is_recovery_mode(1_333).unwrap());
+        assert!(system.is_recovery_mode(1_334).unwrap());
+        
+        // No debt at all is never Recovery Mode
+        assert!(!system.is_recovery_mode(0).unwrap());
+    }
     
-    if remaining_stake == 0 {
-        // Pool completely depleted - start new epoch
-        state.epoch = state.epoch
-            .checked_add(1)
-            .ok_or(AerospacerProtocolError::OverflowError)?;
-        state.p_factor = StateAccount::SCALE_FACTOR;
-        state.total_stake_amount = 0;
-        msg!("  Pool depleted to 0 - starting epoch {}", state.epoch);
-        msg!("  P factor reset to SCALE_FACTOR");
-    } else {
-        // Calculate depletion ratio: (remaining_stake / total_stake)
-        let depletion_ratio = (remaining_stake as u128)
-            .checked_mul(StateAccount::SCALE_FACTOR)
-            .ok_or(AerospacerProtocolError::OverflowError)?
-            .checked_div(total_stake as u128)
-            .ok_or(AerospacerProtocolError::DivideByZeroError)?;
+    #[test]
+    fn test_tcr_after_withdrawal() {
+        let system = system();
         
-        // Update P: P_new = P_old × depletion_ratio
-        state.p_factor = state.p_factor
-            .checked_mul(depletion_ratio)
-            .ok_or(AerospacerProtocolError::OverflowError)?
-            .checked_div(StateAccount::SCALE_FACTOR)
-            .ok_or(AerospacerProtocolError::DivideByZeroError)?;
+        // Withdrawing 250 SOL leaves 1500 of value against 1000 of debt
+        assert_eq!(system.tcr_after_withdrawal("SOL", 250, 1_000).unwrap(), Decimal::from_percent(150));
+        assert!(system.tcr_after_withdrawal("SOL", 251, 1_000).unwrap() < CRITICAL_COLLATERAL_RATIO);
         
-        state.total_stake_amount = remaining_stake;
+        // The system itself is untouched, and unknown denoms change nothing
+        assert_eq!(system.tcr(1_000).unwrap(), Decimal::from_percent(200));
+        assert_eq!(system.tcr_after_withdrawal("ETH", 500, 1_000).unwrap(), Decimal::from_percent(200));
+    }
+    
+    #[test]
+    fn test_removed_collateral_stops_backing_debt() {
+        let mut system = system();
         
-        msg!("  Updated P factor: {} (depletion ratio: {})", state.p_factor, depletion_ratio);
-        msg!("  Remaining stake: {}", remaining_stake);
+        // A batch liquidation that sends 400 SOL to the pool while burning 100 of debt
+        // moves the TCR from 200% (2000 / 1000) down to 133% (1200 / 900)
+        system.remove_collateral("SOL", 400);
+        assert!(system.is_recovery_mode(900).unwrap());
+        assert_eq!(system.amounts, vec![("SOL".to_string(), 600)]);
     }
     
-    // STEP 2: Update S factors for each collateral type (tracks cumulative rewards)
-    // Formula: S_new = S_old + (collateral_seized / total_stake_before_liquidation)
-    // StabilityPoolSnapshot PDAs start after the trove accounts (4 per trove)
-    let snapshot_pdas_start = num_troves * 4;
+    #[test]
+    fn test_adjustment_lowers_collateralization() {
+        let adjustment = |collateral_change, is_collateral_increase, debt_change, is_debt_increase| TroveAdjustment {
+            collateral_change,
+            is_collateral_increase,
+            debt_change,
+            is_debt_increase,
+        };
+        
+        // Withdrawing or borrowing weakens the trove, even alongside a deposit or repayment
+        assert!(adjustment(10, false, 0, false).lowers_collateralization());
+        assert!(adjustment(0, false, 10, true).lowers_collateralization());
+        assert!(adjustment(10, true, 10, true).lowers_collateralization());
+        assert!(adjustment(10, false, 10, false).lowers_collateralization());
+        
+        // Deposits and repayments only strengthen it, and zero changes are ignored
+        assert!(!adjustment(10, true, 0, false).lowers_collateralization());
+        assert!(!adjustment(0, false, 10, false).lowers_collateralization());
+        assert!(!adjustment(10, true, 10, false).lowers_collateralization());
+        assert!(!adjustment(0, false, 0, true).lowers_collateralization());
+    }
     
-    for (denom, amount) in collateral_amounts {
-        // Calculate S increment: (collateral / total_stake) × SCALE_FACTOR
-        let s_increment = (*amount as u128)
-            .checked_mul(StateAccount::SCALE_FACTOR)
-            .ok_or(AerospacerProtocolError::OverflowError)?
-            .checked_div(total_stake as u128)
-            .ok_or(AerospacerProtocolError::DivideByZeroError)?;
+    #[test]
+    fn test_repayment_keeps_gas_compensation() {
+        let debt = GAS_COMPENSATION + 1_000;
+        
+        // Repayments must leave more than the reserve behind
+        assert!(check_repayment_above_gas_compensation(debt, 999).is_ok());
+        assert!(check_repayment_above_gas_compensation(debt, 1_000).is_err());
+        assert!(check_repayment_above_gas_compensation(debt, debt).is_err());
+        
+        // A trove holding no more than the reserve cannot be partially repaid at all
+        assert!(check_repayment_above_gas_compensation(GAS_COMPENSATION, 1).is_err());
+        assert!(check_repayment_above_gas_compensation(GAS_COMPENSATION / 2, 1).is_err());
+    }
+    
+    #[test]
+    fn test_set_liquidity_threshold_rewrites_stored_ratios() {
+        let owner = Pubkey::new_unique();
+        let stale = LiquidityThreshold {
+            owner,
+            ratio: Decimal::from_percent(300),
+            nominal_ratios: vec![],
+        };
+        let mut data = Vec::new();
+        stale.try_serialize(&mut data).unwrap();
+        data.resize(8 + LiquidityThreshold::LEN, 0);
+        
+        // 1000 SOL that fell from 3 to 2 aUSD base units against 1000 of debt
+        let collateral_amounts = vec![("SOL".to_string(), 1_000)];
+        let icr = PriceCalculator::calculate_trove_icr(&collateral_amounts, 1_000, &system().prices).unwrap();
+        assert_eq!(icr, Decimal::from_percent(200));
+        
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        set_liquidity_threshold(&account, icr, &collateral_amounts, 1_000).unwrap();
+        
+        let refreshed = LiquidityThreshold::try_deserialize(&mut &account.try_borrow_data().unwrap()[..]).unwrap();
+        assert_eq!(refreshed.owner, owner);
+        assert_eq!(refreshed.ratio, icr);
+        assert_eq!(refreshed.nominal_ratio("SOL"), Some(crate::sorted_troves::compute_nominal_icr(1_000, 1_000).unwrap()));
         
-        // Expected StabilityPoolSnapshot PDA for this denom
-        let snapshot_seeds = [
-            b"stability_pool_snapshot",
-            denom.as_bytes(),
+        // Accounts the program does not own are never written
+        let foreign_owner = Pubkey::new_unique();
+        let mut data = Vec::new();
+        stale.try_serialize(&mut data).unwrap();
+        let mut lamports = 0;
+        let account = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &foreign_owner, false, 0);
+        assert!(set_liquidity_threshold(&account, icr, &collateral_amounts, 1_000).is_err());
+    }
+    
+    #[test]
+    fn test_gain_moved_into_trove_prices_every_denom() {
+        // 10 ETH at 100 alongside the SOL position, against 1000 of debt
+        let other_collateral = OtherCollateral {
+            amounts: vec![("ETH".to_string(), 10)],
+            prices: vec![("ETH".to_string(), 100, 0)],
+            configs: vec![],
+        };
+        let sol_price = PriceData {
+            denom: "SOL".to_string(),
+            price: 2,
+            decimal: 0,
+            confidence: 0,
+            timestamp: 0,
+            exponent: 0,
+        };
+        assert_eq!(other_collateral.trove_icr("SOL", 300, &sol_price, 1_000).unwrap(), Decimal::from_percent(160));
+        
+        // 200 SOL of stability pool gains on top of the 300 held: (500 × 2 + 10 × 100) / 1000
+        assert_eq!(other_collateral.trove_icr("SOL", 500, &sol_price, 1_000).unwrap(), Decimal::from_percent(200));
+        
+        let mut liquidity_threshold = LiquidityThreshold {
+            owner: Pubkey::default(),
+            ratio: Decimal::ZERO,
+            nominal_ratios: vec![],
+        };
+        liquidity_threshold
+            .set_nominal_ratios(&other_collateral.collateral_amounts("SOL", 500), 1_000)
+            .unwrap();
+        assert_eq!(
+            liquidity_threshold.nominal_ratio("SOL"),
+            Some(crate::sorted_troves::compute_nominal_icr(500, 1_000).unwrap())
+        );
+        assert_eq!(
+            liquidity_threshold.nominal_ratio("ETH"),
+            Some(crate::sorted_troves::compute_nominal_icr(10, 1_000).unwrap())
+        );
+    }
+    
+    #[test]
+    fn test_risk_ratios_are_weighted_by_collateral_value() {
+        // 1000 SOL at 2 (130% / 120%) and 10 ETH at 100 (160% / 140%): two thirds of the value is SOL
+        let configs = vec![
+            config("SOL", 1_000, 5_000),
+            CollateralConfig {
+                minimum_collateral_ratio: 160,
+                liquidation_threshold: 140,
+                ..config("ETH", 1_000, 5_000)
+            },
         ];
-        let (expected_pda, _bump) = Pubkey::find_program_address(&snapshot_seeds, &crate::ID);
+        let prices = vec![("SOL".to_string(), 2, 0), ("ETH".to_string(), 100, 0)];
+        let collateral_amounts = vec![("SOL".to_string(), 1_000), ("ETH".to_string(), 10)];
         
-        // Look for the PDA in remaining_accounts
-        let mut found = false;
-        for i in snapshot_pdas_start..remaining_accounts.len() {
-            let account_info = &remaining_accounts[i];
-            
-            if account_info.key() == expected_pda {
-                let mut data = account_info.try_borrow_mut_data()?;
-                
-                if data.len() >= 8 + StabilityPoolSnapshot::LEN {
-                    // Deserialize and update existing snapshot
-                    let mut snapshot = StabilityPoolSnapshot::try_deserialize(&mut &data[..])?;
-                    
-                    // S_new = S_old + s_increment
-                    snapshot.s_factor = snapshot.s_factor
-                        .checked_add(s_increment)
-                        .ok_or(AerospacerProtocolError::OverflowError)?;
-                    
-                    snapshot.total_collateral_gained = snapshot.total_collateral_gained
-                        .checked_add(*amount)
-                        .ok_or(AerospacerProtocolError::OverflowError)?;
-                    
-                    snapshot.epoch = state.epoch;
-                    
-                    // Serialize back
-                    snapshot.try_serialize(&mut &mut data[..])?;
-                    
-                    msg!("  Updated S factor for {}: +{} (new S: {})", 
-                         denom, s_increment, snapshot.s_factor);
-                } else {
-                    // Initialize new snapshot
-                    let snapshot = StabilityPoolSnapshot {
-                        denom: denom.clone(),
-                        s_factor: s_increment,
-                        total_collateral_gained: *amount,
-                        epoch: state.epoch,
-                    };
-                    snapshot.try_serialize(&mut &mut data[..])?;
-                    
-                    msg!("  Initialized S factor for {}: {}", denom, s_increment);
-                }
-                
-                found = true;
-                break;
-            }
-        }
+        let ratios = trove_risk_ratios(&collateral_amounts, &prices, &configs, 110).unwrap();
+        assert_eq!(ratios.minimum_collateral_ratio, Decimal::from_percent(140));
+        // (2000 × 120 + 1000 × 140) / 3000 = 126.67%, rounded up against the trove
+        assert_eq!(
+            ratios.liquidation_threshold,
+            Decimal::from_ratio(380_000, 300_000, Rounding::Up).unwrap()
+        );
+        assert!(ratios.liquidation_threshold > Decimal::from_ratio(380_000, 300_000, Rounding::Down).unwrap());
         
-        if !found {
-            msg!("  WARNING: StabilityPoolSnapshot PDA not provided for {}", denom);
-            msg!("  Collateral gains will not be tracked for this denomination");
-        }
+        // The protocol minimum collateral ratio is a floor under the weighted one
+        let ratios = trove_risk_ratios(&collateral_amounts, &prices, &configs, 150).unwrap();
+        assert_eq!(ratios.minimum_collateral_ratio, Decimal::from_percent(150));
+        
+        // A trove with no collateral value is held to its strictest denom
+        let empty = vec![("SOL".to_string(), 0), ("ETH".to_string(), 0)];
+        let ratios = trove_risk_ratios(&empty, &prices, &configs, 110).unwrap();
+        assert_eq!(ratios.minimum_collateral_ratio, Decimal::from_percent(160));
+        assert_eq!(ratios.liquidation_threshold, Decimal::from_percent(140));
+        
+        // Every denom must be configured
+        let unconfigured = vec![("BTC".to_string(), 1)];
+        assert!(trove_risk_ratios(&unconfigured, &prices, &configs, 110).is_err());
     }
     
-    msg!("Liquidation gains distribution complete (snapshot algorithm)");
+    #[test]
+    fn test_deposit_cap() {
+        let capped = CollateralConfig { deposit_cap: 1_000, ..config("SOL", 1_000, 5_000) };
+        
+        assert!(check_deposit_cap(&capped, 900, 100).is_ok());
+        assert!(check_deposit_cap(&capped, 900, 101).is_err());
+        assert!(check_deposit_cap(&capped, 1_001, 0).is_err());
+        assert!(check_deposit_cap(&config("SOL", 1_000, 5_000), u64::MAX, 1).is_err());
+    }
     
-    Ok(())
+    #[test]
+    fn test_debt_ceiling_counts_the_debt_a_denom_backs() {
+        // 2000 of SOL value and 1000 of ETH value against 900 of debt: SOL backs 600
+        let mut system = system();
+        system.amounts.push(("ETH".to_string(), 10));
+        system.prices.push(("ETH".to_string(), 100, 0));
+        assert_eq!(system.debt_backed_by("SOL", 900).unwrap(), 600);
+        assert_eq!(system.debt_backed_by("ETH", 900).unwrap(), 300);
+        assert_eq!(system.debt_backed_by("BTC", 900).unwrap(), 0);
+        
+        let ceiling = |debt_ceiling| CollateralConfig { debt_ceiling, ..config("SOL", 1_000, 5_000) };
+        assert!(system.check_debt_ceiling(&ceiling(600), 900).is_ok());
+        assert!(system.check_debt_ceiling(&ceiling(599), 900).is_err());
+        
+        // With no collateral at all no denom backs any debt
+        assert_eq!(SystemCollateral::default().debt_backed_by("SOL", 900).unwrap(), 0);
+    }
+    
+    #[test]
+    fn test_liquidation_penalty_split() {
+        let configs = vec![config("SOL", 1_000, 5_000), config("ETH", 500, 10_000)];
+        let collateral_amounts = vec![("SOL".to_string(), 10_000), ("ETH".to_string(), 2_000)];
+        let penalty = split_liquidation_penalty(&collateral_amounts, &configs).unwrap();
+        
+        // SOL: a 10% penalty of 1000, of which 0.5% of the collateral goes to the liquidator
+        // and the remaining 950 is shared evenly between the treasury and the stability pool.
+        // ETH: a 5% penalty of 100, with the whole shared part going to the treasury
+        assert_eq!(penalty.to_liquidator, vec![("SOL".to_string(), 50), ("ETH".to_string(), 10)]);
+        assert_eq!(penalty.to_treasury, vec![("SOL".to_string(), 475), ("ETH".to_string(), 90)]);
+        assert_eq!(penalty.to_stability_pool, vec![("SOL".to_string(), 475), ("ETH".to_string(), 0)]);
+        assert_eq!(penalty.backing_collateral, vec![("SOL".to_string(), 9_000), ("ETH".to_string(), 1_900)]);
+        
+        // Every denom must be configured
+        let unconfigured = vec![("BTC".to_string(), 1_000)];
+        assert!(split_liquidation_penalty(&unconfigured, &configs).is_err());
+    }
+    
+    #[test]
+    fn test_liquidation_penalty_rounds_towards_the_trove() {
+        let configs = vec![config("SOL", 1_000, 5_000)];
+        let penalty = split_liquidation_penalty(&[("SOL".to_string(), 999)], &configs).unwrap();
+        
+        // 99.9 of penalty rounds down to 99 and 4.995 for the liquidator down to 4; the treasury's
+        // 47.5 rounds down and the stability pool takes the remainder, so nothing is lost
+        assert_eq!(penalty.to_liquidator, vec![("SOL".to_string(), 4)]);
+        assert_eq!(penalty.to_treasury, vec![("SOL".to_string(), 47)]);
+        assert_eq!(penalty.to_stability_pool, vec![("SOL".to_string(), 48)]);
+        assert_eq!(penalty.backing_collateral, vec![("SOL".to_string(), 900)]);
+    }
+    
+    #[test]
+    fn test_liquidator_share_is_capped_at_the_penalty() {
+        // A penalty below LIQUIDATOR_COLLATERAL_BPS all goes to the liquidator
+        let configs = vec![config("SOL", LIQUIDATOR_COLLATERAL_BPS - 20, 5_000)];
+        let penalty = split_liquidation_penalty(&[("SOL".to_string(), 10_000)], &configs).unwrap();
+        
+        assert_eq!(penalty.to_liquidator, vec![("SOL".to_string(), 30)]);
+        assert_eq!(penalty.to_treasury, vec![("SOL".to_string(), 0)]);
+        assert_eq!(penalty.to_stability_pool, vec![("SOL".to_string(), 0)]);
+        assert_eq!(penalty.backing_collateral, vec![("SOL".to_string(), 9_970)]);
+    }
+    
+    #[test]
+    fn test_empty_liquidation_penalty_needs_no_accounts() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = vec![];
+        let account = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &crate::ID, false, 0);
+        let liquidator = Pubkey::new_unique();
+        let nothing = vec![("SOL".to_string(), 0)];
+        
+        // Denoms with nothing to pay are skipped without looking up their vaults
+        assert!(pay_liquidation_penalty(&liquidator, &nothing, &nothing, &account, &account, &account, &[]).is_ok());
+        
+        // Anything owed needs the denom's vault and the liquidator's token account
+        let owed = vec![("SOL".to_string(), 1)];
+        assert!(pay_liquidation_penalty(&liquidator, &owed, &nothing, &account, &account, &account, &[]).is_err());
+        assert!(pay_liquidation_penalty(&liquidator, &nothing, &owed, &account, &account, &account, &[]).is_err());
+    }
 }
@@ -0,0 +1,274 @@
+//! On-chain trove registry
+//!
+//! Every active trove is listed under the denom it was opened with, in a chain of fixed-size
+//! TroveRegistryPage PDAs. Keepers and frontends page through it with query_trove_registry
+//! instead of scanning the program with getProgramAccounts, which many RPC providers throttle.
+//!
+//! open_trove adds the owner to a page of its choosing (the next page index starts a new page),
+//! close_trove and liquidations remove it again.
+
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::error::*;
+
+/// List a newly opened trove on `page` of `denom`'s registry
+///
+/// `page` was created by this instruction if its denom is still empty, in which case it must
+/// extend the chain.
+pub fn register_trove(
+    registry: &mut TroveRegistry,
+    page: &mut TroveRegistryPage,
+    denom: &str,
+    page_index: u32,
+    owner: Pubkey,
+) -> Result<()> {
+    if registry.denom.is_empty() {
+        registry.denom = denom.to_string();
+    }
+    
+    if page.denom.is_empty() {
+        require!(
+            page_index == registry.page_count,
+            AerospacerProtocolError::InvalidTroveRegistryPage
+        );
+        page.denom = denom.to_string();
+        page.page_index = page_index;
+        registry.page_count = registry.page_count
+            .checked_add(1)
+            .ok_or(AerospacerProtocolError::OverflowError)?;
+    }
+    
+    require!(
+        page.owners.len() < TROVE_REGISTRY_PAGE_SIZE,
+        AerospacerProtocolError::TroveRegistryPageFull
+    );
+    require!(
+        !page.owners.contains(&owner),
+        AerospacerProtocolError::TroveExists
+    );
+    
+    page.owners.push(owner);
+    registry.trove_count = registry.trove_count
+        .checked_add(1)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+    Ok(())
+}
+
+/// Take a closed or liquidated trove off `page`
+pub fn deregister_trove(
+    registry: &mut TroveRegistry,
+    page: &mut TroveRegistryPage,
+    owner: &Pubkey,
+) -> Result<()> {
+    let position = page.owners
+        .iter()
+        .position(|o| o == owner)
+        .ok_or(AerospacerProtocolError::TroveNotRegistered)?;
+    
+    page.owners.swap_remove(position);
+    registry.trove_count = registry.trove_count.saturating_sub(1);
+    Ok(())
+}
+
+/// Take a liquidated trove off the registry page listing it, locating the page and its
+/// TroveRegistry by address in remaining_accounts
+///
+/// Used by instructions that close several troves at once. Every registry page in `accounts`
+/// is checked for the owner, so the client only has to include the pages listing the troves.
+pub fn deregister_trove_by_address(owner: &Pubkey, accounts: &[AccountInfo]) -> Result<()> {
+    for page_account in accounts.iter().filter(|a| a.owner == &crate::ID) {
+        let mut page_data = page_account.try_borrow_mut_data()?;
+        if !page_data.starts_with(TroveRegistryPage::DISCRIMINATOR) {
+            continue;
+        }
+        
+        let mut page = TroveRegistryPage::try_deserialize(&mut &page_data[..])?;
+        if !page.owners.contains(owner) {
+            continue;
+        }
+        
+        let page_index_bytes = page.page_index.to_le_bytes();
+        let (expected_page, _bump) = Pubkey::find_program_address(
+            &TroveRegistryPage::seeds(&page.denom, &page_index_bytes),
+            &crate::ID,
+        );
+        require!(
+            page_account.key() == expected_page,
+            AerospacerProtocolError::InvalidTroveRegistryPage
+        );
+        
+        let (expected_registry, _bump) = Pubkey::find_program_address(&TroveRegistry::seeds(&page.denom), &crate::ID);
+        let registry_account = accounts
+            .iter()
+            .find(|a| a.key() == expected_registry)
+            .ok_or(AerospacerProtocolError::InvalidTroveRegistryPage)?;
+        let mut registry_data = registry_account.try_borrow_mut_data()?;
+        let mut registry = TroveRegistry::try_deserialize(&mut &registry_data[..])?;
+        
+        deregister_trove(&mut registry, &mut page, owner)?;
+        
+        page.try_serialize(&mut &mut page_data[..])?;
+        registry.try_serialize(&mut &mut registry_data[..])?;
+        return Ok(());
+    }
+    
+    err!(AerospacerProtocolError::TroveNotRegistered)
+}
+
+/// Every trove owner listed in `denom`'s registry, proven complete
+///
+/// `accounts` must start with the denom's TroveRegistry, then each of its pages in order. A
+/// registry that has never been initialized lists no troves. Returns the owners together with
+/// the number of accounts read.
+pub fn load_registry_owners(denom: &str, accounts: &[AccountInfo]) -> Result<(Vec<Pubkey>, usize)> {
+    let registry_account = accounts.first().ok_or(AerospacerProtocolError::InvalidTroveRegistryPage)?;
+    let (expected_registry, _bump) = Pubkey::find_program_address(&TroveRegistry::seeds(denom), &crate::ID);
+    require!(
+        registry_account.key() == expected_registry,
+        AerospacerProtocolError::InvalidTroveRegistryPage
+    );
+    if registry_account.data_is_empty() {
+        return Ok((Vec::new(), 1));
+    }
+    require!(
+        registry_account.owner == &crate::ID,
+        AerospacerProtocolError::InvalidTroveRegistryPage
+    );
+    let registry = TroveRegistry::try_deserialize(&mut &registry_account.try_borrow_data()?[..])?;
+    
+    let mut owners = Vec::new();
+    let mut consumed = 1;
+    for page_index in 0..registry.page_count {
+        let page_account = accounts.get(consumed).ok_or(AerospacerProtocolError::InvalidTroveRegistryPage)?;
+        let page_index_bytes = page_index.to_le_bytes();
+        let (expected_page, _bump) = Pubkey::find_program_address(
+            &TroveRegistryPage::seeds(denom, &page_index_bytes),
+            &crate::ID,
+        );
+        require!(
+            page_account.key() == expected_page && page_account.owner == &crate::ID,
+            AerospacerProtocolError::InvalidTroveRegistryPage
+        );
+        let page = TroveRegistryPage::try_deserialize(&mut &page_account.try_borrow_data()?[..])?;
+        owners.extend(page.owners);
+        consumed += 1;
+    }
+    
+    require!(
+        owners.len() as u64 == registry.trove_count,
+        AerospacerProtocolError::InvalidTroveRegistryPage
+    );
+    Ok((owners, consumed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn blank_registry() -> TroveRegistry {
+        TroveRegistry {
+            denom: String::new(),
+            page_count: 0,
+            trove_count: 0,
+        }
+    }
+    
+    fn blank_page() -> TroveRegistryPage {
+        TroveRegistryPage {
+            denom: String::new(),
+            page_index: 0,
+            owners: vec![],
+        }
+    }
+    
+    fn account_data<T: AccountSerialize>(account: &T, len: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data.resize(len, 0);
+        data
+    }
+    
+    #[test]
+    fn test_pages_extend_the_chain_and_reuse_freed_slots() {
+        let mut registry = blank_registry();
+        let mut first = blank_page();
+        
+        // A new page must be the next one in the chain
+        assert!(register_trove(&mut registry, &mut first, "SOL", 1, Pubkey::new_unique()).is_err());
+        let owners: Vec<Pubkey> = (0..TROVE_REGISTRY_PAGE_SIZE).map(|_| Pubkey::new_unique()).collect();
+        for owner in &owners {
+            register_trove(&mut registry, &mut first, "SOL", 0, *owner).unwrap();
+        }
+        assert_eq!(registry.denom, "SOL");
+        assert_eq!(registry.page_count, 1);
+        assert_eq!(registry.trove_count, TROVE_REGISTRY_PAGE_SIZE as u64);
+        
+        // Full pages and owners that are already listed are rejected
+        assert!(register_trove(&mut registry, &mut first, "SOL", 0, Pubkey::new_unique()).is_err());
+        let mut second = blank_page();
+        assert!(register_trove(&mut registry, &mut second, "SOL", 2, Pubkey::new_unique()).is_err());
+        let late_owner = Pubkey::new_unique();
+        register_trove(&mut registry, &mut second, "SOL", 1, late_owner).unwrap();
+        assert!(register_trove(&mut registry, &mut second, "SOL", 1, late_owner).is_err());
+        assert_eq!(second.page_index, 1);
+        assert_eq!(registry.page_count, 2);
+        assert_eq!(registry.trove_count, TROVE_REGISTRY_PAGE_SIZE as u64 + 1);
+        
+        // Closing a trove frees its slot for the next one without adding a page
+        deregister_trove(&mut registry, &mut first, &owners[3]).unwrap();
+        assert!(deregister_trove(&mut registry, &mut first, &owners[3]).is_err());
+        assert!(deregister_trove(&mut registry, &mut second, &owners[4]).is_err());
+        register_trove(&mut registry, &mut first, "SOL", 0, Pubkey::new_unique()).unwrap();
+        assert_eq!(registry.page_count, 2);
+        assert_eq!(first.owners.len(), TROVE_REGISTRY_PAGE_SIZE);
+        assert!(!first.owners.contains(&owners[3]));
+        
+        assert_eq!(
+            registry.trove_count,
+            (first.owners.len() + second.owners.len()) as u64
+        );
+    }
+    
+    #[test]
+    fn test_registry_owners_match_the_trove_count() {
+        let mut registry = blank_registry();
+        let mut first = blank_page();
+        let mut second = blank_page();
+        let owners: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        register_trove(&mut registry, &mut first, "SOL", 0, owners[0]).unwrap();
+        register_trove(&mut registry, &mut first, "SOL", 0, owners[1]).unwrap();
+        register_trove(&mut registry, &mut second, "SOL", 1, owners[2]).unwrap();
+        
+        let (registry_key, _) = Pubkey::find_program_address(&TroveRegistry::seeds("SOL"), &crate::ID);
+        let (first_key, _) = Pubkey::find_program_address(&TroveRegistryPage::seeds("SOL", &0u32.to_le_bytes()), &crate::ID);
+        let (second_key, _) = Pubkey::find_program_address(&TroveRegistryPage::seeds("SOL", &1u32.to_le_bytes()), &crate::ID);
+        let mut registry_data = account_data(&registry, TroveRegistry::LEN);
+        let mut first_data = account_data(&first, TroveRegistryPage::LEN);
+        let mut second_data = account_data(&second, TroveRegistryPage::LEN);
+        let (mut registry_lamports, mut first_lamports, mut second_lamports) = (0, 0, 0);
+        let accounts = vec![
+            AccountInfo::new(&registry_key, false, true, &mut registry_lamports, &mut registry_data, &crate::ID, false, 0),
+            AccountInfo::new(&first_key, false, true, &mut first_lamports, &mut first_data, &crate::ID, false, 0),
+            AccountInfo::new(&second_key, false, true, &mut second_lamports, &mut second_data, &crate::ID, false, 0),
+        ];
+        
+        let (listed, consumed) = load_registry_owners("SOL", &accounts).unwrap();
+        assert_eq!(listed, owners);
+        assert_eq!(consumed, 3);
+        
+        // Every page is required, in order
+        assert!(load_registry_owners("SOL", &accounts[..2]).is_err());
+        assert!(load_registry_owners("SOL", &[accounts[0].clone(), accounts[2].clone(), accounts[1].clone()]).is_err());
+        
+        // A liquidation found by address keeps the pages and the count in step
+        deregister_trove_by_address(&owners[2], &accounts).unwrap();
+        let (listed, _) = load_registry_owners("SOL", &accounts).unwrap();
+        assert_eq!(listed, owners[..2]);
+        assert!(deregister_trove_by_address(&owners[2], &accounts).is_err());
+        
+        // A registry whose count disagrees with its pages is rejected
+        registry.trove_count = 3;
+        registry.try_serialize(&mut &mut accounts[0].try_borrow_mut_data().unwrap()[..]).unwrap();
+        assert!(load_registry_owners("SOL", &accounts).is_err());
+    }
+}
@@ -1,8 +1,8 @@
-use std::collections::HashMap;
-
 use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Allocate, Assign, CreateAccount, Transfer};
 use crate::state::*;
 use crate::error::*;
+use crate::decimal::{mul_div, Decimal, Rounding};
 
 // LiquidityData is now defined in trove_management.rs
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
@@ -81,12 +81,14 @@ pub fn get_liquidation_gains<'a>(
 
             if user_stake_amount > 0 && total_stake_amount > 0 {
                 // In Injective: Decimal256::from_ratio(stake_amount, total_stake_amount)
-                // For Solana: simplified calculation
-                let stake_percentage = (user_stake_amount * 1_000_000_000_000_000_000) / total_stake_amount; // Simplified Decimal256
+                let stake_percentage = Decimal::from_ratio(
+                    user_stake_amount as u128,
+                    total_stake_amount as u128,
+                    Rounding::Down,
+                )?;
                 
                 // In Injective: calculate_stake_amount(total_collateral_amount, stake_percentage, false)
-                // For Solana: simplified calculation
-                let collateral_gain = (total_collateral_amount * stake_percentage) / 1_000_000_000_000_000_000;
+                let collateral_gain = stake_percentage.mul_amount(total_collateral_amount, Rounding::Down)?;
                 
                 collateral_gains.push(CollateralGain {
                     block_height,
@@ -133,109 +135,113 @@ pub fn update_total_collateral_from_account_info(
 }
 
 // Fee calculation utilities for protocol-fees integration
-pub fn calculate_protocol_fee(amount: u64, fee_percentage: u8) -> Result<u64> {
-    let fee = amount
-        .checked_mul(fee_percentage as u64)
-        .ok_or(AerospacerProtocolError::OverflowError)?
-        .checked_div(100)
-        .ok_or(AerospacerProtocolError::OverflowError)?;
-    
-    Ok(fee)
+//
+// Liquity's fee model: `StateAccount.base_rate` is raised by every redemption in proportion
+// to its share of the aUSD supply and decays exponentially per minute. Borrowing and
+// redemption fees are the base rate plus a floor, capped by a maximum.
+
+/// Raise the per-minute decay factor to the number of elapsed minutes
+/// The exponent is capped at MAX_DECAY_MINUTES so the loop stays bounded
+pub fn decay_factor(minutes: u64) -> Result<Decimal> {
+    MINUTE_DECAY_FACTOR.checked_pow(minutes.min(MAX_DECAY_MINUTES))
 }
 
-pub fn calculate_net_amount_after_fee(amount: u64, fee_percentage: u8) -> Result<u64> {
-    let fee = calculate_protocol_fee(amount, fee_percentage)?;
-    amount
-        .checked_sub(fee)
-        .ok_or(AerospacerProtocolError::OverflowError.into())
+/// Base rate after decaying for the whole minutes since the last fee operation
+pub fn calculate_decayed_base_rate(state: &StateAccount, now: i64) -> Result<Decimal> {
+    let elapsed = now.saturating_sub(state.last_fee_operation_time).max(0) as u64;
+    state.base_rate.checked_mul(decay_factor(elapsed / 60)?, Rounding::HalfUp)
 }
 
-/// Calculate real ICR for a trove with multi-collateral support
-/// 
-/// Returns ICR as a simple percentage (not scaled)
-/// Example: 150% ICR = 150, 200% ICR = 200
-/// 
-/// This replaces the previous mock implementation
-pub fn get_trove_icr<'a>(
-    user_debt_amount: &UserDebtAmount,
-    user_collateral_amount_accounts: &'a [AccountInfo<'a>],
-    collateral_prices: &HashMap<String, u64>,
-    owner: Pubkey,
-) -> Result<u64> {
-    use crate::oracle::PriceCalculator;
-    
-    let debt = user_debt_amount.amount;
-    
-    // If no debt, return maximum ratio
-    if debt == 0 {
-        return Ok(u64::MAX);
+/// Only whole minutes are decayed, so the timestamp moves once at least one has passed
+fn update_last_fee_operation_time(state: &mut StateAccount, now: i64) {
+    if now.saturating_sub(state.last_fee_operation_time) >= 60 {
+        state.last_fee_operation_time = now;
     }
-    
-    // Collect all collateral amounts for this user
-    let mut collateral_amounts: Vec<(String, u64)> = Vec::new();
-    
-    for account_info in user_collateral_amount_accounts {
-        // Try to deserialize the account data directly
-        let account_data = account_info.try_borrow_data()?;
-        
-        // Skip if account is too small to be a UserCollateralAmount
-        if account_data.len() < 8 + UserCollateralAmount::LEN {
-            continue;
-        }
-        
-        // Try to deserialize as UserCollateralAmount
-        if let Ok(collateral_account) = UserCollateralAmount::try_from_slice(&account_data[8..]) {
-            // Verify it belongs to the owner
-            if collateral_account.owner == owner && collateral_account.amount > 0 {
-                collateral_amounts.push((
-                    collateral_account.denom.clone(),
-                    collateral_account.amount,
-                ));
-            }
-        }
-    }
-    
-    // If no collateral, return 0 ratio (fully liquidatable)
-    if collateral_amounts.is_empty() {
-        return Ok(0);
-    }
-    
-    // Convert HashMap prices to Vec format for PriceCalculator
-    // Prices are stored as raw values, we need to add decimal information
-    let mut price_data: Vec<(String, u64, u8)> = Vec::new();
-    
-    for (denom, _amount) in &collateral_amounts {
-        if let Some(price) = collateral_prices.get(denom) {
-            // Get decimal precision for each denom
-            let decimal = match denom.as_str() {
-                "SOL" => 9,
-                "USDC" => 6,
-                "INJ" => 18,
-                "ATOM" => 6,
-                _ => 6, // Default to 6 decimals
-            };
-            
-            price_data.push((denom.clone(), *price, decimal));
-        }
-    }
-    
-    // Calculate total collateral value and ICR
-    let icr = PriceCalculator::calculate_trove_icr(
-        &collateral_amounts,
-        debt,
-        &price_data,
+}
+
+/// Decay the base rate before a borrowing fee is charged
+pub fn decay_base_rate_from_borrowing(state: &mut StateAccount, now: i64) -> Result<()> {
+    state.base_rate = calculate_decayed_base_rate(state, now)?;
+    update_last_fee_operation_time(state, now);
+    Ok(())
+}
+
+/// Decay the base rate and raise it by half of the redeemed share of the aUSD supply
+/// Returns the new base rate, which is capped at 100%
+pub fn update_base_rate_from_redemption(
+    state: &mut StateAccount,
+    redeemed_amount: u64,
+    total_supply: u64,
+    now: i64,
+) -> Result<Decimal> {
+    require!(total_supply > 0, AerospacerProtocolError::DivideByZeroError);
+
+    let decayed_base_rate = calculate_decayed_base_rate(state, now)?;
+    let redeemed_fraction = Decimal::from_ratio(
+        redeemed_amount as u128,
+        total_supply as u128 * BASE_RATE_BETA,
+        Rounding::Down,
     )?;
-    
-    Ok(icr)
+
+    let new_base_rate = decayed_base_rate
+        .checked_add(redeemed_fraction)?
+        .min(Decimal::ONE);
+
+    state.base_rate = new_base_rate;
+    update_last_fee_operation_time(state, now);
+    Ok(new_base_rate)
+}
+
+/// Borrowing fee rate: base rate plus the 0.5% floor, capped at `protocol_fee` percent
+pub fn get_borrowing_rate(state: &StateAccount) -> Decimal {
+    let max_borrowing_fee = Decimal::from_percent(state.protocol_fee as u64);
+    BORROWING_FEE_FLOOR
+        .saturating_add(state.base_rate)
+        .min(max_borrowing_fee)
+}
+
+/// Redemption fee rate: base rate plus the 0.5% floor, capped at 100%
+pub fn get_redemption_rate(base_rate: Decimal) -> Decimal {
+    REDEMPTION_FEE_FLOOR
+        .saturating_add(base_rate)
+        .min(MAX_REDEMPTION_FEE)
+}
+
+/// Apply a fee rate to an aUSD amount (rounded down, in the payer's favour)
+pub fn calculate_fee(amount: u64, fee_rate: Decimal) -> Result<u64> {
+    fee_rate.mul_amount(amount, Rounding::Down)
+}
+
+/// Express a fee rate in basis points (for logs and client-facing limits)
+pub fn fee_rate_to_bps(fee_rate: Decimal) -> u128 {
+    fee_rate.to_bps()
+}
+
+/// Fail if the fee rate charged at execution is above the rate the user signed for
+pub fn check_max_fee(fee_rate: Decimal, max_fee_bps: u16) -> Result<()> {
+    require!(
+        fee_rate <= Decimal::from_bps(max_fee_bps as u64),
+        AerospacerProtocolError::FeeExceedsMaximum
+    );
+    Ok(())
+}
+
+/// Fail if a redemption pays out less collateral than the redeemer signed for
+pub fn check_min_collateral_out(collateral_out: u64, min_collateral_out: u64) -> Result<()> {
+    require!(
+        collateral_out >= min_collateral_out,
+        AerospacerProtocolError::CollateralOutputBelowMinimum
+    );
+    Ok(())
 }
 
 /// Check if a trove's ICR meets the required minimum ratio
-/// ICR and minimum_ratio are both simple percentages (e.g., 150 = 150%)
+/// The state's minimum ratio is a whole percentage (e.g., 150 = 150%)
 pub fn check_trove_icr_with_ratio(
     state_account: &StateAccount,
-    icr: u64,
+    icr: Decimal,
 ) -> Result<()> {
-    let minimum_ratio = state_account.minimum_collateral_ratio as u64;
+    let minimum_ratio = Decimal::from_percent(state_account.minimum_collateral_ratio as u64);
     
     require!(
         icr >= minimum_ratio,
@@ -246,21 +252,24 @@ pub fn check_trove_icr_with_ratio(
 }
 
 /// Check if a trove is liquidatable based on its ICR
-pub fn is_liquidatable_icr(icr: u64, liquidation_threshold: u64) -> bool {
+pub fn is_liquidatable_icr(icr: Decimal, liquidation_threshold: Decimal) -> bool {
     icr < liquidation_threshold
 }
 
-/// Get the liquidation threshold (typically 110%)
-/// Returns as simple percentage: 110
-pub fn get_liquidation_threshold() -> Result<u64> {
-    // 110% ICR is the liquidation threshold
-    Ok(110u64)
+/// Get the liquidation threshold for the current system mode
+/// Returns the trove's own threshold normally, at least the 150% CCR in Recovery Mode
+pub fn get_liquidation_threshold(recovery_mode: bool, trove_threshold: Decimal) -> Result<Decimal> {
+    if recovery_mode {
+        Ok(trove_threshold.max(CRITICAL_COLLATERAL_RATIO))
+    } else {
+        Ok(trove_threshold)
+    }
 }
 
 /// Check if ICR meets minimum collateral ratio requirement
-/// Both ICR and minimum_collateral_ratio are simple percentages
-pub fn check_minimum_icr(icr: u64, minimum_collateral_ratio: u8) -> Result<()> {
-    let minimum_ratio = minimum_collateral_ratio as u64;
+/// minimum_collateral_ratio is a whole percentage
+pub fn check_minimum_icr(icr: Decimal, minimum_collateral_ratio: u8) -> Result<()> {
+    let minimum_ratio = Decimal::from_percent(minimum_collateral_ratio as u64);
     
     require!(
         icr >= minimum_ratio,
@@ -270,101 +279,505 @@ pub fn check_minimum_icr(icr: u64, minimum_collateral_ratio: u8) -> Result<()> {
     Ok(())
 }
 
+/// Check a proposed admin parameter value against the protocol's hard bounds
+pub fn validate_parameter_value(parameter: ProtocolParameter, value: u64) -> Result<()> {
+    let (min, max) = match parameter {
+        ProtocolParameter::MinimumCollateralRatio => (
+            MIN_MINIMUM_COLLATERAL_RATIO as u64,
+            MAX_MINIMUM_COLLATERAL_RATIO as u64,
+        ),
+        ProtocolParameter::ProtocolFee => (MIN_PROTOCOL_FEE as u64, MAX_PROTOCOL_FEE as u64),
+        ProtocolParameter::ParameterTimelockDelay => (
+            MIN_PARAMETER_TIMELOCK_DELAY as u64,
+            MAX_PARAMETER_TIMELOCK_DELAY as u64,
+        ),
+        ProtocolParameter::UnstakeCooldown => (0, MAX_UNSTAKE_COOLDOWN_SLOTS),
+    };
+
+    require!(
+        value >= min && value <= max,
+        AerospacerProtocolError::ParameterOutOfBounds
+    );
+
+    Ok(())
+}
+
+/// Check a collateral config against the protocol's hard bounds
+/// 
+/// The liquidation threshold may not exceed the minimum collateral ratio, and the penalty must
+/// fit in the threshold's margin over 100% so a trove liquidated at the threshold still backs its debt
+pub fn validate_collateral_config(config: &CollateralConfig) -> Result<()> {
+    require!(
+        !config.denom.is_empty(),
+        AerospacerProtocolError::InvalidAmount
+    );
+    require!(
+        config.liquidation_threshold >= MIN_LIQUIDATION_THRESHOLD
+            && config.liquidation_threshold <= config.minimum_collateral_ratio
+            && config.minimum_collateral_ratio <= MAX_MINIMUM_COLLATERAL_RATIO,
+        AerospacerProtocolError::ParameterOutOfBounds
+    );
+    require!(
+        config.liquidation_penalty_bps >= LIQUIDATOR_COLLATERAL_BPS
+            && config.liquidation_penalty_bps <= MAX_LIQUIDATION_PENALTY_BPS,
+        AerospacerProtocolError::ParameterOutOfBounds
+    );
+    let threshold_margin_bps = (config.liquidation_threshold as u64 - 100) * 100;
+    require!(
+        config.liquidation_penalty_bps as u64 <= threshold_margin_bps,
+        AerospacerProtocolError::ParameterOutOfBounds
+    );
+    require!(
+        config.treasury_share_bps as u64 <= BPS_DENOMINATOR,
+        AerospacerProtocolError::ParameterOutOfBounds
+    );
+
+    Ok(())
+}
+
+/// Queue an admin parameter change behind the state's timelock delay
+/// 
+/// Re-queueing a parameter overwrites the pending value and restarts the delay.
+/// Returns the unix timestamp after which the change can be executed.
+pub fn queue_parameter_change(
+    pending: &mut PendingParameterChange,
+    state: &StateAccount,
+    parameter: ProtocolParameter,
+    new_value: u64,
+    now: i64,
+) -> Result<i64> {
+    validate_parameter_value(parameter, new_value)?;
+
+    let executable_at = now
+        .checked_add(state.parameter_timelock_delay)
+        .ok_or(AerospacerProtocolError::OverflowError)?;
+
+    pending.parameter = parameter;
+    pending.new_value = new_value;
+    pending.queued_at = now;
+    pending.executable_at = executable_at;
+
+    Ok(executable_at)
+}
+
+/// Check that a queued parameter change has waited out its timelock
+pub fn check_parameter_change_executable(pending: &PendingParameterChange, now: i64) -> Result<()> {
+    require!(
+        now >= pending.executable_at,
+        AerospacerProtocolError::TimelockNotExpired
+    );
+
+    Ok(())
+}
+
+/// Write a validated parameter value into the protocol state
+pub fn apply_parameter_change(
+    state: &mut StateAccount,
+    parameter: ProtocolParameter,
+    value: u64,
+) -> Result<()> {
+    validate_parameter_value(parameter, value)?;
+
+    match parameter {
+        ProtocolParameter::MinimumCollateralRatio => {
+            state.minimum_collateral_ratio = value as u8;
+        }
+        ProtocolParameter::ProtocolFee => {
+            state.protocol_fee = value as u8;
+        }
+        ProtocolParameter::ParameterTimelockDelay => {
+            state.parameter_timelock_delay = value as i64;
+        }
+        ProtocolParameter::UnstakeCooldown => {
+            state.unstake_cooldown_slots = value;
+        }
+    }
+
+    Ok(())
+}
+
 // NOTE: Obsolete sorted list functions removed - using off-chain sorting architecture
 // - get_first_trove: No longer needed (no sorted list state)
 // - get_last_trove: No longer needed (no sorted list state)
 
 /// Calculate compounded stake using Liquity Product-Sum algorithm
 /// 
-/// Formula: compounded_deposit = initial_deposit × (P_current / P_snapshot)
+/// Formula: compounded_deposit = initial_deposit × (P_current / P_snapshot) / SCALE_FACTOR^scale_shift
 /// 
 /// This accounts for pool depletion during liquidations:
 /// - P_snapshot: P factor when user last deposited
 /// - P_current: Current P factor
 /// - Ratio P_current/P_snapshot represents the depletion factor
-pub fn calculate_compounded_stake(
-    initial_deposit: u64,
-    p_snapshot: u128,
-    p_current: u128,
-) -> Result<u64> {
+/// - scale_shift: Scale changes since the deposit - each one multiplied P by SCALE_FACTOR
+/// 
+/// A deposit made in an earlier epoch was wiped out when the pool emptied, and one that has
+/// shrunk by more than SCALE_FACTOR (two scale changes, or below a billionth of itself) is
+/// treated as fully depleted, as in Liquity.
+pub fn calculate_compounded_stake(stake: &UserStakeAmount, state: &StateAccount) -> Result<u64> {
+    let initial_deposit = stake.amount;
+    
     // If P_snapshot is 0, this is first deposit or corrupted state - return initial
-    if p_snapshot == 0 {
+    if stake.p_snapshot.is_zero() {
         return Ok(initial_deposit);
     }
     
-    // If P_current is 0, pool is completely depleted - return 0
-    if p_current == 0 {
+    // The pool was emptied after the deposit was made
+    if stake.epoch_snapshot < state.epoch {
         return Ok(0);
     }
     
-    // Calculate: compounded = initial × (P_current / P_snapshot)
-    // Use safe math to prevent overflow
-    let deposit_u128 = initial_deposit as u128;
+    // If P_current is 0, pool is completely depleted - return 0
+    if state.p_factor.is_zero() {
+        return Ok(0);
+    }
     
-    // compounded = (deposit × P_current) / P_snapshot
-    let numerator = deposit_u128
-        .checked_mul(p_current)
-        .ok_or(AerospacerProtocolError::OverflowError)?;
+    let scale_shift = state.current_scale.saturating_sub(stake.scale_snapshot);
+    if scale_shift > 1 {
+        return Ok(0);
+    }
+    let divisor = scale_divisor(stake.p_snapshot, scale_shift).ok_or(AerospacerProtocolError::MathOverflow)?;
     
-    let compounded = numerator
-        .checked_div(p_snapshot)
-        .ok_or(AerospacerProtocolError::DivideByZeroError)?;
+    // compounded = initial × P_current / (P_snapshot × SCALE_FACTOR^shift), rounded down in the pool's favour
+    let compounded = mul_div(initial_deposit as u128, state.p_factor.raw(), divisor, Rounding::Down)?;
+    if compounded < initial_deposit as u128 / SCALE_FACTOR {
+        return Ok(0);
+    }
     
     // Convert back to u64, capping at u64::MAX if overflow
-    let result = if compounded > u64::MAX as u128 {
-        u64::MAX
-    } else {
-        compounded as u64
-    };
-    
-    Ok(result)
+    Ok(u64::try_from(compounded).unwrap_or(u64::MAX))
 }
 
 /// Calculate collateral gain using Liquity Product-Sum algorithm
 /// 
-/// Formula: gain = deposit × (S_current - S_snapshot) / P_snapshot
+/// Formula: gain = deposit × [(S[e][s] - S_snapshot) + S[e][s + 1] / SCALE_FACTOR] / (P_snapshot × SCALE_FACTOR^scale_shift)
 /// 
 /// Where:
-/// - S_snapshot: User's last recorded S factor for this collateral type
-/// - S_current: Current S factor for this collateral type
+/// - S_snapshot: User's last recorded S factor for this collateral type, taken in scale s of epoch e
+/// - S[e][s], S[e][s + 1]: The denom's sums for that scale and the next one
+///   (gains further scales out are too small to count, as in Liquity)
 /// - P_snapshot: User's P factor snapshot (accounts for pool depletion)
+/// - scale_shift: Scales between the deposit's P snapshot and the S snapshot
 /// - deposit: User's stake amount
 pub fn calculate_collateral_gain(
     deposit: u64,
-    s_snapshot: u128,
-    s_current: u128,
-    p_snapshot: u128,
+    p_snapshot: Decimal,
+    s_snapshot: Decimal,
+    scale_shift: u64,
+    sum_at_scale: Decimal,
+    sum_at_next_scale: Decimal,
 ) -> Result<u64> {
     // If P_snapshot is 0, no valid snapshot exists - return 0
-    if p_snapshot == 0 {
+    if p_snapshot.is_zero() {
         return Ok(0);
     }
     
-    // If S hasn't increased, no gain
-    if s_current <= s_snapshot {
+    let Some(divisor) = scale_divisor(p_snapshot, scale_shift) else {
         return Ok(0);
-    }
-    
-    // Calculate S_diff = S_current - S_snapshot
-    let s_diff = s_current.saturating_sub(s_snapshot);
-    
-    // Calculate: gain = (deposit × S_diff) / P_snapshot
-    let deposit_u128 = deposit as u128;
+    };
     
-    let numerator = deposit_u128
-        .checked_mul(s_diff)
+    // S gained since the snapshot, with the next scale's sum brought into this scale's units
+    let s_diff = sum_at_scale
+        .saturating_sub(s_snapshot)
+        .raw()
+        .checked_add(sum_at_next_scale.raw() / SCALE_FACTOR)
         .ok_or(AerospacerProtocolError::OverflowError)?;
+    if s_diff == 0 {
+        return Ok(0);
+    }
     
-    let gain = numerator
-        .checked_div(p_snapshot)
-        .ok_or(AerospacerProtocolError::DivideByZeroError)?;
+    // gain = deposit × S_diff / (P_snapshot × SCALE_FACTOR^shift), rounded down so the vault is never overdrawn
+    let gain = mul_div(deposit as u128, s_diff, divisor, Rounding::Down)?;
     
     // Convert back to u64, capping at u64::MAX if overflow
-    let result = if gain > u64::MAX as u128 {
-        u64::MAX
-    } else {
-        gain as u64
-    };
-    
-    Ok(result)
+    Ok(u64::try_from(gain).unwrap_or(u64::MAX))
+}
+
+/// P_snapshot × SCALE_FACTOR^scale_shift as a raw divisor, or None once it no longer fits
+/// (a deposit that far behind has nothing left to compound or gain)
+fn scale_divisor(p_snapshot: Decimal, scale_shift: u64) -> Option<u128> {
+    let shift = u32::try_from(scale_shift).ok()?;
+    SCALE_FACTOR.checked_pow(shift)?.checked_mul(p_snapshot.raw())
+}
+
+/// Create the program-owned PDA derived from `seeds` at `account_info` with `space` bytes, paid by
+/// `payer`, as `init` would for an account that can only be created from inside a handler
+/// (an address someone already sent lamports to is topped up, allocated and assigned instead)
+pub fn create_pda_account<'info>(
+    account_info: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+    space: usize,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let (expected_pda, bump) = Pubkey::find_program_address(seeds, &crate::ID);
+    require_keys_eq!(account_info.key(), expected_pda, AerospacerProtocolError::InvalidSnapshot);
+
+    let bump_bytes = [bump];
+    let mut signer_seeds = seeds.to_vec();
+    signer_seeds.push(&bump_bytes);
+    let signer = &[&signer_seeds[..]];
+    let rent = Rent::get()?.minimum_balance(space);
+
+    if account_info.lamports() == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                CreateAccount { from: payer.clone(), to: account_info.clone() },
+                signer,
+            ),
+            rent,
+            space as u64,
+            &crate::ID,
+        );
+    }
+
+    let top_up = rent.saturating_sub(account_info.lamports());
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(system_program.clone(), Transfer { from: payer.clone(), to: account_info.clone() }),
+            top_up,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(system_program.clone(), Allocate { account_to_allocate: account_info.clone() }, signer),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(system_program.clone(), Assign { account_to_assign: account_info.clone() }, signer),
+        &crate::ID,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fee_state(base_rate: Decimal, last_fee_operation_time: i64) -> StateAccount {
+        StateAccount {
+            admin: Pubkey::default(),
+            oracle_helper_addr: Pubkey::default(),
+            oracle_state_addr: Pubkey::default(),
+            fee_distributor_addr: Pubkey::default(),
+            fee_state_addr: Pubkey::default(),
+            minimum_collateral_ratio: DEFAULT_MINIMUM_COLLATERAL_RATIO,
+            protocol_fee: DEFAULT_PROTOCOL_FEE,
+            stable_coin_addr: Pubkey::default(),
+            total_debt_amount: 0,
+            total_stake_amount: 0,
+            p_factor: Decimal::ONE,
+            epoch: 0,
+            current_scale: 0,
+            parameter_timelock_delay: 0,
+            base_rate,
+            last_fee_operation_time,
+            unstake_cooldown_slots: 0,
+        }
+    }
+
+    fn assert_close(actual: Decimal, expected: Decimal) {
+        let diff = actual.raw().abs_diff(expected.raw());
+        assert!(diff < 1_000_000_000_000, "{} is not close to {}", actual, expected);
+    }
+
+    #[test]
+    fn test_base_rate_halves_every_twelve_hours() {
+        let mut state = fee_state(Decimal::from_percent(10), 0);
+        decay_base_rate_from_borrowing(&mut state, 12 * 3_600).unwrap();
+        assert_close(state.base_rate, Decimal::from_percent(5));
+        assert_eq!(state.last_fee_operation_time, 12 * 3_600);
+
+        // Under a minute decays nothing and leaves the timestamp where it was
+        let mut state = fee_state(Decimal::from_percent(10), 0);
+        decay_base_rate_from_borrowing(&mut state, 59).unwrap();
+        assert_eq!(state.base_rate, Decimal::from_percent(10));
+        assert_eq!(state.last_fee_operation_time, 0);
+    }
+
+    #[test]
+    fn test_redemption_raises_base_rate_by_half_its_share() {
+        // Redeeming 10% of the supply adds 5%
+        let mut state = fee_state(Decimal::ZERO, 0);
+        let base_rate = update_base_rate_from_redemption(&mut state, 100, 1_000, 0).unwrap();
+        assert_eq!(base_rate, Decimal::from_percent(5));
+        assert_eq!(state.base_rate, base_rate);
+
+        // The rate is decayed before it is raised, and never passes 100%
+        let mut state = fee_state(Decimal::from_percent(10), 0);
+        let base_rate = update_base_rate_from_redemption(&mut state, 100, 1_000, 12 * 3_600).unwrap();
+        assert_close(base_rate, Decimal::from_percent(10));
+        let mut state = fee_state(Decimal::from_percent(90), 0);
+        let base_rate = update_base_rate_from_redemption(&mut state, 400, 1_000, 0).unwrap();
+        assert_eq!(base_rate, Decimal::ONE);
+
+        assert!(update_base_rate_from_redemption(&mut state, 100, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_fee_rates_are_floored_and_capped() {
+        assert_eq!(get_redemption_rate(Decimal::ZERO), REDEMPTION_FEE_FLOOR);
+        assert_eq!(get_redemption_rate(Decimal::from_percent(3)), Decimal::from_bps(350));
+        assert_eq!(get_redemption_rate(Decimal::from_percent(99)), Decimal::from_bps(9_950));
+        assert_eq!(get_redemption_rate(Decimal::ONE), MAX_REDEMPTION_FEE);
+
+        // Borrowing is capped at the protocol fee
+        assert_eq!(get_borrowing_rate(&fee_state(Decimal::ZERO, 0)), BORROWING_FEE_FLOOR);
+        assert_eq!(get_borrowing_rate(&fee_state(Decimal::from_percent(20), 0)), Decimal::from_percent(DEFAULT_PROTOCOL_FEE as u64));
+    }
+
+    #[test]
+    fn test_max_fee_and_min_output_guards() {
+        // A 1% fee passes a 100 bps limit and fails a 99 bps one
+        let fee_rate = get_redemption_rate(Decimal::from_bps(50));
+        assert!(check_max_fee(fee_rate, 100).is_ok());
+        assert!(check_max_fee(fee_rate, 99).is_err());
+        assert!(check_max_fee(Decimal::ZERO, 0).is_ok());
+
+        // A raised base rate trips a limit that the floor rate passed
+        let state = fee_state(Decimal::from_percent(2), 0);
+        assert!(check_max_fee(get_borrowing_rate(&fee_state(Decimal::ZERO, 0)), 50).is_ok());
+        assert!(check_max_fee(get_borrowing_rate(&state), 50).is_err());
+
+        // Fees round down
+        assert_eq!(calculate_fee(1_000, fee_rate).unwrap(), 10);
+        assert_eq!(calculate_fee(199, fee_rate).unwrap(), 1);
+
+        assert!(check_min_collateral_out(100, 100).is_ok());
+        assert!(check_min_collateral_out(99, 100).is_err());
+        assert!(check_min_collateral_out(0, 0).is_ok());
+    }
+
+    #[test]
+    fn test_collateral_config_bounds() {
+        let valid = CollateralConfig {
+            denom: "SOL".to_string(),
+            minimum_collateral_ratio: 130,
+            liquidation_threshold: 120,
+            liquidation_penalty_bps: 1_000,
+            treasury_share_bps: 5_000,
+            debt_ceiling: 0,
+            deposit_cap: 0,
+            decimals: 9,
+            enabled: true,
+        };
+        assert!(validate_collateral_config(&valid).is_ok());
+        assert!(validate_collateral_config(&CollateralConfig { denom: String::new(), ..valid.clone() }).is_err());
+
+        // MIN_LIQUIDATION_THRESHOLD <= threshold <= minimum ratio <= MAX_MINIMUM_COLLATERAL_RATIO
+        let ratios = |minimum_collateral_ratio, liquidation_threshold, liquidation_penalty_bps| CollateralConfig {
+            minimum_collateral_ratio,
+            liquidation_threshold,
+            liquidation_penalty_bps,
+            ..valid.clone()
+        };
+        assert!(validate_collateral_config(&ratios(130, MIN_LIQUIDATION_THRESHOLD, 100)).is_ok());
+        assert!(validate_collateral_config(&ratios(130, MIN_LIQUIDATION_THRESHOLD - 1, 50)).is_err());
+        assert!(validate_collateral_config(&ratios(130, 130, 1_000)).is_ok());
+        assert!(validate_collateral_config(&ratios(130, 131, 1_000)).is_err());
+        assert!(validate_collateral_config(&ratios(MAX_MINIMUM_COLLATERAL_RATIO, 120, 1_000)).is_ok());
+        assert!(validate_collateral_config(&ratios(MAX_MINIMUM_COLLATERAL_RATIO + 1, 120, 1_000)).is_err());
+
+        // LIQUIDATOR_COLLATERAL_BPS <= penalty <= MAX_LIQUIDATION_PENALTY_BPS
+        assert!(validate_collateral_config(&ratios(130, 130, LIQUIDATOR_COLLATERAL_BPS)).is_ok());
+        assert!(validate_collateral_config(&ratios(130, 130, LIQUIDATOR_COLLATERAL_BPS - 1)).is_err());
+        assert!(validate_collateral_config(&ratios(130, 130, MAX_LIQUIDATION_PENALTY_BPS)).is_ok());
+        assert!(validate_collateral_config(&ratios(130, 130, MAX_LIQUIDATION_PENALTY_BPS + 1)).is_err());
+
+        // The penalty must fit in the threshold's margin over 100%
+        assert!(validate_collateral_config(&ratios(130, 110, 1_000)).is_ok());
+        assert!(validate_collateral_config(&ratios(130, 110, 1_001)).is_err());
+
+        let treasury = |treasury_share_bps| CollateralConfig { treasury_share_bps, ..valid.clone() };
+        assert!(validate_collateral_config(&treasury(BPS_DENOMINATOR as u16)).is_ok());
+        assert!(validate_collateral_config(&treasury(BPS_DENOMINATOR as u16 + 1)).is_err());
+    }
+
+    fn blank_pending() -> PendingParameterChange {
+        PendingParameterChange {
+            parameter: ProtocolParameter::ProtocolFee,
+            new_value: 0,
+            queued_at: 0,
+            executable_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_parameter_values_are_bounded() {
+        let bounds = [
+            (
+                ProtocolParameter::MinimumCollateralRatio,
+                MIN_MINIMUM_COLLATERAL_RATIO as u64,
+                MAX_MINIMUM_COLLATERAL_RATIO as u64,
+            ),
+            (ProtocolParameter::ProtocolFee, MIN_PROTOCOL_FEE as u64, MAX_PROTOCOL_FEE as u64),
+            (
+                ProtocolParameter::ParameterTimelockDelay,
+                MIN_PARAMETER_TIMELOCK_DELAY as u64,
+                MAX_PARAMETER_TIMELOCK_DELAY as u64,
+            ),
+            (ProtocolParameter::UnstakeCooldown, 0, MAX_UNSTAKE_COOLDOWN_SLOTS),
+        ];
+
+        for (parameter, min, max) in bounds {
+            assert!(validate_parameter_value(parameter, min).is_ok());
+            assert!(validate_parameter_value(parameter, max).is_ok());
+            assert!(validate_parameter_value(parameter, max + 1).is_err());
+            if min > 0 {
+                assert!(validate_parameter_value(parameter, min - 1).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_queued_change_waits_out_the_timelock() {
+        let mut state = fee_state(Decimal::ZERO, 0);
+        state.parameter_timelock_delay = DEFAULT_PARAMETER_TIMELOCK_DELAY;
+        let mut pending = blank_pending();
+
+        let executable_at = queue_parameter_change(
+            &mut pending,
+            &state,
+            ProtocolParameter::MinimumCollateralRatio,
+            150,
+            1_000,
+        )
+        .unwrap();
+        assert_eq!(executable_at, 1_000 + DEFAULT_PARAMETER_TIMELOCK_DELAY);
+        assert_eq!(pending.parameter, ProtocolParameter::MinimumCollateralRatio);
+        assert_eq!(pending.new_value, 150);
+        assert_eq!(pending.queued_at, 1_000);
+        assert_eq!(pending.executable_at, executable_at);
+
+        assert!(check_parameter_change_executable(&pending, executable_at - 1).is_err());
+        assert!(check_parameter_change_executable(&pending, executable_at).is_ok());
+
+        apply_parameter_change(&mut state, pending.parameter, pending.new_value).unwrap();
+        assert_eq!(state.minimum_collateral_ratio, 150);
+
+        // Out-of-bounds values are rejected at queue time and again at execution
+        assert!(queue_parameter_change(&mut pending, &state, ProtocolParameter::ProtocolFee, 0, 1_000).is_err());
+        assert_eq!(pending.new_value, 150);
+        assert!(apply_parameter_change(&mut state, ProtocolParameter::ProtocolFee, MAX_PROTOCOL_FEE as u64 + 1).is_err());
+        assert_eq!(state.protocol_fee, DEFAULT_PROTOCOL_FEE);
+    }
+
+    #[test]
+    fn test_requeue_and_cancel_restart_the_timelock() {
+        let mut state = fee_state(Decimal::ZERO, 0);
+        state.parameter_timelock_delay = MIN_PARAMETER_TIMELOCK_DELAY;
+        let mut pending = blank_pending();
+
+        queue_parameter_change(&mut pending, &state, ProtocolParameter::ProtocolFee, 2, 0).unwrap();
+        let executable_at =
+            queue_parameter_change(&mut pending, &state, ProtocolParameter::ProtocolFee, 3, 100).unwrap();
+        assert_eq!(pending.new_value, 3);
+        assert_eq!(executable_at, 100 + MIN_PARAMETER_TIMELOCK_DELAY);
+        assert!(check_parameter_change_executable(&pending, MIN_PARAMETER_TIMELOCK_DELAY).is_err());
+
+        // Cancelling closes the pending account, so the next queue starts from a blank one
+        let mut pending = blank_pending();
+        let executable_at =
+            queue_parameter_change(&mut pending, &state, ProtocolParameter::ProtocolFee, 4, 200).unwrap();
+        assert_eq!(executable_at, 200 + MIN_PARAMETER_TIMELOCK_DELAY);
+        assert!(check_parameter_change_executable(&pending, executable_at - 1).is_err());
+    }
 }
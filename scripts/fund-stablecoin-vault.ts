@@ -98,7 +98,7 @@ async function main() {
             userStableAta,        // from
             protocolStablecoinVault, // to
             payer,                // owner signer
-            Number(amountToSend)  // amount in smallest unit (18 decimals)
+            Number(amountToSend)  // amount in smallest unit (6 decimals)
         )
     );
 
@@ -38,13 +38,13 @@ async function main() {
   console.log("Oracle State:", oracleStatePDA.toString());
   console.log("Fees State:", feesStatePDA.toString());
 
-  // Create a devnet stablecoin mint for the protocol (18 decimals)
+  // Create a devnet stablecoin mint for the protocol (6 decimals)
   const stablecoinMint = await createMint(
     provider.connection,
     adminKeypair,
     admin.publicKey,
     null,
-    18
+    6
   );
 
   console.log("Stablecoin Mint:", stablecoinMint.toString());
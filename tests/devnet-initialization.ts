@@ -7,7 +7,7 @@ import { PublicKey, Keypair, SystemProgram, SYSVAR_RENT_PUBKEY, Connection } fro
 import type { AccountMeta } from '@solana/web3.js';
 import { TOKEN_PROGRAM_ID, createMint, createAssociatedTokenAccount, mintTo } from "@solana/spl-token";
 import { assert } from "chai";
-import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData } from './trove-indexer';
+import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData, nominalICR } from './trove-indexer';
 
 describe("Devnet Initialization and Core Testing", () => {
   const provider = anchor.AnchorProvider.env();
@@ -68,18 +68,21 @@ describe("Devnet Initialization and Core Testing", () => {
       programId
     );
 
+    const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
     const thisTrove: TroveData = {
       owner: userPubkey,
       debt: BigInt(loanAmount.toString()),
       collateralAmount: BigInt(collateralAmount.toString()),
       collateralDenom: denom,
       icr: newICR,
+      nicr: newNICR,
       debtAccount: userDebtAccount,
       collateralAccount: userCollateralAccount,
       liquidityThresholdAccount: liquidityThresholdAccount,
     };
 
-    let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+    let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
     if (insertIndex === -1) insertIndex = sortedTroves.length;
     
     const newSortedTroves = [
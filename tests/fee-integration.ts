@@ -18,7 +18,7 @@ import {
 } from "@solana/spl-token";
 import { assert, expect } from "chai";
 import * as fs from "fs";
-import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData } from './trove-indexer';
+import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData, nominalICR } from './trove-indexer';
 
 describe("Fee Contract - Protocol CPI Integration Tests", () => {
   anchor.setProvider(anchor.AnchorProvider.env());
@@ -85,6 +85,8 @@ describe("Fee Contract - Protocol CPI Integration Tests", () => {
       program.programId
     );
     
+    const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
     // Create temp TroveData to find neighbors
     const newTrove: TroveData = {
       owner: userPubkey,
@@ -92,13 +94,14 @@ describe("Fee Contract - Protocol CPI Integration Tests", () => {
       collateralAmount: BigInt(collateralAmount.toString()),
       collateralDenom: denom,
       icr: newICR,
+      nicr: newNICR,
       debtAccount: userDebtAccount,
       collateralAccount: userCollateralAccount,
       liquidityThresholdAccount: liquidityThreshold,
     };
     
     // Insert trove into sorted position and find neighbors
-    let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+    let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
     if (insertIndex === -1) insertIndex = sortedTroves.length;
     
     const newSortedTroves = [
@@ -15,7 +15,7 @@ import {
 } from "@solana/spl-token";
 import { assert } from "chai";
 import { loadTestUsers } from "./test-utils";
-import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData } from './trove-indexer';
+import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData, nominalICR } from './trove-indexer';
 
 // Constants
 const PYTH_ORACLE_ADDRESS = new PublicKey("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
@@ -56,19 +56,22 @@ async function getNeighborHints(
     protocolProgram.programId
   );
 
+  const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
   const thisTrove: TroveData = {
     owner: user,
     debt: BigInt(loanAmount.toString()),
     collateralAmount: BigInt(collateralAmount.toString()),
     collateralDenom: denom,
     icr: newICR,
+    nicr: newNICR,
     debtAccount: userDebtAccount,
     collateralAccount: userCollateralAccount,
     liquidityThresholdAccount: liquidityThresholdAccount,
   };
 
   // Insert this trove into sorted position to find neighbors
-  let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+  let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
   if (insertIndex === -1) insertIndex = sortedTroves.length;
 
   const newSortedTroves = [
@@ -198,7 +201,7 @@ describe("Aeroscraper Protocol Core Operations", () => {
       stablecoinMint = stateAccount.stableCoinAddr;
       console.log("Using existing stablecoin mint:", stablecoinMint.toString());
     } else {
-      stablecoinMint = await createMint(provider.connection, adminKeypair, admin.publicKey, null, 18); // 18 decimals for aUSD
+      stablecoinMint = await createMint(provider.connection, adminKeypair, admin.publicKey, null, 6); // 6 decimals for aUSD
       console.log("Created new stablecoin mint:", stablecoinMint.toString());
     }
 
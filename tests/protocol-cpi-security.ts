@@ -12,7 +12,7 @@ import {
   TestContext,
 } from "./test-utils";
 import { TOKEN_PROGRAM_ID, getAssociatedTokenAddress } from "@solana/spl-token";
-import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData } from './trove-indexer';
+import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData, nominalICR } from './trove-indexer';
 
 describe("Protocol Contract - CPI Security Tests", () => {
   let ctx: TestContext;
@@ -50,18 +50,21 @@ describe("Protocol Contract - CPI Security Tests", () => {
 
     const pdas = derivePDAs(denom, userPubkey, ctx.protocolProgram.programId);
 
+    const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
     const thisTrove: TroveData = {
       owner: userPubkey,
       debt: BigInt(loanAmount.toString()),
       collateralAmount: BigInt(collateralAmount.toString()),
       collateralDenom: denom,
       icr: newICR,
+      nicr: newNICR,
       debtAccount: pdas.userDebtAmount,
       collateralAccount: pdas.userCollateralAmount,
       liquidityThresholdAccount: pdas.liquidityThreshold,
     };
 
-    let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+    let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
     if (insertIndex === -1) insertIndex = sortedTroves.length;
     
     const newSortedTroves = [
@@ -3,7 +3,7 @@ import { Program, BN } from "@coral-xyz/anchor";
 import { AerospacerProtocol } from "../target/types/aerospacer_protocol";
 import { PublicKey } from "@solana/web3.js";
 import { assert } from "chai";
-import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData } from './trove-indexer';
+import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData, nominalICR } from './trove-indexer';
 
 async function getNeighborHints(
   provider: anchor.AnchorProvider,
@@ -34,18 +34,21 @@ async function getNeighborHints(
     protocolProgram.programId
   );
 
+  const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
   const thisTrove: TroveData = {
     owner: user,
     debt: BigInt(loanAmount.toString()),
     collateralAmount: BigInt(collateralAmount.toString()),
     collateralDenom: denom,
     icr: newICR,
+    nicr: newNICR,
     debtAccount: userDebtAccount,
     collateralAccount: userCollateralAccount,
     liquidityThresholdAccount: liquidityThresholdAccount,
   };
 
-  let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+  let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
   if (insertIndex === -1) insertIndex = sortedTroves.length;
   
   const newSortedTroves = [
@@ -14,7 +14,7 @@ import {
   TestContext,
 } from "./test-utils";
 import { getAssociatedTokenAddress, TOKEN_PROGRAM_ID } from "@solana/spl-token";
-import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData } from './trove-indexer';
+import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData, nominalICR } from './trove-indexer';
 import type { AccountMeta } from '@solana/web3.js';
 
 // Helper function to get neighbor hints for trove mutations
@@ -51,6 +51,8 @@ async function getNeighborHints(
     program.programId
   );
 
+  const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
   // Create a temporary TroveData object for this trove
   const thisTrove: TroveData = {
     owner: userPubkey,
@@ -58,13 +60,14 @@ async function getNeighborHints(
     collateralAmount: BigInt(collateralAmount.toString()),
     collateralDenom: denom,
     icr: newICR,
+    nicr: newNICR,
     debtAccount: userDebtAccount,
     collateralAccount: userCollateralAccount,
     liquidityThresholdAccount: liquidityThresholdAccount,
   };
 
   // Insert this trove into sorted position to find neighbors
-  let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+  let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
   if (insertIndex === -1) insertIndex = sortedTroves.length;
   
   const newSortedTroves = [
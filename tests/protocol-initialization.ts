@@ -36,7 +36,7 @@ describe("Protocol Contract - Initialization Tests", () => {
       adminKeypair,
       admin.publicKey,
       null,
-      18
+      6
     );
 
     // Initialize oracle program
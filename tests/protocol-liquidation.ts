@@ -13,7 +13,7 @@ import {
 } from "@solana/spl-token";
 import { assert, expect } from "chai";
 import { setupTestEnvironment, TestContext, derivePDAs, getTokenBalance, loadTestUsers, openTroveForUser } from "./test-utils";
-import { fetchAllTroves, sortTrovesByICR, buildNeighborAccounts, TroveData, findNeighbors } from "./trove-indexer";
+import { fetchAllTroves, sortTrovesByICR, buildNeighborAccounts, TroveData, nominalICR, findNeighbors } from "./trove-indexer";
 
 describe("Protocol Contract - Liquidation Tests", () => {
   const provider = anchor.AnchorProvider.env();
@@ -102,19 +102,22 @@ describe("Protocol Contract - Liquidation Tests", () => {
       protocolProgram.programId
     );
 
+    const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
     const thisTrove: TroveData = {
       owner: user,
       debt: BigInt(loanAmount.toString()),
       collateralAmount: BigInt(collateralAmount.toString()),
       collateralDenom: denom,
       icr: newICR,
+      nicr: newNICR,
       debtAccount: userDebtAccount,
       collateralAccount: userCollateralAccount,
       liquidityThresholdAccount: liquidityThresholdAccount,
     };
 
     // Insert this trove into sorted position to find neighbors
-    let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+    let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
     if (insertIndex === -1) insertIndex = sortedTroves.length;
 
     const newSortedTroves = [
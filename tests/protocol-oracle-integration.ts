@@ -15,7 +15,7 @@ import {
   SOL_PRICE_FEED,
   TestContext,
 } from "./test-utils";
-import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData } from './trove-indexer';
+import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData, nominalICR } from './trove-indexer';
 
 // Helper function to get neighbor hints for trove mutations using off-chain sorting
 async function getNeighborHints(
@@ -52,19 +52,22 @@ async function getNeighborHints(
     protocolProgram.programId
   );
 
+  const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
   const thisTrove: TroveData = {
     owner: user,
     debt: BigInt(loanAmount.toString()),
     collateralAmount: BigInt(collateralAmount.toString()),
     collateralDenom: denom,
     icr: newICR,
+    nicr: newNICR,
     debtAccount: userDebtAccount,
     collateralAccount: userCollateralAccount,
     liquidityThresholdAccount: liquidityThresholdAccount,
   };
 
   // Insert this trove into sorted position to find neighbors
-  let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+  let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
   if (insertIndex === -1) insertIndex = sortedTroves.length;
   
   const newSortedTroves = [
@@ -7,7 +7,7 @@ import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
 import type { AccountMeta } from '@solana/web3.js';
 import { createMint, createAssociatedTokenAccount, mintTo, TOKEN_PROGRAM_ID, getAssociatedTokenAddress } from "@solana/spl-token";
 import { assert, expect } from "chai";
-import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData } from './trove-indexer';
+import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData, nominalICR } from './trove-indexer';
 import { setupTestEnvironment, TestContext, derivePDAs, loadTestUsers, openTroveForUser } from "./test-utils";
 
 /**
@@ -58,19 +58,22 @@ async function getNeighborHints(
     protocolProgram.programId
   );
 
+  const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
   const thisTrove: TroveData = {
     owner: user,
     debt: BigInt(loanAmount.toString()),
     collateralAmount: BigInt(collateralAmount.toString()),
     collateralDenom: denom,
     icr: newICR,
+    nicr: newNICR,
     debtAccount: userDebtAccount,
     collateralAccount: userCollateralAccount,
     liquidityThresholdAccount: liquidityThresholdAccount,
   };
 
   // Insert this trove into sorted position to find neighbors
-  let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+  let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
   if (insertIndex === -1) insertIndex = sortedTroves.length;
 
   const newSortedTroves = [
@@ -15,7 +15,7 @@ import {
   TestContext,
 } from "./test-utils";
 import { getAssociatedTokenAddress, TOKEN_PROGRAM_ID } from "@solana/spl-token";
-import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData } from './trove-indexer';
+import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData, nominalICR } from './trove-indexer';
 import type { AccountMeta } from '@solana/web3.js';
 
 describe("Protocol Contract - Security Tests", () => {
@@ -55,19 +55,22 @@ describe("Protocol Contract - Security Tests", () => {
       ctx.protocolProgram.programId
     );
 
+    const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
     const thisTrove: TroveData = {
       owner: user,
       debt: BigInt(loanAmount.toString()),
       collateralAmount: BigInt(collateralAmount.toString()),
       collateralDenom: denom,
       icr: newICR,
+      nicr: newNICR,
       debtAccount: userDebtAccount,
       collateralAccount: userCollateralAccount,
       liquidityThresholdAccount: liquidityThresholdAccount,
     };
 
     // Insert this trove into sorted position to find neighbors
-    let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+    let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
     if (insertIndex === -1) insertIndex = sortedTroves.length;
     
     const newSortedTroves = [
@@ -34,7 +34,7 @@ describe("Aerospacer Protocol - Simple Test", () => {
       admin.payer,
       admin.publicKey,
       null,
-      18
+      6
     );
 
     // Initialize oracle program using PDA
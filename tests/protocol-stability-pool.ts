@@ -60,7 +60,7 @@ describe("Protocol Contract - Stability Pool Tests", () => {
     await provider.sendAndConfirm(staker2Tx, [admin.payer]);
 
     // Create mints
-    stablecoinMint = await createMint(provider.connection, admin.payer, admin.publicKey, null, 18);
+    stablecoinMint = await createMint(provider.connection, admin.payer, admin.publicKey, null, 6);
     collateralMint = await createMint(provider.connection, admin.payer, admin.publicKey, null, 9);
 
     // Create token accounts
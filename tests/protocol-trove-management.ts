@@ -12,7 +12,7 @@ import {
   TOKEN_PROGRAM_ID,
 } from "@solana/spl-token";
 import { assert, expect } from "chai";
-import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData } from './trove-indexer';
+import { fetchAllTroves, sortTrovesByICR, findNeighbors, buildNeighborAccounts, TroveData, nominalICR } from './trove-indexer';
 import { loadTestUsers } from "./test-utils";
 
 // Helper function to get neighbor hints for trove mutations
@@ -51,19 +51,22 @@ async function getNeighborHints(
     protocolProgram.programId
   );
 
+  const newNICR = nominalICR(BigInt(collateralAmount.toString()), BigInt(loanAmount.toString()));
+
   const thisTrove: TroveData = {
     owner: user,
     debt: BigInt(loanAmount.toString()),
     collateralAmount: BigInt(collateralAmount.toString()),
     collateralDenom: denom,
     icr: newICR,
+    nicr: newNICR,
     debtAccount: userDebtAccount,
     collateralAccount: userCollateralAccount,
     liquidityThresholdAccount: liquidityThresholdAccount,
   };
 
   // Insert this trove into sorted position to find neighbors
-  let insertIndex = sortedTroves.findIndex((t) => t.icr > newICR);
+  let insertIndex = sortedTroves.findIndex((t) => t.nicr > newNICR);
   if (insertIndex === -1) insertIndex = sortedTroves.length;
 
   const newSortedTroves = [
@@ -140,7 +143,7 @@ describe("Protocol Contract - Trove Management Tests", () => {
     await provider.sendAndConfirm(user4Tx, [admin.payer]);
 
     // Create mints
-    stablecoinMint = await createMint(provider.connection, admin.payer, admin.publicKey, null, 18);
+    stablecoinMint = await createMint(provider.connection, admin.payer, admin.publicKey, null, 6);
     collateralMint = await createMint(provider.connection, admin.payer, admin.publicKey, null, 9);
 
     // Create token accounts
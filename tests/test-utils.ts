@@ -202,7 +202,7 @@ export async function setupTestEnvironment(): Promise<TestContext> {
       admin.payer,
       admin.publicKey,
       null,
-      18
+      6
     );
     console.log("✅ Created new stablecoin mint:", stablecoinMint.toString());
   }
@@ -3,7 +3,7 @@
  * 
  * NEW ARCHITECTURE:
  * - Fetch all troves from Solana via RPC (no size limits)
- * - Sort troves by ICR off-chain (efficient, no compute limits)
+ * - Sort troves by nominal ICR (NICR) off-chain per collateral denom (efficient, no compute limits)
  * - Find 2-3 neighbor accounts for validation
  * - Pass only neighbors via remainingAccounts (~6-9 accounts = ~200 bytes)
  * 
@@ -23,6 +23,7 @@ export interface TroveData {
   collateralAmount: bigint;
   collateralDenom: string;
   icr: bigint; // Individual Collateralization Ratio
+  nicr: bigint; // Nominal ICR in collateralDenom (collateral * 1e20 / debt) - the sorted list order
 
   // Account addresses for passing to contract
   debtAccount: PublicKey;
@@ -30,6 +31,9 @@ export interface TroveData {
   liquidityThresholdAccount: PublicKey;
 }
 
+// Scale of the nominal ICR, as in sorted_troves::NICR_PRECISION
+export const NICR_PRECISION = 100_000_000_000_000_000_000n;
+
 export interface NeighborHints {
   prev: TroveData | null;
   next: TroveData | null;
@@ -108,12 +112,19 @@ export async function fetchAllTroves(
           program.programId
         );
 
+        // The contract validates hints against the NICR stored for this denom
+        const stored = liquidityThreshold.nominalRatios.find((n) => n.denom === denom);
+        const nicr = stored
+          ? BigInt(stored.ratio.toString())
+          : nominalICR(collateralAmount, debt);
+
         troves.push({
           owner,
           debt,
           collateralAmount,
           collateralDenom: denom,
           icr,
+          nicr,
           debtAccount: debtAccountInfo.publicKey,
           collateralAccount: collateralPda,
           liquidityThresholdAccount: liquidityThresholdPda,
@@ -129,19 +140,31 @@ export async function fetchAllTroves(
 }
 
 /**
- * Sort troves by ICR (ascending order: riskiest first)
+ * Nominal ICR of a trove in one denom (collateral * 1e20 / debt), matching sorted_troves::compute_nominal_icr
+ */
+export function nominalICR(collateralAmount: bigint, debt: bigint): bigint {
+  if (debt === 0n) {
+    return (1n << 128n) - 1n; // Decimal::MAX - a trove without debt sorts last
+  }
+  return (collateralAmount * NICR_PRECISION) / debt;
+}
+
+/**
+ * Sort troves of one collateral denom by NICR (ascending order: riskiest first)
+ * 
+ * Lower NICR = riskier = earlier in list
+ * Higher NICR = safer = later in list
  * 
- * Lower ICR = riskier = earlier in list
- * Higher ICR = safer = later in list
+ * The NICR leaves the price out, so the order stays valid when the price moves
  * 
- * @param troves - Array of trove data
- * @returns Sorted array (ascending ICR)
+ * @param troves - Array of trove data (all in the same collateral denom)
+ * @returns Sorted array (ascending NICR)
  */
 export function sortTrovesByICR(troves: TroveData[]): TroveData[] {
   return [...troves].sort((a, b) => {
-    // Sort by ICR ascending (lowest/riskiest first)
-    if (a.icr < b.icr) return -1;
-    if (a.icr > b.icr) return 1;
+    // Sort by NICR ascending (lowest/riskiest first)
+    if (a.nicr < b.nicr) return -1;
+    if (a.nicr > b.nicr) return 1;
 
     // Tie-breaker: sort by debt descending (larger debt first)
     if (a.debt > b.debt) return -1;